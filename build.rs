@@ -0,0 +1,94 @@
+use std::env;
+
+/// The features requiring newer libfprint versions: the feature, the version and the cfg enabling its APIs.
+const VERSIONS: &[(&str, (u32, u32), &str)] = &[
+    ("v1_94", (1, 94), "libfprint_1_94"),
+    ("v1_96", (1, 96), "libfprint_1_96"),
+];
+
+/// Parses the major and minor numbers of a version, e.g. `1.94.5`.
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut numbers = version.split('.').map(|number| number.parse().ok());
+    Some((numbers.next()??, numbers.next()??))
+}
+
+/// Returns the version of the installed libfprint.
+fn libfprint_version() -> Option<String> {
+    // Set by libfprint-sys from the library it links, see its build script
+    println!("cargo:rerun-if-env-changed=DEP_LIBFPRINT_2_VERSION");
+    if let Ok(version) = env::var("DEP_LIBFPRINT_2_VERSION") {
+        return Some(version);
+    }
+    pkg_config::Config::new()
+        .cargo_metadata(false)
+        .env_metadata(false)
+        .probe("libfprint-2")
+        .ok()
+        .map(|library| library.version)
+}
+
+/// Builds the NBIS libraries bundled in `nbis/`, see its README.
+#[cfg(feature = "host-matching")]
+fn build_nbis() {
+    for library in ["bozorth3", "mindtct"] {
+        let dir = format!("nbis/{library}");
+        let mut sources: Vec<_> = std::fs::read_dir(format!("{dir}/src"))
+            .expect("missing NBIS sources")
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "c"))
+            .collect();
+        if library == "bozorth3" {
+            sources.push("nbis/globals.c".into());
+        }
+        sources.sort();
+
+        cc::Build::new()
+            .files(sources)
+            .include(format!("{dir}/include"))
+            .define("NOVERBOSE", None)
+            // Keeps the warnings of the bundled sources out of the build output
+            .warnings(false)
+            .flag_if_supported("-w")
+            .compile(library);
+        println!("cargo:rerun-if-changed={dir}");
+    }
+    println!("cargo:rerun-if-changed=nbis/globals.c");
+    println!("cargo:rustc-link-lib=m");
+}
+
+fn main() {
+    #[cfg(feature = "host-matching")]
+    build_nbis();
+
+    let found = libfprint_version();
+    let detected = found.as_deref().and_then(parse_version);
+
+    let mut missing = Vec::new();
+    for &(feature, version, cfg) in VERSIONS {
+        println!("cargo:rustc-check-cfg=cfg({cfg})");
+        // The features require the version, the APIs of newer versions are enabled when they are found
+        let required = env::var_os(format!("CARGO_FEATURE_{}", feature.to_uppercase())).is_some();
+        match detected {
+            Some(detected) if detected >= version => {}
+            Some(_) if required => missing.push(feature),
+            None if required => {}
+            _ => continue,
+        }
+        println!("cargo:rustc-cfg={cfg}");
+    }
+
+    if !missing.is_empty() {
+        let minimums: Vec<String> = VERSIONS
+            .iter()
+            .map(|(feature, (major, minor), _)| {
+                format!("  {feature}: libfprint >= {major}.{minor}")
+            })
+            .collect();
+        panic!(
+            "libfprint {} is installed, but the features {} require a newer version.\nMinimum versions per feature:\n{}",
+            found.unwrap_or_default(),
+            missing.join(", "),
+            minimums.join("\n")
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use std::sync::{Arc, Mutex};
 
-use libfprint_rs::{FpContext, FpDevice, FpFinger, FpPrint};
+use libfprint_rs::{FpContext, FpDevice, FpFinger, FpPrint, FpSerializedPrint};
 
 fn main() {
     // Get context
@@ -17,17 +17,23 @@ fn main() {
     // Create a template print
     let template = FpPrint::new(&dev);
     template.set_finger(FpFinger::RightRing);
-    template.set_username("test");
+    template.set_username("test").unwrap();
 
     // User data that we will use on the callback function,
     // to mutate the value of a counter, it must be wrapped in an Arc<Mutex<T>>
     let counter = Arc::new(Mutex::new(0));
 
     // Get the new print from the user
-    let _new_print = dev
+    let new_print = dev
         .enroll_sync(template, None, Some(progress_cb), Some(counter.clone()))
         .unwrap();
 
+    // Save the print to disk, load it later with FpSerializedPrint::read_from
+    FpSerializedPrint::new(&new_print)
+        .unwrap()
+        .write_to("print.bin")
+        .unwrap();
+
     // Get the total of time the enroll callback was called
     println!("Total enroll stages: {}", counter.lock().unwrap());
 }
@@ -1,4 +1,4 @@
-use libfprint_rs::{FpContext, FpDevice, FpPrint};
+use libfprint_rs::{FpContext, FpDevice, FpMatchOutcome, FpPrint};
 
 fn main() {
     // Get devices
@@ -13,21 +13,14 @@ fn main() {
         .enroll_sync(template, None, Some(progress_cb), None)
         .unwrap();
 
-    // New print where we will store the next print
-    let mut new_print = FpPrint::new(&dev);
-
     // Verify if the next print matches the previously enrolled print
-    let matched = dev
-        .verify_sync(
-            &enrolled_print,
-            None,
-            Some(match_cb),
-            None,
-            Some(&mut new_print),
-        )
+    let outcome = dev
+        .verify_sync(&enrolled_print, None, Some(match_cb), None)
         .unwrap();
-    if matched {
-        println!("Matched again");
+    match outcome {
+        FpMatchOutcome::Matched(_) => println!("Matched again"),
+        FpMatchOutcome::NotMatched(_) => println!("Not matched"),
+        FpMatchOutcome::Retry(reason) => println!("Retry: {}", reason),
     }
 }
 
@@ -44,7 +37,7 @@ pub fn progress_cb(
 pub fn match_cb(
     _device: &FpDevice,
     matched_print: Option<FpPrint>,
-    _print: FpPrint,
+    _print: Option<FpPrint>,
     _error: Option<glib::Error>,
     _data: &Option<()>,
 ) -> () {
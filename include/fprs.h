@@ -0,0 +1,68 @@
+/*
+ * C ABI of libfprint-rs, built with the `capi` feature:
+ *
+ *     cargo rustc --release --features capi --crate-type cdylib
+ *
+ * Every object is opaque and freed by its _free function. Functions returning an int return FPRS_OK on
+ * success and FPRS_ERROR on failure, fprs_last_error then describes the error of the calling thread.
+ */
+#ifndef FPRS_H
+#define FPRS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define FPRS_OK 0
+#define FPRS_ERROR -1
+/* The scan failed for a reason the user can fix, the user should try again */
+#define FPRS_RETRY 1
+
+/* The numbering of libfprint's FpFinger */
+#define FPRS_FINGER_UNKNOWN 0
+#define FPRS_FINGER_LEFT_THUMB 1
+#define FPRS_FINGER_LEFT_INDEX 2
+#define FPRS_FINGER_LEFT_MIDDLE 3
+#define FPRS_FINGER_LEFT_RING 4
+#define FPRS_FINGER_LEFT_LITTLE 5
+#define FPRS_FINGER_RIGHT_THUMB 6
+#define FPRS_FINGER_RIGHT_INDEX 7
+#define FPRS_FINGER_RIGHT_MIDDLE 8
+#define FPRS_FINGER_RIGHT_RING 9
+#define FPRS_FINGER_RIGHT_LITTLE 10
+
+typedef struct fprs_context fprs_context;
+typedef struct fprs_device fprs_device;
+typedef struct fprs_print fprs_print;
+
+/* Owned by the library, valid until the next call failing on the thread */
+const char *fprs_last_error(void);
+
+fprs_context *fprs_context_new(void);
+void fprs_context_free(fprs_context *context);
+size_t fprs_context_device_count(const fprs_context *context);
+fprs_device *fprs_context_device(const fprs_context *context, size_t index);
+
+void fprs_device_free(fprs_device *device);
+char *fprs_device_name(const fprs_device *device);
+char *fprs_device_driver(const fprs_device *device);
+int fprs_device_open(const fprs_device *device);
+int fprs_device_close(const fprs_device *device);
+int fprs_device_enroll(const fprs_device *device, const char *username, int finger, fprs_print **print);
+int fprs_device_verify(const fprs_device *device, const fprs_print *print, int *matched);
+
+int fprs_print_serialize(const fprs_print *print, uint8_t **data, size_t *len);
+fprs_print *fprs_print_deserialize(const uint8_t *data, size_t len);
+void fprs_print_free(fprs_print *print);
+
+void fprs_bytes_free(uint8_t *data, size_t len);
+void fprs_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FPRS_H */
@@ -1,14 +1,87 @@
-use std::{env, path::PathBuf};
+use std::{env, ffi::OsString, path::PathBuf};
+
+/// The headers and the version of the libfprint to build against.
+struct Libfprint {
+    include_paths: Vec<PathBuf>,
+    version: Option<String>,
+}
+
+/// Reads an environment variable for the target, `NAME_<target>` first, then `NAME`, e.g.
+/// `LIBFPRINT_LIB_DIR_aarch64_unknown_linux_gnu`.
+fn target_env(name: &str) -> Option<OsString> {
+    let target = env::var("TARGET").unwrap();
+    let keys = [
+        format!("{name}_{target}"),
+        format!("{name}_{}", target.replace('-', "_")),
+        name.to_string(),
+    ];
+    keys.iter()
+        .inspect(|key| println!("cargo:rerun-if-env-changed={key}"))
+        .find_map(env::var_os)
+}
+
+/// Finds libfprint, from the `LIBFPRINT_LIB_DIR` and `LIBFPRINT_INCLUDE_DIR` overrides or from pkg-config.
+/// pkg-config honors `PKG_CONFIG_SYSROOT_DIR` and the variables of the target when cross-compiling.
+fn probe(link: bool) -> Libfprint {
+    if let Some(lib_dirs) = target_env("LIBFPRINT_LIB_DIR") {
+        // The include directories of libfprint, GLib and GUsb, separated like PATH
+        let include_dirs = target_env("LIBFPRINT_INCLUDE_DIR")
+            .expect("LIBFPRINT_INCLUDE_DIR must be set with LIBFPRINT_LIB_DIR");
+        if link {
+            for dir in env::split_paths(&lib_dirs) {
+                println!("cargo:rustc-link-search=native={}", dir.display());
+            }
+            println!("cargo:rustc-link-lib=fprint-2");
+            println!("cargo:rustc-link-lib=gusb");
+        }
+        return Libfprint {
+            include_paths: env::split_paths(&include_dirs).collect(),
+            version: target_env("LIBFPRINT_VERSION").and_then(|version| version.into_string().ok()),
+        };
+    }
+
+    let libfprint = pkg_config::Config::new()
+        .atleast_version("1.90")
+        .cargo_metadata(link)
+        .probe("libfprint-2")
+        .unwrap_or_else(|err| panic!("libfprint-rs requires libfprint 1.90 or newer: {err}"));
+    // libfprint drives USB devices with GUsb, used to reset them
+    let gusb = pkg_config::Config::new()
+        .cargo_metadata(link)
+        .probe("gusb")
+        .unwrap();
+    Libfprint {
+        include_paths: libfprint
+            .include_paths
+            .into_iter()
+            .chain(gusb.include_paths)
+            .collect(),
+        version: Some(libfprint.version),
+    }
+}
 
 fn main() {
-    let libfprint = pkg_config::probe_library("libfprint-2").unwrap();
+    // Without linking, libfprint is loaded at runtime, see `load`
+    let dlopen = cfg!(feature = "dlopen");
+
+    let libfprint = probe(!dlopen);
+    // Exposed to libfprint-rs as DEP_LIBFPRINT_2_VERSION, to enable the APIs of newer versions
+    if let Some(version) = &libfprint.version {
+        println!("cargo:version={version}");
+    }
+    // bindgen passes the target to clang, the headers of the C library come from the sysroot
+    let sysroot = target_env("PKG_CONFIG_SYSROOT_DIR");
 
-    let bindings = bindgen::Builder::default()
+    let builder = bindgen::Builder::default()
         .allowlist_function("fp_device.*")
         .allowlist_function("fp_print.*")
         .allowlist_function("fp_context.*")
         .allowlist_function("fp_image.*")
+        .allowlist_function("fp_minutia.*")
         .allowlist_function("g_ptr_array_free")
+        .allowlist_function("g_usb_device_open")
+        .allowlist_function("g_usb_device_close")
+        .allowlist_function("g_usb_device_reset")
         .allowlist_type("FpContextClass")
         .allowlist_type("FpPrint_autoptr")
         .allowlist_type("FpDeviceClass")
@@ -20,12 +93,64 @@ fn main() {
                 .iter()
                 .map(|path| format!("-I{}", path.to_string_lossy())),
         )
+        .clang_args(sysroot.map(|sysroot| format!("--sysroot={}", sysroot.to_string_lossy())))
         .header("wrapper.h")
-        .parse_callbacks(Box::new(bindgen::CargoCallbacks))
-        .generate()
-        .expect("Unable to generate bindings");
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks));
     let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    #[cfg(not(feature = "dlopen"))]
+    let bindings = builder.generate().expect("Unable to generate bindings");
+    #[cfg(feature = "dlopen")]
+    let bindings = {
+        let declarations = builder
+            .clone()
+            .generate()
+            .expect("Unable to generate bindings");
+        std::fs::write(out_path.join("shims.rs"), shims(&declarations.to_string()))
+            .expect("Couldn't write shims!");
+        // The symbols missing from older libfprints only fail when they are called
+        builder
+            .dynamic_library_name("Library")
+            .dynamic_link_require_all(false)
+            .generate()
+            .expect("Unable to generate bindings")
+    };
     bindings
         .write_to_file(out_path.join("libfprint.rs"))
         .expect("Couldn't write bindings!");
 }
+
+/// Generates a function calling the loaded library for each declared function, so the dynamically loaded
+/// bindings keep the API of the linked ones.
+#[cfg(feature = "dlopen")]
+fn shims(declarations: &str) -> String {
+    let file = syn::parse_file(declarations).expect("Unable to parse bindings");
+    let functions = file
+        .items
+        .into_iter()
+        .filter_map(|item| match item {
+            syn::Item::ForeignMod(block) => Some(block.items),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|item| match item {
+            syn::ForeignItem::Fn(function) => Some(function.sig),
+            _ => None,
+        });
+
+    let shims = functions.map(|sig| {
+        let name = &sig.ident;
+        let inputs = &sig.inputs;
+        let output = &sig.output;
+        let args = inputs.iter().filter_map(|arg| match arg {
+            syn::FnArg::Typed(arg) => Some(&arg.pat),
+            syn::FnArg::Receiver(_) => None,
+        });
+        quote::quote! {
+            pub unsafe extern "C" fn #name(#inputs) #output {
+                library().#name(#(#args),*)
+            }
+        }
+    });
+    quote::quote!(#(#shims)*).to_string()
+}
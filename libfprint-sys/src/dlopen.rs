@@ -0,0 +1,27 @@
+//! Runtime loading of libfprint, with the `dlopen` feature. Every function loads the library on its first
+//! call, and panics if it can not be loaded: call `load` first to handle a missing libfprint.
+use std::sync::OnceLock;
+
+use super::*;
+
+/// The file name of libfprint.
+pub const LIBRARY_NAME: &str = "libfprint-2.so.2";
+
+static LIBRARY: OnceLock<Result<Library, libloading::Error>> = OnceLock::new();
+
+/// Load libfprint, once. The error of the first attempt is returned on every call.
+pub fn load() -> Result<(), &'static libloading::Error> {
+    LIBRARY
+        .get_or_init(|| unsafe { Library::new(LIBRARY_NAME) })
+        .as_ref()
+        .map(|_| ())
+}
+
+fn library() -> &'static Library {
+    match LIBRARY.get_or_init(|| unsafe { Library::new(LIBRARY_NAME) }) {
+        Ok(library) => library,
+        Err(err) => panic!("{} could not be loaded: {}", LIBRARY_NAME, err),
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/shims.rs"));
@@ -2,3 +2,8 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 include!(concat!(env!("OUT_DIR"), "/libfprint.rs"));
+
+#[cfg(feature = "dlopen")]
+mod dlopen;
+#[cfg(feature = "dlopen")]
+pub use dlopen::*;
@@ -1 +1,2 @@
-#include <libfprint-2/fprint.h>
\ No newline at end of file
+#include <libfprint-2/fprint.h>
+#include <gusb.h>
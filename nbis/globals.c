@@ -0,0 +1,24 @@
+/*
+ * Globals of the bozorth3 library that NBIS leaves for the bozorth3 program to define, set to the defaults
+ * of that program. They are only read by the library.
+ */
+#include <stdio.h>
+#include <bozorth.h>
+
+int m1_xyt = 0;
+int max_minutiae = DEFAULT_BOZORTH_MINUTIAE;
+int min_computable_minutiae = MIN_COMPUTABLE_BOZORTH_MINUTIAE;
+
+int verbose_main = 0;
+int verbose_load = 0;
+int verbose_bozorth = 0;
+int verbose_threshold = 0;
+
+FILE *errorfp = NULL;
+
+/* Sends the warnings of bozorth3 to the standard error, errorfp can not be initialized statically. */
+void fprs_nbis_init(void)
+{
+	if (errorfp == NULL)
+		errorfp = stderr;
+}
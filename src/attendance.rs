@@ -0,0 +1,140 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant, SystemTime},
+};
+
+use gio::{prelude::CancellableExt, Cancellable};
+
+use crate::{
+    device::{FpDevice, FpIdentifyResult},
+    error::FpRetryError,
+    print::FpPrint,
+};
+
+/// A match reported by `FpAttendance::run`.
+#[derive(Debug, Clone)]
+pub struct FpAttendanceEvent {
+    /// The username of the matched print, `None` if it has none.
+    pub username: Option<String>,
+    /// The matched print of the gallery.
+    pub print: FpPrint,
+    /// When the finger was identified.
+    pub at: SystemTime,
+}
+
+#[cfg(not(doctest))]
+/// Identifies fingers continuously, for time and attendance terminals.
+///
+/// Each identified user is reported once per cooldown window: a user who scans again, or keeps their finger on
+/// the sensor, within the window is ignored. Prints without a username are reported on every match.
+/// # Example:
+/// ```no_run
+/// use std::time::Duration;
+/// use libfprint_rs::{Cancellable, FpAttendance, FpContext};
+///
+/// let ctx = FpContext::new();
+/// let devices = ctx.devices();
+/// let dev = devices.get(0).unwrap();
+/// dev.open_sync(None).unwrap();
+///
+/// let gallery = load_prints();
+/// let mut attendance = FpAttendance::new(Duration::from_secs(60));
+/// attendance.run(&dev, &gallery, &Cancellable::new(), |event| {
+///     println!("{:?} clocked in at {:?}", event.username, event.at);
+/// })?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct FpAttendance {
+    cooldown: Duration,
+    last_seen: HashMap<String, Instant>,
+}
+
+impl FpAttendance {
+    /// Creates an attendance loop ignoring the repeated matches of a user within `cooldown`.
+    pub fn new(cooldown: Duration) -> Self {
+        FpAttendance {
+            cooldown,
+            last_seen: HashMap::new(),
+        }
+    }
+
+    /// Identify fingers among `gallery` until `cancellable` is cancelled, calling `on_match` for every match
+    /// outside the cooldown of its user. Unknown fingers and scans to retry are ignored.
+    ///
+    /// Returns `Ok` once cancelled, or the first error that is not a retry.
+    pub fn run(
+        &mut self,
+        device: &FpDevice,
+        gallery: &[FpPrint],
+        cancellable: &Cancellable,
+        mut on_match: impl FnMut(FpAttendanceEvent),
+    ) -> Result<(), crate::GError> {
+        while !cancellable.is_cancelled() {
+            match device.identify_sync(gallery, Some(cancellable), None, None::<()>) {
+                Ok(FpIdentifyResult {
+                    matched: Some((_, print)),
+                    ..
+                }) => {
+                    let username = print.username();
+                    if self.admit(username.as_deref(), Instant::now()) {
+                        on_match(FpAttendanceEvent {
+                            username,
+                            print,
+                            at: SystemTime::now(),
+                        });
+                    }
+                }
+                Ok(_) => {}
+                Err(err) if err.kind::<FpRetryError>().is_some() => {}
+                Err(_) if cancellable.is_cancelled() => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget the last matches, so every user is reported on their next match.
+    pub fn reset(&mut self) {
+        self.last_seen.clear();
+    }
+
+    /// Whether a match of `username` at `now` is reported, recording it.
+    fn admit(&mut self, username: Option<&str>, now: Instant) -> bool {
+        let Some(username) = username else {
+            return true;
+        };
+        // Drop the users whose cooldown is over, the map only holds the recent ones
+        let cooldown = self.cooldown;
+        self.last_seen
+            .retain(|_, seen| now.saturating_duration_since(*seen) < cooldown);
+        if self.last_seen.contains_key(username) {
+            return false;
+        }
+        self.last_seen.insert(username.to_string(), now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::FpAttendance;
+
+    #[test]
+    fn cooldown() {
+        let start = Instant::now();
+        let at = |s: u64| start + Duration::from_secs(s);
+        let mut attendance = FpAttendance::new(Duration::from_secs(60));
+
+        assert!(attendance.admit(Some("alice"), at(0)));
+        assert!(!attendance.admit(Some("alice"), at(30)));
+        assert!(attendance.admit(Some("bob"), at(30)));
+        assert!(attendance.admit(None, at(31)));
+        assert!(attendance.admit(None, at(32)));
+        // The cooldown starts from the reported match, not from the ignored ones
+        assert!(attendance.admit(Some("alice"), at(60)));
+        assert!(!attendance.admit(Some("bob"), at(89)));
+        assert!(attendance.admit(Some("bob"), at(90)));
+    }
+}
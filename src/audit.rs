@@ -0,0 +1,68 @@
+//! Audit events of enrollments and verifications, for compliance logging.
+//!
+//! An `AuditSink` is given to the layers running the operations, e.g. `FpDeviceManager::set_audit_sink`, which
+//! report every enrollment, match and failure to it, so the sink does not need to wrap every call.
+//!
+//! # Example:
+//! ```no_run
+//! use libfprint_rs::{audit::{AuditRecord, AuditSink}, FpContext, FpDeviceManager};
+//!
+//! struct Syslog;
+//!
+//! impl AuditSink for Syslog {
+//!     fn on_match(&self, record: &AuditRecord) {
+//!         println!("{:?} matched on {:?} at {:?}", record.username, record.device, record.at);
+//!     }
+//! }
+//!
+//! let ctx = FpContext::new();
+//! let mut manager = FpDeviceManager::new(&ctx);
+//! manager.set_audit_sink(Syslog);
+//! ```
+use std::time::SystemTime;
+
+use crate::{
+    device::{FpDevice, FpDeviceId},
+    error::FpError,
+    finger::FpFinger,
+    print::FpPrint,
+};
+
+/// The subject of an audit event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// The device the operation ran on.
+    pub device: FpDeviceId,
+    /// The user of the print, if known.
+    pub username: Option<String>,
+    /// The finger of the print, if known.
+    pub finger: Option<FpFinger>,
+    /// When the event happened.
+    pub at: SystemTime,
+}
+
+impl AuditRecord {
+    /// Creates a record of an event of `device` happening now, about `print` if given.
+    pub fn new(device: &FpDevice, print: Option<&FpPrint>) -> Self {
+        AuditRecord {
+            device: device.id(),
+            username: print.and_then(FpPrint::username),
+            finger: print.map(FpPrint::finger),
+            at: SystemTime::now(),
+        }
+    }
+}
+
+/// Receives the audit events of the operations. Every method does nothing by default.
+pub trait AuditSink {
+    /// An enrollment started, the record holds the user and finger of the template.
+    fn on_enroll_started(&self, _record: &AuditRecord) {}
+    /// An enrollment completed.
+    fn on_enroll_completed(&self, _record: &AuditRecord) {}
+    /// A finger matched, the record holds the user and finger of the matching print.
+    fn on_match(&self, _record: &AuditRecord) {}
+    /// A finger did not match, the record holds the user and finger of the verified print, none for identify.
+    fn on_no_match(&self, _record: &AuditRecord) {}
+    /// An operation failed.
+    fn on_error(&self, _record: &AuditRecord, _error: &FpError) {}
+}
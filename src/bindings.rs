@@ -0,0 +1,448 @@
+//! UniFFI bindings of the `FpDeviceManager`, so Kotlin, Swift or Python frontends can reuse the same
+//! fingerprint backend (requires the `uniffi` feature).
+//!
+//! The foreign code creates a `Backend` with a `Listener`. The backend runs a `FpDeviceManager` on its own
+//! thread and main context: the methods only queue operations, and every result is delivered to
+//! `Listener::on_event` from that thread. The devices are only driven with the asynchronous API, dispatched by
+//! that main context: the `_sync` operations would iterate the global default main context instead. The
+//! bindings are generated from the built library with `uniffi-bindgen generate --library`.
+//!
+//! As with the `dbus` module, fingers are passed as strings, e.g. `"right index"`, and prints as the bytes of
+//! `FpPrint::serialize`, validated with `FpSerializedPrint::deserialize_for`. Devices are named by `FpDevice::persistent_id`. Operations given no device run
+//! on the device returned by `FpDeviceManager::select`.
+//! # Example:
+//! ```kotlin
+//! class Ui : Listener {
+//!     override fun onEvent(event: Event) {
+//!         if (event is Event.Completed && event.outcome is Outcome.Enrolled) {
+//!             savePrint(event.outcome.print)
+//!         }
+//!     }
+//! }
+//!
+//! val backend = Backend(Ui())
+//! backend.open(null)
+//! backend.enroll(null, "bruce", "right index")
+//! ```
+use std::{
+    future::poll_fn,
+    pin::Pin,
+    sync::{mpsc as sync_mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use futures_channel::mpsc;
+use futures_core::Stream;
+
+use crate::{
+    context::{FpContext, SHUTDOWN_GRACE},
+    device::{FpDevice, FpMatchOutcome},
+    error::FpError,
+    finger::FpFinger,
+    manager::{FpDeviceManager, FpManagerEvent, FpOperation, FpOperationResult},
+    print::FpPrint,
+    serialized::FpSerializedPrint,
+};
+
+/// A managed device.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct DeviceInfo {
+    /// The identifier of the device, see `FpDevice::persistent_id`.
+    pub id: String,
+    /// The human readable name of the device.
+    pub name: String,
+    /// The name of the driver of the device.
+    pub driver: String,
+}
+
+impl From<&FpDevice> for DeviceInfo {
+    fn from(device: &FpDevice) -> Self {
+        DeviceInfo {
+            id: device.persistent_id(),
+            name: device.name(),
+            driver: device.driver(),
+        }
+    }
+}
+
+/// The error of a `Backend` call or operation.
+#[derive(Debug, Clone, uniffi::Error)]
+pub enum BackendError {
+    /// The operation failed, `code` is the `FpError::code` of the error.
+    Failed { code: u32, message: String },
+    /// No managed device has the given identifier, or no device is managed.
+    NoDevice { id: Option<String> },
+    /// An argument is invalid, e.g. an unknown finger.
+    InvalidArgument { message: String },
+    /// The thread of the backend has stopped.
+    Stopped,
+}
+
+impl std::fmt::Display for BackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendError::Failed { code, message } => write!(f, "{}: {}", code, message),
+            BackendError::NoDevice { id: Some(id) } => write!(f, "No managed device {}", id),
+            BackendError::NoDevice { id: None } => write!(f, "No managed device"),
+            BackendError::InvalidArgument { message } => write!(f, "{}", message),
+            BackendError::Stopped => write!(f, "The backend has stopped"),
+        }
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+impl From<FpError> for BackendError {
+    fn from(error: FpError) -> Self {
+        BackendError::Failed {
+            code: error.code(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<crate::GError> for BackendError {
+    fn from(error: crate::GError) -> Self {
+        FpError::from(error).into()
+    }
+}
+
+/// The result of a successful operation.
+#[derive(Debug, Clone, uniffi::Enum)]
+pub enum Outcome {
+    /// The device was opened.
+    Opened,
+    /// The device was closed.
+    Closed,
+    /// The serialized print that was enrolled.
+    Enrolled { print: Vec<u8> },
+    /// Whether the finger matches the verified print.
+    Verified { matched: bool },
+    /// The scan failed for a reason the user can fix, e.g. the finger was not centered.
+    Retry { reason: String },
+    /// The username and the finger of the identified print, if any.
+    Identified {
+        username: Option<String>,
+        finger: Option<String>,
+    },
+    /// The captured image, one byte per pixel by rows.
+    Captured {
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+    },
+}
+
+/// An event of a `Backend`.
+#[derive(Debug, Clone, uniffi::Enum)]
+pub enum Event {
+    /// A device was plugged and is now managed.
+    Added { device: DeviceInfo },
+    /// A device was unplugged, its queued operations are dropped.
+    Removed { device: DeviceInfo },
+    /// An operation completed on `device`.
+    Completed {
+        device: DeviceInfo,
+        outcome: Outcome,
+    },
+    /// An operation failed on `device`, `code` is the `FpError::code` of the error.
+    Failed {
+        device: DeviceInfo,
+        code: u32,
+        message: String,
+    },
+}
+
+/// Receives the events of a `Backend`, on the thread of the backend.
+#[uniffi::export(with_foreign)]
+pub trait Listener: Send + Sync {
+    /// Called for every event of the backend.
+    fn on_event(&self, event: Event);
+}
+
+/// An operation sent to the thread of the backend. Prints are serialized, GObjects stay on that thread.
+enum Operation {
+    Open,
+    Close,
+    Enroll(String, FpFinger),
+    Verify(Vec<u8>),
+    Identify(Vec<Vec<u8>>),
+    Capture(bool),
+}
+
+type Reply<T> = sync_mpsc::Sender<Result<T, BackendError>>;
+
+enum Command {
+    Devices(Reply<Vec<DeviceInfo>>),
+    Start(Option<String>, Operation, Reply<DeviceInfo>),
+    Cancel(Option<String>),
+    CancelAll,
+    Unlock(String),
+}
+
+/// A fingerprint backend running a `FpDeviceManager` on its own thread. Dropping the backend cancels the
+/// running operations and closes the devices.
+#[derive(uniffi::Object)]
+pub struct Backend {
+    commands: mpsc::UnboundedSender<Command>,
+    worker: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+#[uniffi::export]
+impl Backend {
+    /// Start the backend, delivering its events to `listener`.
+    #[uniffi::constructor]
+    pub fn new(listener: Arc<dyn Listener>) -> Arc<Self> {
+        let (commands, receiver) = mpsc::unbounded();
+        let worker = thread::spawn(move || run(listener, receiver));
+        Arc::new(Backend {
+            commands,
+            worker: Mutex::new(Some(worker)),
+        })
+    }
+
+    /// The managed devices.
+    pub fn devices(&self) -> Result<Vec<DeviceInfo>, BackendError> {
+        self.call(Command::Devices)
+    }
+    /// Queue opening the device, returning it. The same goes for the other operations.
+    pub fn open(&self, device: Option<String>) -> Result<DeviceInfo, BackendError> {
+        self.start(device, Operation::Open)
+    }
+    /// Queue closing the device.
+    pub fn close(&self, device: Option<String>) -> Result<DeviceInfo, BackendError> {
+        self.start(device, Operation::Close)
+    }
+    /// Queue enrolling `finger` of `username`.
+    pub fn enroll(
+        &self,
+        device: Option<String>,
+        username: String,
+        finger: String,
+    ) -> Result<DeviceInfo, BackendError> {
+        let finger = match finger.parse() {
+            Ok(FpFinger::Unknown) | Err(()) => {
+                return Err(BackendError::InvalidArgument {
+                    message: format!("Unknown finger {}", finger),
+                })
+            }
+            Ok(finger) => finger,
+        };
+        self.start(device, Operation::Enroll(username, finger))
+    }
+    /// Queue verifying the scanned finger against the serialized `print`.
+    pub fn verify(
+        &self,
+        device: Option<String>,
+        print: Vec<u8>,
+    ) -> Result<DeviceInfo, BackendError> {
+        self.start(device, Operation::Verify(print))
+    }
+    /// Queue identifying the scanned finger among the serialized `prints`.
+    pub fn identify(
+        &self,
+        device: Option<String>,
+        prints: Vec<Vec<u8>>,
+    ) -> Result<DeviceInfo, BackendError> {
+        self.start(device, Operation::Identify(prints))
+    }
+    /// Queue capturing an image, waiting for a finger if `wait_for_finger`.
+    pub fn capture(
+        &self,
+        device: Option<String>,
+        wait_for_finger: bool,
+    ) -> Result<DeviceInfo, BackendError> {
+        self.start(device, Operation::Capture(wait_for_finger))
+    }
+    /// Cancel the operation running on the device, the following queued operations still run.
+    pub fn cancel(&self, device: Option<String>) {
+        let _ = self.commands.unbounded_send(Command::Cancel(device));
+    }
+    /// Cancel the operations running on every device.
+    pub fn cancel_all(&self) {
+        let _ = self.commands.unbounded_send(Command::CancelAll);
+    }
+    /// Lift the lockout of `username`, see `FpDeviceManager::unlock`.
+    pub fn unlock(&self, username: String) {
+        let _ = self.commands.unbounded_send(Command::Unlock(username));
+    }
+}
+
+impl Backend {
+    fn start(
+        &self,
+        device: Option<String>,
+        operation: Operation,
+    ) -> Result<DeviceInfo, BackendError> {
+        self.call(|reply| Command::Start(device, operation, reply))
+    }
+    /// Send a command to the thread of the backend and wait for its reply.
+    fn call<T>(&self, command: impl FnOnce(Reply<T>) -> Command) -> Result<T, BackendError> {
+        let (reply, response) = sync_mpsc::channel();
+        self.commands
+            .unbounded_send(command(reply))
+            .map_err(|_| BackendError::Stopped)?;
+        response.recv().map_err(|_| BackendError::Stopped)?
+    }
+}
+
+impl Drop for Backend {
+    fn drop(&mut self) {
+        self.commands.close_channel();
+        let worker = self
+            .worker
+            .get_mut()
+            .unwrap_or_else(|err| err.into_inner())
+            .take();
+        if let Some(worker) = worker {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// The thread of the backend: runs the commands until the backend is dropped.
+fn run(listener: Arc<dyn Listener>, mut commands: mpsc::UnboundedReceiver<Command>) {
+    let main_context = glib::MainContext::new();
+    let _ = main_context.with_thread_default(|| {
+        let context = FpContext::new();
+        let mut manager = FpDeviceManager::new(&context);
+        let mut events = manager.events().expect("the events of a new manager");
+        main_context.spawn_local(async move {
+            while let Some(event) = poll_fn(|cx| Pin::new(&mut events).poll_next(cx)).await {
+                listener.on_event(to_event(event));
+            }
+        });
+
+        main_context.block_on(async {
+            while let Some(command) = poll_fn(|cx| Pin::new(&mut commands).poll_next(cx)).await {
+                handle(&manager, command);
+            }
+            // `FpContext::shutdown` closes the devices with the `_sync` operations, which iterate the global
+            // default main context: close them from the queues of the manager, on this context
+            manager.cancel_all();
+            for device in manager.devices().iter().filter(|device| device.is_open()) {
+                manager.start(device, FpOperation::Close);
+            }
+            let started = Instant::now();
+            while manager.devices().iter().any(FpDevice::is_open)
+                && started.elapsed() < SHUTDOWN_GRACE
+            {
+                glib::timeout_future(Duration::from_millis(10)).await;
+            }
+        });
+        drop(manager);
+        drop(context);
+    });
+}
+
+fn handle(manager: &FpDeviceManager, command: Command) {
+    match command {
+        Command::Devices(reply) => {
+            let devices = manager.devices().iter().map(DeviceInfo::from).collect();
+            let _ = reply.send(Ok(devices));
+        }
+        Command::Start(id, operation, reply) => {
+            let started = find(manager, id).and_then(|device| {
+                let operation = to_operation(&device, operation)?;
+                match manager.start(&device, operation) {
+                    true => Ok(DeviceInfo::from(&device)),
+                    false => Err(BackendError::NoDevice {
+                        id: Some(device.persistent_id()),
+                    }),
+                }
+            });
+            let _ = reply.send(started);
+        }
+        Command::Cancel(id) => {
+            if let Ok(device) = find(manager, id) {
+                manager.cancel(&device);
+            }
+        }
+        Command::CancelAll => manager.cancel_all(),
+        Command::Unlock(username) => manager.unlock(&username),
+    }
+}
+
+/// The device named `id`, or the selected device.
+fn find(manager: &FpDeviceManager, id: Option<String>) -> Result<FpDevice, BackendError> {
+    let device = match &id {
+        Some(id) => manager
+            .devices()
+            .into_iter()
+            .find(|device| device.persistent_id() == *id),
+        None => manager.select(),
+    };
+    device.ok_or(BackendError::NoDevice { id })
+}
+
+fn to_operation(device: &FpDevice, operation: Operation) -> Result<FpOperation, BackendError> {
+    Ok(match operation {
+        Operation::Open => FpOperation::Open,
+        Operation::Close => FpOperation::Close,
+        Operation::Enroll(username, finger) => FpOperation::Enroll(
+            FpPrint::builder(device)
+                .username(&username)
+                .finger(finger)
+                .build()?,
+        ),
+        // The prints come from the foreign code: validate them, and check they were enrolled on the device
+        Operation::Verify(print) => {
+            FpOperation::Verify(FpSerializedPrint::from_bytes(print).deserialize_for(device)?)
+        }
+        Operation::Identify(prints) => FpOperation::Identify(
+            prints
+                .into_iter()
+                .map(|print| FpSerializedPrint::from_bytes(print).deserialize_for(device))
+                .collect::<Result<_, _>>()?,
+        ),
+        Operation::Capture(wait_for_finger) => FpOperation::Capture(wait_for_finger),
+    })
+}
+
+fn to_event(event: FpManagerEvent) -> Event {
+    match event {
+        FpManagerEvent::Added(device) => Event::Added {
+            device: DeviceInfo::from(&device),
+        },
+        FpManagerEvent::Removed(device) => Event::Removed {
+            device: DeviceInfo::from(&device),
+        },
+        FpManagerEvent::Completed { device, result } => {
+            let device = DeviceInfo::from(&device);
+            match result.map_err(FpError::from).and_then(to_outcome) {
+                Ok(outcome) => Event::Completed { device, outcome },
+                Err(error) => Event::Failed {
+                    device,
+                    code: error.code(),
+                    message: error.to_string(),
+                },
+            }
+        }
+    }
+}
+
+fn to_outcome(result: FpOperationResult) -> Result<Outcome, FpError> {
+    Ok(match result {
+        FpOperationResult::Opened => Outcome::Opened,
+        FpOperationResult::Closed => Outcome::Closed,
+        FpOperationResult::Enrolled(print) => Outcome::Enrolled {
+            print: print.serialize()?,
+        },
+        FpOperationResult::Verified(FpMatchOutcome::Retry(reason)) => Outcome::Retry {
+            reason: reason.to_string(),
+        },
+        FpOperationResult::Verified(outcome) => Outcome::Verified {
+            matched: outcome.is_match(),
+        },
+        FpOperationResult::Identified(result) => Outcome::Identified {
+            username: result.print().and_then(FpPrint::username),
+            finger: result.print().map(|print| print.finger().to_string()),
+        },
+        FpOperationResult::Captured(image) => Outcome::Captured {
+            width: image.width(),
+            height: image.height(),
+            data: image.data(),
+        },
+    })
+}
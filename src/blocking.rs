@@ -0,0 +1,170 @@
+//! The blocking API.
+//!
+//! `blocking::FpDevice` wraps a device and only exposes its blocking operations, under the same names as the
+//! operations of `futures::FpDevice` (e.g. `open`, `enroll`, `verify`). They block the calling thread until the
+//! operation completes, which may take as long as the user takes to place their finger on the sensor, so they
+//! must not be called from an async executor thread, use the `futures` module instead (requires the `futures`
+//! feature).
+//!
+//! A device runs one operation at a time: calling an operation on a device busy with another one, e.g. from
+//! another thread, fails with the `FP_DEVICE_ERROR_BUSY` error, unless `FpDevice::set_queue_operations` makes
+//! it wait.
+//! # Example:
+//! ```no_run
+//! use libfprint_rs::{blocking, FpContext, FpPrint};
+//!
+//! let ctx = FpContext::new();
+//! let dev = blocking::FpDevice::from(ctx.devices().remove(0));
+//!
+//! dev.open(None).unwrap();
+//! let template = FpPrint::new(dev.device());
+//! let print = dev.enroll(template, None, None, None::<()>).unwrap();
+//! let outcome = dev.verify(&print, None, None, None::<()>).unwrap();
+//! dev.close(None).unwrap();
+//! ```
+use gio::Cancellable;
+
+use crate::{image::FpImage, print::FpPrint};
+
+pub use crate::device::{
+    FpEnrollProgress, FpEnrollSet, FpExclusiveOutcome, FpFingerPrompt, FpIdentifyResult, FpMatchCb,
+    FpMatchOutcome,
+};
+
+/// A device exposing the blocking operations only, see the module documentation. The other methods of the
+/// device, e.g. its name or features, are reached through `FpDevice::device`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FpDevice {
+    device: crate::FpDevice,
+}
+
+impl From<crate::FpDevice> for FpDevice {
+    fn from(device: crate::FpDevice) -> Self {
+        Self { device }
+    }
+}
+
+impl AsRef<crate::FpDevice> for FpDevice {
+    fn as_ref(&self) -> &crate::FpDevice {
+        &self.device
+    }
+}
+
+impl FpDevice {
+    /// The wrapped device.
+    pub fn device(&self) -> &crate::FpDevice {
+        &self.device
+    }
+    /// Returns the wrapped device.
+    pub fn into_device(self) -> crate::FpDevice {
+        self.device
+    }
+
+    /// Open the device. See `FpDevice::open_sync`.
+    pub fn open(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::GError> {
+        self.device.open_sync(cancellable)
+    }
+    /// Close the device. See `FpDevice::close_sync`.
+    pub fn close(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::GError> {
+        self.device.close_sync(cancellable)
+    }
+    #[cfg(libfprint_1_94)]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    /// Prepare the device for suspend. See `FpDevice::suspend_sync`.
+    pub fn suspend(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::GError> {
+        self.device.suspend_sync(cancellable)
+    }
+    #[cfg(libfprint_1_94)]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    /// Resume the device after suspend. See `FpDevice::resume_sync`.
+    pub fn resume(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::GError> {
+        self.device.resume_sync(cancellable)
+    }
+    /// Enroll a new print. See `FpDevice::enroll_sync`.
+    pub fn enroll<T>(
+        &self,
+        template: FpPrint,
+        cancellable: Option<&Cancellable>,
+        progress_cb: Option<FpEnrollProgress<T>>,
+        progress_data: Option<T>,
+    ) -> Result<FpPrint, crate::GError> {
+        self.device
+            .enroll_sync(template, cancellable, progress_cb, progress_data)
+    }
+    /// Verify a given print. See `FpDevice::verify_sync`.
+    pub fn verify<T>(
+        &self,
+        enrolled_print: &FpPrint,
+        cancellable: Option<&Cancellable>,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+    ) -> Result<FpMatchOutcome, crate::GError> {
+        self.device
+            .verify_sync(enrolled_print, cancellable, match_cb, match_data)
+    }
+    /// Verify a given print, checking that the finger does not belong to another user. See
+    /// `FpDevice::verify_exclusive_sync`.
+    pub fn verify_exclusive<'a, T, I, P>(
+        &self,
+        enrolled_print: &FpPrint,
+        exclusion: I,
+        cancellable: Option<&Cancellable>,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+    ) -> Result<FpExclusiveOutcome, crate::GError>
+    where
+        I: IntoIterator<Item = &'a P>,
+        P: AsRef<FpPrint> + 'a,
+    {
+        self.device.verify_exclusive_sync(
+            enrolled_print,
+            exclusion,
+            cancellable,
+            match_cb,
+            match_data,
+        )
+    }
+    /// Identify a print among a gallery. See `FpDevice::identify_sync`.
+    pub fn identify<'a, T, I, P>(
+        &self,
+        prints: I,
+        cancellable: Option<&Cancellable>,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+    ) -> Result<FpIdentifyResult, crate::GError>
+    where
+        I: IntoIterator<Item = &'a P>,
+        P: AsRef<FpPrint> + 'a,
+    {
+        self.device
+            .identify_sync(prints, cancellable, match_cb, match_data)
+    }
+    /// Capture an image. See `FpDevice::capture_sync`.
+    pub fn capture(
+        &self,
+        wait_for_finger: bool,
+        cancellable: Option<&Cancellable>,
+    ) -> Result<FpImage, crate::GError> {
+        self.device.capture_sync(wait_for_finger, cancellable)
+    }
+    /// Delete a print from the device storage. See `FpDevice::delete_print_sync`.
+    pub fn delete_print(
+        &self,
+        enrolled_print: &FpPrint,
+        cancellable: Option<&Cancellable>,
+    ) -> Result<(), crate::GError> {
+        self.device.delete_print_sync(enrolled_print, cancellable)
+    }
+    /// List the prints stored on the device. See `FpDevice::list_prints_sync`.
+    pub fn list_prints(
+        &self,
+        cancellable: Option<&Cancellable>,
+    ) -> Result<Vec<FpPrint>, crate::GError> {
+        self.device.list_prints_sync(cancellable)
+    }
+    #[cfg(libfprint_1_96)]
+    /// Delete every print stored on the device. See `FpDevice::clear_storage_sync`.
+    pub fn clear_storage(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::GError> {
+        self.device.clear_storage_sync(cancellable)
+    }
+}
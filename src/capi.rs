@@ -0,0 +1,394 @@
+//! A small C ABI over the crate, for applications in other languages that use libfprint as a fingerprint
+//! backend (requires the `capi` feature). The declarations are in `include/fprs.h`.
+//!
+//! The library is built as a shared object with
+//! `cargo rustc --release --features capi --crate-type cdylib`.
+//!
+//! Every object is opaque and is freed by its `_free` function. Functions returning an `int` return
+//! `FPRS_OK` on success and `FPRS_ERROR` on failure. On failure, `fprs_last_error` describes the error of the
+//! calling thread. The functions block the calling thread and no panic crosses the ABI.
+//! # Example:
+//! ```c
+//! fprs_context *ctx = fprs_context_new();
+//! fprs_device *dev = fprs_context_device(ctx, 0);
+//! fprs_print *print = NULL;
+//!
+//! if (dev && fprs_device_open(dev) == FPRS_OK &&
+//!     fprs_device_enroll(dev, "bruce", FPRS_FINGER_RIGHT_INDEX, &print) == FPRS_OK) {
+//!     uint8_t *data;
+//!     size_t len;
+//!     fprs_print_serialize(print, &data, &len);
+//!     save(data, len);
+//!     fprs_bytes_free(data, len);
+//! } else {
+//!     fprintf(stderr, "%s\n", fprs_last_error());
+//! }
+//! ```
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{
+    context::FpContext, device::FpDevice, device::FpMatchOutcome, finger::FpFinger, print::FpPrint,
+    serialized::FpSerializedPrint,
+};
+
+/// The operation succeeded.
+pub const FPRS_OK: c_int = 0;
+/// The operation failed, see `fprs_last_error`.
+pub const FPRS_ERROR: c_int = -1;
+/// The scan failed for a reason the user can fix, e.g. the finger was not centered: the user should try
+/// again. See `fprs_last_error` for the reason.
+pub const FPRS_RETRY: c_int = 1;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Opaque libfprint context.
+pub struct Context(FpContext);
+/// Opaque fingerprint reader.
+pub struct Device(FpDevice);
+/// Opaque enrolled print.
+pub struct Print(FpPrint);
+
+/// Record the error of the calling thread.
+fn set_error(message: impl ToString) {
+    // Interior nul bytes would truncate the message, they are dropped
+    let message = message.to_string().replace('\0', "");
+    let message = CString::new(message).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Run `f` at the ABI boundary, turning its errors and panics into `FPRS_ERROR`.
+fn call(f: impl FnOnce() -> Result<c_int, String>) -> c_int {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(code)) => code,
+        Ok(Err(message)) => {
+            set_error(message);
+            FPRS_ERROR
+        }
+        Err(_) => {
+            set_error("libfprint-rs panicked");
+            FPRS_ERROR
+        }
+    }
+}
+
+/// Borrow the object behind a pointer given by the caller.
+unsafe fn borrow<'a, T>(ptr: *const T, name: &str) -> Result<&'a T, String> {
+    ptr.as_ref().ok_or_else(|| format!("{} is NULL", name))
+}
+
+/// Copy a string to a C string owned by the caller, freed with `fprs_string_free`.
+fn to_c_string(string: String) -> *mut c_char {
+    CString::new(string.replace('\0', ""))
+        .unwrap_or_default()
+        .into_raw()
+}
+
+/// The error of the last failed call on the calling thread, or NULL. The string is owned by the library and
+/// valid until the next call failing on the thread.
+#[no_mangle]
+pub extern "C" fn fprs_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Create a libfprint context, or NULL on failure.
+#[no_mangle]
+pub extern "C" fn fprs_context_new() -> *mut Context {
+    let mut context = ptr::null_mut();
+    call(|| {
+        context = Box::into_raw(Box::new(Context(FpContext::new())));
+        Ok(FPRS_OK)
+    });
+    context
+}
+
+/// Free a context returned by `fprs_context_new`. NULL is ignored.
+///
+/// # Safety
+/// `context` must be NULL or returned by `fprs_context_new`, and not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn fprs_context_free(context: *mut Context) {
+    if !context.is_null() {
+        drop(Box::from_raw(context));
+    }
+}
+
+/// The number of devices known to the context.
+///
+/// # Safety
+/// `context` must be returned by `fprs_context_new`.
+#[no_mangle]
+pub unsafe extern "C" fn fprs_context_device_count(context: *const Context) -> usize {
+    let mut count = 0;
+    call(|| {
+        count = borrow(context, "context")?.0.devices().len();
+        Ok(FPRS_OK)
+    });
+    count
+}
+
+/// The device at `index`, or NULL if it is out of range. It is freed with `fprs_device_free`.
+///
+/// # Safety
+/// `context` must be returned by `fprs_context_new`.
+#[no_mangle]
+pub unsafe extern "C" fn fprs_context_device(context: *const Context, index: usize) -> *mut Device {
+    let mut device = ptr::null_mut();
+    call(|| {
+        let dev = borrow(context, "context")?
+            .0
+            .devices()
+            .into_iter()
+            .nth(index)
+            .ok_or_else(|| format!("No device at index {}", index))?;
+        device = Box::into_raw(Box::new(Device(dev)));
+        Ok(FPRS_OK)
+    });
+    device
+}
+
+/// Free a device returned by `fprs_context_device`. NULL is ignored. The device is not closed.
+///
+/// # Safety
+/// `device` must be NULL or returned by `fprs_context_device`, and not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn fprs_device_free(device: *mut Device) {
+    if !device.is_null() {
+        drop(Box::from_raw(device));
+    }
+}
+
+/// The human readable name of the device, freed with `fprs_string_free`, or NULL on failure.
+///
+/// # Safety
+/// `device` must be returned by `fprs_context_device`.
+#[no_mangle]
+pub unsafe extern "C" fn fprs_device_name(device: *const Device) -> *mut c_char {
+    let mut name = ptr::null_mut();
+    call(|| {
+        name = to_c_string(borrow(device, "device")?.0.name());
+        Ok(FPRS_OK)
+    });
+    name
+}
+
+/// The name of the driver of the device, freed with `fprs_string_free`, or NULL on failure.
+///
+/// # Safety
+/// `device` must be returned by `fprs_context_device`.
+#[no_mangle]
+pub unsafe extern "C" fn fprs_device_driver(device: *const Device) -> *mut c_char {
+    let mut driver = ptr::null_mut();
+    call(|| {
+        driver = to_c_string(borrow(device, "device")?.0.driver());
+        Ok(FPRS_OK)
+    });
+    driver
+}
+
+/// Open the device.
+///
+/// # Safety
+/// `device` must be returned by `fprs_context_device`.
+#[no_mangle]
+pub unsafe extern "C" fn fprs_device_open(device: *const Device) -> c_int {
+    call(|| {
+        let device = borrow(device, "device")?;
+        device.0.open_sync(None).map_err(|err| err.to_string())?;
+        Ok(FPRS_OK)
+    })
+}
+
+/// Close the device.
+///
+/// # Safety
+/// `device` must be returned by `fprs_context_device`.
+#[no_mangle]
+pub unsafe extern "C" fn fprs_device_close(device: *const Device) -> c_int {
+    call(|| {
+        let device = borrow(device, "device")?;
+        device.0.close_sync(None).map_err(|err| err.to_string())?;
+        Ok(FPRS_OK)
+    })
+}
+
+/// Enroll a finger of `username` on the opened device. `finger` is one of the `FPRS_FINGER_` values, the
+/// numbering of libfprint's `FpFinger`. On success `*print` is set to the new print, freed with
+/// `fprs_print_free`.
+///
+/// # Safety
+/// `device` must be returned by `fprs_context_device`, `username` must be a nul terminated string, and
+/// `print` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn fprs_device_enroll(
+    device: *const Device,
+    username: *const c_char,
+    finger: c_int,
+    print: *mut *mut Print,
+) -> c_int {
+    call(|| {
+        let device = &borrow(device, "device")?.0;
+        if username.is_null() || print.is_null() {
+            return Err("username or print is NULL".to_string());
+        }
+        let username = CStr::from_ptr(username)
+            .to_str()
+            .map_err(|_| "username is not valid UTF-8".to_string())?;
+        let finger =
+            FpFinger::try_from(finger).map_err(|_| format!("Invalid finger {}", finger))?;
+
+        let template = FpPrint::new(device);
+        template
+            .set_username(username)
+            .map_err(|err| err.to_string())?;
+        template.set_finger(finger);
+        let enrolled = device
+            .enroll_sync(template, None, None, None::<()>)
+            .map_err(|err| err.to_string())?;
+        *print = Box::into_raw(Box::new(Print(enrolled)));
+        Ok(FPRS_OK)
+    })
+}
+
+/// Verify the finger on the opened device against `print`. On `FPRS_OK`, `*matched` is set to 1 if the
+/// finger matches and 0 otherwise. `FPRS_RETRY` is returned if the scan should be retried.
+///
+/// # Safety
+/// `device` must be returned by `fprs_context_device`, `print` by `fprs_device_enroll` or
+/// `fprs_print_deserialize`, and `matched` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn fprs_device_verify(
+    device: *const Device,
+    print: *const Print,
+    matched: *mut c_int,
+) -> c_int {
+    call(|| {
+        let device = &borrow(device, "device")?.0;
+        let print = &borrow(print, "print")?.0;
+        if matched.is_null() {
+            return Err("matched is NULL".to_string());
+        }
+        match device
+            .verify_sync(print, None, None, None::<()>)
+            .map_err(|err| err.to_string())?
+        {
+            FpMatchOutcome::Matched(_) => *matched = 1,
+            FpMatchOutcome::NotMatched(_) => *matched = 0,
+            FpMatchOutcome::Retry(reason) => {
+                set_error(reason);
+                return Ok(FPRS_RETRY);
+            }
+        }
+        Ok(FPRS_OK)
+    })
+}
+
+/// Serialize `print` for storage. On success `*data` and `*len` are set to the serialized bytes, freed with
+/// `fprs_bytes_free`.
+///
+/// # Safety
+/// `print` must be returned by `fprs_device_enroll` or `fprs_print_deserialize`, and `data` and `len` must be
+/// valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn fprs_print_serialize(
+    print: *const Print,
+    data: *mut *mut u8,
+    len: *mut usize,
+) -> c_int {
+    call(|| {
+        let print = &borrow(print, "print")?.0;
+        if data.is_null() || len.is_null() {
+            return Err("data or len is NULL".to_string());
+        }
+        let bytes = print.serialize().map_err(|err| err.to_string())?;
+        let bytes = Box::into_raw(bytes.into_boxed_slice());
+        *len = bytes.len();
+        *data = bytes.cast();
+        Ok(FPRS_OK)
+    })
+}
+
+/// Load a print serialized by `fprs_print_serialize`, or NULL on failure, e.g. when the data is corrupt or
+/// truncated, see `fprs_last_error`. It is freed with `fprs_print_free`.
+///
+/// # Safety
+/// `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fprs_print_deserialize(data: *const u8, len: usize) -> *mut Print {
+    let mut print = ptr::null_mut();
+    call(|| {
+        if data.is_null() {
+            return Err("data is NULL".to_string());
+        }
+        let bytes = std::slice::from_raw_parts(data, len);
+        let loaded = FpSerializedPrint::from_bytes(bytes.to_vec())
+            .deserialize()
+            .map_err(|err| err.to_string())?;
+        print = Box::into_raw(Box::new(Print(loaded)));
+        Ok(FPRS_OK)
+    });
+    print
+}
+
+/// Free a print. NULL is ignored.
+///
+/// # Safety
+/// `print` must be NULL or returned by `fprs_device_enroll` or `fprs_print_deserialize`, and not used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn fprs_print_free(print: *mut Print) {
+    if !print.is_null() {
+        drop(Box::from_raw(print));
+    }
+}
+
+/// Free the bytes returned by `fprs_print_serialize`. NULL is ignored.
+///
+/// # Safety
+/// `data` and `len` must be NULL or returned by `fprs_print_serialize`, and not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn fprs_bytes_free(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+    }
+}
+
+/// Free a string returned by the library. NULL is ignored.
+///
+/// # Safety
+/// `string` must be NULL or returned by the library, and not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn fprs_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::{call, fprs_last_error, FPRS_ERROR, FPRS_OK};
+
+    #[test]
+    fn errors() {
+        assert_eq!(call(|| Ok(FPRS_OK)), FPRS_OK);
+        assert_eq!(call(|| Err("no\0device".to_string())), FPRS_ERROR);
+        let message = unsafe { CStr::from_ptr(fprs_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "nodevice");
+
+        assert_eq!(call(|| panic!("boom")), FPRS_ERROR);
+        let message = unsafe { CStr::from_ptr(fprs_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "libfprint-rs panicked");
+    }
+}
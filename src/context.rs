@@ -1,14 +1,40 @@
-use glib::{translate::ToGlibPtr, wrapper};
+use glib::{translate::ToGlibPtr, wrapper, ObjectExt};
 
 use crate::FpDevice;
 
 wrapper! {
     /// This struct allows you to discover fingerprint scanning hardware. This is the starting point when integrating libfprint-rs into your software.
+    ///
+    /// # Drop behavior
+    /// The devices only hold a weak reference to their context, so the context is freed once the last user
+    /// reference to it is dropped. This releases its list of devices, but does not close them: an `FpDevice`
+    /// obtained from the context stays alive while it is referenced, and a device that is freed while still open is
+    /// not cleaned up by libfprint, leaving its USB interface claimed until the process exits. Close the
+    /// devices, or call `FpContext::shutdown`, before dropping the context.
     pub struct FpContext(Object<libfprint_sys::FpContext, libfprint_sys::FpContextClass>);
     match fn {
         type_ => || libfprint_sys::fp_context_get_type() as usize,
     }
 }
+/// Key of the list of devices of a context, see `FpContext::devices`.
+const DEVICE_CACHE: &str = "devices";
+/// The list of devices of a context, kept up to date from its hotplug signals.
+type DeviceCache = std::cell::RefCell<Vec<FpDevice>>;
+
+/// How long `FpContext::shutdown` waits for the cancelled operations to complete.
+pub const SHUTDOWN_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// The global context, see `FpContext::global`.
+struct GlobalContext {
+    thread: std::thread::ThreadId,
+    context: FpContext,
+}
+// Safety: The context is only accessed from the thread that created it, `FpContext::global` checks it.
+unsafe impl Send for GlobalContext {}
+unsafe impl Sync for GlobalContext {}
+
+static GLOBAL_CONTEXT: std::sync::OnceLock<GlobalContext> = std::sync::OnceLock::new();
+
 impl FpContext {
     #[cfg(not(doctest))]
     /// Create a new `FpContext`
@@ -19,10 +45,32 @@ impl FpContext {
     /// let context = FpContext::new();
     /// ```
     pub fn new() -> Self {
-        unsafe { glib::translate::from_glib_none(libfprint_sys::fp_context_new()) }
+        unsafe { glib::translate::from_glib_full(libfprint_sys::fp_context_new()) }
+    }
+    #[cfg(all(feature = "dlopen", not(doctest)))]
+    /// Create a new `FpContext`, or return `FpError::Unavailable` if libfprint is not installed.
+    ///
+    /// With the `dlopen` feature libfprint is loaded at runtime, so applications where fingerprint
+    /// authentication is optional start without it. Call this function before any other of the crate: they
+    /// panic when libfprint can not be loaded.
+    /// # Examples:
+    /// ```rust
+    /// use libfprint_rs::FpContext;
+    ///
+    /// match FpContext::try_new() {
+    ///     Ok(context) => println!("{} devices", context.devices().len()),
+    ///     Err(err) => println!("{}", err),
+    /// }
+    /// ```
+    pub fn try_new() -> Result<Self, crate::FpError> {
+        libfprint_sys::load().map_err(|err| crate::FpError::Unavailable(err.to_string()))?;
+        Ok(Self::new())
     }
     #[cfg(not(doctest))]
     /// Get the list of devices connected to the system
+    ///
+    /// The list is built on the first call, then kept up to date from the hotplug signals of the context, so
+    /// later calls return it without walking the devices again. See `FpContext::refresh` to rebuild it.
     /// # Examples:
     /// ```rust
     /// use libfprint_rs::FpContext;
@@ -31,13 +79,144 @@ impl FpContext {
     /// let devices = context.devices();
     /// ```
     pub fn devices(&self) -> Vec<FpDevice> {
-        use glib::translate::FromGlibPtrContainer;
+        match unsafe { self.data::<DeviceCache>(DEVICE_CACHE) } {
+            Some(cache) => unsafe { cache.as_ref() }.borrow().clone(),
+            None => self.load_devices(),
+        }
+    }
 
-        unsafe {
+    /// Rebuild the list of devices returned by `FpContext::devices`, and return it.
+    ///
+    /// libfprint enumerates the USB devices once and then follows hotplug events. Pending events of the
+    /// thread default main context are dispatched first, so devices plugged since the last iteration are
+    /// included.
+    pub fn refresh(&self) -> Vec<FpDevice> {
+        let main_context = glib::MainContext::ref_thread_default();
+        while main_context.iteration(false) {}
+
+        self.load_devices()
+    }
+    /// Whether the device list of the context holds `device`, see `FpContext::devices`.
+    pub(crate) fn holds(&self, device: &FpDevice) -> bool {
+        unsafe { self.data::<DeviceCache>(DEVICE_CACHE) }
+            .is_some_and(|cache| unsafe { cache.as_ref() }.borrow().contains(device))
+    }
+    /// Build the list of devices, and keep it up to date from the hotplug signals.
+    fn load_devices(&self) -> Vec<FpDevice> {
+        let devices: Vec<FpDevice> = unsafe {
             let devs = libfprint_sys::fp_context_get_devices(self.to_glib_none().0);
+            crate::ptr_array::to_vec_none(devs.cast())
+        };
+        // Devices already listed keep their link to the context
+        devices
+            .iter()
+            .filter(|dev| dev.context().is_none())
+            .for_each(|dev| dev.set_context(self));
 
-            let devs = devs.cast::<glib::ffi::GPtrArray>();
-            FromGlibPtrContainer::from_glib_none(devs)
+        let cache = unsafe { self.data::<DeviceCache>(DEVICE_CACHE) };
+        match cache {
+            Some(cache) => *unsafe { cache.as_ref() }.borrow_mut() = devices.clone(),
+            None => {
+                unsafe { self.set_data(DEVICE_CACHE, DeviceCache::new(devices.clone())) };
+                // The handlers look the cache up from the context, so they do not keep it alive
+                self.connect_local("device-added", false, |values| {
+                    if let (Ok(context), Ok(device)) =
+                        (values[0].get::<FpContext>(), values[1].get::<FpDevice>())
+                    {
+                        if let Some(cache) = unsafe { context.data::<DeviceCache>(DEVICE_CACHE) } {
+                            device.set_context(&context);
+                            unsafe { cache.as_ref() }.borrow_mut().push(device);
+                        }
+                    }
+                    None
+                });
+                self.connect_local("device-removed", false, |values| {
+                    if let (Ok(context), Ok(device)) =
+                        (values[0].get::<FpContext>(), values[1].get::<FpDevice>())
+                    {
+                        if let Some(cache) = unsafe { context.data::<DeviceCache>(DEVICE_CACHE) } {
+                            unsafe { cache.as_ref() }
+                                .borrow_mut()
+                                .retain(|other| *other != device);
+                        }
+                    }
+                    None
+                });
+            }
+        }
+        devices
+    }
+
+    #[cfg(not(doctest))]
+    /// Get the process-wide context, creating it on the first call.
+    ///
+    /// Helper libraries should use this context instead of creating their own, so that a single context
+    /// owns the USB devices of the process.
+    ///
+    /// # Thread affinity
+    /// Like every GObject in libfprint, the context is bound to the thread that created it: its devices are
+    /// driven by that thread's default main context. The global context belongs to the thread that first
+    /// called `FpContext::global`, usually the main thread.
+    ///
+    /// # Panics
+    /// Panics if called from a thread other than the one that created the global context.
+    /// # Examples:
+    /// ```rust
+    /// use libfprint_rs::FpContext;
+    ///
+    /// let context = FpContext::global();
+    /// let devices = context.devices();
+    /// ```
+    pub fn global() -> FpContext {
+        let global = GLOBAL_CONTEXT.get_or_init(|| GlobalContext {
+            thread: std::thread::current().id(),
+            context: FpContext::new(),
+        });
+        assert!(
+            global.thread == std::thread::current().id(),
+            "FpContext::global() called from a thread other than the one that created it"
+        );
+        global.context.clone()
+    }
+
+    /// Close every open device of the context.
+    ///
+    /// Pending events of the thread default main context are dispatched first, so that asynchronous
+    /// operations that already finished deliver their results before the devices are closed. The operations
+    /// still running are cancelled, see `FpDevice::cancel_running`, and are given `SHUTDOWN_GRACE` to
+    /// complete, otherwise closing their device fails with a busy error.
+    ///
+    /// The devices are also detached from the context, `FpDevice::context` returns `None` for them.
+    ///
+    /// Returns the devices that could not be closed, with the error that made them fail.
+    pub fn shutdown(&self) -> Result<(), Vec<(FpDevice, crate::GError)>> {
+        let main_context = glib::MainContext::ref_thread_default();
+        while main_context.iteration(false) {}
+
+        let devices = self.devices();
+        devices.iter().for_each(FpDevice::cancel_running);
+        let started = std::time::Instant::now();
+        while devices
+            .iter()
+            .any(|dev| dev.running_operation().is_some() || dev.has_pending_operations())
+            && started.elapsed() < SHUTDOWN_GRACE
+        {
+            if !main_context.iteration(false) {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        }
+
+        let failed: Vec<_> = devices
+            .iter()
+            .filter(|dev| dev.is_open())
+            .filter_map(|dev| dev.close_sync(None).err().map(|err| (dev.clone(), err)))
+            .collect();
+        devices.iter().for_each(|dev| drop(dev.take_context()));
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(failed)
         }
     }
 
@@ -50,3 +229,104 @@ impl FpContext {
         }
     }
 }
+
+/// A hotplug event of a `FpContext`, see `FpContext::device_events`.
+#[cfg(feature = "futures")]
+#[derive(Debug, Clone)]
+pub enum FpDeviceEvent {
+    /// A new device was connected to the system.
+    Added(FpDevice),
+    /// A device was removed from the system.
+    Removed(FpDevice),
+}
+
+/// Stream of the hotplug events of a `FpContext`, returned by `FpContext::device_events`.
+/// The signal handlers are disconnected when the stream is dropped.
+#[cfg(feature = "futures")]
+pub struct FpDeviceEvents {
+    context: FpContext,
+    handlers: Option<(glib::SignalHandlerId, glib::SignalHandlerId)>,
+    receiver: futures_channel::mpsc::UnboundedReceiver<FpDeviceEvent>,
+}
+
+#[cfg(feature = "futures")]
+impl futures_core::Stream for FpDeviceEvents {
+    type Item = FpDeviceEvent;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        std::pin::Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+#[cfg(feature = "futures")]
+impl Drop for FpDeviceEvents {
+    fn drop(&mut self) {
+        use glib::ObjectExt;
+
+        if let Some((added, removed)) = self.handlers.take() {
+            self.context.disconnect(added);
+            self.context.disconnect(removed);
+        }
+    }
+}
+
+#[cfg(feature = "futures")]
+impl FpContext {
+    #[cfg(not(doctest))]
+    /// Returns a stream of the devices added to or removed from the system.
+    ///
+    /// The events are emitted from the thread default main context of the thread that created the context,
+    /// which must be running for the stream to yield anything. Devices that are already connected are not
+    /// reported, use `FpContext::devices` to get them.
+    /// # Examples:
+    /// ```no_run
+    /// use futures_util::StreamExt;
+    /// use libfprint_rs::{FpContext, FpDeviceEvent};
+    ///
+    /// let context = FpContext::new();
+    /// context.enumerate();
+    /// let mut events = context.device_events();
+    ///
+    /// glib::MainContext::default().block_on(async {
+    ///     while let Some(event) = events.next().await {
+    ///         match event {
+    ///             FpDeviceEvent::Added(dev) => println!("Added {}", dev.name()),
+    ///             FpDeviceEvent::Removed(dev) => println!("Removed {}", dev.name()),
+    ///         }
+    ///     }
+    /// });
+    /// ```
+    pub fn device_events(&self) -> FpDeviceEvents {
+        use glib::ObjectExt;
+
+        let (sender, receiver) = futures_channel::mpsc::unbounded();
+
+        let added = {
+            let sender = sender.clone();
+            self.connect_local("device-added", false, move |values| {
+                if let (Ok(context), Ok(device)) =
+                    (values[0].get::<FpContext>(), values[1].get::<FpDevice>())
+                {
+                    device.set_context(&context);
+                    let _ = sender.unbounded_send(FpDeviceEvent::Added(device));
+                }
+                None
+            })
+        };
+        let removed = self.connect_local("device-removed", false, move |values| {
+            if let Ok(device) = values[1].get::<FpDevice>() {
+                let _ = sender.unbounded_send(FpDeviceEvent::Removed(device));
+            }
+            None
+        });
+
+        FpDeviceEvents {
+            context: self.clone(),
+            handlers: Some((added, removed)),
+            receiver,
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt::Display;
+
+use glib::translate::{FromGlib, IntoGlib};
+
+use crate::GDate;
+
+/// A valid calendar date, used for the enroll date of prints. Years range from 1 to 65535, like GDate.
+///
+/// Dates are ordered chronologically and can be converted from and to `GDate`, and to `chrono::NaiveDate`
+/// or `time::Date` with the `chrono` and `time` features.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FpDate {
+    year: u16,
+    month: u8,
+    day: u8,
+}
+
+impl FpDate {
+    /// Creates a date from a year, a month (1 to 12) and a day of the month, or None if the date does not exist.
+    pub fn new(year: u16, month: u8, day: u8) -> Option<Self> {
+        if !(1..=12).contains(&month) || !GDate::valid_dmy(day, glib_month(month), year) {
+            return None;
+        }
+        Some(FpDate { year, month, day })
+    }
+    /// Returns the current date in the local timezone.
+    pub fn today() -> Self {
+        let now = glib::DateTime::now_local()
+            .or_else(|_| glib::DateTime::now_utc())
+            .expect("Failed to get the current time");
+        FpDate::new(
+            now.year() as u16,
+            now.month() as u8,
+            now.day_of_month() as u8,
+        )
+        .expect("The current date is out of range")
+    }
+    /// Creates a date from its Julian day, day 1 being January 1st of year 1.
+    pub fn from_julian(julian: u32) -> Option<Self> {
+        GDate::from_julian(julian)
+            .ok()
+            .and_then(|date| FpDate::try_from(&date).ok())
+    }
+
+    /// Returns the year of the date.
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+    /// Returns the month of the date, from 1 to 12.
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+    /// Returns the day of the month of the date, from 1.
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+    /// Returns the Julian day of the date, day 1 being January 1st of year 1.
+    pub fn julian(&self) -> u32 {
+        GDate::from(*self).julian()
+    }
+    /// Returns the number of days from this date to `other`, negative if `other` is earlier.
+    pub fn days_until(&self, other: &FpDate) -> i64 {
+        other.julian() as i64 - self.julian() as i64
+    }
+}
+
+fn glib_month(month: u8) -> glib::DateMonth {
+    unsafe { glib::DateMonth::from_glib(month as i32) }
+}
+
+impl Display for FpDate {
+    /// Formats the date as ISO 8601, e.g. `2023-09-14`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl From<FpDate> for GDate {
+    fn from(date: FpDate) -> Self {
+        GDate::from_dmy(date.day, glib_month(date.month), date.year)
+            .expect("FpDate is always a valid date")
+    }
+}
+
+impl TryFrom<&GDate> for FpDate {
+    type Error = glib::BoolError;
+
+    fn try_from(date: &GDate) -> Result<Self, Self::Error> {
+        if !date.valid() {
+            return Err(glib::bool_error!("Invalid date"));
+        }
+        FpDate::new(date.year(), date.month().into_glib() as u8, date.day())
+            .ok_or_else(|| glib::bool_error!("Invalid date"))
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<FpDate> for chrono::NaiveDate {
+    fn from(date: FpDate) -> Self {
+        chrono::NaiveDate::from_ymd_opt(date.year as i32, date.month as u32, date.day as u32)
+            .expect("FpDate is always in the range of chrono::NaiveDate")
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<chrono::NaiveDate> for FpDate {
+    type Error = glib::BoolError;
+
+    fn try_from(date: chrono::NaiveDate) -> Result<Self, Self::Error> {
+        use chrono::Datelike;
+
+        u16::try_from(date.year())
+            .ok()
+            .and_then(|year| FpDate::new(year, date.month() as u8, date.day() as u8))
+            .ok_or_else(|| glib::bool_error!("Year {} is out of range", date.year()))
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<FpDate> for time::Date {
+    type Error = time::error::ComponentRange;
+
+    fn try_from(date: FpDate) -> Result<Self, Self::Error> {
+        let month = time::Month::try_from(date.month)?;
+        time::Date::from_calendar_date(date.year as i32, month, date.day)
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<time::Date> for FpDate {
+    type Error = glib::BoolError;
+
+    fn try_from(date: time::Date) -> Result<Self, Self::Error> {
+        u16::try_from(date.year())
+            .ok()
+            .and_then(|year| FpDate::new(year, date.month() as u8, date.day()))
+            .ok_or_else(|| glib::bool_error!("Year {} is out of range", date.year()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FpDate;
+
+    #[test]
+    fn validation() {
+        assert!(FpDate::new(2024, 2, 29).is_some());
+        assert!(FpDate::new(2023, 2, 29).is_none());
+        assert!(FpDate::new(1900, 2, 29).is_none());
+        assert!(FpDate::new(2023, 13, 1).is_none());
+        assert!(FpDate::new(0, 1, 1).is_none());
+        assert_eq!(FpDate::new(2023, 9, 4).unwrap().to_string(), "2023-09-04");
+        assert!(FpDate::new(2023, 9, 4) < FpDate::new(2023, 10, 1));
+    }
+}
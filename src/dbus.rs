@@ -0,0 +1,286 @@
+//! A small D-Bus service exposing enroll, verify and identify (requires the `dbus` feature).
+//!
+//! Systems that can not run fprintd can use this module to offer fingerprint authentication to several local
+//! clients through a single daemon. The service is split in two halves:
+//! - `FpDbusService` implements the `INTERFACE_NAME` interface and is served with zbus.
+//! - `FpDbusWorker` owns the device and the `PrintStore`, and runs the requests one after the other on the
+//!   thread calling `FpDbusWorker::run`, which must be the thread owning the `FpContext`.
+//!
+//! Enroll, delete and list_fingers change or reveal the prints of a user, they are only allowed to the user
+//! themselves, i.e. to the callers whose Unix user has the requested username, to root, and to the users
+//! allowed with `FpDbusService::allow_uid`; the others get `org.freedesktop.DBus.Error.AccessDenied`. Verify
+//! and identify are allowed to every caller, they only match the finger on the sensor. The callers are
+//! identified by the bus, so the service must be served on a bus that only local users can reach, e.g. the
+//! system bus with a policy allowing them to call it.
+//!
+//! Fingers are passed as strings, e.g. `"right index"`, see `FpFinger`'s `FromStr` implementation. Failed
+//! operations reply with `org.freedesktop.DBus.Error.Failed`, whose message starts with the `FpError::code` of
+//! the error, e.g. `105: Corrupt print data: ...`.
+//! # Example:
+//! ```no_run
+//! use libfprint_rs::{dbus, store::MemoryStore, FpContext};
+//!
+//! let ctx = FpContext::new();
+//! let dev = ctx.devices().remove(0);
+//!
+//! let (service, worker) = dbus::service(dev, MemoryStore::new());
+//! let _connection = zbus::blocking::connection::Builder::system()
+//!     .unwrap()
+//!     .name("io.github.LibfprintRs")
+//!     .unwrap()
+//!     .serve_at(dbus::OBJECT_PATH, service)
+//!     .unwrap()
+//!     .build()
+//!     .unwrap();
+//! worker.run().unwrap();
+//! ```
+use std::{collections::HashSet, ffi::CStr, sync::mpsc};
+
+use futures_channel::oneshot;
+use zbus::{fdo, message::Header, names::BusName, Connection};
+
+use crate::{
+    device::{FpDevice, FpMatchOutcome},
+    error::FpError,
+    finger::FpFinger,
+    print::FpPrint,
+    store::PrintStore,
+};
+
+/// Name of the D-Bus interface implemented by `FpDbusService`.
+pub const INTERFACE_NAME: &str = "io.github.LibfprintRs.Device1";
+/// Object path the service is expected to be served at.
+pub const OBJECT_PATH: &str = "/io/github/LibfprintRs/Device";
+
+type Reply<T> = oneshot::Sender<Result<T, String>>;
+
+enum Request {
+    Enroll(String, FpFinger, Reply<()>),
+    Verify(String, FpFinger, Reply<bool>),
+    Identify(Reply<Option<(String, FpFinger)>>),
+    ListFingers(String, Reply<Vec<FpFinger>>),
+    Delete(String, FpFinger, Reply<bool>),
+}
+
+/// Creates the two halves of the service for `device`, storing the prints in `store`.
+pub fn service<S: PrintStore>(device: FpDevice, store: S) -> (FpDbusService, FpDbusWorker<S>) {
+    let (sender, receiver) = mpsc::channel();
+    let service = FpDbusService {
+        requests: sender,
+        allowed_uids: HashSet::new(),
+    };
+    let worker = FpDbusWorker {
+        device,
+        store,
+        requests: receiver,
+    };
+    (service, worker)
+}
+
+/// The D-Bus half of the service, forwarding the calls to its `FpDbusWorker`.
+#[derive(Debug)]
+pub struct FpDbusService {
+    requests: mpsc::Sender<Request>,
+    allowed_uids: HashSet<u32>,
+}
+
+impl FpDbusService {
+    /// Allow the Unix user `uid` to enroll, delete and list the fingers of every user, e.g. for the user
+    /// running the login manager. Root is always allowed.
+    pub fn allow_uid(mut self, uid: u32) -> Self {
+        self.allowed_uids.insert(uid);
+        self
+    }
+
+    /// Check that the sender of the call may manage the prints of `username`.
+    async fn authorize(
+        &self,
+        header: &Header<'_>,
+        connection: &Connection,
+        username: &str,
+    ) -> fdo::Result<()> {
+        let sender = header
+            .sender()
+            .ok_or_else(|| fdo::Error::AccessDenied("The call has no sender".to_string()))?;
+        let uid = fdo::DBusProxy::new(connection)
+            .await?
+            .get_connection_unix_user(BusName::Unique(sender.to_owned()))
+            .await?;
+        if uid == 0
+            || self.allowed_uids.contains(&uid)
+            || user_name(uid).is_some_and(|name| name == username)
+        {
+            return Ok(());
+        }
+        Err(fdo::Error::AccessDenied(format!(
+            "User {} may not manage the prints of {}",
+            uid, username
+        )))
+    }
+
+    async fn call<T>(&self, request: impl FnOnce(Reply<T>) -> Request) -> fdo::Result<T> {
+        let (reply, response) = oneshot::channel();
+        self.requests
+            .send(request(reply))
+            .map_err(|_| fdo::Error::Failed("The worker has stopped".to_string()))?;
+        response
+            .await
+            .map_err(|_| fdo::Error::Failed("The worker has stopped".to_string()))?
+            .map_err(fdo::Error::Failed)
+    }
+}
+
+/// Returns the name of the Unix user `uid`.
+fn user_name(uid: u32) -> Option<String> {
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0; 4096];
+    let mut result = std::ptr::null_mut();
+    let res = unsafe {
+        libc::getpwuid_r(
+            uid,
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if res != 0 || result.is_null() {
+        return None;
+    }
+    let name = unsafe { CStr::from_ptr(passwd.pw_name) };
+    name.to_str().ok().map(str::to_string)
+}
+
+fn parse_finger(finger: &str) -> fdo::Result<FpFinger> {
+    match finger.parse() {
+        Ok(FpFinger::Unknown) | Err(()) => Err(fdo::Error::InvalidArgs(format!(
+            "Unknown finger {}",
+            finger
+        ))),
+        Ok(finger) => Ok(finger),
+    }
+}
+
+#[zbus::interface(name = "io.github.LibfprintRs.Device1")]
+impl FpDbusService {
+    /// Enroll `finger` of `username`, replacing the previous print.
+    async fn enroll(
+        &self,
+        username: &str,
+        finger: &str,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> fdo::Result<()> {
+        self.authorize(&header, connection, username).await?;
+        let finger = parse_finger(finger)?;
+        self.call(|reply| Request::Enroll(username.to_string(), finger, reply))
+            .await
+    }
+    /// Verify the scanned finger against the print of `finger` of `username`.
+    async fn verify(&self, username: &str, finger: &str) -> fdo::Result<bool> {
+        let finger = parse_finger(finger)?;
+        self.call(|reply| Request::Verify(username.to_string(), finger, reply))
+            .await
+    }
+    /// Identify the scanned finger among every stored print, returning whether it matched, the username and
+    /// the finger.
+    async fn identify(&self) -> fdo::Result<(bool, String, String)> {
+        let matched = self.call(Request::Identify).await?;
+        Ok(match matched {
+            Some((username, finger)) => (true, username, finger.to_string()),
+            None => (false, String::new(), String::new()),
+        })
+    }
+    /// List the enrolled fingers of `username`.
+    async fn list_fingers(
+        &self,
+        username: &str,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> fdo::Result<Vec<String>> {
+        self.authorize(&header, connection, username).await?;
+        let fingers = self
+            .call(|reply| Request::ListFingers(username.to_string(), reply))
+            .await?;
+        Ok(fingers.iter().map(FpFinger::to_string).collect())
+    }
+    /// Delete the print of `finger` of `username`, returning whether it existed.
+    async fn delete(
+        &self,
+        username: &str,
+        finger: &str,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> fdo::Result<bool> {
+        self.authorize(&header, connection, username).await?;
+        let finger = parse_finger(finger)?;
+        self.call(|reply| Request::Delete(username.to_string(), finger, reply))
+            .await
+    }
+}
+
+/// The device half of the service, see `service`.
+pub struct FpDbusWorker<S> {
+    device: FpDevice,
+    store: S,
+    requests: mpsc::Receiver<Request>,
+}
+
+impl<S: PrintStore> FpDbusWorker<S> {
+    /// Open the device and run the requests until the `FpDbusService` is dropped, then close the device.
+    pub fn run(mut self) -> Result<(), FpError> {
+        self.device.open_sync(None)?;
+        while let Ok(request) = self.requests.recv() {
+            match request {
+                Request::Enroll(username, finger, reply) => {
+                    let _ = reply.send(self.enroll(&username, finger).map_err(to_reply));
+                }
+                Request::Verify(username, finger, reply) => {
+                    let _ = reply.send(self.verify(&username, finger).map_err(to_reply));
+                }
+                Request::Identify(reply) => {
+                    let _ = reply.send(self.identify().map_err(to_reply));
+                }
+                Request::ListFingers(username, reply) => {
+                    let _ = reply.send(self.store.list(&username).map_err(to_reply));
+                }
+                Request::Delete(username, finger, reply) => {
+                    let _ = reply.send(self.store.delete(&username, finger).map_err(to_reply));
+                }
+            }
+        }
+        self.device.close_sync(None)?;
+        Ok(())
+    }
+
+    fn enroll(&mut self, username: &str, finger: FpFinger) -> Result<(), FpError> {
+        let template = FpPrint::builder(&self.device)
+            .username(username)
+            .finger(finger)
+            .build()?;
+        let print = self.device.enroll_sync(template, None, None, None::<()>)?;
+        self.store.put(&print)
+    }
+    fn verify(&mut self, username: &str, finger: FpFinger) -> Result<bool, FpError> {
+        let print = self.store.get(username, finger)?.ok_or_else(|| {
+            let message = format!("No print enrolled for {} of {}", finger, username);
+            crate::GError::new(gio::IOErrorEnum::NotFound, &message)
+        })?;
+        match self.device.verify_sync(&print, None, None, None::<()>)? {
+            FpMatchOutcome::Retry(retry) => {
+                Err(crate::GError::new(retry, &retry.to_string()).into())
+            }
+            outcome => Ok(outcome.is_match()),
+        }
+    }
+    fn identify(&mut self) -> Result<Option<(String, FpFinger)>, FpError> {
+        let gallery = self.store.gallery()?;
+        Ok(self
+            .device
+            .identify_users(&gallery, None, None, None::<()>)?)
+    }
+}
+
+fn to_reply(error: FpError) -> String {
+    format!("{}: {}", error.code(), error)
+}
@@ -0,0 +1,161 @@
+use std::collections::BTreeSet;
+use std::sync::{Mutex, OnceLock};
+
+use glib::{LogField, LogLevel, LogWriterOutput};
+
+use crate::device::FpDevice;
+
+/// Environment variable listing the log domains whose debug messages GLib prints.
+const DEBUG_ENV: &str = "G_MESSAGES_DEBUG";
+/// Environment variable enabling the debug messages of every libfprint domain.
+const FP_DEBUG_ENV: &str = "FP_DEBUG";
+
+/// The domains whose debug messages are printed, set when `FpDebug` installs its log writer.
+static DOMAINS: OnceLock<Mutex<BTreeSet<String>>> = OnceLock::new();
+
+#[cfg(not(doctest))]
+/// Runtime control of the debug messages of libfprint, the equivalent of setting `G_MESSAGES_DEBUG` or
+/// `FP_DEBUG` before startup, e.g. to toggle verbose sensor logging from support tooling without restarting
+/// the process.
+///
+/// The first call installs a GLib log writer that filters the debug and info messages on a set of domains kept
+/// by `FpDebug`, and hands every other message to the default writer. The set starts with the domains of
+/// `G_MESSAGES_DEBUG`, and with `libfprint-*` when `FP_DEBUG` is set to anything but `0`; the environment is
+/// only read, never modified. GLib accepts a single log writer per process, applications installing their own
+/// with `g_log_set_writer_func` cannot use `FpDebug`.
+///
+/// Domains are matched exactly, except for `all`, which enables every domain, and patterns ending in `*`,
+/// which match by prefix. libfprint logs under `libfprint-device`, `libfprint-context`,
+/// `libfprint-image_device`, `libfprint-SSM` and `libfprint-<driver>` among others.
+/// # Example:
+/// ```no_run
+/// use libfprint_rs::{FpContext, FpDebug};
+///
+/// let ctx = FpContext::new();
+/// let devices = ctx.devices();
+/// let dev = devices.get(0).unwrap();
+///
+/// FpDebug::enable_driver(&dev);
+/// dev.open_sync(None).unwrap();
+/// FpDebug::disable_all();
+/// ```
+pub struct FpDebug;
+
+impl FpDebug {
+    /// Print the debug messages of every domain.
+    pub fn enable_all() {
+        Self::update(|domains| {
+            domains.clear();
+            domains.insert("all".to_string());
+        });
+    }
+    /// Stop printing debug messages.
+    pub fn disable_all() {
+        Self::update(BTreeSet::clear);
+    }
+    /// Print the debug messages of `domain`, or of the domains starting with `prefix` for `prefix*`.
+    pub fn enable(domain: &str) {
+        Self::update(|domains| {
+            domains.insert(domain.to_string());
+        });
+    }
+    /// Stop printing the debug messages of `domain`. Domains enabled through `all` or a pattern can only be
+    /// disabled by disabling that entry, or with `FpDebug::disable_all`.
+    pub fn disable(domain: &str) {
+        Self::update(|domains| {
+            domains.remove(domain);
+        });
+    }
+    /// Print the debug messages of the driver of `device`.
+    pub fn enable_driver(device: &FpDevice) {
+        Self::enable(&format!("libfprint-{}", device.driver()));
+    }
+    /// Returns the domains whose debug messages are printed, as enabled: `all` and patterns are not expanded.
+    pub fn domains() -> Vec<String> {
+        let domains = domains().lock().unwrap_or_else(|err| err.into_inner());
+        domains.iter().cloned().collect()
+    }
+    /// Whether the debug messages of `domain` are printed.
+    pub fn is_enabled(domain: &str) -> bool {
+        let domains = domains().lock().unwrap_or_else(|err| err.into_inner());
+        enabled(&domains, domain)
+    }
+
+    fn update(f: impl FnOnce(&mut BTreeSet<String>)) {
+        let mut domains = domains().lock().unwrap_or_else(|err| err.into_inner());
+        f(&mut domains);
+    }
+}
+
+/// The domains of `FpDebug`, seeded from the environment and installing the log writer on first use.
+fn domains() -> &'static Mutex<BTreeSet<String>> {
+    DOMAINS.get_or_init(|| {
+        let mut domains: BTreeSet<String> = std::env::var(DEBUG_ENV)
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        if std::env::var(FP_DEBUG_ENV).is_ok_and(|value| !value.is_empty() && value != "0") {
+            domains.insert("libfprint-*".to_string());
+        }
+        glib::log_set_writer_func(write);
+        Mutex::new(domains)
+    })
+}
+
+/// Whether `domain` is enabled by one of `domains`.
+fn enabled(domains: &BTreeSet<String>, domain: &str) -> bool {
+    domains.iter().any(|enabled| {
+        enabled == "all"
+            || match enabled.strip_suffix('*') {
+                Some(prefix) => domain.starts_with(prefix),
+                None => enabled == domain,
+            }
+    })
+}
+
+/// The log writer of `FpDebug`. The default writer drops the debug messages of the domains missing from
+/// `G_MESSAGES_DEBUG`, so the enabled ones are written the way it would write them.
+fn write(level: LogLevel, fields: &[LogField<'_>]) -> LogWriterOutput {
+    if !matches!(level, LogLevel::Debug | LogLevel::Info) {
+        return glib::log_writer_default(level, fields);
+    }
+    let domain = fields
+        .iter()
+        .find(|field| field.key() == "GLIB_DOMAIN")
+        .and_then(LogField::value_str)
+        .unwrap_or_default();
+    let domains = domains().lock().unwrap_or_else(|err| err.into_inner());
+    if !enabled(&domains, domain) {
+        return LogWriterOutput::Handled;
+    }
+    drop(domains);
+
+    if glib::log_writer_is_journald(std::io::stderr())
+        && glib::log_writer_journald(level, fields) == LogWriterOutput::Handled
+    {
+        return LogWriterOutput::Handled;
+    }
+    glib::log_writer_standard_streams(level, fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::enabled;
+
+    #[test]
+    fn domain_patterns() {
+        let domains: BTreeSet<String> = ["libfprint-device", "libfprint-goodix*"]
+            .map(str::to_string)
+            .into();
+        assert!(enabled(&domains, "libfprint-device"));
+        assert!(enabled(&domains, "libfprint-goodixmoc"));
+        assert!(!enabled(&domains, "libfprint-context"));
+        assert!(!enabled(&domains, ""));
+
+        let all: BTreeSet<String> = ["all".to_string()].into();
+        assert!(enabled(&all, "libfprint-SSM"));
+    }
+}
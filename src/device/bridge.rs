@@ -0,0 +1,130 @@
+use std::sync::{Arc, Mutex};
+
+use glib::{thread_guard::ThreadGuard, MainContext};
+
+use crate::print::FpPrint;
+
+use super::FpDevice;
+
+/// A call of the enroll progress callback, see `FpMainContextBridge::enroll_progress`.
+#[derive(Debug)]
+pub struct FpEnrollEvent {
+    /// The number of completed stages.
+    pub completed_stages: i32,
+    /// The print of the last completed stage, if any.
+    pub print: Option<FpPrint>,
+    /// The error of a retried scan, if any.
+    pub error: Option<crate::GError>,
+}
+
+/// A call of the match callback, see `FpMainContextBridge::match_cb`.
+#[derive(Debug)]
+pub struct FpMatchEvent {
+    /// The matching print, if any.
+    pub matched: Option<FpPrint>,
+    /// The newly scanned print, if any.
+    pub print: Option<FpPrint>,
+    /// The error of a retried scan, if any.
+    pub error: Option<crate::GError>,
+}
+
+type Callback<E> = Arc<Mutex<Option<ThreadGuard<Box<dyn Fn(E)>>>>>;
+
+#[cfg(not(doctest))]
+/// Runs the progress and match callbacks of an operation on a `glib::MainContext`, e.g. the one of the GTK UI
+/// thread, so they can update widgets while the operation runs on another thread.
+///
+/// The bridge is the data of the operation, with `FpMainContextBridge::enroll_progress` or
+/// `FpMainContextBridge::match_cb` as its callback. The events are delivered in order, from the iterations of
+/// the main context.
+/// # Example:
+/// ```no_run
+/// use libfprint_rs::{FpEnrollEvent, FpMainContextBridge, FpPrint};
+///
+/// let label = gtk::Label::new(None);
+/// let bridge = FpMainContextBridge::new(&glib::MainContext::default(), move |event: FpEnrollEvent| {
+///     label.set_text(&format!("{} stages done", event.completed_stages));
+/// });
+///
+/// std::thread::spawn(move || {
+///     let template = FpPrint::new(&dev);
+///     dev.enroll_sync(template, None, Some(FpMainContextBridge::enroll_progress), Some(bridge))
+/// });
+/// ```
+pub struct FpMainContextBridge<E: Send + 'static> {
+    context: MainContext,
+    callback: Option<Callback<E>>,
+}
+
+impl<E: Send + 'static> FpMainContextBridge<E> {
+    /// Creates a bridge calling `callback` on `context`.
+    ///
+    /// # Panics
+    /// Must be called from the thread owning `context`: `callback` is not `Send`, calling or dropping it from
+    /// another thread panics.
+    pub fn new(context: &MainContext, callback: impl Fn(E) + 'static) -> Self {
+        let callback: Box<dyn Fn(E)> = Box::new(callback);
+        FpMainContextBridge {
+            context: context.clone(),
+            callback: Some(Arc::new(Mutex::new(Some(ThreadGuard::new(callback))))),
+        }
+    }
+    /// Calls the callback with `event` on the main context.
+    pub fn send(&self, event: E) {
+        if let Some(callback) = self.callback.clone() {
+            self.context.invoke(move || {
+                if let Some(callback) = callback.lock().unwrap().as_ref() {
+                    (callback.get_ref())(event);
+                }
+            });
+        }
+    }
+}
+
+impl FpMainContextBridge<FpEnrollEvent> {
+    /// An enroll progress callback sending its calls to the bridge, see `FpEnrollProgress`.
+    pub fn enroll_progress(
+        _device: &FpDevice,
+        completed_stages: i32,
+        print: Option<FpPrint>,
+        error: Option<crate::GError>,
+        bridge: &Option<Self>,
+    ) {
+        if let Some(bridge) = bridge {
+            bridge.send(FpEnrollEvent {
+                completed_stages,
+                print,
+                error,
+            });
+        }
+    }
+}
+
+impl FpMainContextBridge<FpMatchEvent> {
+    /// A match callback sending its calls to the bridge, see `FpMatchCb`.
+    pub fn match_cb(
+        _device: &FpDevice,
+        matched: Option<FpPrint>,
+        print: Option<FpPrint>,
+        error: Option<crate::GError>,
+        bridge: &Option<Self>,
+    ) {
+        if let Some(bridge) = bridge {
+            bridge.send(FpMatchEvent {
+                matched,
+                print,
+                error,
+            });
+        }
+    }
+}
+
+impl<E: Send + 'static> Drop for FpMainContextBridge<E> {
+    fn drop(&mut self) {
+        // The callback is dropped on the main context, after the events sent before
+        if let Some(callback) = self.callback.take() {
+            self.context
+                .invoke(move || drop(callback.lock().unwrap().take()));
+        }
+    }
+}
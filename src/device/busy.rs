@@ -0,0 +1,308 @@
+use std::{
+    sync::{Condvar, Mutex, MutexGuard},
+    thread::{self, ThreadId},
+    time::Duration,
+};
+
+use gio::{prelude::CancellableExt, Cancellable};
+use glib::{translate::ToGlibPtr, ObjectExt, ObjectType};
+
+use super::FpDevice;
+use crate::error::FpContextual;
+
+/// Key of the queueing flag of a device, see `FpDevice::set_queue_operations`. It is only read and written
+/// with `RUNNING` locked.
+const QUEUE_OPERATIONS: &str = "queue-operations";
+/// Key of the flag of a device whose last handle was dropped while operations were pending, to close it
+/// once they complete.
+const CLOSE_WHEN_IDLE: &str = "close-when-idle";
+/// How often the devices with pending operations are checked for a remaining handle, see `cancel_orphaned`.
+const ORPHAN_CHECK: Duration = Duration::from_millis(250);
+/// An operation running on a device, see `OperationGuard`.
+struct Running {
+    device: usize,
+    thread: ThreadId,
+    operation: &'static str,
+    cancellable: Cancellable,
+}
+
+/// The synchronous operations running in the process.
+static RUNNING: Mutex<Vec<Running>> = Mutex::new(Vec::new());
+/// Notified when an operation completes.
+static COMPLETED: Condvar = Condvar::new();
+
+/// An asynchronous operation pending on a device, see `PendingOperation`.
+struct Pending {
+    device: usize,
+    operation: &'static str,
+    cancellable: Cancellable,
+}
+
+/// The asynchronous operations pending in the process.
+static PENDING: Mutex<Vec<Pending>> = Mutex::new(Vec::new());
+
+/// Marks a device busy with a synchronous operation until dropped.
+///
+/// libfprint runs a single operation at a time per device, and does not expect its synchronous operations
+/// to be called from several threads at once. The guard makes the second caller fail with
+/// `FP_DEVICE_ERROR_BUSY`, or wait for the first one if the device queues its operations.
+/// Suspending and resuming are not guarded, libfprint expects them while an operation runs.
+pub(crate) struct OperationGuard {
+    device: usize,
+    cancellable: Cancellable,
+}
+
+impl OperationGuard {
+    /// Mark `device` busy with `operation`, cancelled with `cancellable` or `FpDevice::cancel_running`.
+    pub(crate) fn acquire(
+        device: &FpDevice,
+        operation: &'static str,
+        cancellable: Option<&Cancellable>,
+    ) -> Result<Self, FpContextual<crate::GError>> {
+        let key = device.as_ptr() as usize;
+        let current = thread::current().id();
+        let mut running = lock();
+        while let Some(busy) = running.iter().find(|running| running.device == key) {
+            // Waiting for an operation of the same thread, e.g. started from a callback, never completes
+            if busy.thread == current || !queues_operations(device, &running) {
+                let error = busy_error(busy.operation, operation);
+                return Err(FpContextual::new(operation, Some(device), error));
+            }
+            running = COMPLETED
+                .wait(running)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+        let cancellable = cancellable.cloned().unwrap_or_default();
+        running.push(Running {
+            device: key,
+            thread: current,
+            operation,
+            cancellable: cancellable.clone(),
+        });
+        Ok(OperationGuard {
+            device: key,
+            cancellable,
+        })
+    }
+    /// The cancellable to pass to the operation.
+    pub(crate) fn cancellable(&self) -> &Cancellable {
+        &self.cancellable
+    }
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        lock().retain(|running| running.device != self.device);
+        COMPLETED.notify_all();
+    }
+}
+
+/// Lock the running operations. They are only updated by the guards, a poisoned lock is still consistent.
+fn lock() -> MutexGuard<'static, Vec<Running>> {
+    RUNNING.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Whether the operations on `device` wait for the running one, with `RUNNING` locked.
+fn queues_operations(device: &FpDevice, _running: &MutexGuard<'_, Vec<Running>>) -> bool {
+    unsafe { device.data::<bool>(QUEUE_OPERATIONS) }
+        .map(|queue| unsafe { *queue.as_ref() })
+        .unwrap_or(false)
+}
+
+/// The error returned to the second caller.
+fn busy_error(running: &'static str, operation: &'static str) -> crate::GError {
+    let message = format!(
+        "Cannot {}: the device is busy with another operation ({})",
+        operation, running
+    );
+    unsafe {
+        let domain = libfprint_sys::fp_device_error_quark();
+        let code = libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_BUSY as i32;
+        let error = glib::ffi::g_error_new_literal(domain, code, message.to_glib_none().0);
+        glib::translate::from_glib_full(error)
+    }
+}
+
+/// Whether `error` is `FP_DEVICE_ERROR_BUSY`, i.e. the device is running another operation.
+pub(crate) fn is_busy(error: &crate::GError) -> bool {
+    let raw: *const glib::ffi::GError = error.to_glib_none().0;
+    let domain = unsafe { glib::translate::from_glib(libfprint_sys::fp_device_error_quark()) };
+    error.domain() == domain
+        && unsafe { (*raw).code } == libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_BUSY as i32
+}
+
+/// Marks an asynchronous operation pending on a device until dropped, once it completed.
+///
+/// The operation is cancelled by `FpDevice::cancel_running`, and when the last handle to the device was
+/// dropped, see the drop behavior of `FpDevice`. The device is then closed once its operations completed.
+pub(crate) struct PendingOperation {
+    device: FpDevice,
+    cancellable: Cancellable,
+    /// Checks whether the device is still referenced, on the main context of the operation.
+    check: glib::Source,
+}
+
+impl PendingOperation {
+    /// Mark `operation` pending on `device`, cancelled with `cancellable` or `FpDevice::cancel_running`.
+    pub(crate) fn start(
+        device: &FpDevice,
+        operation: &'static str,
+        cancellable: Option<&Cancellable>,
+    ) -> Self {
+        let cancellable = cancellable.cloned().unwrap_or_default();
+        lock_pending().push(Pending {
+            device: device.as_ptr() as usize,
+            operation,
+            cancellable: cancellable.clone(),
+        });
+        let weak = device.downgrade();
+        let check = glib::timeout_source_new(
+            ORPHAN_CHECK,
+            None,
+            glib::Priority::DEFAULT_IDLE,
+            move || {
+                if let Some(device) = weak.upgrade() {
+                    cancel_orphaned(&device);
+                }
+                glib::ControlFlow::Continue
+            },
+        );
+        check.attach(Some(&glib::MainContext::ref_thread_default()));
+        PendingOperation {
+            device: device.clone(),
+            cancellable,
+            check,
+        }
+    }
+    /// The cancellable to pass to the operation.
+    pub(crate) fn cancellable(&self) -> &Cancellable {
+        &self.cancellable
+    }
+}
+
+impl Drop for PendingOperation {
+    fn drop(&mut self) {
+        self.check.destroy();
+        let key = self.device.as_ptr() as usize;
+        let idle = {
+            let mut pending = lock_pending();
+            if let Some(index) = pending.iter().position(|pending| {
+                pending.device == key && pending.cancellable == self.cancellable
+            }) {
+                pending.swap_remove(index);
+            }
+            !pending.iter().any(|pending| pending.device == key)
+        };
+        let orphaned = idle && unsafe { self.device.steal_data::<bool>(CLOSE_WHEN_IDLE) }.is_some();
+        if orphaned && self.device.is_open() {
+            // Not tracked, nobody is left to cancel it
+            unsafe {
+                libfprint_sys::fp_device_close(
+                    self.device.to_glib_none().0,
+                    std::ptr::null_mut(),
+                    Some(orphan_closed),
+                    std::ptr::null_mut(),
+                )
+            };
+        }
+    }
+}
+
+/// Completes the close of a device whose last handle was dropped, nobody is left to report its error to.
+unsafe extern "C" fn orphan_closed(
+    source: *mut libfprint_sys::GObject,
+    res: *mut libfprint_sys::GAsyncResult,
+    _user_data: *mut std::ffi::c_void,
+) {
+    let mut error = std::ptr::null_mut();
+    if libfprint_sys::fp_device_close_finish(source.cast(), res, std::ptr::addr_of_mut!(error))
+        == glib::ffi::GFALSE
+    {
+        glib::ffi::g_error_free(error.cast());
+    }
+}
+
+/// Lock the pending operations, a poisoned lock is still consistent like `RUNNING`.
+fn lock_pending() -> MutexGuard<'static, Vec<Pending>> {
+    PENDING.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Cancel the pending operations of `device` if the application dropped its last handle to it.
+///
+/// `FpDevice` is a plain reference to a GObject, there is no hook for dropping it: the references held by the
+/// library are counted instead. The device list of the context and libfprint hold one each, every pending
+/// operation one in its `PendingOperation` and one in the task of libfprint, and the caller one. A reference
+/// this misses, e.g. of a future, only keeps the operations running.
+fn cancel_orphaned(device: &FpDevice) {
+    if unsafe { device.data::<bool>(CLOSE_WHEN_IDLE) }.is_some() {
+        return;
+    }
+    let key = device.as_ptr() as usize;
+    let (pending, cancellables) = {
+        let pending = lock_pending();
+        let pending: Vec<&Pending> = pending.iter().filter(|p| p.device == key).collect();
+        let cancellables: Vec<Cancellable> = pending
+            .iter()
+            .filter(|pending| pending.operation != "close")
+            .map(|pending| pending.cancellable.clone())
+            .collect();
+        (pending.len() as u32, cancellables)
+    };
+    let listed = device
+        .context()
+        .is_some_and(|context| context.holds(device));
+    let internal = 1 + 2 * pending + if listed { 2 } else { 0 };
+    if cancellables.is_empty() || device.ref_count() > internal {
+        return;
+    }
+    unsafe { device.set_data(CLOSE_WHEN_IDLE, true) };
+    // Outside of the lock, the operations may complete right away
+    for cancellable in cancellables {
+        cancellable.cancel();
+    }
+}
+
+impl FpDevice {
+    /// Whether the synchronous operations on the device wait for the running one when called from another
+    /// thread, instead of failing with `FP_DEVICE_ERROR_BUSY`. This is off by default.
+    ///
+    /// Operations called from the thread running the operation, e.g. from its callbacks, always fail.
+    pub fn set_queue_operations(&self, queue: bool) {
+        let _running = lock();
+        unsafe { self.set_data(QUEUE_OPERATIONS, queue) };
+    }
+    /// The synchronous operation running on the device, e.g. `"verify"`, if any.
+    pub fn running_operation(&self) -> Option<&'static str> {
+        let key = self.as_ptr() as usize;
+        lock()
+            .iter()
+            .find(|running| running.device == key)
+            .map(|running| running.operation)
+    }
+    /// Cancel the synchronous operation running on the device and its pending asynchronous operations, if
+    /// any, e.g. from another thread before closing the device. They still have to complete, see
+    /// `FpContext::shutdown`.
+    pub fn cancel_running(&self) {
+        let key = self.as_ptr() as usize;
+        let mut cancellables: Vec<Cancellable> = lock()
+            .iter()
+            .filter(|running| running.device == key)
+            .map(|running| running.cancellable.clone())
+            .collect();
+        cancellables.extend(
+            lock_pending()
+                .iter()
+                .filter(|pending| pending.device == key)
+                .map(|pending| pending.cancellable.clone()),
+        );
+        // Outside of the locks, the handlers of the cancellables run right away
+        for cancellable in cancellables {
+            cancellable.cancel();
+        }
+    }
+    /// Whether asynchronous operations started on the device have not completed yet.
+    pub(crate) fn has_pending_operations(&self) -> bool {
+        let key = self.as_ptr() as usize;
+        lock_pending().iter().any(|pending| pending.device == key)
+    }
+}
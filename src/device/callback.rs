@@ -1,4 +1,4 @@
-use std::{os::raw::c_int, os::raw::c_void, sync::Arc};
+use std::{os::raw::c_int, os::raw::c_void};
 
 use glib::translate::{FromGlibPtrBorrow, FromGlibPtrNone};
 
@@ -15,31 +15,29 @@ pub(crate) extern "C" fn fp_enroll_progress<F, T>(
 ) where
     F: Fn(&FpDevice, i32, Option<FpPrint>, Option<glib::Error>, &Option<T>) -> (),
 {
+    // We borrow the device and the error, they are only copied for the callback
+    let device = unsafe { FpDevice::from_glib_borrow(device) };
+    let borrowed_error = match error.is_null() {
+        true => None,
+        false => Some(unsafe { glib::Error::from_glib_borrow(error.cast()) }),
+    };
+    super::trace::enroll_stage(&device, completed_stages, borrowed_error.as_deref());
+
     // If user data is not null, a callback function was provided
     if !user_data.is_null() {
-        // We "reconstruct" the UserData from the void pointer
-        // Safety: This pointer is created on FpDevice::enroll_sync, and is only
-        // used here. After the callback is called, the pointer is forgotten. Then
-        // back in FpDevice::enroll_sync, the pointer is dropped. So we are the only
-        // "owners" of the pointer. The user does not have access to this pointer
-        let callback_data: Arc<UserData<F, T>> = unsafe { Arc::from_raw(user_data.cast()) };
+        // Safety: The pointer points to the UserData of the operation, which outlives it: the sync
+        // functions keep it on their stack until libfprint returns, and the async ones free it in the
+        // destroy notify of the operation. We only borrow it.
+        let callback_data: &UserData<F, T> = unsafe { &*user_data.cast() };
 
-        // We borrow the device
-        let device = unsafe { FpDevice::from_glib_borrow(device) };
         // Convert the raw pointer to a Rust struct
         let print = match print.is_null() {
             true => None,
             false => Some(unsafe { FpPrint::from_glib_none(print) }),
         };
-
-        let error = match error.is_null() {
-            true => None,
-            false => Some(unsafe { glib::Error::from_glib_none(error.cast()) }),
-        };
+        let error = borrowed_error.map(|error| (*error).clone());
 
         callback_data.callback_enroll(&device, completed_stages, print, error);
-
-        _ = std::mem::forget(callback_data);
     }
 }
 
@@ -50,22 +48,23 @@ pub(crate) extern "C" fn fp_match_cb<F, T>(
     user_data: *mut c_void,
     error: *mut libfprint_sys::GError,
 ) where
-    F: Fn(&FpDevice, Option<FpPrint>, FpPrint, Option<glib::Error>, &Option<T>),
+    F: Fn(&FpDevice, Option<FpPrint>, Option<FpPrint>, Option<glib::Error>, &Option<T>),
 {
     if !user_data.is_null() {
-        // We reconstruct the UserData struct from the pointer
-        // Safety: We are the only ones who have access to the pointer,
-        // which is created either at verify_sync or identify_sync. Either way, the pointer is
-        // forgotten after the callback is called, so we aer the only "owners" of the pointer.
-        let callback_data: Arc<UserData<F, T>> = unsafe { Arc::from_raw(user_data.cast()) };
+        // Safety: See `fp_enroll_progress`, the UserData outlives the operation and is only borrowed.
+        let callback_data: &UserData<F, T> = unsafe { &*user_data.cast() };
 
-        let device = unsafe { FpDevice::from_glib_none(device) };
+        let device = unsafe { FpDevice::from_glib_borrow(device) };
 
         let match_print = match match_print.is_null() {
             true => None,
             false => Some(unsafe { FpPrint::from_glib_none(match_print) }),
         };
-        let print = unsafe { FpPrint::from_glib_none(print) };
+        // The scanned print is null when the scan failed with a retry error
+        let print = match print.is_null() {
+            true => None,
+            false => Some(unsafe { FpPrint::from_glib_none(print) }),
+        };
         let error = match error.is_null() {
             true => None,
             false => Some(unsafe { glib::Error::from_glib_none(error.cast()) }),
@@ -1,11 +1,118 @@
+use std::fmt::Display;
+
 use glib::translate::{FromGlibPtrFull, FromGlibPtrNone, ToGlibPtr};
+use glib::ObjectExt;
+
+use crate::context::FpContext;
 
 use super::{
-    enums::{FpDeviceFeature, FpFingerStatus, FpScanType},
+    enums::{FpDeviceFeature, FpFingerStatus, FpFingerStatusFlags, FpScanType},
     FpDevice,
 };
 
+/// Stable identifier of a device, made of its driver and device ID.
+///
+/// `FpDevice` compares and hashes by object identity, like every GObject wrapper. `FpDeviceId` compares by
+/// value instead, so it can be used as a key for devices that were enumerated by different contexts or
+/// re-enumerated after a hotplug event.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FpDeviceId {
+    /// The ID of the driver.
+    pub driver: String,
+    /// The ID of the device.
+    pub device_id: String,
+}
+
+/// A snapshot of the properties of a device, see `FpDevice::info`.
+///
+/// Unlike `FpDevice` it holds no reference to the underlying GObject, so it can be freely sent between
+/// threads, stored or logged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FpDeviceInfo {
+    /// The human readable name of the device.
+    pub name: String,
+    /// The ID of the driver.
+    pub driver: String,
+    /// The stable identifier of the device.
+    pub id: FpDeviceId,
+    /// The scan type of the device.
+    pub scan_type: FpScanType,
+    /// The features supported by the device.
+    pub features: Vec<FpDeviceFeature>,
+    /// The number of enroll stages of the device.
+    pub nr_enroll_stages: i32,
+}
+
+impl Display for FpDeviceInfo {
+    /// Formats the device for device pickers, e.g. `Goodix MOC Fingerprint Sensor (goodixmoc, press)`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}, {})", self.name, self.driver, self.scan_type)
+    }
+}
+
+impl Display for FpDevice {
+    /// Formats the device for device pickers, e.g. `Goodix MOC Fingerprint Sensor (goodixmoc, press)`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}, {})",
+            self.name(),
+            self.driver(),
+            self.scan_type()
+        )
+    }
+}
+
 impl FpDevice {
+    /// Returns a snapshot of the properties of the device. The device does not need to be open.
+    pub fn info(&self) -> FpDeviceInfo {
+        FpDeviceInfo {
+            name: self.name(),
+            driver: self.driver(),
+            id: self.id(),
+            scan_type: self.scan_type(),
+            features: self.features(),
+            nr_enroll_stages: self.nr_enroll_stage(),
+        }
+    }
+    /// The stable identifier of the device, suitable as a `HashMap` key.
+    pub fn id(&self) -> FpDeviceId {
+        FpDeviceId {
+            driver: self.driver(),
+            device_id: self.device_id(),
+        }
+    }
+    #[cfg(not(doctest))]
+    /// An identifier of the device that survives reboots and re-enumeration, made of the driver and the USB
+    /// port path of the device, e.g. `goodixmoc@1-3.2` for port 2 of the hub on port 3 of bus 1. It names
+    /// where the reader is plugged, so config files can pin a reader even when several of the same model
+    /// are attached. Devices that are not USB devices use their device ID instead, e.g. `virtual_image@0`.
+    ///
+    /// The USB serial number is not used: most sensors do not report one, and reading it needs the device
+    /// to be open.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::FpContext;
+    ///
+    /// let ctx = FpContext::new();
+    /// let front_door = ctx
+    ///     .devices()
+    ///     .into_iter()
+    ///     .find(|dev| dev.persistent_id() == "goodixmoc@1-3.2");
+    /// ```
+    pub fn persistent_id(&self) -> String {
+        let location = self
+            .usb_device()
+            .map(|usb_device| usb_port_path(&usb_device))
+            .unwrap_or_else(|| self.device_id());
+        format!("{}@{}", self.driver(), location)
+    }
+    /// The GUsbDevice of USB devices, that libfprint keeps in a private property.
+    pub(crate) fn usb_device(&self) -> Option<glib::Object> {
+        self.find_property("fpi-usb-device")
+            .filter(|pspec| pspec.flags().contains(glib::ParamFlags::READABLE))
+            .and_then(|_| self.property::<Option<glib::Object>>("fpi-usb-device"))
+    }
     /// The ID of the driver.
     pub fn driver(&self) -> String {
         unsafe {
@@ -24,7 +131,7 @@ impl FpDevice {
     pub fn name(&self) -> String {
         unsafe {
             let name = libfprint_sys::fp_device_get_name(self.to_glib_none().0);
-            glib::GString::from_glib_full(name).to_string()
+            glib::GString::from_glib_none(name).to_string()
         }
     }
     /// Retrieves the scan type of the device.
@@ -41,34 +148,50 @@ impl FpDevice {
         unsafe { libfprint_sys::fp_device_get_nr_enroll_stages(self.to_glib_none().0) }
     }
     /// Retrieves the finger status flags for the device. This can be used by the UI to present the relevant feedback, although it is not guaranteed to be a relevant value when not performing any action.
+    ///
+    /// A finger that is present takes precedence over a needed one, see `FpDevice::finger_status_flags` for both.
     pub fn finger_status(&self) -> FpFingerStatus {
+        let flags = self.finger_status_flags();
+        if flags.is_present() {
+            FpFingerStatus::Present
+        } else if flags.is_needed() {
+            FpFingerStatus::Needed
+        } else {
+            FpFingerStatus::None
+        }
+    }
+    /// Retrieves the raw finger status flags for the device, queried on demand for polling UIs and debug tools.
+    pub fn finger_status_flags(&self) -> FpFingerStatusFlags {
         let status = unsafe { libfprint_sys::fp_device_get_finger_status(self.to_glib_none().0) };
-        match status {
-            libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_NONE => FpFingerStatus::None,
-            libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_NEEDED => FpFingerStatus::Needed,
-            libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_PRESENT => FpFingerStatus::Present,
-            _ => panic!("Unknown finger status"),
+        FpFingerStatusFlags::from_bits(status)
+    }
+    #[cfg(libfprint_1_94)]
+    /// Retrieves the temperature of the device.
+    pub fn temperature(&self) -> super::FpTemperature {
+        use super::FpTemperature;
+
+        let temperature =
+            unsafe { libfprint_sys::fp_device_get_temperature(self.to_glib_none().0) };
+        match temperature {
+            libfprint_sys::FpTemperature_FP_TEMPERATURE_COLD => FpTemperature::Cold,
+            libfprint_sys::FpTemperature_FP_TEMPERATURE_WARM => FpTemperature::Warm,
+            libfprint_sys::FpTemperature_FP_TEMPERATURE_HOT => FpTemperature::Hot,
+            _ => panic!("Unknown temperature"),
         }
     }
-    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-    /// Gets the FpDeviceFeature's supported by the device .
+    /// Gets the FpDeviceFeature's supported by the device, `FpDeviceFeature::None` if it supports none.
     pub fn features(&self) -> Vec<FpDeviceFeature> {
         // Unmask the features bitfield and return a vector of FpDeviceFeature
-        let mut features = Vec::new();
-        let x = unsafe { libfprint_sys::fp_device_get_features(self.to_glib_none().0) };
-        if x == 0 {
+        let features =
+            unsafe { libfprint_sys::fp_device_get_features(self.to_glib_none().0) } as u32;
+        if features == 0 {
             return vec![FpDeviceFeature::None];
-        } else {
-            (0..31).for_each(|i| {
-                let mask = 1 << i;
-                if (mask & x) > 0 {
-                    if let Ok(feature) = FpDeviceFeature::try_from(2_u32.pow(i)) {
-                        features.push(feature);
-                    }
-                }
-            });
         }
-        features
+        (0..32)
+            .map(|i| 1_u32 << i)
+            .filter(|mask| features & mask != 0)
+            .filter_map(|mask| FpDeviceFeature::try_from(mask).ok())
+            .collect()
     }
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     /// Checks if device supports the requested FpDeviceFeature.
@@ -77,8 +200,59 @@ impl FpDevice {
             unsafe { libfprint_sys::fp_device_has_feature(self.to_glib_none().0, feature as u32) };
         res == glib::ffi::GTRUE
     }
+    /// Whether the device supports `feature`, on every architecture unlike `FpDevice::has_feature`.
+    pub(crate) fn supports(&self, feature: FpDeviceFeature) -> bool {
+        let features = unsafe { libfprint_sys::fp_device_get_features(self.to_glib_none().0) };
+        features as u32 & feature as u32 != 0
+    }
     /// Whether the device is open or not
     pub fn is_open(&self) -> bool {
         unsafe { libfprint_sys::fp_device_is_open(self.to_glib_none().0) == glib::ffi::GTRUE }
     }
+    /// The context the device was obtained from, `None` once the context was freed or shut down, see
+    /// `FpContext::shutdown`.
+    ///
+    /// The device only holds a weak reference to it: the context owns its devices, a strong reference back
+    /// would keep both alive forever.
+    pub fn context(&self) -> Option<FpContext> {
+        unsafe {
+            self.data::<glib::WeakRef<FpContext>>("context")
+                .and_then(|ctx| ctx.as_ref().upgrade())
+        }
+    }
+    pub(crate) fn set_context(&self, context: &FpContext) {
+        unsafe { self.set_data("context", context.downgrade()) };
+    }
+    pub(crate) fn take_context(&self) -> Option<FpContext> {
+        unsafe { self.steal_data::<glib::WeakRef<FpContext>>("context") }
+            .and_then(|ctx| ctx.upgrade())
+    }
+}
+
+/// The port path of a GUsbDevice as in sysfs, e.g. `1-3.2`: its bus, then the ports from the root hub.
+fn usb_port_path(usb_device: &glib::Object) -> String {
+    let raw_usb: *mut glib::gobject_ffi::GObject = usb_device.to_glib_none().0;
+    let raw_usb: *mut libfprint_sys::GUsbDevice = raw_usb.cast();
+    let bus = unsafe { libfprint_sys::g_usb_device_get_bus(raw_usb) };
+
+    let mut ports = Vec::new();
+    let mut device = usb_device.clone();
+    loop {
+        let raw: *mut glib::gobject_ffi::GObject = device.to_glib_none().0;
+        let raw: *mut libfprint_sys::GUsbDevice = raw.cast();
+        // Root hubs have no port
+        let port = unsafe { libfprint_sys::g_usb_device_get_port_number(raw) };
+        if port == 0 {
+            break;
+        }
+        ports.push(port.to_string());
+        let parent = unsafe { libfprint_sys::g_usb_device_get_parent(raw) };
+        if parent.is_null() {
+            break;
+        }
+        device =
+            unsafe { glib::Object::from_glib_full(parent.cast::<glib::gobject_ffi::GObject>()) };
+    }
+    ports.reverse();
+    format!("{}-{}", bus, ports.join("."))
 }
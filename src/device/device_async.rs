@@ -0,0 +1,438 @@
+use std::{cell::RefCell, os::raw::c_void, rc::Rc, time::Instant};
+
+use gio::Cancellable;
+use glib::translate::{FromGlibPtrFull, ToGlibPtr};
+use glib::ObjectExt;
+
+use crate::device::{
+    callback::{fp_enroll_progress, fp_match_cb},
+    fn_pointer, UserData,
+};
+use crate::{image::FpImage, print::FpPrint};
+
+use super::{
+    busy::PendingOperation,
+    trace::{ErrorContext, OperationSpan},
+    FpDevice, FpEnrollProgress, FpIdentifyResult, FpMatchCb, FpMatchOutcome,
+};
+
+type FinishFunc = unsafe extern "C" fn(
+    *mut libfprint_sys::FpDevice,
+    *mut libfprint_sys::GAsyncResult,
+    *mut *mut libfprint_sys::GError,
+) -> libfprint_sys::gboolean;
+
+/// Trampoline for the operations whose `_finish` function only reports success or failure.
+unsafe extern "C" fn finish_trampoline<P: FnOnce(Result<(), crate::GError>) + 'static>(
+    source: *mut libfprint_sys::GObject,
+    res: *mut libfprint_sys::GAsyncResult,
+    user_data: *mut c_void,
+) {
+    // Safety: The pointer was created from a box in `FpDevice::start`, and GIO calls this
+    // function exactly once, so we are the only owners of it.
+    let data: Box<(FinishFunc, PendingOperation, P)> = Box::from_raw(user_data.cast());
+    let (finish, pending, callback) = *data;
+
+    let mut error = std::ptr::null_mut();
+    let ok = finish(source.cast(), res, std::ptr::addr_of_mut!(error));
+    drop(pending);
+    if ok == glib::ffi::GFALSE {
+        callback(Err(glib::Error::from_glib_full(error.cast())));
+    } else {
+        callback(Ok(()));
+    }
+}
+
+unsafe extern "C" fn enroll_trampoline<P: FnOnce(Result<FpPrint, crate::GError>) + 'static>(
+    source: *mut libfprint_sys::GObject,
+    res: *mut libfprint_sys::GAsyncResult,
+    user_data: *mut c_void,
+) {
+    let data: Box<(OperationSpan, PendingOperation, P)> = Box::from_raw(user_data.cast());
+    let (span, pending, callback) = *data;
+    let callback = move |res| {
+        drop(pending);
+        callback(span.finish(res))
+    };
+    let mut error = std::ptr::null_mut();
+
+    let ptr =
+        libfprint_sys::fp_device_enroll_finish(source.cast(), res, std::ptr::addr_of_mut!(error));
+    if ptr.is_null() {
+        callback(Err(glib::Error::from_glib_full(error.cast())));
+    } else {
+        let fp = FpPrint::from_glib_full(ptr);
+        fp.set_data("set", true);
+        callback(Ok(fp));
+    }
+}
+
+unsafe extern "C" fn verify_trampoline<
+    P: FnOnce(Result<FpMatchOutcome, crate::GError>) + 'static,
+>(
+    source: *mut libfprint_sys::GObject,
+    res: *mut libfprint_sys::GAsyncResult,
+    user_data: *mut c_void,
+) {
+    let data: Box<(OperationSpan, PendingOperation, P)> = Box::from_raw(user_data.cast());
+    let (span, pending, callback) = *data;
+    let callback = move |res| {
+        drop(pending);
+        callback(span.finish(res))
+    };
+    let mut error = std::ptr::null_mut();
+    let mut matched = glib::ffi::GFALSE;
+    let mut new_print = std::ptr::null_mut();
+
+    let ok = libfprint_sys::fp_device_verify_finish(
+        source.cast(),
+        res,
+        &mut matched,
+        std::ptr::addr_of_mut!(new_print),
+        std::ptr::addr_of_mut!(error),
+    );
+    callback(FpMatchOutcome::from_raw(ok, matched, new_print, error));
+}
+
+/// The state of an identification until it completes: its gallery, to find the position of the matched print,
+/// and its start time.
+type IdentifyData<P> = (OperationSpan, PendingOperation, Vec<FpPrint>, Instant, P);
+
+unsafe extern "C" fn identify_trampoline<
+    P: FnOnce(Result<FpIdentifyResult, crate::GError>) + 'static,
+>(
+    source: *mut libfprint_sys::GObject,
+    res: *mut libfprint_sys::GAsyncResult,
+    user_data: *mut c_void,
+) {
+    let data: Box<IdentifyData<P>> = Box::from_raw(user_data.cast());
+    let (span, pending, gallery, start, callback) = *data;
+    let callback = move |res| {
+        drop(pending);
+        callback(span.finish(res))
+    };
+    let mut error = std::ptr::null_mut();
+    let mut print_match = std::ptr::null_mut();
+    let mut new_print = std::ptr::null_mut();
+
+    let ok = libfprint_sys::fp_device_identify_finish(
+        source.cast(),
+        res,
+        std::ptr::addr_of_mut!(print_match),
+        std::ptr::addr_of_mut!(new_print),
+        std::ptr::addr_of_mut!(error),
+    );
+    let duration = start.elapsed();
+    let new_scan = (!new_print.is_null()).then(|| FpPrint::from_glib_full(new_print));
+    let matched = (!print_match.is_null()).then(|| FpPrint::from_glib_full(print_match));
+    if ok == glib::ffi::GFALSE {
+        callback(Err(glib::Error::from_glib_full(error.cast())));
+    } else {
+        callback(Ok(FpIdentifyResult::new(
+            &gallery, matched, new_scan, duration,
+        )));
+    }
+}
+
+unsafe extern "C" fn capture_trampoline<P: FnOnce(Result<FpImage, crate::GError>) + 'static>(
+    source: *mut libfprint_sys::GObject,
+    res: *mut libfprint_sys::GAsyncResult,
+    user_data: *mut c_void,
+) {
+    let data: Box<(ErrorContext, PendingOperation, P)> = Box::from_raw(user_data.cast());
+    let (context, pending, callback) = *data;
+    let callback = move |res| {
+        drop(pending);
+        callback(context.finish(res))
+    };
+    let mut error = std::ptr::null_mut();
+
+    let ptr =
+        libfprint_sys::fp_device_capture_finish(source.cast(), res, std::ptr::addr_of_mut!(error));
+    if ptr.is_null() {
+        callback(Err(glib::Error::from_glib_full(error.cast())));
+    } else {
+        callback(Ok(FpImage::from_glib_full(ptr)));
+    }
+}
+
+/// Run an operation of the asynchronous API of `FpDevice`, started by `start` with its completion callback,
+/// iterating `main_context` until it completes. Unlike the `_sync` operations of libfprint, which iterate the
+/// global default main context, this waits on the context the callback is dispatched on.
+pub(crate) fn wait<R: 'static>(
+    main_context: &glib::MainContext,
+    start: impl FnOnce(Box<dyn FnOnce(R)>),
+) -> R {
+    let result = Rc::new(RefCell::new(None));
+    {
+        let result = result.clone();
+        start(Box::new(move |res| *result.borrow_mut() = Some(res)));
+    }
+    loop {
+        if let Some(res) = result.borrow_mut().take() {
+            return res;
+        }
+        main_context.iteration(true);
+    }
+}
+
+/// Drops the callback data passed to libfprint once the operation no longer needs it.
+pub(crate) unsafe extern "C" fn destroy_user_data<F, T>(ptr: *mut c_void) {
+    let _: Box<UserData<F, T>> = Box::from_raw(ptr.cast());
+}
+
+type StartFunc = unsafe extern "C" fn(
+    *mut libfprint_sys::FpDevice,
+    *mut libfprint_sys::GCancellable,
+    libfprint_sys::GAsyncReadyCallback,
+    *mut c_void,
+);
+impl FpDevice {
+    /// Starts one of the operations that only take a cancellable, and whose `_finish` function only
+    /// reports success or failure.
+    fn start<P: FnOnce(Result<(), crate::GError>) + 'static>(
+        &self,
+        operation: &'static str,
+        start: StartFunc,
+        finish: FinishFunc,
+        cancellable: Option<&Cancellable>,
+        callback: P,
+    ) {
+        let pending = PendingOperation::start(self, operation, cancellable);
+        let raw_cancel: *mut gio::ffi::GCancellable = pending.cancellable().to_glib_none().0;
+        let user_data: Box<(FinishFunc, PendingOperation, P)> =
+            Box::new((finish, pending, callback));
+
+        unsafe {
+            start(
+                self.to_glib_none().0,
+                raw_cancel.cast(),
+                Some(finish_trampoline::<P>),
+                Box::into_raw(user_data).cast(),
+            )
+        };
+    }
+
+    /// Start an asynchronous operation to open the device. `callback` is called on the thread default
+    /// main context once the operation completes.
+    pub fn open<P: FnOnce(Result<(), crate::GError>) + 'static>(
+        &self,
+        cancellable: Option<&Cancellable>,
+        callback: P,
+    ) {
+        let span = OperationSpan::new(self, "open");
+        self.start(
+            "open",
+            libfprint_sys::fp_device_open,
+            libfprint_sys::fp_device_open_finish,
+            cancellable,
+            move |res| callback(span.finish(res)),
+        );
+    }
+
+    /// Start an asynchronous operation to close the device. `callback` is called on the thread default
+    /// main context once the operation completes.
+    pub fn close<P: FnOnce(Result<(), crate::GError>) + 'static>(
+        &self,
+        cancellable: Option<&Cancellable>,
+        callback: P,
+    ) {
+        let context = ErrorContext::new(self, "close");
+        self.start(
+            "close",
+            libfprint_sys::fp_device_close,
+            libfprint_sys::fp_device_close_finish,
+            cancellable,
+            move |res| callback(context.finish(res)),
+        );
+    }
+
+    #[cfg(libfprint_1_94)]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    /// Start an asynchronous operation to prepare the device for suspend.
+    pub fn suspend<P: FnOnce(Result<(), crate::GError>) + 'static>(
+        &self,
+        cancellable: Option<&Cancellable>,
+        callback: P,
+    ) {
+        let context = ErrorContext::new(self, "suspend");
+        self.start(
+            "suspend",
+            libfprint_sys::fp_device_suspend,
+            libfprint_sys::fp_device_suspend_finish,
+            cancellable,
+            move |res| callback(context.finish(res)),
+        );
+    }
+
+    #[cfg(libfprint_1_94)]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    /// Start an asynchronous operation to resume the device after suspend.
+    pub fn resume<P: FnOnce(Result<(), crate::GError>) + 'static>(
+        &self,
+        cancellable: Option<&Cancellable>,
+        callback: P,
+    ) {
+        let context = ErrorContext::new(self, "resume");
+        self.start(
+            "resume",
+            libfprint_sys::fp_device_resume,
+            libfprint_sys::fp_device_resume_finish,
+            cancellable,
+            move |res| callback(context.finish(res)),
+        );
+    }
+
+    /// Start an asynchronous operation to enroll a new print. `progress_cb` will be called for each
+    /// stage of the enrollment process, and `callback` once the operation completes.
+    pub fn enroll<T: 'static, P: FnOnce(Result<FpPrint, crate::GError>) + 'static>(
+        &self,
+        template: FpPrint,
+        cancellable: Option<&Cancellable>,
+        progress_cb: Option<FpEnrollProgress<T>>,
+        progress_data: Option<T>,
+        callback: P,
+    ) {
+        let span = OperationSpan::new(self, "enroll");
+        let template = match self.check_print(template) {
+            Ok(template) => template,
+            Err(err) => return callback(span.finish(Err(err))),
+        };
+        let pending = PendingOperation::start(self, "enroll", cancellable);
+        let raw_cancel: *mut gio::ffi::GCancellable = pending.cancellable().to_glib_none().0;
+
+        let user_ptr = fn_pointer!(progress_cb, progress_data);
+        let destroy = match user_ptr.is_null() {
+            true => None,
+            false => Some(destroy_user_data::<FpEnrollProgress<T>, T> as _),
+        };
+
+        // Raw template: transfer full
+        let raw_template: *mut libfprint_sys::FpPrint = template.to_glib_full();
+        let callback: Box<(OperationSpan, PendingOperation, P)> =
+            Box::new((span, pending, callback));
+
+        unsafe {
+            libfprint_sys::fp_device_enroll(
+                self.to_glib_none().0,
+                raw_template,
+                raw_cancel.cast(),
+                Some(fp_enroll_progress::<FpEnrollProgress<T>, T>),
+                user_ptr,
+                destroy,
+                Some(enroll_trampoline::<P>),
+                Box::into_raw(callback).cast(),
+            )
+        };
+    }
+
+    /// Start an asynchronous operation to verify a given print. `match_cb` will be called as soon as
+    /// the matcher has decided, see `FpMatchCb`, and `callback` once the operation completes.
+    pub fn verify<T: 'static, P: FnOnce(Result<FpMatchOutcome, crate::GError>) + 'static>(
+        &self,
+        enrolled_print: &FpPrint,
+        cancellable: Option<&Cancellable>,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+        callback: P,
+    ) {
+        let span = OperationSpan::new(self, "verify");
+        let pending = PendingOperation::start(self, "verify", cancellable);
+        let raw_cancel: *mut gio::ffi::GCancellable = pending.cancellable().to_glib_none().0;
+
+        let ptr = fn_pointer!(match_cb, match_data);
+        let destroy = match ptr.is_null() {
+            true => None,
+            false => Some(destroy_user_data::<FpMatchCb<T>, T> as _),
+        };
+        let callback: Box<(OperationSpan, PendingOperation, P)> =
+            Box::new((span, pending, callback));
+
+        unsafe {
+            libfprint_sys::fp_device_verify(
+                self.to_glib_none().0,
+                enrolled_print.to_glib_none().0,
+                raw_cancel.cast(),
+                Some(fp_match_cb::<FpMatchCb<T>, T>),
+                ptr,
+                destroy,
+                Some(verify_trampoline::<P>),
+                Box::into_raw(callback).cast(),
+            )
+        };
+    }
+
+    /// Start an asynchronous operation to identify a print. `match_cb` will be called as soon as the
+    /// matcher has decided, see `FpMatchCb`, and `callback` once the operation completes with the
+    /// position of the matching print in `prints`, if any, like `FpDevice::identify_sync`. The gallery is
+    /// borrowed, the operation only keeps references to its prints.
+    pub fn identify<'a, T: 'static, P, I, G>(
+        &self,
+        prints: I,
+        cancellable: Option<&Cancellable>,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+        callback: P,
+    ) where
+        P: FnOnce(Result<FpIdentifyResult, crate::GError>) + 'static,
+        I: IntoIterator<Item = &'a G>,
+        G: AsRef<FpPrint> + 'a,
+    {
+        let span = OperationSpan::new(self, "identify");
+        let pending = PendingOperation::start(self, "identify", cancellable);
+        let raw_cancel: *mut gio::ffi::GCancellable = pending.cancellable().to_glib_none().0;
+
+        let ptr = fn_pointer!(match_cb, match_data);
+        let destroy = match ptr.is_null() {
+            true => None,
+            false => Some(destroy_user_data::<FpMatchCb<T>, T> as _),
+        };
+        // Keeps references to the prints to find the position of the matched one
+        let gallery: Vec<FpPrint> = prints
+            .into_iter()
+            .map(|print| print.as_ref().clone())
+            .collect();
+        // Create a GPtrArray borrowing the prints, libfprint keeps its own copy of it
+        let raw_prints = crate::ptr_array::gallery(&gallery);
+        let callback: Box<IdentifyData<P>> =
+            Box::new((span, pending, gallery, Instant::now(), callback));
+
+        unsafe {
+            libfprint_sys::fp_device_identify(
+                self.to_glib_none().0,
+                raw_prints.cast(),
+                raw_cancel.cast(),
+                Some(fp_match_cb::<FpMatchCb<T>, T>),
+                ptr,
+                destroy,
+                Some(identify_trampoline::<P>),
+                Box::into_raw(callback).cast(),
+            );
+            libfprint_sys::g_ptr_array_free(raw_prints.cast(), 1);
+        };
+    }
+
+    /// Start an asynchronous operation to capture an image. `callback` is called once the operation completes.
+    pub fn capture<P: FnOnce(Result<FpImage, crate::GError>) + 'static>(
+        &self,
+        wait_for_finger: bool,
+        cancellable: Option<&Cancellable>,
+        callback: P,
+    ) {
+        let context = ErrorContext::new(self, "capture");
+        let pending = PendingOperation::start(self, "capture", cancellable);
+        let raw_cancel: *mut gio::ffi::GCancellable = pending.cancellable().to_glib_none().0;
+        let callback: Box<(ErrorContext, PendingOperation, P)> =
+            Box::new((context, pending, callback));
+
+        unsafe {
+            libfprint_sys::fp_device_capture(
+                self.to_glib_none().0,
+                wait_for_finger as i32,
+                raw_cancel.cast(),
+                Some(capture_trampoline::<P>),
+                Box::into_raw(callback).cast(),
+            )
+        };
+    }
+}
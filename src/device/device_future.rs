@@ -0,0 +1,254 @@
+use std::{future::Future, pin::Pin};
+
+use crate::{error::FpRetryError, image::FpImage, print::FpPrint};
+
+use super::{FpDevice, FpEnrollProgress, FpIdentifyResult, FpMatchCb, FpMatchOutcome};
+
+/// Boxed future returned by the `_future` methods of `FpDevice`.
+pub type FpFuture<T> = Pin<Box<dyn Future<Output = Result<T, crate::GError>> + 'static>>;
+
+/// A stage of an enrollment started with `FpDevice::enroll_async`.
+#[derive(Debug, Clone)]
+pub struct FpEnrollStage {
+    /// The number of enroll stages completed so far.
+    pub completed_stages: i32,
+    /// The last scanned print, if any.
+    pub print: Option<FpPrint>,
+    /// The retry error of the stage, if the scan failed.
+    pub error: Option<crate::GError>,
+}
+
+fn enroll_stage_cb(
+    _device: &FpDevice,
+    completed_stages: i32,
+    print: Option<FpPrint>,
+    error: Option<crate::GError>,
+    sender: &Option<futures_channel::mpsc::UnboundedSender<FpEnrollStage>>,
+) {
+    if let Some(sender) = sender {
+        let _ = sender.unbounded_send(FpEnrollStage {
+            completed_stages,
+            print,
+            error,
+        });
+    }
+}
+
+/// A preview of a scan of an enrollment started with `FpDevice::enroll_preview`.
+#[derive(Debug, Clone)]
+pub struct FpPreviewFrame {
+    /// The number of enroll stages completed so far.
+    pub completed_stages: i32,
+    /// The downscaled image of the scan.
+    pub image: FpImage,
+    /// Why the scan failed, if it did.
+    pub retry: Option<FpRetryError>,
+}
+
+/// The sender of the previews and the bounds of their images.
+type PreviewData = (
+    futures_channel::mpsc::UnboundedSender<FpPreviewFrame>,
+    u32,
+    u32,
+);
+
+fn enroll_preview_cb(
+    _device: &FpDevice,
+    completed_stages: i32,
+    print: Option<FpPrint>,
+    error: Option<crate::GError>,
+    data: &Option<PreviewData>,
+) {
+    let image = print.as_ref().and_then(FpPrint::image);
+    if let (Some((sender, max_width, max_height)), Some(image)) = (data, image) {
+        let _ = sender.unbounded_send(FpPreviewFrame {
+            completed_stages,
+            image: image.thumbnail(*max_width, *max_height),
+            retry: error.map(|err| err.kind().unwrap_or(FpRetryError::General)),
+        });
+    }
+}
+
+impl FpDevice {
+    /// Open the device, returning a future. See `FpDevice::open`.
+    pub fn open_future(&self) -> FpFuture<()> {
+        Box::pin(gio::GioFuture::new(self, |obj, cancellable, send| {
+            obj.open(Some(cancellable), move |res| send.resolve(res));
+        }))
+    }
+
+    /// Close the device, returning a future. See `FpDevice::close`.
+    pub fn close_future(&self) -> FpFuture<()> {
+        Box::pin(gio::GioFuture::new(self, |obj, cancellable, send| {
+            obj.close(Some(cancellable), move |res| send.resolve(res));
+        }))
+    }
+
+    #[cfg(libfprint_1_94)]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    /// Prepare the device for suspend, returning a future. See `FpDevice::suspend`.
+    pub fn suspend_future(&self) -> FpFuture<()> {
+        Box::pin(gio::GioFuture::new(self, |obj, cancellable, send| {
+            obj.suspend(Some(cancellable), move |res| send.resolve(res));
+        }))
+    }
+
+    #[cfg(libfprint_1_94)]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    /// Resume the device after suspend, returning a future. See `FpDevice::resume`.
+    pub fn resume_future(&self) -> FpFuture<()> {
+        Box::pin(gio::GioFuture::new(self, |obj, cancellable, send| {
+            obj.resume(Some(cancellable), move |res| send.resolve(res));
+        }))
+    }
+
+    /// Enroll a new print, returning a future. See `FpDevice::enroll`.
+    pub fn enroll_future<T: 'static>(
+        &self,
+        template: FpPrint,
+        progress_cb: Option<FpEnrollProgress<T>>,
+        progress_data: Option<T>,
+    ) -> FpFuture<FpPrint> {
+        Box::pin(gio::GioFuture::new(self, move |obj, cancellable, send| {
+            obj.enroll(
+                template,
+                Some(cancellable),
+                progress_cb,
+                progress_data,
+                move |res| send.resolve(res),
+            );
+        }))
+    }
+
+    #[cfg(not(doctest))]
+    /// Enroll a new print, returning a future for the enrolled print and a stream of the enrollment stages.
+    ///
+    /// The operation starts when the future is first polled, and the stream ends once it completes. The stream
+    /// does not need to be consumed for the enrollment to make progress.
+    /// # Example:
+    /// ```no_run
+    /// use futures_util::{future, StreamExt};
+    /// use libfprint_rs::{FpContext, FpPrint};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    ///
+    /// glib::MainContext::default().block_on(async {
+    ///     dev.open_future().await.unwrap();
+    ///     let (print, stages) = dev.enroll_async(FpPrint::new(&dev));
+    ///     let stages = stages.for_each(|stage| {
+    ///         println!("Enroll stage: {}", stage.completed_stages);
+    ///         future::ready(())
+    ///     });
+    ///     let (print, _) = future::join(print, stages).await;
+    /// });
+    /// ```
+    pub fn enroll_async(
+        &self,
+        template: FpPrint,
+    ) -> (
+        FpFuture<FpPrint>,
+        impl futures_core::Stream<Item = FpEnrollStage> + Unpin + 'static,
+    ) {
+        let (sender, receiver) = futures_channel::mpsc::unbounded();
+        let future = self.enroll_future(template, Some(enroll_stage_cb), Some(sender));
+        (future, receiver)
+    }
+
+    #[cfg(not(doctest))]
+    /// Enroll a new print, returning a future for the enrolled print and a stream of previews of the scans,
+    /// downscaled to fit in `max_width` by `max_height`, e.g. to show the finger in an enrollment UI.
+    ///
+    /// Previews are only sent for the scans whose print carries the scanned image, as with the image devices
+    /// (see `FpDevice::scan_type` and `FpDeviceFeature::Capture`), the stream of other devices stays empty.
+    /// The stream ends once the enrollment completes.
+    /// # Example:
+    /// ```no_run
+    /// use futures_util::{future, StreamExt};
+    /// use libfprint_rs::{FpContext, FpPrint};
+    ///
+    /// let ctx = FpContext::new();
+    /// let dev = ctx.devices().remove(0);
+    ///
+    /// glib::MainContext::default().block_on(async {
+    ///     dev.open_future().await.unwrap();
+    ///     let (print, previews) = dev.enroll_preview(FpPrint::new(&dev), 128, 128);
+    ///     let previews = previews.for_each(|frame| {
+    ///         show_image(frame.image.width(), frame.image.height(), &frame.image.data());
+    ///         future::ready(())
+    ///     });
+    ///     let (print, _) = future::join(print, previews).await;
+    /// });
+    /// ```
+    pub fn enroll_preview(
+        &self,
+        template: FpPrint,
+        max_width: u32,
+        max_height: u32,
+    ) -> (
+        FpFuture<FpPrint>,
+        impl futures_core::Stream<Item = FpPreviewFrame> + Unpin + 'static,
+    ) {
+        let (sender, receiver) = futures_channel::mpsc::unbounded();
+        let future = self.enroll_future(
+            template,
+            Some(enroll_preview_cb),
+            Some((sender, max_width, max_height)),
+        );
+        (future, receiver)
+    }
+
+    /// Verify a given print, returning a future. See `FpDevice::verify`.
+    pub fn verify_future<T: 'static>(
+        &self,
+        enrolled_print: &FpPrint,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+    ) -> FpFuture<FpMatchOutcome> {
+        // The future may start after the caller's borrow ends: keep a reference to the print, not a copy
+        let enrolled_print = enrolled_print.clone();
+        Box::pin(gio::GioFuture::new(self, move |obj, cancellable, send| {
+            obj.verify(
+                &enrolled_print,
+                Some(cancellable),
+                match_cb,
+                match_data,
+                move |res| send.resolve(res),
+            );
+        }))
+    }
+
+    /// Identify a print, returning a future. See `FpDevice::identify`.
+    pub fn identify_future<'a, T: 'static, I, G>(
+        &self,
+        prints: I,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+    ) -> FpFuture<FpIdentifyResult>
+    where
+        I: IntoIterator<Item = &'a G>,
+        G: AsRef<FpPrint> + 'a,
+    {
+        // The future may start after the prints are gone, it keeps references to them
+        let prints: Vec<FpPrint> = prints.into_iter().map(|p| p.as_ref().clone()).collect();
+        Box::pin(gio::GioFuture::new(self, move |obj, cancellable, send| {
+            obj.identify(
+                &prints,
+                Some(cancellable),
+                match_cb,
+                match_data,
+                move |res| send.resolve(res),
+            );
+        }))
+    }
+
+    /// Capture an image, returning a future. See `FpDevice::capture`.
+    pub fn capture_future(&self, wait_for_finger: bool) -> FpFuture<FpImage> {
+        Box::pin(gio::GioFuture::new(self, move |obj, cancellable, send| {
+            obj.capture(wait_for_finger, Some(cancellable), move |res| {
+                send.resolve(res)
+            });
+        }))
+    }
+}
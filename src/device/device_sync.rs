@@ -1,21 +1,149 @@
-use crate::device::{callback::fp_match_cb, fn_pointer, UserData};
+use crate::device::{borrowed_ptr, callback::fp_match_cb, UserData};
+use crate::error::{FpContextual, FpError, FpRetryError};
+use crate::finger::FpFinger;
 use crate::image::FpImage;
 use gio::Cancellable;
-use glib::translate::FromGlibPtrNone;
 use glib::translate::{FromGlibPtrFull, ToGlibPtr};
 use glib::ObjectExt;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 use crate::print::FpPrint;
 
-use super::FpDevice;
+use super::{
+    busy::OperationGuard,
+    trace::{ErrorContext, OperationSpan},
+    FpDevice, FpDeviceFeature,
+};
 
 /// This type represents the callback function for the `FpDevice::enroll` implementation and will be called for each stage of the enrollment process.
 pub type FpEnrollProgress<T> =
     fn(&FpDevice, i32, Option<FpPrint>, Option<crate::GError>, &Option<T>) -> ();
-/// This type represents the callback function for the `FpDevice::verify` and `FpDevice::identify` implementations and will be called when a print is matched.
+/// This type represents the callback function for the `FpDevice::verify` and `FpDevice::identify` implementations.
+///
+/// The callback is an early report: it is called as soon as the matcher has decided, which may be well
+/// before the operation itself completes (e.g. while the driver is still post-processing the image or
+/// waiting for the finger to be removed). Use it where latency matters, such as unlocking a door, and
+/// treat the return value of the sync function as the final result.
+///
+/// The arguments are the matched print (if any), the newly scanned print (`None` if the scan failed)
+/// and the retry error describing why the scan failed, if it did.
 pub type FpMatchCb<T> =
-    fn(&FpDevice, Option<FpPrint>, FpPrint, Option<crate::GError>, &Option<T>) -> ();
+    fn(&FpDevice, Option<FpPrint>, Option<FpPrint>, Option<crate::GError>, &Option<T>) -> ();
+
+/// The outcome of `FpDevice::verify_sync`. The prints are the newly scanned prints, which may be `None`
+/// if the driver does not provide them.
+#[derive(Debug)]
+pub enum FpMatchOutcome {
+    /// The scanned finger matches the enrolled print.
+    Matched(Option<FpPrint>),
+    /// The scanned finger does not match the enrolled print.
+    NotMatched(Option<FpPrint>),
+    /// The scan failed, the user should try again. This should not count as a failed attempt.
+    Retry(FpRetryError),
+}
+
+/// The outcome of `FpDevice::verify_exclusive_sync`.
+#[derive(Debug)]
+pub enum FpExclusiveOutcome {
+    /// The scanned finger matches no print of the exclusion gallery, the outcome of the verification is given.
+    Verified(FpMatchOutcome),
+    /// The scanned finger matches a print of the exclusion gallery, whose username is given (empty if it has
+    /// none).
+    MatchedOtherUser(String),
+}
+
+/// The print matched by `FpDevice::identify_many`.
+#[derive(Debug, Clone)]
+pub struct FpGalleryMatch {
+    /// The label of the gallery holding the matched print.
+    pub gallery: String,
+    /// The position of the matched print in its gallery.
+    pub index: usize,
+    /// The matched print.
+    pub print: FpPrint,
+}
+
+/// The result of `FpDevice::identify_sync` and of the other identify operations.
+#[derive(Debug, Clone)]
+pub struct FpIdentifyResult {
+    /// The position in the gallery of the matched print and the print itself, `None` if no print matched.
+    pub matched: Option<(usize, FpPrint)>,
+    /// The newly scanned print, `None` if the driver does not provide it.
+    pub new_scan: Option<FpPrint>,
+    /// The time the identification took, from the start of the scan until the operation completed.
+    pub duration: Duration,
+}
+
+impl FpIdentifyResult {
+    /// Builds the result of an identification of `gallery`, finding the position of the `matched` print.
+    pub(crate) fn new<P: AsRef<FpPrint>>(
+        gallery: &[P],
+        matched: Option<FpPrint>,
+        new_scan: Option<FpPrint>,
+        duration: Duration,
+    ) -> Self {
+        // libfprint returns the print of the gallery itself, fall back to an equal print in case a driver
+        // returns a copy
+        let matched = matched.and_then(|matched| {
+            let raw: *mut libfprint_sys::FpPrint = matched.to_glib_none().0;
+            gallery
+                .iter()
+                .position(|p| std::ptr::eq(p.as_ref().to_glib_none().0, raw))
+                .or_else(|| gallery.iter().position(|p| p.as_ref().equal(&matched)))
+                .map(|index| (index, matched))
+        });
+        FpIdentifyResult {
+            matched,
+            new_scan,
+            duration,
+        }
+    }
+    /// Whether the scanned finger matches a print of the gallery.
+    pub fn is_match(&self) -> bool {
+        self.matched.is_some()
+    }
+    /// The matched print, if any.
+    pub fn print(&self) -> Option<&FpPrint> {
+        self.matched.as_ref().map(|(_, print)| print)
+    }
+}
+
+impl FpMatchOutcome {
+    /// Whether the scanned finger matches the enrolled print.
+    pub fn is_match(&self) -> bool {
+        matches!(self, FpMatchOutcome::Matched(_))
+    }
+
+    /// Builds the outcome from the raw results of a verify operation, taking ownership of `new_print` and `error`.
+    pub(crate) unsafe fn from_raw(
+        res: libfprint_sys::gboolean,
+        matched: libfprint_sys::gboolean,
+        new_print: *mut libfprint_sys::FpPrint,
+        error: *mut libfprint_sys::GError,
+    ) -> Result<Self, crate::GError> {
+        let new_print = match new_print.is_null() {
+            true => None,
+            false => Some(FpPrint::from_glib_full(new_print)),
+        };
+
+        // If res is false, the operation failed, so the `error` pointer must be pointing
+        // to a valid error
+        if res == glib::ffi::GFALSE {
+            let error = glib::Error::from_glib_full(error.cast());
+            return match error.kind::<FpRetryError>() {
+                Some(retry) => Ok(FpMatchOutcome::Retry(retry)),
+                None => Err(error),
+            };
+        }
+        // Else there must be a response
+        if matched == glib::ffi::GTRUE {
+            Ok(FpMatchOutcome::Matched(new_print))
+        } else {
+            Ok(FpMatchOutcome::NotMatched(new_print))
+        }
+    }
+}
 
 impl FpDevice {
     #[cfg(not(doctest))]
@@ -31,6 +159,9 @@ impl FpDevice {
     /// dev.open_sync(None).unwrap();
     /// ```
     pub fn open_sync(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::GError> {
+        let guard = OperationGuard::acquire(self, "open", cancellable)?;
+        let cancellable = Some(guard.cancellable());
+        let span = OperationSpan::enter(self, "open");
         let raw_cancel = match cancellable {
             Some(p) => p.to_glib_none().0,
             None => std::ptr::null_mut(),
@@ -45,10 +176,11 @@ impl FpDevice {
                 std::ptr::addr_of_mut!(error),
             )
         };
-        if res == glib::ffi::GFALSE {
-            return Err(unsafe { glib::Error::from_glib_full(error.cast()) });
-        }
-        Ok(())
+        let result = match res == glib::ffi::GFALSE {
+            true => Err(unsafe { glib::Error::from_glib_full(error.cast()) }),
+            false => Ok(()),
+        };
+        span.finish(result)
     }
     #[cfg(not(doctest))]
     /// Close the device synchronously.
@@ -64,6 +196,9 @@ impl FpDevice {
     /// dev.close_sync(None).unwrap();
     /// ```
     pub fn close_sync(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::GError> {
+        let guard = OperationGuard::acquire(self, "close", cancellable)?;
+        let context = ErrorContext::new(self, "close");
+        let cancellable = Some(guard.cancellable());
         let raw_cancel = match cancellable {
             Some(p) => p.to_glib_none().0,
             None => std::ptr::null_mut(),
@@ -79,7 +214,7 @@ impl FpDevice {
             )
         };
         if res == glib::ffi::GFALSE {
-            return Err(unsafe { glib::Error::from_glib_full(error.cast()) });
+            return Err(context.error(unsafe { glib::Error::from_glib_full(error.cast()) }));
         }
         Ok(())
     }
@@ -112,17 +247,34 @@ impl FpDevice {
         progress_cb: Option<FpEnrollProgress<T>>,
         progress_data: Option<T>,
     ) -> Result<FpPrint, crate::GError> {
+        self.enroll_contextual_sync(template, cancellable, progress_cb, progress_data)
+            .map_err(FpContextual::into_error)
+    }
+    /// `FpDevice::enroll_sync`, returning the error wrapped in its context.
+    pub(super) fn enroll_contextual_sync<T>(
+        &self,
+        template: FpPrint,
+        cancellable: Option<&Cancellable>,
+        progress_cb: Option<FpEnrollProgress<T>>,
+        progress_data: Option<T>,
+    ) -> Result<FpPrint, FpContextual<crate::GError>> {
+        let guard = OperationGuard::acquire(self, "enroll", cancellable)?;
+        let cancellable = Some(guard.cancellable());
+        let span = OperationSpan::enter(self, "enroll");
+        let template = match self.check_print(template) {
+            Ok(template) => template,
+            Err(err) => return span.finish_contextual(Err(err)),
+        };
         let mut error = std::ptr::null_mut();
 
-        let template = self.check_print(template);
-
         let raw_dev = self.to_glib_none().0;
         let raw_cancel = match cancellable {
             Some(p) => p.to_glib_none().0,
             None => std::ptr::null_mut(),
         };
 
-        let user_ptr = fn_pointer!(progress_cb, progress_data);
+        let user_data = UserData::new(progress_cb, progress_data);
+        let user_ptr = borrowed_ptr(&user_data);
 
         // Raw template: transfer full
         let raw_template: *mut libfprint_sys::FpPrint = template.to_glib_full();
@@ -138,30 +290,32 @@ impl FpDevice {
             )
         };
 
-        if !user_ptr.is_null() {
-            let _: Arc<UserData<FpEnrollProgress<T>, T>> =
-                unsafe { Arc::from_raw(user_ptr.cast()) };
-        }
-
-        if !ptr.is_null() {
+        let result = if !ptr.is_null() {
             let fp = unsafe { FpPrint::from_glib_full(ptr) };
             unsafe {
                 fp.set_data("set", true);
             }
-            return Ok(fp);
+            Ok(fp)
         } else {
-            return Err(unsafe { glib::Error::from_glib_full(error.cast()) });
-        }
+            Err(unsafe { glib::Error::from_glib_full(error.cast()) })
+        };
+        span.finish_contextual(result)
     }
 
     #[cfg(not(doctest))]
     /// Verify a given print synchronously.
-    /// `match_cb` will be called when the verification is done.
+    /// `match_cb` will be called as soon as the matcher has decided, see `FpMatchCb`.
+    ///
+    /// The enrolled print is borrowed, so the same print can be verified on every attempt without cloning or
+    /// deserializing it again.
+    ///
+    /// A scan that failed for a reason the user can fix (e.g. the finger was not centered) is reported as
+    /// `FpMatchOutcome::Retry` instead of as an error, so it can be told apart from a finger that does not match.
     /// # Example:
     /// ```no_run
-    /// use libfprint_rs::{FpDevice, FpContext, FpPrint, GError};
+    /// use libfprint_rs::{FpDevice, FpContext, FpMatchOutcome, FpPrint, GError};
     ///
-    /// pub fn match_cb(device: &FpDevice, matched_print: Option<FpPrint>, enrolled_print: FpPrint,
+    /// pub fn match_cb(device: &FpDevice, matched_print: Option<FpPrint>, print: Option<FpPrint>,
     /// error: Option<GError>, data: &Option<i32>) {
     ///     if matched_print.is_some() {
     ///         println!("Matched print: {:?}", matched_print);
@@ -173,11 +327,10 @@ impl FpDevice {
     /// dev.open_sync(None).unwrap();
     ///
     /// let some_print: FpPrint = foreign_function_that_gets_print();
-    /// let mut new_print = FpPrint::new(&dev); // The variable that will hold the new print
-    /// let verified = dev.verify_sync(&some_print, None, Some(match_cb), Some(10), Some(&mut
-    /// new_print)).unwrap();
-    /// if verified {
-    ///    println!("Print verified");println
+    /// match dev.verify_sync(&some_print, None, Some(match_cb), Some(10)).unwrap() {
+    ///     FpMatchOutcome::Matched(_) => println!("Print verified"),
+    ///     FpMatchOutcome::NotMatched(_) => println!("Wrong finger"),
+    ///     FpMatchOutcome::Retry(reason) => println!("{}", reason),
     /// }
     /// ```
     pub fn verify_sync<T>(
@@ -186,17 +339,16 @@ impl FpDevice {
         cancellable: Option<&Cancellable>,
         match_cb: Option<FpMatchCb<T>>,
         match_data: Option<T>,
-        print: Option<&mut FpPrint>, // TODO: Handle initialized
-    ) -> Result<bool, crate::GError> {
-        let ptr = fn_pointer!(match_cb, match_data);
+    ) -> Result<FpMatchOutcome, crate::GError> {
+        let guard = OperationGuard::acquire(self, "verify", cancellable)?;
+        let cancellable = Some(guard.cancellable());
+        let span = OperationSpan::enter(self, "verify");
+        let user_data = UserData::new(match_cb, match_data);
+        let ptr = borrowed_ptr(&user_data);
         let mut error = std::ptr::null_mut();
         let mut matched = glib::ffi::GFALSE;
 
         let mut new_print: libfprint_sys::FpPrint_autoptr = std::ptr::null_mut();
-        let new_print_ptr = match print {
-            Some(_) => std::ptr::addr_of_mut!(new_print),
-            None => std::ptr::null_mut(),
-        };
 
         let raw_cancel = match cancellable {
             Some(p) => p.to_glib_none().0,
@@ -211,31 +363,86 @@ impl FpDevice {
                 Some(fp_match_cb::<FpMatchCb<T>, T>),
                 ptr,
                 &mut matched,
-                new_print_ptr,
+                std::ptr::addr_of_mut!(new_print),
                 &mut error,
             )
         };
 
-        match print {
-            Some(p) => {
-                if !new_print.is_null() {
-                    *p = unsafe { FpPrint::from_glib_full(new_print) };
+        span.finish(unsafe { FpMatchOutcome::from_raw(res, matched, new_print, error) })
+    }
+    #[cfg(not(doctest))]
+    /// Verify a given print synchronously, checking that the finger does not also belong to another user.
+    ///
+    /// The scan is identified against `exclusion`, e.g. the prints of the other users, and the enrolled print.
+    /// A finger matching a print of `exclusion` is reported as `FpExclusiveOutcome::MatchedOtherUser`, to detect a
+    /// person enrolled under several accounts. The exclusion gallery is searched first, so the other user is
+    /// reported even when the finger also matches the enrolled print; match-on-chip devices choose the reported
+    /// print themselves.
+    ///
+    /// The check requires a device supporting identification, see `FpDeviceFeature::Identify`: it fails with
+    /// `gio::IOErrorEnum::NotSupported` on the others, before scanning.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{FpContext, FpExclusiveOutcome, FpPrint};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    /// dev.open_sync(None).unwrap();
+    ///
+    /// let enrolled: FpPrint = load_print("bob");
+    /// let others: Vec<FpPrint> = load_prints_except("bob");
+    /// match dev.verify_exclusive_sync(&enrolled, &others, None, None, None::<()>).unwrap() {
+    ///     FpExclusiveOutcome::MatchedOtherUser(user) => println!("This finger belongs to {}", user),
+    ///     FpExclusiveOutcome::Verified(outcome) => println!("Verified: {}", outcome.is_match()),
+    /// }
+    /// ```
+    pub fn verify_exclusive_sync<'a, T, I, P>(
+        &self,
+        enrolled_print: &FpPrint,
+        exclusion: I,
+        cancellable: Option<&Cancellable>,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+    ) -> Result<FpExclusiveOutcome, crate::GError>
+    where
+        I: IntoIterator<Item = &'a P>,
+        P: AsRef<FpPrint> + 'a,
+    {
+        if !self.supports(FpDeviceFeature::Identify) {
+            let context = ErrorContext::new(self, "verify");
+            return Err(context.error(glib::Error::new(
+                gio::IOErrorEnum::NotSupported,
+                "Exclusive verification needs a device supporting identification",
+            )));
+        }
+        let mut gallery: Vec<&FpPrint> = exclusion.into_iter().map(AsRef::as_ref).collect();
+        gallery.push(enrolled_print);
+
+        let result = match self.identify_sync(&gallery, cancellable, match_cb, match_data) {
+            Ok(result) => result,
+            Err(err) => match err.kind::<FpRetryError>() {
+                Some(retry) => {
+                    return Ok(FpExclusiveOutcome::Verified(FpMatchOutcome::Retry(retry)))
                 }
-            }
-            None => {}
+                None => return Err(err),
+            },
         };
+        let scanned = result.new_scan;
 
-        // If res is false, the operation failed, so the `error` pointer must be pointing
-        // to a valid error
-        if res == glib::ffi::GFALSE {
-            return Err(unsafe { glib::Error::from_glib_none(error.cast()) });
-        }
-        // Else there must be a response
-        return Ok(matched == glib::ffi::GTRUE);
+        Ok(match result.matched {
+            Some((_, matched)) if matched != *enrolled_print => {
+                FpExclusiveOutcome::MatchedOtherUser(matched.username().unwrap_or_default())
+            }
+            Some(_) => FpExclusiveOutcome::Verified(FpMatchOutcome::Matched(scanned)),
+            None => FpExclusiveOutcome::Verified(FpMatchOutcome::NotMatched(scanned)),
+        })
     }
+    #[cfg(libfprint_1_94)]
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     /// Prepare device for suspend.
     pub fn suspend_sync(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::GError> {
+        let context = ErrorContext::new(self, "suspend");
         let raw_cancel = match cancellable {
             Some(p) => p.to_glib_none().0,
             None => std::ptr::null_mut(),
@@ -251,14 +458,16 @@ impl FpDevice {
             )
         };
         if res == glib::ffi::GFALSE {
-            return Err(unsafe { glib::Error::from_glib_full(error.cast()) });
+            return Err(context.error(unsafe { glib::Error::from_glib_full(error.cast()) }));
         }
         Ok(())
     }
 
+    #[cfg(libfprint_1_94)]
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     /// Resume device after suspend.
     pub fn resume_sync(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::GError> {
+        let context = ErrorContext::new(self, "resume");
         let raw_cancel = match cancellable {
             Some(p) => p.to_glib_none().0,
             None => std::ptr::null_mut(),
@@ -274,19 +483,84 @@ impl FpDevice {
             )
         };
         if res == glib::ffi::GFALSE {
-            return Err(unsafe { glib::Error::from_glib_full(error.cast()) });
+            return Err(context.error(unsafe { glib::Error::from_glib_full(error.cast()) }));
         }
         Ok(())
     }
+    #[cfg(not(doctest))]
+    /// Reset the USB port of the device, a last resort to recover a stuck reader.
+    ///
+    /// This is disruptive for the whole device: the operations in flight fail, and an open device must be
+    /// closed and opened again before it can be used. If the device re-enumerates, it is removed from its
+    /// `FpContext` and added again as a new device. Fails with `gio::IOErrorEnum::NotSupported` for devices
+    /// that are not USB devices, e.g. virtual devices.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::FpContext;
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    ///
+    /// if dev.open_sync(None).is_err() {
+    ///     dev.reset().unwrap();
+    ///     dev.open_sync(None).unwrap();
+    /// }
+    /// ```
+    pub fn reset(&self) -> Result<(), crate::GError> {
+        let context = ErrorContext::new(self, "reset");
+        let usb_device = self.usb_device().ok_or_else(|| {
+            context.error(glib::Error::new(
+                gio::IOErrorEnum::NotSupported,
+                "The device is not a USB device",
+            ))
+        })?;
+        let raw_usb: *mut glib::gobject_ffi::GObject = usb_device.to_glib_none().0;
+        let raw_usb: *mut libfprint_sys::GUsbDevice = raw_usb.cast();
+
+        let mut error = std::ptr::null_mut();
+        // The USB device is only open while libfprint uses it
+        let opened = !self.is_open();
+        if opened {
+            let res =
+                unsafe { libfprint_sys::g_usb_device_open(raw_usb, std::ptr::addr_of_mut!(error)) };
+            if res == glib::ffi::GFALSE {
+                return Err(context.error(unsafe { glib::Error::from_glib_full(error.cast()) }));
+            }
+        }
+
+        let res =
+            unsafe { libfprint_sys::g_usb_device_reset(raw_usb, std::ptr::addr_of_mut!(error)) };
+        let result = match res == glib::ffi::GFALSE {
+            true => Err(context.error(unsafe { glib::Error::from_glib_full(error.cast()) })),
+            false => Ok(()),
+        };
+
+        if opened {
+            // The reset may have invalidated the handle, closing it can fail
+            let mut close_error = std::ptr::null_mut();
+            let res = unsafe {
+                libfprint_sys::g_usb_device_close(raw_usb, std::ptr::addr_of_mut!(close_error))
+            };
+            if res == glib::ffi::GFALSE {
+                drop(unsafe { glib::Error::from_glib_full(close_error.cast()) });
+            }
+        }
+        result
+    }
+
     #[cfg(not(doctest))]
     /// Identify a print synchronously.
     ///
-    /// `match_cb` will be called when a print matches or at the end of the operation.
+    /// The gallery is borrowed, e.g. `&Vec<FpPrint>`, `&[&FpPrint]` or an iterator over references to prints, so
+    /// large galleries are neither moved nor cloned. `match_cb` will be called as soon as the matcher has
+    /// decided, see `FpMatchCb`. The result holds the position of the matched print in the gallery, the newly
+    /// scanned print and the time the identification took.
     /// # Example:
     /// ```no_run
     /// use libfprint_rs::{FpDevice, FpContext, FpPrint, GError};
     ///
-    /// pub fn match_cb(device: &FpDevice, matched_print: Option<FpPrint>, enrolled_print: FpPrint,
+    /// pub fn match_cb(device: &FpDevice, matched_print: Option<FpPrint>, print: Option<FpPrint>,
     /// error: Option<GError>, data: &Option<i32>) {
     ///     if matched_print.is_some() {
     ///         println!("Matched print: {:?}", matched_print);
@@ -299,30 +573,33 @@ impl FpDevice {
     /// dev.open_sync(None).unwrap();
     ///
     /// let vec_prints: Vec<FpPrint> = function_returning_Vec_prints();
-    /// let mut new_print = FpPrint::new(&dev); // The variable that will hold the new print
-    /// let print_identified = dev.identify_sync(&vec_prints, None, Some(match_cb), Some(10), Some(&mut
-    /// new_print)).unwrap();
-    /// if print_identified.is_some() {
-    ///     println!("Found matching print on vector passed");
+    /// let result = dev.identify_sync(&vec_prints, None, Some(match_cb), Some(10)).unwrap();
+    /// if let Some((index, _)) = result.matched {
+    ///     println!("Found matching print {} on vector passed in {:?}", index, result.duration);
     /// }
     /// ```
-    pub fn identify_sync<T>(
+    pub fn identify_sync<'a, T, I, P>(
         &self,
-        prints: &Vec<FpPrint>,
+        prints: I,
         cancellable: Option<&Cancellable>,
         match_cb: Option<FpMatchCb<T>>,
         match_data: Option<T>,
-        print: Option<&mut FpPrint>, // TODO: Handle initialized
-    ) -> Result<Option<FpPrint>, crate::GError> {
-        // Arc the function content and the data, get the pointer. If no function is provided
+    ) -> Result<FpIdentifyResult, crate::GError>
+    where
+        I: IntoIterator<Item = &'a P>,
+        P: AsRef<FpPrint> + 'a,
+    {
+        let guard = OperationGuard::acquire(self, "identify", cancellable)?;
+        let cancellable = Some(guard.cancellable());
+        // Keep the function and the data on the stack, get the pointer. If no function is provided
         // then a null pointer is returned.
+        let span = OperationSpan::enter(self, "identify");
+        let user_data = UserData::new(match_cb, match_data);
+        let ptr = borrowed_ptr(&user_data);
 
-        use glib::translate::ToGlibContainerFromSlice;
-        let ptr = fn_pointer!(match_cb, match_data);
-
-        // Create a GPtrArray from the vector of prints
-        let raw_prints: (*mut glib::ffi::GPtrArray, _) =
-            ToGlibContainerFromSlice::to_glib_container_from_slice(&prints);
+        // Create a GPtrArray borrowing the prints
+        let gallery: Vec<&FpPrint> = prints.into_iter().map(AsRef::as_ref).collect();
+        let raw_prints = crate::ptr_array::gallery(&gallery);
 
         let raw_cancel = match cancellable {
             Some(p) => p.to_glib_none().0,
@@ -330,46 +607,212 @@ impl FpDevice {
         };
 
         let mut new_print: libfprint_sys::FpPrint_autoptr = std::ptr::null_mut();
-        let new_print_ptr = match print {
-            Some(_) => std::ptr::addr_of_mut!(new_print),
-            None => std::ptr::null_mut(),
-        };
-
         let mut print_match = std::ptr::null_mut();
 
         let mut error = std::ptr::null_mut();
 
+        let start = Instant::now();
         let res = unsafe {
             libfprint_sys::fp_device_identify_sync(
                 self.to_glib_none().0,
-                raw_prints.0.cast(),
+                raw_prints.cast(),
                 raw_cancel.cast(),
                 Some(fp_match_cb::<FpMatchCb<T>, T>),
                 ptr,
-                new_print_ptr,
                 std::ptr::addr_of_mut!(print_match),
+                std::ptr::addr_of_mut!(new_print),
                 std::ptr::addr_of_mut!(error),
             )
         };
-        unsafe { libfprint_sys::g_ptr_array_free(raw_prints.0.cast(), 1) };
+        let duration = start.elapsed();
+        unsafe { libfprint_sys::g_ptr_array_free(raw_prints.cast(), 1) };
 
-        match print {
-            Some(p) => {
-                if !new_print.is_null() {
-                    *p = unsafe { FpPrint::from_glib_full(new_print) };
-                }
-            }
-            None => {}
-        };
+        let new_scan =
+            (!new_print.is_null()).then(|| unsafe { FpPrint::from_glib_full(new_print) });
+        let matched =
+            (!print_match.is_null()).then(|| unsafe { FpPrint::from_glib_full(print_match) });
 
-        if res == glib::ffi::GFALSE {
-            return Err(unsafe { glib::Error::from_glib_full(error.cast()) });
-        }
-        if print_match.is_null() {
-            return Ok(None);
+        let result = if res == glib::ffi::GFALSE {
+            Err(unsafe { glib::Error::from_glib_full(error.cast()) })
         } else {
-            return Ok(Some(unsafe { FpPrint::from_glib_full(print_match) }));
+            Ok(FpIdentifyResult::new(&gallery, matched, new_scan, duration))
+        };
+        span.finish(result)
+    }
+    #[cfg(not(doctest))]
+    /// Identify which user a finger belongs to, synchronously.
+    ///
+    /// `users` maps each username to their enrolled prints. The gallery is flattened to run a single
+    /// identification and the match is mapped back to its owner. Returns the username and the finger of
+    /// the matched print, or `None` if no print matched.
+    /// # Example:
+    /// ```no_run
+    /// use std::collections::HashMap;
+    /// use libfprint_rs::{FpContext, FpPrint};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    /// dev.open_sync(None).unwrap();
+    ///
+    /// let users: HashMap<String, Vec<FpPrint>> = load_users();
+    /// if let Some((username, finger)) = dev.identify_users(&users, None, None, None::<()>).unwrap() {
+    ///     println!("Welcome {} ({})", username, finger);
+    /// }
+    /// ```
+    pub fn identify_users<T>(
+        &self,
+        users: &HashMap<String, Vec<FpPrint>>,
+        cancellable: Option<&Cancellable>,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+    ) -> Result<Option<(String, FpFinger)>, crate::GError> {
+        let (owners, gallery): (Vec<&String>, Vec<&FpPrint>) = users
+            .iter()
+            .flat_map(|(username, prints)| prints.iter().map(move |p| (username, p)))
+            .unzip();
+
+        let result = self.identify_sync(&gallery, cancellable, match_cb, match_data)?;
+
+        Ok(result
+            .matched
+            .map(|(i, _)| (owners[i].clone(), gallery[i].finger())))
+    }
+    #[cfg(not(doctest))]
+    /// Identify a finger among several labeled galleries, synchronously.
+    ///
+    /// The galleries (e.g. `"employees"` and `"visitors"`) are searched in a single identification, and the
+    /// match is mapped back to the label of its gallery and its position there. A print appearing in several
+    /// galleries is reported in the first one. Returns `None` if no print matched.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{FpContext, FpPrint};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    /// dev.open_sync(None).unwrap();
+    ///
+    /// let employees: Vec<FpPrint> = load_employees();
+    /// let visitors: Vec<FpPrint> = load_visitors();
+    /// let galleries = [("employees", employees.as_slice()), ("visitors", visitors.as_slice())];
+    /// if let Some(matched) = dev.identify_many(&galleries, None, None, None::<()>).unwrap() {
+    ///     println!("Matched entry {} of the {}", matched.index, matched.gallery);
+    /// }
+    /// ```
+    pub fn identify_many<T>(
+        &self,
+        galleries: &[(&str, &[FpPrint])],
+        cancellable: Option<&Cancellable>,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+    ) -> Result<Option<FpGalleryMatch>, crate::GError> {
+        let (entries, gallery): (Vec<(&str, usize)>, Vec<&FpPrint>) = galleries
+            .iter()
+            .flat_map(|(label, prints)| {
+                prints
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, p)| ((*label, index), p))
+            })
+            .unzip();
+
+        let result = self.identify_sync(&gallery, cancellable, match_cb, match_data)?;
+
+        Ok(result.matched.map(|(i, matched)| {
+            let (label, index) = entries[i];
+            FpGalleryMatch {
+                gallery: label.to_string(),
+                index,
+                print: matched,
+            }
+        }))
+    }
+    #[cfg(not(doctest))]
+    /// Returns the prints among `prints` that are compatible with the device, see `FpPrint::compatible`, e.g. to
+    /// keep the stored prints of a user the attached reader can match before asking them to touch the sensor.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{FpContext, FpPrint};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    ///
+    /// let prints: Vec<FpPrint> = load_prints("bruce");
+    /// let usable = dev.compatible_prints(&prints);
+    /// if usable.is_empty() {
+    ///     println!("Please enroll a finger on this reader first");
+    /// }
+    /// ```
+    pub fn compatible_prints<'a, I, P>(&self, prints: I) -> Vec<&'a FpPrint>
+    where
+        I: IntoIterator<Item = &'a P>,
+        P: AsRef<FpPrint> + 'a,
+    {
+        prints
+            .into_iter()
+            .map(AsRef::as_ref)
+            .filter(|print| print.compatible(self))
+            .collect()
+    }
+    /// Returns the positions in `prints` of the prints that are not compatible with the device, e.g. enrolled
+    /// with another driver or on another match-on-chip device, see `FpPrint::compatible`.
+    pub fn incompatible_prints<'a, I, P>(&self, prints: I) -> Vec<usize>
+    where
+        I: IntoIterator<Item = &'a P>,
+        P: AsRef<FpPrint> + 'a,
+    {
+        prints
+            .into_iter()
+            .enumerate()
+            .filter(|(_, print)| !print.as_ref().compatible(self))
+            .map(|(i, _)| i)
+            .collect()
+    }
+    #[cfg(not(doctest))]
+    /// Identify a print like `FpDevice::identify_sync`, checking the gallery first.
+    ///
+    /// If a print of the gallery is not compatible with the device, no scan is started and
+    /// `FpError::IncompatibleGallery` is returned with the positions of the incompatible prints, see
+    /// `FpDevice::incompatible_prints`.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{FpContext, FpError, FpPrint};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    /// dev.open_sync(None).unwrap();
+    ///
+    /// let mut gallery: Vec<FpPrint> = load_prints();
+    /// let result = match dev.identify_checked_sync(&gallery, None, None, None::<()>) {
+    ///     Err(FpError::IncompatibleGallery(incompatible)) => {
+    ///         for i in incompatible.into_iter().rev() {
+    ///             gallery.remove(i);
+    ///         }
+    ///         dev.identify_sync(&gallery, None, None, None::<()>)?
+    ///     }
+    ///     result => result?,
+    /// };
+    /// ```
+    pub fn identify_checked_sync<'a, T, I, P>(
+        &self,
+        prints: I,
+        cancellable: Option<&Cancellable>,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+    ) -> Result<FpIdentifyResult, FpError>
+    where
+        I: IntoIterator<Item = &'a P>,
+        P: AsRef<FpPrint> + 'a,
+    {
+        let gallery: Vec<&FpPrint> = prints.into_iter().map(AsRef::as_ref).collect();
+        let incompatible = self.incompatible_prints(&gallery);
+        if !incompatible.is_empty() {
+            return Err(FpError::IncompatibleGallery(incompatible));
         }
+        Ok(self.identify_sync(&gallery, cancellable, match_cb, match_data)?)
     }
     #[cfg(not(doctest))]
     /// Start an synchronous operation to capture an image.
@@ -387,6 +830,9 @@ impl FpDevice {
         wait_for_finger: bool,
         cancellable: Option<&Cancellable>,
     ) -> Result<FpImage, crate::GError> {
+        let guard = OperationGuard::acquire(self, "capture", cancellable)?;
+        let context = ErrorContext::new(self, "capture");
+        let cancellable = Some(guard.cancellable());
         let raw_cancel = match cancellable {
             Some(p) => p.to_glib_none().0,
             None => std::ptr::null_mut(),
@@ -403,43 +849,124 @@ impl FpDevice {
             )
         };
         if raw_image.is_null() {
-            return Err(unsafe { glib::Error::from_glib_full(raw_error.cast()) });
+            return Err(context.error(unsafe { glib::Error::from_glib_full(raw_error.cast()) }));
         }
         Ok(unsafe { FpImage::from_glib_full(raw_image) })
     }
 
-    /// Delete a given print from the device.
-    pub fn delete_print_sync() {
-        unimplemented!()
+    /// Delete a given print from the device storage. Only match-on-chip devices have a storage, see
+    /// `FpDevice::list_prints_sync`.
+    pub fn delete_print_sync(
+        &self,
+        enrolled_print: &FpPrint,
+        cancellable: Option<&Cancellable>,
+    ) -> Result<(), crate::GError> {
+        let guard = OperationGuard::acquire(self, "delete_print", cancellable)?;
+        let context = ErrorContext::new(self, "delete_print");
+        let cancellable = Some(guard.cancellable());
+        let raw_cancel = match cancellable {
+            Some(p) => p.to_glib_none().0,
+            None => std::ptr::null_mut(),
+        };
+
+        let mut error = std::ptr::null_mut();
+
+        let res = unsafe {
+            libfprint_sys::fp_device_delete_print_sync(
+                self.to_glib_none().0,
+                enrolled_print.to_glib_none().0,
+                raw_cancel.cast(),
+                std::ptr::addr_of_mut!(error),
+            )
+        };
+        if res == glib::ffi::GFALSE {
+            return Err(context.error(unsafe { glib::Error::from_glib_full(error.cast()) }));
+        }
+        Ok(())
     }
-    /// List device stored prints synchronously.
-    pub fn list_prints_sync() {
-        unimplemented!()
+    /// List the prints stored on the device. Fails with `gio::IOErrorEnum::NotSupported` if the device has no storage.
+    pub fn list_prints_sync(
+        &self,
+        cancellable: Option<&Cancellable>,
+    ) -> Result<Vec<FpPrint>, crate::GError> {
+        let guard = OperationGuard::acquire(self, "list_prints", cancellable)?;
+        let context = ErrorContext::new(self, "list_prints");
+        let cancellable = Some(guard.cancellable());
+        use glib::translate::FromGlibPtrContainer;
+
+        let raw_cancel = match cancellable {
+            Some(p) => p.to_glib_none().0,
+            None => std::ptr::null_mut(),
+        };
+
+        let mut error = std::ptr::null_mut();
+
+        let prints = unsafe {
+            libfprint_sys::fp_device_list_prints_sync(
+                self.to_glib_none().0,
+                raw_cancel.cast(),
+                std::ptr::addr_of_mut!(error),
+            )
+        };
+        if prints.is_null() {
+            return Err(context.error(unsafe { glib::Error::from_glib_full(error.cast()) }));
+        }
+        // The array is owned by us, its elements are released by its free function
+        let prints = prints.cast::<glib::ffi::GPtrArray>();
+        Ok(unsafe { FromGlibPtrContainer::from_glib_container(prints) })
     }
-    /// Clear sensor storage.
-    pub fn clear_storage_sync() {
-        unimplemented!()
+    #[cfg(libfprint_1_96)]
+    /// Delete every print stored on the device.
+    pub fn clear_storage_sync(
+        &self,
+        cancellable: Option<&Cancellable>,
+    ) -> Result<(), crate::GError> {
+        let guard = OperationGuard::acquire(self, "clear_storage", cancellable)?;
+        let context = ErrorContext::new(self, "clear_storage");
+        let cancellable = Some(guard.cancellable());
+        let raw_cancel = match cancellable {
+            Some(p) => p.to_glib_none().0,
+            None => std::ptr::null_mut(),
+        };
+
+        let mut error = std::ptr::null_mut();
+
+        let res = unsafe {
+            libfprint_sys::fp_device_clear_storage_sync(
+                self.to_glib_none().0,
+                raw_cancel.cast(),
+                std::ptr::addr_of_mut!(error),
+            )
+        };
+        if res == glib::ffi::GFALSE {
+            return Err(context.error(unsafe { glib::Error::from_glib_full(error.cast()) }));
+        }
+        Ok(())
     }
 
-    fn check_print(&self, template: FpPrint) -> FpPrint {
+    /// Copies a template created with `FpPrint::new` onto a new template of the device. Fails with
+    /// `gio::IOErrorEnum::InvalidData` if its metadata is rejected by the setters of `FpPrint`.
+    pub(super) fn check_print(&self, template: FpPrint) -> Result<FpPrint, crate::GError> {
         // This checks if the template was created with FpPrint::new() or not
         let set: Option<bool> = unsafe { template.steal_data("set") };
-        if set == Some(true) {
-            let empty_template = FpPrint::new(&self);
-            if let Some(username) = template.username() {
-                empty_template.set_username(&username);
-            }
-            if let Some(description) = template.description() {
-                empty_template.set_description(&description);
-            }
-            empty_template.set_finger(template.finger());
-            if let Some(date) = template.enroll_date() {
-                empty_template.set_enroll_date(date);
-            }
-            drop(template);
-            return empty_template;
-        } else {
-            return template;
-        };
+        if set != Some(true) {
+            return Ok(template);
+        }
+        let empty_template = FpPrint::new(self);
+        let invalid =
+            |err: FpError| glib::Error::new(gio::IOErrorEnum::InvalidData, &err.to_string());
+        if let Some(username) = template.username() {
+            empty_template.set_username(&username).map_err(invalid)?;
+        }
+        if let Some(description) = template.description() {
+            empty_template
+                .set_description(&description)
+                .map_err(invalid)?;
+        }
+        empty_template.set_finger(template.finger());
+        if let Some(date) = template.enroll_date() {
+            empty_template.set_enroll_date(date);
+        }
+        Ok(empty_template)
     }
 }
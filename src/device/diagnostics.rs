@@ -0,0 +1,216 @@
+use std::fmt::Write;
+
+#[cfg(libfprint_1_94)]
+use super::FpTemperature;
+use super::{FpDevice, FpDeviceFeature, FpFingerStatusFlags, FpScanType};
+
+/// Every feature a device can report, in the order of their bits.
+const FEATURES: [FpDeviceFeature; 10] = [
+    FpDeviceFeature::Capture,
+    FpDeviceFeature::Identify,
+    FpDeviceFeature::Verify,
+    FpDeviceFeature::Storage,
+    FpDeviceFeature::StorageList,
+    FpDeviceFeature::StorageDelete,
+    FpDeviceFeature::StorageClear,
+    FpDeviceFeature::DuplicatesCheck,
+    FpDeviceFeature::AlwaysOn,
+    FpDeviceFeature::UpdatePrint,
+];
+
+/// The state of a device gathered by `FpDevice::diagnostics`, for support bundles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FpDiagnostics {
+    /// The human readable name of the device.
+    pub name: String,
+    /// The ID of the driver.
+    pub driver: String,
+    /// The ID of the device.
+    pub device_id: String,
+    /// The scan type of the device.
+    pub scan_type: FpScanType,
+    /// The number of enroll stages of the device.
+    pub nr_enroll_stages: i32,
+    /// The features supported by the device.
+    pub features: Vec<FpDeviceFeature>,
+    /// Whether the device was already open, or was opened for the report.
+    pub was_open: bool,
+    #[cfg(libfprint_1_94)]
+    /// The temperature of the device.
+    pub temperature: FpTemperature,
+    /// The finger status of the device.
+    pub finger_status: FpFingerStatusFlags,
+    /// The number of prints stored on the device, `None` if it has no storage or listing it failed.
+    pub stored_prints: Option<usize>,
+    /// The steps that failed with their error, e.g. `("open", "...")`.
+    pub errors: Vec<(&'static str, String)>,
+}
+
+impl FpDiagnostics {
+    /// Serialize the report as a JSON object.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{");
+        let _ = write!(out, "\"name\":{}", json_string(&self.name));
+        let _ = write!(out, ",\"driver\":{}", json_string(&self.driver));
+        let _ = write!(out, ",\"device_id\":{}", json_string(&self.device_id));
+        let _ = write!(out, ",\"scan_type\":\"{}\"", self.scan_type);
+        let _ = write!(out, ",\"nr_enroll_stages\":{}", self.nr_enroll_stages);
+        let features: Vec<String> = self
+            .features
+            .iter()
+            .map(|feature| format!("\"{:?}\"", feature))
+            .collect();
+        let _ = write!(out, ",\"features\":[{}]", features.join(","));
+        let _ = write!(out, ",\"was_open\":{}", self.was_open);
+        #[cfg(libfprint_1_94)]
+        let _ = write!(out, ",\"temperature\":\"{:?}\"", self.temperature);
+        let _ = write!(
+            out,
+            ",\"finger_status\":{{\"needed\":{},\"present\":{}}}",
+            self.finger_status.is_needed(),
+            self.finger_status.is_present()
+        );
+        match self.stored_prints {
+            Some(count) => {
+                let _ = write!(out, ",\"stored_prints\":{}", count);
+            }
+            None => out.push_str(",\"stored_prints\":null"),
+        }
+        let errors: Vec<String> = self
+            .errors
+            .iter()
+            .map(|(step, error)| {
+                format!(
+                    "{{\"step\":{},\"error\":{}}}",
+                    json_string(step),
+                    json_string(error)
+                )
+            })
+            .collect();
+        let _ = write!(out, ",\"errors\":[{}]}}", errors.join(","));
+        out
+    }
+}
+
+/// Quote and escape `value` as a JSON string.
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl FpDevice {
+    #[cfg(not(doctest))]
+    /// Gather the state of the device in a single report, e.g. to attach it to a support request.
+    ///
+    /// The device is opened if needed, and closed again after. The steps that fail are recorded in
+    /// `FpDiagnostics::errors` instead of failing the report.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::FpContext;
+    ///
+    /// let ctx = FpContext::new();
+    /// for dev in ctx.devices() {
+    ///     std::fs::write(format!("{}.json", dev.device_id()), dev.diagnostics().to_json()).unwrap();
+    /// }
+    /// ```
+    pub fn diagnostics(&self) -> FpDiagnostics {
+        let mut errors = Vec::new();
+        let was_open = self.is_open();
+        let open = was_open
+            || match self.open_sync(None) {
+                Ok(()) => true,
+                Err(err) => {
+                    errors.push(("open", err.to_string()));
+                    false
+                }
+            };
+
+        let stored_prints = if open && self.supports(FpDeviceFeature::StorageList) {
+            match self.list_prints_sync(None) {
+                Ok(prints) => Some(prints.len()),
+                Err(err) => {
+                    errors.push(("list_prints", err.to_string()));
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let diagnostics = FpDiagnostics {
+            name: self.name(),
+            driver: self.driver(),
+            device_id: self.device_id(),
+            scan_type: self.scan_type(),
+            nr_enroll_stages: self.nr_enroll_stage(),
+            features: FEATURES
+                .into_iter()
+                .filter(|feature| self.supports(*feature))
+                .collect(),
+            was_open,
+            #[cfg(libfprint_1_94)]
+            temperature: self.temperature(),
+            finger_status: self.finger_status_flags(),
+            stored_prints,
+            errors,
+        };
+        if open && !was_open {
+            if let Err(err) = self.close_sync(None) {
+                let mut diagnostics = diagnostics;
+                diagnostics.errors.push(("close", err.to_string()));
+                return diagnostics;
+            }
+        }
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{json_string, FpDiagnostics};
+    use crate::device::{FpDeviceFeature, FpFingerStatusFlags, FpScanType};
+
+    #[test]
+    fn to_json() {
+        assert_eq!(
+            json_string("a \"b\"\\\n\u{1}"),
+            "\"a \\\"b\\\"\\\\\\n\\u0001\""
+        );
+
+        let diagnostics = FpDiagnostics {
+            name: "Virtual device".to_string(),
+            driver: "virtual_image".to_string(),
+            device_id: "0".to_string(),
+            scan_type: FpScanType::Swipe,
+            nr_enroll_stages: 5,
+            features: vec![FpDeviceFeature::Capture, FpDeviceFeature::Verify],
+            was_open: false,
+            #[cfg(libfprint_1_94)]
+            temperature: crate::device::FpTemperature::Cold,
+            finger_status: FpFingerStatusFlags::NEEDED,
+            stored_prints: None,
+            errors: vec![("list_prints", "Not supported".to_string())],
+        };
+        let json = diagnostics.to_json();
+        assert!(json.starts_with(
+            "{\"name\":\"Virtual device\",\"driver\":\"virtual_image\",\"device_id\":\"0\",\"scan_type\":\"swipe\""
+        ));
+        assert!(json.contains(",\"features\":[\"Capture\",\"Verify\"],\"was_open\":false"));
+        assert!(json.ends_with(
+            ",\"finger_status\":{\"needed\":true,\"present\":false},\"stored_prints\":null,\"errors\":[{\"step\":\"list_prints\",\"error\":\"Not supported\"}]}"
+        ));
+    }
+}
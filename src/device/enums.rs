@@ -1,19 +1,74 @@
 /// The scan type of the device.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum FpScanType {
     Swipe = libfprint_sys::FpScanType_FP_SCAN_TYPE_SWIPE as isize,
     Press = libfprint_sys::FpScanType_FP_SCAN_TYPE_PRESS as isize,
 }
 
+impl std::fmt::Display for FpScanType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FpScanType::Swipe => write!(f, "swipe"),
+            FpScanType::Press => write!(f, "press"),
+        }
+    }
+}
+
 /// The finger status flags for the device.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum FpFingerStatus {
     None = libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_NONE as isize,
     Needed = libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_NEEDED as isize,
     Present = libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_PRESENT as isize,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// The raw finger status flags of the device: a finger can be needed while one is already present.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FpFingerStatusFlags(u32);
+
+impl FpFingerStatusFlags {
+    /// The sensor is not waiting for a finger and none is present.
+    pub const NONE: Self = Self(libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_NONE);
+    /// The sensor is waiting for a finger.
+    pub const NEEDED: Self = Self(libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_NEEDED);
+    /// A finger is on the sensor.
+    pub const PRESENT: Self = Self(libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_PRESENT);
+
+    pub(crate) fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+    /// Returns the raw flags.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+    /// Whether every flag of `other` is set.
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+    /// Whether the sensor is waiting for a finger.
+    pub fn is_needed(&self) -> bool {
+        self.contains(Self::NEEDED)
+    }
+    /// Whether a finger is on the sensor.
+    pub fn is_present(&self) -> bool {
+        self.contains(Self::PRESENT)
+    }
+}
+
+#[cfg(libfprint_1_94)]
+/// The temperature of the device, devices that heat up are throttled until they cool down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FpTemperature {
+    /// The device is cold.
+    Cold = libfprint_sys::FpTemperature_FP_TEMPERATURE_COLD as isize,
+    /// The device is warm, usual operation.
+    Warm = libfprint_sys::FpTemperature_FP_TEMPERATURE_WARM as isize,
+    /// The device is hot, operations are refused until it cools down.
+    Hot = libfprint_sys::FpTemperature_FP_TEMPERATURE_HOT as isize,
+}
+
+/// The features supported by a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum FpDeviceFeature {
     /// Device does not support any feature
     None = 0,
@@ -0,0 +1,163 @@
+use std::fmt::Display;
+
+use gio::Cancellable;
+
+use crate::{error::FpRetryError, print::FpPrint};
+
+use super::FpDevice;
+
+/// What the user should do next during an enrollment, see `FpDevice::enroll_guided_sync`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FpEnrollGuidance {
+    /// A stage was completed, the user should lift their finger and place it again, unless `completed` is
+    /// `total`.
+    StageCompleted {
+        /// The number of completed stages.
+        completed: u32,
+        /// The number of stages of the enrollment.
+        total: u32,
+    },
+    /// The scan of the stage after the `completed` ones failed for `reason` and is retried.
+    Retry {
+        /// The number of completed stages.
+        completed: u32,
+        /// Why the scan failed, `FpRetryError::General` for errors outside of the retry domain.
+        reason: FpRetryError,
+    },
+}
+
+impl FpEnrollGuidance {
+    /// Builds the guidance of a call of the enroll progress callback, for an enrollment of `total` stages.
+    pub fn from_progress(completed_stages: i32, error: Option<&crate::GError>, total: u32) -> Self {
+        let completed = completed_stages.max(0) as u32;
+        match error {
+            Some(err) => FpEnrollGuidance::Retry {
+                completed,
+                reason: err.kind::<FpRetryError>().unwrap_or(FpRetryError::General),
+            },
+            None => FpEnrollGuidance::StageCompleted { completed, total },
+        }
+    }
+    /// Whether the enrollment is waiting for a new scan.
+    pub fn needs_scan(&self) -> bool {
+        match self {
+            FpEnrollGuidance::StageCompleted { completed, total } => completed < total,
+            FpEnrollGuidance::Retry { .. } => true,
+        }
+    }
+}
+
+impl Display for FpEnrollGuidance {
+    /// Formats the instruction to show the user, e.g. `The swipe was too short, please try again`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FpEnrollGuidance::StageCompleted { completed, total } if completed >= total => {
+                write!(f, "Enrollment complete")
+            }
+            FpEnrollGuidance::StageCompleted { completed, total } => write!(
+                f,
+                "Scan {} of {} done, lift your finger and place it again",
+                completed, total
+            ),
+            FpEnrollGuidance::Retry { reason, .. } => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// This type represents the callback function for `FpDevice::enroll_guided_sync`, it is called with the
+/// guidance of every scan while the enrollment runs.
+pub type FpGuidanceCb<T> = fn(&FpDevice, FpEnrollGuidance, &Option<T>);
+
+/// The progress data of `FpDevice::enroll_guided_sync`, wrapping the data of the user's callback.
+struct GuidanceData<T> {
+    total: u32,
+    guidance_cb: FpGuidanceCb<T>,
+    guidance_data: Option<T>,
+}
+
+fn guidance_progress<T>(
+    device: &FpDevice,
+    completed_stages: i32,
+    _print: Option<FpPrint>,
+    error: Option<crate::GError>,
+    data: &Option<GuidanceData<T>>,
+) {
+    if let Some(data) = data {
+        let guidance =
+            FpEnrollGuidance::from_progress(completed_stages, error.as_ref(), data.total);
+        (data.guidance_cb)(device, guidance, &data.guidance_data);
+    }
+}
+
+impl FpDevice {
+    #[cfg(not(doctest))]
+    /// Enroll a new print like `FpDevice::enroll_sync`, calling `guidance_cb` as soon as each scan completed
+    /// or failed, so the user can be told what to do while the enrollment runs.
+    ///
+    /// libfprint reports why a scan failed, e.g. the finger was not centered, but not in which direction to
+    /// move it.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{FpContext, FpDevice, FpEnrollGuidance, FpPrint};
+    ///
+    /// fn guidance_cb(_device: &FpDevice, guidance: FpEnrollGuidance, _data: &Option<()>) {
+    ///     println!("{}", guidance);
+    /// }
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    /// dev.open_sync(None).unwrap();
+    ///
+    /// let print = dev.enroll_guided_sync(FpPrint::new(&dev), None, guidance_cb, None).unwrap();
+    /// ```
+    pub fn enroll_guided_sync<T>(
+        &self,
+        template: FpPrint,
+        cancellable: Option<&Cancellable>,
+        guidance_cb: FpGuidanceCb<T>,
+        guidance_data: Option<T>,
+    ) -> Result<FpPrint, crate::GError> {
+        let data = GuidanceData {
+            total: self.nr_enroll_stage().max(0) as u32,
+            guidance_cb,
+            guidance_data,
+        };
+        self.enroll_sync(
+            template,
+            cancellable,
+            Some(guidance_progress::<T>),
+            Some(data),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FpEnrollGuidance;
+    use crate::error::FpRetryError;
+
+    #[test]
+    fn guidance() {
+        let next = FpEnrollGuidance::from_progress(2, None, 5);
+        assert_eq!(
+            next,
+            FpEnrollGuidance::StageCompleted {
+                completed: 2,
+                total: 5
+            }
+        );
+        assert!(next.needs_scan());
+        assert!(!FpEnrollGuidance::from_progress(5, None, 5).needs_scan());
+
+        let retry = FpEnrollGuidance::Retry {
+            completed: 2,
+            reason: FpRetryError::TooShort,
+        };
+        assert!(retry.needs_scan());
+        assert_eq!(
+            retry.to_string(),
+            "The swipe was too short, please try again"
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, Write},
+    os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+    path::{Path, PathBuf},
+};
+
+use crate::error::FpError;
+
+use super::FpDevice;
+
+/// An advisory lock on a device, shared by every process using this crate. The lock is released when
+/// this struct is dropped, or when the process exits.
+#[derive(Debug)]
+pub struct FpDeviceLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl FpDeviceLock {
+    /// Acquire the lock of `device`, using a lock file in `dir`. Every process must use the same directory.
+    pub fn acquire_in(dir: impl AsRef<Path>, device: &FpDevice) -> Result<Self, FpError> {
+        let id = device.id();
+        let name: String = format!("{}-{}.lock", id.driver, id.device_id)
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '.' => c,
+                _ => '_',
+            })
+            .collect();
+
+        std::fs::create_dir_all(dir.as_ref())?;
+        Self::acquire_path(dir.as_ref().join(name))
+    }
+
+    fn acquire_path(path: PathBuf) -> Result<Self, FpError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(0o666)
+            .open(&path)?;
+
+        let res = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        if res != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::EWOULDBLOCK) {
+                return Err(err.into());
+            }
+            // The owner writes its PID to the file once it holds the lock
+            let mut content = String::new();
+            let pid = match file.read_to_string(&mut content) {
+                Ok(_) => content.trim().parse().unwrap_or(0),
+                Err(_) => 0,
+            };
+            return Err(FpError::DeviceLockedByPid(pid));
+        }
+
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", std::process::id())?;
+
+        Ok(FpDeviceLock { file, path })
+    }
+
+    /// The path of the lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for FpDeviceLock {
+    fn drop(&mut self) {
+        // Closing the file releases the lock, the file itself is kept so no other process can
+        // lock a file that is about to be removed.
+        let _ = self.file.set_len(0);
+    }
+}
+
+impl FpDevice {
+    #[cfg(not(doctest))]
+    /// Acquire an advisory lock on the device, shared by every process using this crate.
+    ///
+    /// Two processes opening the same device race for its USB interface. Locking the device before opening it
+    /// makes the second process fail fast with `FpError::DeviceLockedByPid` instead. The lock file is created in
+    /// `$XDG_RUNTIME_DIR/libfprint-rs`, or in the temporary directory if it is not set, use
+    /// `FpDeviceLock::acquire_in` to choose another directory.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{FpContext, FpError};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    ///
+    /// let _lock = match dev.lock() {
+    ///     Ok(lock) => lock,
+    ///     Err(FpError::DeviceLockedByPid(pid)) => panic!("The device is in use by process {}", pid),
+    ///     Err(err) => panic!("{}", err),
+    /// };
+    /// dev.open_sync(None).unwrap();
+    /// ```
+    pub fn lock(&self) -> Result<FpDeviceLock, FpError> {
+        let dir = std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir)
+            .join("libfprint-rs");
+        FpDeviceLock::acquire_in(dir, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FpDeviceLock;
+    use crate::error::FpError;
+
+    #[test]
+    fn second_lock_fails() {
+        let path =
+            std::env::temp_dir().join(format!("libfprint-rs-test-{}.lock", std::process::id()));
+
+        let lock = FpDeviceLock::acquire_path(path.clone()).unwrap();
+        match FpDeviceLock::acquire_path(path.clone()) {
+            Err(FpError::DeviceLockedByPid(pid)) => assert_eq!(pid, std::process::id()),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        drop(lock);
+
+        assert!(FpDeviceLock::acquire_path(path.clone()).is_ok());
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -1,17 +1,66 @@
+mod bridge;
+mod busy;
 mod callback;
 mod device;
+mod device_async;
+#[cfg(feature = "futures")]
+mod device_future;
 mod device_sync;
+mod diagnostics;
 mod enums;
+mod guidance;
+mod lock;
+mod notify;
+mod progress;
+mod quirks;
+mod timing;
+mod trace;
 mod user_data;
+mod watchdog;
+mod weak;
+mod wizard;
 
-pub use device_sync::{FpEnrollProgress, FpMatchCb};
+pub use bridge::{FpEnrollEvent, FpMainContextBridge, FpMatchEvent};
+pub(crate) use busy::is_busy;
+pub use device::{FpDeviceId, FpDeviceInfo};
+pub(crate) use device_async::wait;
+#[cfg(feature = "futures")]
+pub use device_future::{FpEnrollStage, FpFuture, FpPreviewFrame};
+pub use device_sync::{
+    FpEnrollProgress, FpExclusiveOutcome, FpGalleryMatch, FpIdentifyResult, FpMatchCb,
+    FpMatchOutcome,
+};
+pub use diagnostics::FpDiagnostics;
+#[cfg(libfprint_1_94)]
+pub use enums::FpTemperature;
+pub use enums::{FpDeviceFeature, FpFingerStatus, FpFingerStatusFlags, FpScanType};
 use gio::AsyncInitable;
 use glib::wrapper;
+pub use guidance::{FpEnrollGuidance, FpGuidanceCb};
+pub use lock::FpDeviceLock;
+pub use progress::{FpEnrollOutcome, FpEnrollTracker};
+pub use quirks::{FpDeviceCapabilities, FpDriverQuirks, FpQuirkTable};
+pub use timing::FpMatchTiming;
+pub use watchdog::{FpWatchdog, FpWedgeRecovery};
+pub use weak::FpWeakDevice;
+pub use wizard::{FpEnrollSet, FpFingerPrompt};
 
 wrapper! {
 #[cfg(not(doctest))]
 /// Fingerpint device routines. You can interact with fingerprint devices using this struct.
 ///
+/// # Drop behavior
+/// `FpDevice` is a reference to the device, and its context holds another. Once the application dropped its
+/// last handle to a device with asynchronous operations pending, they are cancelled, within a quarter of a
+/// second while the main context of the operations is iterated, and the device is closed once they completed.
+/// A device dropped while idle stays open, and a device freed while open leaves its USB interface claimed.
+/// Tear down in this order:
+/// 1. Cancel the asynchronous operations, e.g. by dropping their futures.
+/// 2. Call `FpContext::shutdown`, it cancels the operations still running, waits for them, and closes the
+///    devices. A single device can be closed with `FpDevice::close_sync` or `FpDevice::close_future`
+///    instead.
+/// 3. Drop the devices and the context.
+///
 /// # Examples:
 /// ```rust
 /// use libfprint_rs::FpContext;
@@ -40,6 +89,22 @@ pub(crate) struct UserData<F, T> {
     data: Option<T>,
 }
 
+impl<F, T> UserData<F, T> {
+    /// Creates the callback data of a sync operation, `None` without a callback.
+    pub(crate) fn new(function: Option<F>, data: Option<T>) -> Option<Self> {
+        function.map(|function| UserData { function, data })
+    }
+}
+
+/// Returns the user data pointer of a sync operation, null without a callback. Sync operations do not
+/// allocate their callback data: it stays on their stack, and outlives the operation.
+pub(crate) fn borrowed_ptr<F, T>(user_data: &Option<UserData<F, T>>) -> *mut std::ffi::c_void {
+    match user_data {
+        Some(data) => data as *const UserData<F, T> as *mut std::ffi::c_void,
+        None => std::ptr::null_mut(),
+    }
+}
+
 impl<F, T> Drop for UserData<F, T> {
     fn drop(&mut self) {
         if !self.data.is_none() {
@@ -48,6 +113,7 @@ impl<F, T> Drop for UserData<F, T> {
     }
 }
 
+/// Boxes the callback data of an async operation, freed by the destroy notify of the operation.
 macro_rules! fn_pointer {
     ($function:ident, $struct:ident) => {{
         let ptr: *mut std::ffi::c_void = match $function {
@@ -56,8 +122,8 @@ macro_rules! fn_pointer {
                     function: cb,
                     data: $struct,
                 };
-                let boxed = std::sync::Arc::new(data);
-                std::sync::Arc::into_raw(boxed) as *mut std::ffi::c_void
+                let boxed = Box::new(data);
+                Box::into_raw(boxed) as *mut std::ffi::c_void
             }
             None => std::ptr::null_mut(),
         };
@@ -0,0 +1,60 @@
+use glib::{ObjectExt, SignalHandlerId};
+
+#[cfg(libfprint_1_94)]
+use super::enums::FpTemperature;
+use super::{enums::FpFingerStatus, FpDevice};
+
+impl FpDevice {
+    #[cfg(not(doctest))]
+    /// Calls `callback` every time the libfprint property `name` of the device changes, e.g. `"finger-status"`.
+    ///
+    /// The callback runs on the thread default main context of the thread that created the device, which must be
+    /// iterated, as the `_sync` operations do. Disconnect it with `glib::ObjectExt::disconnect` and the returned id.
+    /// # Example:
+    /// ```no_run
+    /// use glib::ObjectExt;
+    /// use libfprint_rs::FpContext;
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    ///
+    /// let handler = dev.connect_property_changed("nr-enroll-stages", |dev| {
+    ///     println!("{} enroll stages", dev.nr_enroll_stage());
+    /// });
+    /// dev.disconnect(handler);
+    /// ```
+    pub fn connect_property_changed<F: Fn(&FpDevice) + 'static>(
+        &self,
+        name: &str,
+        callback: F,
+    ) -> SignalHandlerId {
+        self.connect_notify_local(Some(name), move |device, _| callback(device))
+    }
+    /// Calls `callback` with the new finger status every time it changes, see `FpDevice::finger_status`.
+    pub fn connect_finger_status_changed<F: Fn(&FpDevice, FpFingerStatus) + 'static>(
+        &self,
+        callback: F,
+    ) -> SignalHandlerId {
+        self.connect_property_changed("finger-status", move |device| {
+            callback(device, device.finger_status())
+        })
+    }
+    #[cfg(libfprint_1_94)]
+    /// Calls `callback` with the new temperature every time it changes, see `FpDevice::temperature`.
+    pub fn connect_temperature_changed<F: Fn(&FpDevice, FpTemperature) + 'static>(
+        &self,
+        callback: F,
+    ) -> SignalHandlerId {
+        self.connect_property_changed("temperature", move |device| {
+            callback(device, device.temperature())
+        })
+    }
+    /// Calls `callback` with whether the device is open every time it is opened or closed.
+    pub fn connect_open_changed<F: Fn(&FpDevice, bool) + 'static>(
+        &self,
+        callback: F,
+    ) -> SignalHandlerId {
+        self.connect_property_changed("open", move |device| callback(device, device.is_open()))
+    }
+}
@@ -0,0 +1,298 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use gio::Cancellable;
+
+use crate::{
+    error::{FpContextual, FpRetryError},
+    print::FpPrint,
+};
+
+use super::{FpDevice, FpEnrollProgress};
+
+#[cfg(not(doctest))]
+/// Tracks the progress of an enrollment, for progress bars: percent complete, duration of each stage and an
+/// estimation of the remaining time.
+///
+/// Feed it from the enroll progress callback with `FpEnrollTracker::update`. Retried scans report the same
+/// number of completed stages with an error: they are counted with their retry code, e.g. to tell users
+/// struggling with the position of their finger from a dirty sensor, and their time is included in the duration
+/// of the stage they retried.
+/// # Example:
+/// ```no_run
+/// use std::sync::{Arc, Mutex};
+/// use libfprint_rs::{FpDevice, FpEnrollTracker, FpPrint};
+///
+/// fn progress_cb(
+///     _device: &FpDevice,
+///     completed_stages: i32,
+///     _print: Option<FpPrint>,
+///     error: Option<glib::Error>,
+///     tracker: &Option<Arc<Mutex<FpEnrollTracker>>>,
+/// ) {
+///     let mut tracker = tracker.as_ref().unwrap().lock().unwrap();
+///     tracker.update(completed_stages, error.as_ref());
+///     println!("{}% done, {:?} left", tracker.percent(), tracker.remaining());
+/// }
+///
+/// let tracker = Arc::new(Mutex::new(FpEnrollTracker::for_device(&dev)));
+/// let print = dev.enroll_sync(template, None, Some(progress_cb), Some(tracker))?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct FpEnrollTracker {
+    nr_stages: u32,
+    started: Instant,
+    stage_started: Instant,
+    durations: Vec<Duration>,
+    retries: u32,
+    retries_per_stage: Vec<u32>,
+    retry_codes_per_stage: Vec<Vec<FpRetryError>>,
+}
+
+impl FpEnrollTracker {
+    /// Creates a tracker for an enrollment of `nr_enroll_stages` stages, starting now.
+    pub fn new(nr_enroll_stages: i32) -> Self {
+        let now = Instant::now();
+        FpEnrollTracker {
+            nr_stages: nr_enroll_stages.max(0) as u32,
+            started: now,
+            stage_started: now,
+            durations: Vec::new(),
+            retries: 0,
+            retries_per_stage: vec![0; nr_enroll_stages.max(0) as usize],
+            retry_codes_per_stage: vec![Vec::new(); nr_enroll_stages.max(0) as usize],
+        }
+    }
+    /// Creates a tracker for an enrollment on `device`, starting now.
+    pub fn for_device(device: &FpDevice) -> Self {
+        Self::new(device.nr_enroll_stage())
+    }
+
+    /// Record a call of the enroll progress callback, with its number of completed stages and error. Errors
+    /// outside of the retry domain are counted as `FpRetryError::General`.
+    pub fn update(&mut self, completed_stages: i32, error: Option<&crate::GError>) {
+        let retry = error.map(|err| err.kind::<FpRetryError>().unwrap_or(FpRetryError::General));
+        self.update_at(Instant::now(), completed_stages, retry);
+    }
+    fn update_at(&mut self, now: Instant, completed_stages: i32, retry: Option<FpRetryError>) {
+        if let Some(code) = retry {
+            self.retries += 1;
+            // The retried scan was the one of the stage after the completed ones
+            let stage = completed_stages.max(0) as usize;
+            let stage = stage.min(self.retries_per_stage.len().saturating_sub(1));
+            if let Some(retries) = self.retries_per_stage.get_mut(stage) {
+                *retries += 1;
+                self.retry_codes_per_stage[stage].push(code);
+            }
+        }
+        let completed = (completed_stages.max(0) as u32).min(self.nr_stages) as usize;
+        if completed <= self.durations.len() {
+            return;
+        }
+        // Stages completed at once share the time elapsed since the previous one
+        let stages = (completed - self.durations.len()) as u32;
+        let duration = now.saturating_duration_since(self.stage_started) / stages;
+        self.durations.resize(completed, duration);
+        self.stage_started = now;
+    }
+
+    /// Returns the number of stages of the enrollment.
+    pub fn nr_stages(&self) -> u32 {
+        self.nr_stages
+    }
+    /// Returns the number of completed stages.
+    pub fn completed_stages(&self) -> u32 {
+        self.durations.len() as u32
+    }
+    /// Whether every stage is completed.
+    pub fn is_complete(&self) -> bool {
+        self.nr_stages > 0 && self.completed_stages() == self.nr_stages
+    }
+    /// Returns the number of scans that had to be retried.
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+    /// Returns the number of retried scans of each stage.
+    pub fn retries_per_stage(&self) -> &[u32] {
+        &self.retries_per_stage
+    }
+    /// Returns the retry codes of the retried scans of each stage, in order.
+    pub fn retry_codes_per_stage(&self) -> &[Vec<FpRetryError>] {
+        &self.retry_codes_per_stage
+    }
+    /// Returns the number of retried scans for each retry code, over every stage.
+    pub fn retry_counts(&self) -> HashMap<FpRetryError, u32> {
+        let mut counts = HashMap::new();
+        for code in self.retry_codes_per_stage.iter().flatten() {
+            *counts.entry(*code).or_insert(0) += 1;
+        }
+        counts
+    }
+    /// Returns the percentage of completed stages, from 0 to 100.
+    pub fn percent(&self) -> u8 {
+        if self.nr_stages == 0 {
+            return 0;
+        }
+        (self.completed_stages() * 100 / self.nr_stages) as u8
+    }
+    /// Returns the duration of each completed stage, retries included.
+    pub fn stage_durations(&self) -> &[Duration] {
+        &self.durations
+    }
+    /// Returns the time elapsed since the start of the enrollment.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+    /// Estimates the remaining time from the average duration of the completed stages. `None` is returned
+    /// until a stage is completed.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.remaining_at(Instant::now())
+    }
+    fn remaining_at(&self, now: Instant) -> Option<Duration> {
+        let completed = self.completed_stages();
+        if completed == 0 {
+            return None;
+        }
+        let average = self.durations.iter().sum::<Duration>() / completed;
+        let left = average * (self.nr_stages - completed);
+        // The running stage already took part of its share
+        let running = now.saturating_duration_since(self.stage_started);
+        Some(left.saturating_sub(running.min(average)))
+    }
+}
+
+/// The result of `FpDevice::enroll_outcome_sync`: the enrolled print with statistics of the enrollment, for
+/// dashboards tracking how painful enrollments are.
+#[derive(Debug)]
+pub struct FpEnrollOutcome {
+    /// The enrolled print.
+    pub print: FpPrint,
+    /// The number of completed stages.
+    pub stages_completed: u32,
+    /// The number of retried scans of each stage.
+    pub retries_per_stage: Vec<u32>,
+    /// The retry codes of the retried scans of each stage, in order.
+    pub retry_codes_per_stage: Vec<Vec<FpRetryError>>,
+    /// The duration of the enrollment.
+    pub duration: Duration,
+}
+
+/// The progress data of `FpDevice::enroll_outcome_sync`, wrapping the data of the user's callback.
+struct OutcomeData<T> {
+    tracker: RefCell<FpEnrollTracker>,
+    progress_cb: Option<FpEnrollProgress<T>>,
+    progress_data: Option<T>,
+}
+
+fn outcome_progress<T>(
+    device: &FpDevice,
+    completed_stages: i32,
+    print: Option<FpPrint>,
+    error: Option<crate::GError>,
+    data: &Option<Rc<OutcomeData<T>>>,
+) {
+    if let Some(data) = data {
+        data.tracker
+            .borrow_mut()
+            .update(completed_stages, error.as_ref());
+        if let Some(progress_cb) = data.progress_cb {
+            progress_cb(device, completed_stages, print, error, &data.progress_data);
+        }
+    }
+}
+
+impl FpDevice {
+    #[cfg(not(doctest))]
+    /// Enroll a new print like `FpDevice::enroll_sync`, returning it with the statistics of the enrollment.
+    ///
+    /// The error holds the number of stages completed before the failure.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{FpContext, FpPrint};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    /// dev.open_sync(None).unwrap();
+    ///
+    /// match dev.enroll_outcome_sync(FpPrint::new(&dev), None, None, None::<()>) {
+    ///     Ok(outcome) => println!("Enrolled in {:?}, retries: {:?}", outcome.duration, outcome.retries_per_stage),
+    ///     // e.g. "enroll failed on goodixmoc device 0 after 3 enroll stages: ..."
+    ///     Err(err) => eprintln!("{}", err),
+    /// }
+    /// ```
+    pub fn enroll_outcome_sync<T>(
+        &self,
+        template: FpPrint,
+        cancellable: Option<&Cancellable>,
+        progress_cb: Option<FpEnrollProgress<T>>,
+        progress_data: Option<T>,
+    ) -> Result<FpEnrollOutcome, FpContextual<crate::GError>> {
+        let data = Rc::new(OutcomeData {
+            tracker: RefCell::new(FpEnrollTracker::for_device(self)),
+            progress_cb,
+            progress_data,
+        });
+        let print = self.enroll_contextual_sync(
+            template,
+            cancellable,
+            Some(outcome_progress::<T>),
+            Some(data.clone()),
+        )?;
+
+        let tracker = data.tracker.borrow();
+        Ok(FpEnrollOutcome {
+            print,
+            stages_completed: tracker.completed_stages(),
+            retries_per_stage: tracker.retries_per_stage().to_vec(),
+            retry_codes_per_stage: tracker.retry_codes_per_stage().to_vec(),
+            duration: tracker.elapsed(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::FpEnrollTracker;
+    use crate::error::FpRetryError;
+
+    #[test]
+    fn retried_stages() {
+        let start = Instant::now();
+        let at = |s: u64| start + Duration::from_secs(s);
+        let mut tracker = FpEnrollTracker::new(4);
+        tracker.started = start;
+        tracker.stage_started = start;
+        assert_eq!(tracker.remaining_at(start), None);
+
+        tracker.update_at(at(2), 1, None);
+        // A retry of the second stage, which completes after 4 seconds
+        tracker.update_at(at(4), 1, Some(FpRetryError::CenterFinger));
+        tracker.update_at(at(6), 2, None);
+        assert_eq!(tracker.percent(), 50);
+        assert_eq!(tracker.retries(), 1);
+        assert_eq!(tracker.retries_per_stage(), &[0, 1, 0, 0]);
+        assert_eq!(
+            tracker.retry_codes_per_stage()[1],
+            &[FpRetryError::CenterFinger]
+        );
+        assert_eq!(
+            tracker.stage_durations(),
+            &[Duration::from_secs(2), Duration::from_secs(4)]
+        );
+        assert_eq!(tracker.remaining_at(at(6)), Some(Duration::from_secs(6)));
+        assert_eq!(tracker.remaining_at(at(7)), Some(Duration::from_secs(5)));
+
+        // Both remaining stages reported at once
+        tracker.update_at(at(10), 4, None);
+        assert!(tracker.is_complete());
+        assert_eq!(tracker.stage_durations()[3], Duration::from_secs(2));
+        assert_eq!(tracker.remaining_at(at(10)), Some(Duration::ZERO));
+    }
+}
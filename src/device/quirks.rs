@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use super::{FpDevice, FpDeviceFeature, FpDeviceInfo};
+
+/// Known quirks of a driver, that libfprint does not report at runtime. `None` means unknown.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FpDriverQuirks {
+    /// Whether the driver matches prints on the chip, storing the templates on the device.
+    pub match_on_chip: bool,
+    /// Maximum number of templates the device can store.
+    pub max_stored_templates: Option<u32>,
+    /// Maximum number of prints that can be passed to a single identification.
+    pub max_identify_gallery: Option<u32>,
+    /// Known oddities of the enroll process, e.g. stages that are commonly retried.
+    pub enroll_notes: Option<String>,
+}
+
+/// A table of driver quirks keyed by driver name, see `FpDevice::capabilities`.
+///
+/// `FpQuirkTable::builtin` only contains what the libfprint drivers hard-code, integrators can complete or
+/// override it with their own data using `FpQuirkTable::insert`.
+#[derive(Debug, Clone, Default)]
+pub struct FpQuirkTable {
+    entries: HashMap<String, FpDriverQuirks>,
+}
+
+/// Drivers doing the matching on the chip, with the maximum number of templates and the enroll notes their
+/// libfprint driver hard-codes.
+const MATCH_ON_CHIP_DRIVERS: [(&str, Option<u32>, Option<&str>); 7] = [
+    ("synaptics", None, None),
+    (
+        "goodixmoc",
+        None,
+        Some("A finger already enrolled on the chip is rejected with FP_DEVICE_ERROR_DATA_DUPLICATE"),
+    ),
+    ("elanmoc", None, None),
+    (
+        "egismoc",
+        Some(10),
+        Some(
+            "A finger already enrolled on the chip is rejected with FP_DEVICE_ERROR_DATA_DUPLICATE, some \
+             models take 20 enroll stages instead of 10",
+        ),
+    ),
+    ("fpcmoc", None, None),
+    ("realtek", None, None),
+    ("virtual_device_storage", None, None),
+];
+
+impl FpQuirkTable {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        FpQuirkTable::default()
+    }
+    /// Creates the table of quirks shipped with this crate.
+    pub fn builtin() -> Self {
+        let mut table = FpQuirkTable::new();
+        for (driver, max_stored_templates, enroll_notes) in MATCH_ON_CHIP_DRIVERS {
+            table.insert(
+                driver,
+                FpDriverQuirks {
+                    match_on_chip: true,
+                    max_stored_templates,
+                    enroll_notes: enroll_notes.map(str::to_string),
+                    ..Default::default()
+                },
+            );
+        }
+        table
+    }
+    /// Adds or replaces the quirks of `driver`.
+    pub fn insert(&mut self, driver: &str, quirks: FpDriverQuirks) {
+        self.entries.insert(driver.to_string(), quirks);
+    }
+    /// Returns the quirks of `driver`, if known.
+    pub fn get(&self, driver: &str) -> Option<&FpDriverQuirks> {
+        self.entries.get(driver)
+    }
+}
+
+/// The capabilities of a device: its runtime properties merged with the quirks of its driver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FpDeviceCapabilities {
+    /// The runtime properties of the device.
+    pub info: FpDeviceInfo,
+    /// The quirks of the driver, with `match_on_chip` also set if the device reports a storage, and
+    /// `max_stored_templates` set to 0 if it does not.
+    pub quirks: FpDriverQuirks,
+}
+
+impl FpDevice {
+    /// Returns the capabilities of the device, merging its runtime features with the quirks
+    /// of its driver found in `table`.
+    pub fn capabilities(&self, table: &FpQuirkTable) -> FpDeviceCapabilities {
+        let info = self.info();
+        let mut quirks = table.get(&info.driver).cloned().unwrap_or_default();
+
+        if info.features.contains(&FpDeviceFeature::Storage) {
+            quirks.match_on_chip = true;
+        } else {
+            quirks.max_stored_templates = Some(0);
+        }
+
+        FpDeviceCapabilities { info, quirks }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FpQuirkTable;
+
+    #[test]
+    fn builtin_quirks() {
+        let table = FpQuirkTable::builtin();
+        let egismoc = table.get("egismoc").unwrap();
+        assert!(egismoc.match_on_chip);
+        assert_eq!(egismoc.max_stored_templates, Some(10));
+        assert!(table
+            .get("synaptics")
+            .unwrap()
+            .max_stored_templates
+            .is_none());
+        assert!(table.get("elan").is_none());
+    }
+}
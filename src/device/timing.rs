@@ -0,0 +1,205 @@
+use std::{
+    cell::Cell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use gio::Cancellable;
+use glib::ObjectExt;
+
+use crate::print::FpPrint;
+
+use super::{FpDevice, FpIdentifyResult, FpMatchCb, FpMatchOutcome};
+
+/// How long the parts of a verification or identification took, see `FpDevice::verify_timed_sync`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FpMatchTiming {
+    /// From the start of the operation until the scan completed, waiting for the finger included.
+    pub capture: Duration,
+    /// From the end of the scan until the matcher decided.
+    pub matching: Duration,
+    /// The duration of the whole operation.
+    pub total: Duration,
+}
+
+impl FpMatchTiming {
+    /// The timing of an operation started at `started` and finished at `finished`. Without a known end of the scan,
+    /// e.g. drivers matching on the chip, the matching time is zero; without a known decision, it lasts until the
+    /// end.
+    fn new(
+        started: Instant,
+        captured: Option<Instant>,
+        matched: Option<Instant>,
+        finished: Instant,
+    ) -> Self {
+        let matched = matched.unwrap_or(finished);
+        let captured = captured.unwrap_or(matched).min(matched);
+        FpMatchTiming {
+            capture: captured.saturating_duration_since(started),
+            matching: matched.saturating_duration_since(captured),
+            total: finished.saturating_duration_since(started),
+        }
+    }
+}
+
+/// The instants recorded while a timed operation runs.
+struct Timer {
+    started: Instant,
+    waiting: Cell<bool>,
+    captured: Cell<Option<Instant>>,
+    matched: Cell<Option<Instant>>,
+}
+
+impl Timer {
+    fn finish(&self) -> FpMatchTiming {
+        FpMatchTiming::new(
+            self.started,
+            self.captured.get(),
+            self.matched.get(),
+            Instant::now(),
+        )
+    }
+}
+
+/// The match data of the timed operations, wrapping the data of the user's callback.
+struct TimedData<T> {
+    timer: Rc<Timer>,
+    match_cb: Option<FpMatchCb<T>>,
+    match_data: Option<T>,
+}
+
+fn timed_match<T>(
+    device: &FpDevice,
+    matched: Option<FpPrint>,
+    print: Option<FpPrint>,
+    error: Option<crate::GError>,
+    data: &Option<TimedData<T>>,
+) {
+    if let Some(data) = data {
+        if data.timer.matched.get().is_none() {
+            data.timer.matched.set(Some(Instant::now()));
+        }
+        if let Some(match_cb) = data.match_cb {
+            match_cb(device, matched, print, error, &data.match_data);
+        }
+    }
+}
+
+impl FpDevice {
+    /// Run `operation` with the timed match data, returning its result with the timing.
+    fn timed<T, R>(
+        &self,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+        operation: impl FnOnce(Option<FpMatchCb<TimedData<T>>>, Option<TimedData<T>>) -> R,
+    ) -> (R, FpMatchTiming) {
+        let timer = Rc::new(Timer {
+            started: Instant::now(),
+            waiting: Cell::new(false),
+            captured: Cell::new(None),
+            matched: Cell::new(None),
+        });
+        // The scan is complete once the device stops waiting for a finger
+        let handler = {
+            let timer = timer.clone();
+            self.connect_property_changed("finger-status", move |device| {
+                let needed = device.finger_status_flags().is_needed();
+                if timer.waiting.replace(needed) && !needed && timer.captured.get().is_none() {
+                    timer.captured.set(Some(Instant::now()));
+                }
+            })
+        };
+        let data = TimedData {
+            timer: timer.clone(),
+            match_cb,
+            match_data,
+        };
+        let result = operation(Some(timed_match::<T>), Some(data));
+        self.disconnect(handler);
+        (result, timer.finish())
+    }
+
+    #[cfg(not(doctest))]
+    /// Verify a given print like `FpDevice::verify_sync`, returning the outcome with the time the capture and
+    /// the matching took, e.g. to monitor latency regressions after firmware or libfprint updates.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{FpContext, FpPrint};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    /// dev.open_sync(None).unwrap();
+    ///
+    /// let print: FpPrint = load_print();
+    /// let (outcome, timing) = dev.verify_timed_sync(&print, None, None, None::<()>).unwrap();
+    /// println!("Matched: {} in {:?}", outcome.is_match(), timing.matching);
+    /// ```
+    pub fn verify_timed_sync<T>(
+        &self,
+        enrolled_print: &FpPrint,
+        cancellable: Option<&Cancellable>,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+    ) -> Result<(FpMatchOutcome, FpMatchTiming), crate::GError> {
+        let (outcome, timing) = self.timed(match_cb, match_data, |match_cb, match_data| {
+            self.verify_sync(enrolled_print, cancellable, match_cb, match_data)
+        });
+        Ok((outcome?, timing))
+    }
+    #[cfg(not(doctest))]
+    /// Identify a print like `FpDevice::identify_sync`, returning its result with the time the capture and the
+    /// matching took.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{FpContext, FpPrint};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    /// dev.open_sync(None).unwrap();
+    ///
+    /// let gallery: Vec<FpPrint> = load_prints();
+    /// let (result, timing) = dev.identify_timed_sync(&gallery, None, None, None::<()>).unwrap();
+    /// println!("Identified {:?} in {:?}", result.print().and_then(|p| p.username()), timing.total);
+    /// ```
+    pub fn identify_timed_sync<'a, T, I, P>(
+        &self,
+        prints: I,
+        cancellable: Option<&Cancellable>,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+    ) -> Result<(FpIdentifyResult, FpMatchTiming), crate::GError>
+    where
+        I: IntoIterator<Item = &'a P>,
+        P: AsRef<FpPrint> + 'a,
+    {
+        let (result, timing) = self.timed(match_cb, match_data, |match_cb, match_data| {
+            self.identify_sync(prints, cancellable, match_cb, match_data)
+        });
+        Ok((result?, timing))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::FpMatchTiming;
+
+    #[test]
+    fn timing() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        let timing = FpMatchTiming::new(start, Some(at(1500)), Some(at(1800)), at(2000));
+        assert_eq!(timing.capture, Duration::from_millis(1500));
+        assert_eq!(timing.matching, Duration::from_millis(300));
+        assert_eq!(timing.total, Duration::from_millis(2000));
+
+        // Match on chip: the end of the scan is not reported
+        let timing = FpMatchTiming::new(start, None, Some(at(900)), at(1000));
+        assert_eq!(timing.capture, Duration::from_millis(900));
+        assert_eq!(timing.matching, Duration::ZERO);
+    }
+}
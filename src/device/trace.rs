@@ -0,0 +1,272 @@
+//! Instrumentation of device operations: spans emitted with the `tracing` feature, counters and histograms
+//! with the `metrics` feature, and the context attached to the errors they return.
+use glib::ObjectExt;
+
+use super::{FpDevice, FpIdentifyResult, FpMatchOutcome, FpWeakDevice};
+#[cfg(feature = "metrics")]
+use crate::error::FpRetryError;
+use crate::{error::FpContextual, print::FpPrint};
+
+/// Key of the number of enroll stages completed by the running enrollment of a device, see `enroll_stage`.
+const COMPLETED_STAGES: &str = "fprs-completed-stages";
+
+/// The outcome recorded in the span of an operation that succeeded.
+#[cfg_attr(not(any(feature = "tracing", feature = "metrics")), allow(dead_code))]
+pub(crate) trait Outcome {
+    fn outcome(&self) -> &'static str;
+    /// The reason of the scan to retry, if the operation asks for one.
+    #[cfg(feature = "metrics")]
+    fn retry(&self) -> Option<FpRetryError> {
+        None
+    }
+}
+
+impl Outcome for () {
+    fn outcome(&self) -> &'static str {
+        "ok"
+    }
+}
+
+impl Outcome for FpPrint {
+    fn outcome(&self) -> &'static str {
+        "enrolled"
+    }
+}
+
+impl Outcome for FpMatchOutcome {
+    fn outcome(&self) -> &'static str {
+        match self {
+            FpMatchOutcome::Matched(_) => "matched",
+            FpMatchOutcome::NotMatched(_) => "not_matched",
+            FpMatchOutcome::Retry(_) => "retry",
+        }
+    }
+    #[cfg(feature = "metrics")]
+    fn retry(&self) -> Option<FpRetryError> {
+        match self {
+            FpMatchOutcome::Retry(reason) => Some(*reason),
+            _ => None,
+        }
+    }
+}
+
+impl Outcome for FpIdentifyResult {
+    fn outcome(&self) -> &'static str {
+        match self.is_match() {
+            true => "identified",
+            false => "not_identified",
+        }
+    }
+}
+
+/// The operation and device attached to the errors of an operation, see `FpContextual::into_error`.
+///
+/// The device is held weakly, so a pending operation does not count as a handle to it, see `PendingOperation`.
+pub(crate) struct ErrorContext {
+    operation: &'static str,
+    device: FpWeakDevice,
+}
+
+impl ErrorContext {
+    pub(crate) fn new(device: &FpDevice, operation: &'static str) -> Self {
+        ErrorContext {
+            operation,
+            device: device.downgrade(),
+        }
+    }
+    /// Wrap `error` in the context, with the number of completed stages for an enrollment.
+    pub(crate) fn contextual(&self, error: crate::GError) -> FpContextual<crate::GError> {
+        let device = self.device.upgrade();
+        let contextual = FpContextual::new(self.operation, device.as_ref(), error);
+        match device {
+            Some(device) if self.operation == "enroll" => {
+                let stages = unsafe { device.data::<u32>(COMPLETED_STAGES) };
+                contextual.at_stage(stages.map_or(0, |stages| unsafe { *stages.as_ref() }))
+            }
+            _ => contextual,
+        }
+    }
+    /// Attach the context to `error`, see `FpContextual::into_error`.
+    pub(crate) fn error(&self, error: crate::GError) -> crate::GError {
+        self.contextual(error).into_error()
+    }
+    /// Attach the context to the error of `result`.
+    pub(crate) fn finish<R>(&self, result: Result<R, crate::GError>) -> Result<R, crate::GError> {
+        result.map_err(|error| self.error(error))
+    }
+}
+
+/// The span and metrics of an operation on a device, carrying the device id, driver, operation and outcome.
+/// The errors it finishes with get the context of the operation.
+pub(crate) struct OperationSpan {
+    context: ErrorContext,
+    #[cfg(feature = "tracing")]
+    span: SpanState,
+    #[cfg(feature = "metrics")]
+    metrics: OperationMetrics,
+}
+
+#[cfg(feature = "metrics")]
+struct OperationMetrics {
+    operation: &'static str,
+    driver: String,
+    started: std::time::Instant,
+}
+
+#[cfg(feature = "metrics")]
+impl OperationMetrics {
+    fn new(device: &FpDevice, operation: &'static str) -> Self {
+        OperationMetrics {
+            operation,
+            driver: device.driver(),
+            started: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+enum SpanState {
+    /// The span of a synchronous operation, entered until it completes.
+    Entered(tracing::span::EnteredSpan),
+    /// The span of an asynchronous operation.
+    Detached(tracing::Span),
+}
+
+impl OperationSpan {
+    #[cfg(feature = "tracing")]
+    fn span(device: &FpDevice, operation: &'static str) -> tracing::Span {
+        tracing::info_span!(
+            "fp_operation",
+            operation,
+            device_id = %device.device_id(),
+            driver = %device.driver(),
+            outcome = tracing::field::Empty,
+        )
+    }
+
+    fn context(device: &FpDevice, operation: &'static str) -> ErrorContext {
+        if operation == "enroll" {
+            unsafe { device.set_data(COMPLETED_STAGES, 0u32) };
+        }
+        ErrorContext::new(device, operation)
+    }
+
+    /// Creates the span of a synchronous operation, entered until `OperationSpan::finish`.
+    pub(crate) fn enter(device: &FpDevice, operation: &'static str) -> Self {
+        OperationSpan {
+            context: Self::context(device, operation),
+            #[cfg(feature = "tracing")]
+            span: SpanState::Entered(Self::span(device, operation).entered()),
+            #[cfg(feature = "metrics")]
+            metrics: OperationMetrics::new(device, operation),
+        }
+    }
+
+    /// Creates the span of an asynchronous operation.
+    pub(crate) fn new(device: &FpDevice, operation: &'static str) -> Self {
+        OperationSpan {
+            context: Self::context(device, operation),
+            #[cfg(feature = "tracing")]
+            span: SpanState::Detached(Self::span(device, operation)),
+            #[cfg(feature = "metrics")]
+            metrics: OperationMetrics::new(device, operation),
+        }
+    }
+
+    /// Record the outcome of the operation and close the span, returning `result` with the context of the
+    /// operation attached to its error.
+    pub(crate) fn finish<R: Outcome>(
+        self,
+        result: Result<R, crate::GError>,
+    ) -> Result<R, crate::GError> {
+        self.finish_contextual(result)
+            .map_err(FpContextual::into_error)
+    }
+
+    /// Like `OperationSpan::finish`, returning the error wrapped in its context.
+    pub(crate) fn finish_contextual<R: Outcome>(
+        self,
+        result: Result<R, crate::GError>,
+    ) -> Result<R, FpContextual<crate::GError>> {
+        #[cfg(feature = "tracing")]
+        {
+            let span: &tracing::Span = match &self.span {
+                SpanState::Entered(span) => span,
+                SpanState::Detached(span) => span,
+            };
+            match &result {
+                Ok(value) => {
+                    span.record("outcome", value.outcome());
+                    tracing::info!(parent: span, outcome = value.outcome(), "operation completed");
+                }
+                Err(err) => {
+                    span.record("outcome", "error");
+                    tracing::warn!(parent: span, error = %err, "operation failed");
+                }
+            }
+        }
+        #[cfg(feature = "metrics")]
+        {
+            let metrics = &self.metrics;
+            let outcome = match &result {
+                Ok(value) => value.outcome(),
+                Err(_) => "error",
+            };
+            let labels = [
+                ("operation", metrics.operation.to_string()),
+                ("driver", metrics.driver.clone()),
+                ("outcome", outcome.to_string()),
+            ];
+            metrics::counter!("libfprint_operations_total", &labels).increment(1);
+            metrics::histogram!("libfprint_operation_duration_seconds", &labels)
+                .record(metrics.started.elapsed().as_secs_f64());
+            if let Some(reason) = result.as_ref().ok().and_then(Outcome::retry) {
+                retried(metrics.operation, &metrics.driver, reason);
+            }
+        }
+        result.map_err(|error| self.context.contextual(error))
+    }
+}
+
+/// Count a scan to retry.
+#[cfg(feature = "metrics")]
+fn retried(operation: &'static str, driver: &str, reason: FpRetryError) {
+    let reason = match reason {
+        FpRetryError::General => "general",
+        FpRetryError::TooShort => "too_short",
+        FpRetryError::CenterFinger => "center_finger",
+        FpRetryError::RemoveFinger => "remove_finger",
+        FpRetryError::TooFast => "too_fast",
+    };
+    metrics::counter!(
+        "libfprint_retries_total",
+        "operation" => operation,
+        "driver" => driver.to_string(),
+        "reason" => reason,
+    )
+    .increment(1);
+}
+
+/// Report an enroll stage, in the span of the running enrollment. `error` is the reason of the scan to retry.
+/// The completed stages are kept for the context of the error the enrollment may fail with.
+#[cfg_attr(
+    not(any(feature = "tracing", feature = "metrics")),
+    allow(unused_variables)
+)]
+pub(crate) fn enroll_stage(
+    device: &FpDevice,
+    completed_stages: i32,
+    error: Option<&crate::GError>,
+) {
+    unsafe { device.set_data(COMPLETED_STAGES, completed_stages.max(0) as u32) };
+    #[cfg(feature = "tracing")]
+    tracing::info!(
+        stage = completed_stages,
+        retry = error.is_some(),
+        "enroll stage"
+    );
+    #[cfg(feature = "metrics")]
+    if let Some(reason) = error.and_then(|error| error.kind::<FpRetryError>()) {
+        retried("enroll", &device.driver(), reason);
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use gio::{
+    prelude::{CancellableExt, CancellableExtManual},
+    Cancellable,
+};
+
+use crate::error::FpError;
+
+use super::{wait, FpDevice};
+
+/// How a wedged device was recovered, see `FpError::DeviceWedged`.
+#[derive(Debug)]
+pub enum FpWedgeRecovery {
+    /// The device was closed and opened again, it can be used.
+    Reopened,
+    /// The device could not be closed, the error is given.
+    CloseFailed(crate::GError),
+    /// The device was closed but could not be opened again, the error is given.
+    ReopenFailed(crate::GError),
+}
+
+#[cfg(not(doctest))]
+/// Cancels operations that do not complete before a deadline, for firmwares that wedge in the middle of a
+/// scan.
+///
+/// The operation is started with the asynchronous API of `FpDevice`, and the watchdog iterates the thread
+/// default main context, where its callback is dispatched, while it is pending. Once the deadline passes
+/// the operation is cancelled. If it fails soon after, within the grace period, a
+/// `gio::IOErrorEnum::TimedOut` error is returned. If it is still pending at the end of the grace period,
+/// the device is considered wedged and `FpError::DeviceWedged` is returned with the outcome of the recovery:
+/// the device is closed and opened again. libfprint does not close a device while an operation runs, so if
+/// closing fails the USB port of the device is reset (see `FpDevice::reset`), which fails the transfers of
+/// the operation, and closing is tried again once the operation completed or another grace period passed.
+/// # Example:
+/// ```no_run
+/// use std::time::Duration;
+/// use libfprint_rs::{FpContext, FpPrint, FpWatchdog};
+///
+/// let ctx = FpContext::new();
+/// let devices = ctx.devices();
+/// let dev = devices.get(0).unwrap();
+/// dev.open_sync(None).unwrap();
+///
+/// let watchdog = FpWatchdog::new(Duration::from_secs(30));
+/// let print = watchdog.run(&dev, None, |cancellable, done| {
+///     dev.enroll(FpPrint::new(&dev), Some(cancellable), None, None::<()>, done)
+/// });
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct FpWatchdog {
+    deadline: Duration,
+    grace: Duration,
+}
+
+/// The slot the result of the watched operation is stored in by its callback.
+type Pending<R> = Rc<RefCell<Option<Result<R, crate::GError>>>>;
+
+impl FpWatchdog {
+    /// Creates a watchdog cancelling the operations after `deadline`, with a grace period of 5 seconds.
+    pub fn new(deadline: Duration) -> Self {
+        FpWatchdog {
+            deadline,
+            grace: Duration::from_secs(5),
+        }
+    }
+    /// Set the time the operations have to complete once cancelled, before the device is considered wedged.
+    pub fn grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Run `operation` on `device` under the watchdog. `operation` must start an asynchronous operation of
+    /// `device` with the given cancellable, which is also cancelled with `cancellable`, and the given
+    /// completion callback.
+    pub fn run<R: 'static>(
+        &self,
+        device: &FpDevice,
+        cancellable: Option<&Cancellable>,
+        operation: impl FnOnce(&Cancellable, Box<dyn FnOnce(Result<R, crate::GError>)>),
+    ) -> Result<R, FpError> {
+        let main_context = glib::MainContext::ref_thread_default();
+        let watched = Cancellable::new();
+        let parent = cancellable.map(|parent| {
+            let watched = watched.clone();
+            (parent, parent.connect_cancelled(move |_| watched.cancel()))
+        });
+
+        let pending: Pending<R> = Rc::new(RefCell::new(None));
+        {
+            let pending = pending.clone();
+            operation(
+                &watched,
+                Box::new(move |res| *pending.borrow_mut() = Some(res)),
+            );
+        }
+        let started = Instant::now();
+        let cancelled_at = started + self.deadline;
+        let wedged_at = cancelled_at + self.grace;
+        // Wake the loop up at the deadline and at the end of the grace period
+        let sources = [
+            wake_up(&main_context, self.deadline),
+            wake_up(&main_context, self.deadline + self.grace),
+        ];
+
+        let mut fired = false;
+        let result = loop {
+            if let Some(res) = pending.borrow_mut().take() {
+                break Some(res);
+            }
+            let now = Instant::now();
+            if now >= wedged_at {
+                break None;
+            }
+            if now >= cancelled_at && !fired {
+                fired = true;
+                watched.cancel();
+                continue;
+            }
+            main_context.iteration(true);
+        };
+
+        sources.iter().for_each(glib::Source::destroy);
+        if let Some((parent, Some(handler))) = parent {
+            parent.disconnect_cancelled(handler);
+        }
+
+        match result {
+            Some(Err(_)) if fired => Err(crate::GError::new(
+                gio::IOErrorEnum::TimedOut,
+                "The operation did not complete before the deadline",
+            )
+            .into()),
+            Some(result) => result.map_err(FpError::from),
+            None => Err(FpError::DeviceWedged(self.recover(
+                &main_context,
+                device,
+                &pending,
+            ))),
+        }
+    }
+
+    /// Close and open the device again, while the watched operation may still be pending.
+    fn recover<R>(
+        &self,
+        main_context: &glib::MainContext,
+        device: &FpDevice,
+        pending: &Pending<R>,
+    ) -> FpWedgeRecovery {
+        if let Err(err) = wait(main_context, |done| device.close(None, done)) {
+            // The operation still runs: fail its transfers, and give it the time to complete
+            if device.reset().is_err() {
+                return FpWedgeRecovery::CloseFailed(err);
+            }
+            let source = wake_up(main_context, self.grace);
+            let started = Instant::now();
+            while pending.borrow().is_none() && started.elapsed() < self.grace {
+                main_context.iteration(true);
+            }
+            source.destroy();
+            if let Err(err) = wait(main_context, |done| device.close(None, done)) {
+                return FpWedgeRecovery::CloseFailed(err);
+            }
+        }
+        match wait(main_context, |done| device.open(None, done)) {
+            Ok(()) => FpWedgeRecovery::Reopened,
+            Err(err) => FpWedgeRecovery::ReopenFailed(err),
+        }
+    }
+}
+
+/// Attach a source to `main_context` waking it up after `after`.
+fn wake_up(main_context: &glib::MainContext, after: Duration) -> glib::Source {
+    let source = glib::timeout_source_new(after, None, glib::Priority::DEFAULT, || {
+        glib::ControlFlow::Break
+    });
+    source.attach(Some(main_context));
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{FpContext, FpError, FpWatchdog, FpWedgeRecovery};
+
+    #[test]
+    fn wedged() {
+        // Run with FP_VIRTUAL_IMAGE set, for the virtual_image device
+        let ctx = FpContext::new();
+        let dev = ctx
+            .devices()
+            .into_iter()
+            .find(|dev| dev.driver() == "virtual_image")
+            .unwrap();
+        dev.open_sync(None).unwrap();
+        let watchdog =
+            FpWatchdog::new(Duration::from_millis(200)).grace(Duration::from_millis(200));
+
+        // A capture waits for a finger until cancelled
+        let result = watchdog.run(&dev, None, |cancellable, done| {
+            dev.capture(true, Some(cancellable), done)
+        });
+        match result {
+            Err(FpError::GLib(err)) => assert!(err.matches(gio::IOErrorEnum::TimedOut)),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+
+        // An operation that never completes
+        let started = Instant::now();
+        let result = watchdog.run(&dev, None, |_, done: Box<dyn FnOnce(Result<(), _>)>| {
+            drop(done)
+        });
+        assert!(started.elapsed() >= Duration::from_millis(400));
+        assert!(matches!(
+            result,
+            Err(FpError::DeviceWedged(FpWedgeRecovery::Reopened))
+        ));
+        assert!(dev.is_open());
+        dev.close_sync(None).unwrap();
+    }
+}
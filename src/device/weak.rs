@@ -0,0 +1,44 @@
+use glib::{ObjectExt, WeakRef};
+
+use super::FpDevice;
+
+#[cfg(not(doctest))]
+/// A weak reference to a `FpDevice`, returned by `FpDevice::downgrade`.
+///
+/// It does not keep the device alive: callbacks stored in long-lived structures can hold it instead of the
+/// device, and `FpWeakDevice::upgrade` returns `None` once the device was freed, e.g. after it was unplugged
+/// and the context released it. It is `Send` and `Sync` through `WeakRef`, like the device it points to.
+/// # Example:
+/// ```no_run
+/// use libfprint_rs::FpContext;
+///
+/// let ctx = FpContext::new();
+/// let devices = ctx.devices();
+/// let weak = devices.get(0).unwrap().downgrade();
+///
+/// let on_timeout = move || {
+///     if let Some(dev) = weak.upgrade() {
+///         dev.open_sync(None).unwrap();
+///     }
+/// };
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FpWeakDevice(WeakRef<FpDevice>);
+
+impl FpWeakDevice {
+    /// Creates a weak reference to no device, `FpWeakDevice::upgrade` always returns `None`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns the device, or `None` if it was freed.
+    pub fn upgrade(&self) -> Option<FpDevice> {
+        self.0.upgrade()
+    }
+}
+
+impl FpDevice {
+    /// Creates a weak reference to the device, that does not keep it alive.
+    pub fn downgrade(&self) -> FpWeakDevice {
+        FpWeakDevice(ObjectExt::downgrade(self))
+    }
+}
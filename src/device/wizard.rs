@@ -0,0 +1,110 @@
+use gio::{prelude::CancellableExt, Cancellable};
+
+use crate::{finger::FpFinger, print::FpPrint};
+
+use super::{FpDevice, FpEnrollProgress};
+
+/// This type represents the callback function for `FpDevice::enroll_fingers_sync`, it will be called before
+/// each finger is enrolled so the user can be prompted to place the right finger on the sensor.
+/// Returning `false` skips the finger.
+pub type FpFingerPrompt<T> = fn(&FpDevice, FpFinger, &Option<T>) -> bool;
+
+/// The result of enrolling a set of fingers for a single user with `FpDevice::enroll_fingers_sync`.
+#[derive(Debug)]
+pub struct FpEnrollSet {
+    /// The username every print of the set was enrolled for.
+    pub username: String,
+    /// The prints that were successfully enrolled, in enrollment order.
+    pub prints: Vec<(FpFinger, FpPrint)>,
+    /// The fingers whose enrollment failed, with the error that made them fail.
+    pub failed: Vec<(FpFinger, crate::GError)>,
+    /// The fingers that were skipped, either by the prompt callback or because the operation was cancelled.
+    pub skipped: Vec<FpFinger>,
+}
+
+impl FpEnrollSet {
+    /// Whether every requested finger was enrolled.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty() && self.skipped.is_empty()
+    }
+    /// Returns the print enrolled for `finger`, if any.
+    pub fn print(&self, finger: FpFinger) -> Option<&FpPrint> {
+        self.prints
+            .iter()
+            .find(|(f, _)| *f == finger)
+            .map(|(_, print)| print)
+    }
+}
+
+impl FpDevice {
+    #[cfg(not(doctest))]
+    /// Enroll several fingers for the same user, one after the other.
+    ///
+    /// `prompt_cb` is called before each finger, `progress_cb` is called for each stage of every enrollment.
+    /// A failed enrollment does not stop the process, the error is recorded in the returned `FpEnrollSet`
+    /// and the next finger is enrolled. If `cancellable` is cancelled the remaining fingers are skipped.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{FpContext, FpDevice, FpFinger};
+    ///
+    /// pub fn prompt_cb(_device: &FpDevice, finger: FpFinger, _data: &Option<()>) -> bool {
+    ///     println!("Place your {} finger on the sensor", finger);
+    ///     true
+    /// }
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    /// dev.open_sync(None).unwrap();
+    ///
+    /// let fingers = [FpFinger::RightIndex, FpFinger::LeftIndex];
+    /// let set = dev.enroll_fingers_sync("Bruce Banner", &fingers, None, Some(prompt_cb), None, None);
+    /// for (finger, error) in &set.failed {
+    ///     println!("Could not enroll {}: {}", finger, error);
+    /// }
+    /// ```
+    pub fn enroll_fingers_sync<T: Clone>(
+        &self,
+        username: &str,
+        fingers: &[FpFinger],
+        cancellable: Option<&Cancellable>,
+        prompt_cb: Option<FpFingerPrompt<T>>,
+        progress_cb: Option<FpEnrollProgress<T>>,
+        data: Option<T>,
+    ) -> FpEnrollSet {
+        let mut set = FpEnrollSet {
+            username: username.to_string(),
+            prints: Vec::new(),
+            failed: Vec::new(),
+            skipped: Vec::new(),
+        };
+
+        for &finger in fingers {
+            if cancellable.is_some_and(|c| c.is_cancelled()) {
+                set.skipped.push(finger);
+                continue;
+            }
+            if let Some(prompt) = prompt_cb {
+                if !prompt(self, finger, &data) {
+                    set.skipped.push(finger);
+                    continue;
+                }
+            }
+
+            let template = FpPrint::new(self);
+            if let Err(error) = template.set_username(username) {
+                let error =
+                    crate::GError::new(gio::IOErrorEnum::InvalidArgument, &error.to_string());
+                set.failed.push((finger, error));
+                continue;
+            }
+            template.set_finger(finger);
+
+            match self.enroll_sync(template, cancellable, progress_cb, data.clone()) {
+                Ok(print) => set.prints.push((finger, print)),
+                Err(error) => set.failed.push((finger, error)),
+            }
+        }
+        set
+    }
+}
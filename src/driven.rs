@@ -0,0 +1,109 @@
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use async_io::{Async, Timer};
+
+use crate::poll::FpMainContextFd;
+
+#[cfg(not(doctest))]
+/// Runs a future while driving the thread default `glib::MainContext`, so the futures of the crate can be
+/// awaited from executors other than GLib's, e.g. `smol` or `async-std` (requires the `async-io` feature).
+///
+/// The main context is watched through `FpMainContextFd` with the `async-io` reactor, and dispatched from
+/// the polls of the future: no thread is dedicated to it. The future must be polled from a single thread,
+/// the one owning the main context, e.g. with `smol::LocalExecutor` or `futures::executor::block_on`.
+/// # Example:
+/// ```no_run
+/// use libfprint_rs::{FpContext, FpDriven};
+///
+/// let ctx = FpContext::new();
+/// let devices = ctx.devices();
+/// let dev = devices.get(0).unwrap();
+///
+/// let image = smol::block_on(FpDriven::new(async {
+///     dev.open_future().await?;
+///     dev.capture_future(true).await
+/// }))
+/// .unwrap();
+/// ```
+pub struct FpDriven<F: Future> {
+    future: Pin<Box<F>>,
+    /// The descriptor of the main context, created on the first poll.
+    main_context: Option<Async<FpMainContextFd>>,
+    timer: Timer,
+}
+
+impl<F: Future> FpDriven<F> {
+    /// Wraps `future`, driving the thread default main context of the thread polling it.
+    pub fn new(future: F) -> Self {
+        FpDriven {
+            future: Box::pin(future),
+            main_context: None,
+            timer: Timer::never(),
+        }
+    }
+}
+
+impl<F: Future> Future for FpDriven<F> {
+    /// The output of the future, or the error of the descriptor of the main context.
+    type Output = io::Result<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.main_context.is_none() {
+            let context = glib::MainContext::ref_thread_default();
+            this.main_context = Some(Async::new(FpMainContextFd::new(&context)?)?);
+        }
+        loop {
+            if let Poll::Ready(output) = this.future.as_mut().poll(cx) {
+                return Poll::Ready(Ok(output));
+            }
+            let main_context = this.main_context.as_mut().expect("created above");
+            // The future may have attached sources, they are only watched once dispatched
+            // Safety: The descriptor is not replaced, `Async` keeps watching the same one
+            let dispatched = unsafe { main_context.get_mut() }.dispatch()?;
+            if dispatched {
+                continue;
+            }
+            match main_context.get_ref().timeout() {
+                Some(timeout) if timeout == Duration::ZERO => continue,
+                Some(timeout) => this.timer.set_after(timeout),
+                None => this.timer = Timer::never(),
+            }
+            if main_context.poll_readable(cx)?.is_ready() {
+                continue;
+            }
+            if Pin::new(&mut this.timer).poll(cx).is_ready() {
+                continue;
+            }
+            return Poll::Pending;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::FpDriven;
+
+    #[test]
+    fn drive() {
+        let context = glib::MainContext::new();
+        context
+            .with_thread_default(|| {
+                let output = async_io::block_on(FpDriven::new(async {
+                    // The timeout is attached from the poll, outside of the dispatch
+                    glib::timeout_future(Duration::from_millis(10)).await;
+                    7
+                }));
+                assert_eq!(output.unwrap(), 7);
+            })
+            .unwrap();
+    }
+}
@@ -0,0 +1,342 @@
+use std::fmt::Display;
+
+use crate::device::{FpDevice, FpDeviceId, FpWedgeRecovery};
+use crate::print::FpMetadataField;
+
+use glib::{
+    error::ErrorDomain,
+    translate::{FromGlib, ToGlibPtr},
+    Quark,
+};
+
+/// Error codes of the retry error domain. A retry error means the scan failed in a way that the user
+/// can fix, e.g. by placing the finger again on the sensor.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FpRetryError {
+    /// The scan did not succeed due to poor scan quality or other general user scanning problem.
+    General = libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_GENERAL as isize,
+    /// The scan did not succeed because the finger swipe was too short.
+    TooShort = libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_TOO_SHORT as isize,
+    /// The scan did not succeed because the finger was not centered on the scanner.
+    CenterFinger = libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_CENTER_FINGER as isize,
+    /// The scan did not succeed due to quality or pressure problems, the user should remove their finger from the scanner before retrying.
+    RemoveFinger = libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_REMOVE_FINGER as isize,
+    /// The scan did not succeed because the finger swipe or touch was too fast.
+    TooFast = libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_TOO_FAST as isize,
+}
+
+impl ErrorDomain for FpRetryError {
+    fn domain() -> Quark {
+        unsafe { Quark::from_glib(libfprint_sys::fp_device_retry_quark()) }
+    }
+
+    fn code(self) -> i32 {
+        self as i32
+    }
+
+    fn from(code: i32) -> Option<Self> {
+        match code as u32 {
+            libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_GENERAL => Some(FpRetryError::General),
+            libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_TOO_SHORT => Some(FpRetryError::TooShort),
+            libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_CENTER_FINGER => {
+                Some(FpRetryError::CenterFinger)
+            }
+            libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_REMOVE_FINGER => {
+                Some(FpRetryError::RemoveFinger)
+            }
+            libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_TOO_FAST => Some(FpRetryError::TooFast),
+            _ => None,
+        }
+    }
+}
+
+impl Display for FpRetryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FpRetryError::General => write!(f, "The scan did not succeed, please try again"),
+            FpRetryError::TooShort => write!(f, "The swipe was too short, please try again"),
+            FpRetryError::CenterFinger => {
+                write!(
+                    f,
+                    "The finger was not centered on the sensor, please try again"
+                )
+            }
+            FpRetryError::RemoveFinger => {
+                write!(f, "Please remove the finger from the sensor and try again")
+            }
+            FpRetryError::TooFast => write!(f, "The finger was moved too fast, please try again"),
+        }
+    }
+}
+
+impl std::error::Error for FpRetryError {}
+
+/// Errors returned by the helpers of this crate that are not plain libfprint operations.
+#[derive(Debug)]
+pub enum FpError {
+    /// An error reported by libfprint.
+    GLib(crate::GError),
+    /// The device is locked by another process, whose PID is given (0 if unknown), see `FpDevice::lock`.
+    DeviceLockedByPid(u32),
+    /// An I/O error, including the errors of the database of `store::SqliteStore`.
+    Io(std::io::Error),
+    /// A required metadata field of a print is not set, see `FpPrint::builder`.
+    MissingMetadata(FpMetadataField),
+    /// A metadata field of a print was rejected, the field is given with the reason, see `FpPrint::set_username`.
+    InvalidMetadata(FpMetadataField, &'static str),
+    /// Serialized print data is malformed, the reason is given.
+    CorruptPrint(&'static str),
+    /// Serialized print data uses a format version this crate does not know, the version is given.
+    UnsupportedFormat(u8),
+    /// A print was enrolled with the driver given first, not with the driver of the device, given second.
+    DriverMismatch(String, String),
+    /// Serialized print data uses the format of libfprint 0.x, see `FpSerializedPrint::upgrade`.
+    LegacyPrint,
+    /// The device did not complete a cancelled operation and was reopened, see `FpWatchdog`. The outcome
+    /// of the recovery is given.
+    DeviceWedged(FpWedgeRecovery),
+    /// libfprint could not be loaded, the reason is given, see `FpContext::try_new`.
+    Unavailable(String),
+    /// Prints of a gallery are not compatible with the device, their positions are given, see
+    /// `FpDevice::identify_checked_sync`.
+    IncompatibleGallery(Vec<usize>),
+}
+
+impl Display for FpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FpError::GLib(err) => write!(f, "{}", err),
+            FpError::DeviceLockedByPid(pid) => {
+                write!(f, "The device is locked by another process (PID {})", pid)
+            }
+            FpError::Io(err) => write!(f, "I/O error: {}", err),
+            FpError::MissingMetadata(field) => write!(f, "The print has no {}", field),
+            FpError::InvalidMetadata(field, reason) => {
+                write!(f, "The {} of the print {}", field, reason)
+            }
+            FpError::CorruptPrint(reason) => write!(f, "Corrupt print data: {}", reason),
+            FpError::UnsupportedFormat(version) => {
+                write!(f, "Unsupported print format version {}", version)
+            }
+            FpError::DriverMismatch(driver, expected) => write!(
+                f,
+                "The print was enrolled with the {} driver, not with {}",
+                driver, expected
+            ),
+            FpError::LegacyPrint => write!(f, "The print uses a legacy libfprint format"),
+            FpError::DeviceWedged(recovery) => match recovery {
+                FpWedgeRecovery::Reopened => {
+                    write!(f, "The device stopped responding and was reopened")
+                }
+                FpWedgeRecovery::CloseFailed(err) => write!(
+                    f,
+                    "The device stopped responding and could not be closed: {}",
+                    err
+                ),
+                FpWedgeRecovery::ReopenFailed(err) => write!(
+                    f,
+                    "The device stopped responding and could not be reopened: {}",
+                    err
+                ),
+            },
+            FpError::Unavailable(reason) => {
+                write!(f, "Fingerprint support is unavailable: {}", reason)
+            }
+            FpError::IncompatibleGallery(prints) => write!(
+                f,
+                "{} prints of the gallery are not compatible with the device",
+                prints.len()
+            ),
+        }
+    }
+}
+
+impl FpError {
+    /// Returns a stable numeric code of the error, for services that log or transport errors across process
+    /// boundaries. The codes never change meaning:
+    ///
+    /// | Code | Error |
+    /// |------|-------|
+    /// | 1 | `GLib` error of another domain |
+    /// | 101 | `DeviceLockedByPid` |
+    /// | 102 | `Io` |
+    /// | 103 | `MissingMetadata` |
+    /// | 104 | `InvalidMetadata` |
+    /// | 105 | `CorruptPrint` |
+    /// | 106 | `UnsupportedFormat` |
+    /// | 107 | `DriverMismatch` |
+    /// | 108 | `LegacyPrint` |
+    /// | 109 | `DeviceWedged` |
+    /// | 110 | `Unavailable` |
+    /// | 111 | `IncompatibleGallery` |
+    /// | 200 + code | `GLib` error of the libfprint `FpDeviceError` domain, e.g. 204 for `FP_DEVICE_ERROR_BUSY` |
+    /// | 300 + code | `GLib` error of the retry domain, see `FpRetryError` |
+    /// | 400 | `GLib` error `gio::IOErrorEnum::Cancelled` |
+    pub fn code(&self) -> u32 {
+        match self {
+            FpError::GLib(err) => glib_code(err),
+            FpError::DeviceLockedByPid(_) => 101,
+            FpError::Io(_) => 102,
+            FpError::MissingMetadata(_) => 103,
+            FpError::InvalidMetadata(_, _) => 104,
+            FpError::CorruptPrint(_) => 105,
+            FpError::UnsupportedFormat(_) => 106,
+            FpError::DriverMismatch(_, _) => 107,
+            FpError::LegacyPrint => 108,
+            FpError::DeviceWedged(_) => 109,
+            FpError::Unavailable(_) => 110,
+            FpError::IncompatibleGallery(_) => 111,
+        }
+    }
+}
+
+/// Returns the code of a GLib error, see `FpError::code`.
+fn glib_code(err: &crate::GError) -> u32 {
+    let raw: *const glib::ffi::GError = err.to_glib_none().0;
+    let code = unsafe { (*raw).code };
+    let device_domain = unsafe { Quark::from_glib(libfprint_sys::fp_device_error_quark()) };
+    match u32::try_from(code) {
+        Ok(code) if code < 100 && err.domain() == device_domain => 200 + code,
+        Ok(code) if code < 100 && err.domain() == FpRetryError::domain() => 300 + code,
+        _ if err.matches(gio::IOErrorEnum::Cancelled) => 400,
+        _ => 1,
+    }
+}
+
+impl std::error::Error for FpError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FpError::GLib(err) => Some(err),
+            FpError::Io(err) => Some(err),
+            FpError::DeviceWedged(
+                FpWedgeRecovery::CloseFailed(err) | FpWedgeRecovery::ReopenFailed(err),
+            ) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// An error with the operation that returned it, so a bare driver error like "transfer failed" can be traced
+/// back to a device and a step in the logs, see `FpResultExt::context`. The operations of `FpDevice` already
+/// return their errors with the context in the message, see `FpContextual::into_error`.
+#[derive(Debug)]
+pub struct FpContextual<E = FpError> {
+    /// The name of the failed operation, e.g. `"enroll"`.
+    pub operation: &'static str,
+    /// The device the operation ran on, if any.
+    pub device: Option<FpDeviceId>,
+    /// The number of enroll stages completed before the failure, if the operation was an enrollment.
+    pub stage: Option<u32>,
+    /// The error returned by the operation.
+    pub error: E,
+}
+
+impl<E> FpContextual<E> {
+    /// Wraps the `error` of `operation`, which ran on `device` if given.
+    pub fn new(operation: &'static str, device: Option<&FpDevice>, error: E) -> Self {
+        FpContextual {
+            operation,
+            device: device.map(FpDevice::id),
+            stage: None,
+            error,
+        }
+    }
+    /// Set the number of enroll stages completed before the failure.
+    pub fn at_stage(mut self, stage: u32) -> Self {
+        self.stage = Some(stage);
+        self
+    }
+    /// Returns the wrapped error, dropping the context.
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+    /// Converts the wrapped error, keeping the context.
+    pub fn map<F>(self, f: impl FnOnce(E) -> F) -> FpContextual<F> {
+        FpContextual {
+            operation: self.operation,
+            device: self.device,
+            stage: self.stage,
+            error: f(self.error),
+        }
+    }
+}
+
+impl FpContextual<crate::GError> {
+    /// Returns the wrapped error with the context prepended to its message. The domain and code are kept, so
+    /// `glib::Error::kind` and `FpError::code` still classify it.
+    pub fn into_error(self) -> crate::GError {
+        let message = self.to_string();
+        unsafe {
+            let raw: *const glib::ffi::GError = self.error.to_glib_none().0;
+            let error = glib::ffi::g_error_new_literal(
+                (*raw).domain,
+                (*raw).code,
+                message.to_glib_none().0,
+            );
+            glib::translate::from_glib_full(error)
+        }
+    }
+}
+
+impl From<FpContextual<crate::GError>> for crate::GError {
+    fn from(contextual: FpContextual<crate::GError>) -> Self {
+        contextual.into_error()
+    }
+}
+
+impl FpContextual<FpError> {
+    /// Returns the stable numeric code of the wrapped error, see `FpError::code`.
+    pub fn code(&self) -> u32 {
+        self.error.code()
+    }
+}
+
+impl<E: Display> Display for FpContextual<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} failed", self.operation)?;
+        if let Some(device) = &self.device {
+            write!(f, " on {} device {}", device.driver, device.device_id)?;
+        }
+        if let Some(stage) = self.stage {
+            write!(f, " after {} enroll stages", stage)?;
+        }
+        write!(f, ": {}", self.error)
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for FpContextual<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Attaches the operation and device to the error of a result, see `FpContextual`.
+pub trait FpResultExt<T, E> {
+    /// Wraps the error of `operation`, which ran on `device`.
+    fn context(self, operation: &'static str, device: &FpDevice) -> Result<T, FpContextual<E>>;
+}
+
+impl<T, E> FpResultExt<T, E> for Result<T, E> {
+    fn context(self, operation: &'static str, device: &FpDevice) -> Result<T, FpContextual<E>> {
+        self.map_err(|error| FpContextual::new(operation, Some(device), error))
+    }
+}
+
+impl From<crate::GError> for FpError {
+    fn from(err: crate::GError) -> Self {
+        FpError::GLib(err)
+    }
+}
+
+impl From<std::io::Error> for FpError {
+    fn from(err: std::io::Error) -> Self {
+        FpError::Io(err)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for FpError {
+    fn from(err: rusqlite::Error) -> Self {
+        FpError::Io(std::io::Error::other(err))
+    }
+}
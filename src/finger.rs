@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
 /// Enum representing a finger. This can be used to specify which finger was used to enroll a new print and can be added as part of the
 /// print metadata.
@@ -17,6 +17,33 @@ pub enum FpFinger {
     RightLittle = libfprint_sys::FpFinger_FP_FINGER_RIGHT_LITTLE as isize,
 }
 
+impl FpFinger {
+    /// All the possible fingers, in the same order as their libfprint numeric values.
+    pub const ALL: [FpFinger; 11] = [
+        FpFinger::Unknown,
+        FpFinger::LeftThumb,
+        FpFinger::LeftIndex,
+        FpFinger::LeftMiddle,
+        FpFinger::LeftRing,
+        FpFinger::LeftLittle,
+        FpFinger::RightThumb,
+        FpFinger::RightIndex,
+        FpFinger::RightMiddle,
+        FpFinger::RightRing,
+        FpFinger::RightLittle,
+    ];
+
+    /// Returns an iterator over all the possible fingers, see `FpFinger::ALL`.
+    pub fn iter() -> impl Iterator<Item = FpFinger> {
+        FpFinger::ALL.into_iter()
+    }
+
+    /// Returns the stable numeric value of the finger, as used by libfprint.
+    pub fn as_u32(self) -> u32 {
+        self as u32
+    }
+}
+
 impl Display for FpFinger {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -41,6 +68,17 @@ impl From<FpFinger> for i32 {
     }
 }
 
+impl From<FpFinger> for u32 {
+    fn from(finger: FpFinger) -> Self {
+        finger as u32
+    }
+}
+
+/// Converts a libfprint finger value.
+///
+/// # Panics
+/// Panics for values that do not name a finger, e.g. read from storage or from a newer libfprint. Use
+/// `FpFinger::try_from` for those, the crate itself only converts with it.
 impl From<u32> for FpFinger {
     fn from(value: u32) -> Self {
         match value {
@@ -84,3 +122,52 @@ impl TryFrom<i32> for FpFinger {
         }
     }
 }
+
+impl FromStr for FpFinger {
+    type Err = ();
+
+    /// Parses a finger from its name. The parsing is case insensitive and accepts spaces, dashes
+    /// or underscores as separators, so `"Left Index"`, `"left-index"` and `"LEFT_INDEX"` are all valid.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name: String = s
+            .trim()
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .flat_map(char::to_lowercase)
+            .collect();
+
+        FpFinger::iter()
+            .find(|finger| {
+                let other: String = finger
+                    .to_string()
+                    .chars()
+                    .filter(|c| *c != ' ')
+                    .flat_map(char::to_lowercase)
+                    .collect();
+                other == name
+            })
+            .ok_or(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FpFinger;
+
+    #[test]
+    fn display_round_trip() {
+        for finger in FpFinger::iter() {
+            assert_eq!(finger.to_string().parse::<FpFinger>(), Ok(finger));
+            assert_eq!(FpFinger::from(finger.as_u32()), finger);
+            assert_eq!(FpFinger::try_from(finger as i32), Ok(finger));
+        }
+        assert!(FpFinger::try_from(11).is_err());
+        assert!(FpFinger::try_from(-1).is_err());
+        assert_eq!("left_index".parse::<FpFinger>(), Ok(FpFinger::LeftIndex));
+        assert_eq!(
+            "RIGHT-LITTLE".parse::<FpFinger>(),
+            Ok(FpFinger::RightLittle)
+        );
+        assert!("left toe".parse::<FpFinger>().is_err());
+    }
+}
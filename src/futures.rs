@@ -0,0 +1,139 @@
+//! The asynchronous API, enabled with the `futures` feature.
+//!
+//! `futures::FpDevice` wraps a device and only exposes its operations returning futures, under the same names
+//! as the operations of `blocking::FpDevice` (e.g. `open`, `enroll`, `verify`). Dropping a future before it
+//! completes cancels its operation. The operations are driven by the GLib main context of the calling thread,
+//! so the futures must be polled from a thread owning its thread default `glib::MainContext`, for example:
+//! ```no_run
+//! use libfprint_rs::{futures, FpContext};
+//!
+//! let ctx = FpContext::new();
+//! let dev = futures::FpDevice::from(ctx.devices().remove(0));
+//!
+//! glib::MainContext::default().block_on(async {
+//!     dev.open().await.unwrap();
+//!     let image = dev.capture(true).await.unwrap();
+//!     dev.close().await.unwrap();
+//! });
+//! ```
+use crate::{image::FpImage, print::FpPrint};
+
+pub use crate::context::{FpDeviceEvent, FpDeviceEvents};
+pub use crate::device::{
+    FpEnrollProgress, FpEnrollStage, FpFuture, FpIdentifyResult, FpMatchCb, FpMatchOutcome,
+    FpPreviewFrame,
+};
+pub use crate::manager::{
+    FpDeviceManager, FpForgetReport, FpManagerEvent, FpOperation, FpOperationResult,
+    FpSelectionPreference,
+};
+
+/// A device exposing the operations returning futures only, see the module documentation. The other methods
+/// of the device, e.g. its name or features, are reached through `FpDevice::device`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FpDevice {
+    device: crate::FpDevice,
+}
+
+impl From<crate::FpDevice> for FpDevice {
+    fn from(device: crate::FpDevice) -> Self {
+        Self { device }
+    }
+}
+
+impl AsRef<crate::FpDevice> for FpDevice {
+    fn as_ref(&self) -> &crate::FpDevice {
+        &self.device
+    }
+}
+
+impl FpDevice {
+    /// The wrapped device.
+    pub fn device(&self) -> &crate::FpDevice {
+        &self.device
+    }
+    /// Returns the wrapped device.
+    pub fn into_device(self) -> crate::FpDevice {
+        self.device
+    }
+
+    /// Open the device. See `FpDevice::open_future`.
+    pub fn open(&self) -> FpFuture<()> {
+        self.device.open_future()
+    }
+    /// Close the device. See `FpDevice::close_future`.
+    pub fn close(&self) -> FpFuture<()> {
+        self.device.close_future()
+    }
+    #[cfg(libfprint_1_94)]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    /// Prepare the device for suspend. See `FpDevice::suspend_future`.
+    pub fn suspend(&self) -> FpFuture<()> {
+        self.device.suspend_future()
+    }
+    #[cfg(libfprint_1_94)]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    /// Resume the device after suspend. See `FpDevice::resume_future`.
+    pub fn resume(&self) -> FpFuture<()> {
+        self.device.resume_future()
+    }
+    /// Enroll a new print. See `FpDevice::enroll_future`.
+    pub fn enroll<T: 'static>(
+        &self,
+        template: FpPrint,
+        progress_cb: Option<FpEnrollProgress<T>>,
+        progress_data: Option<T>,
+    ) -> FpFuture<FpPrint> {
+        self.device
+            .enroll_future(template, progress_cb, progress_data)
+    }
+    /// Enroll a new print, with a stream of the enrollment stages. See `FpDevice::enroll_async`.
+    pub fn enroll_stages(
+        &self,
+        template: FpPrint,
+    ) -> (
+        FpFuture<FpPrint>,
+        impl futures_core::Stream<Item = FpEnrollStage> + Unpin + 'static,
+    ) {
+        self.device.enroll_async(template)
+    }
+    /// Enroll a new print, with a stream of previews of the scans. See `FpDevice::enroll_preview`.
+    pub fn enroll_preview(
+        &self,
+        template: FpPrint,
+        max_width: u32,
+        max_height: u32,
+    ) -> (
+        FpFuture<FpPrint>,
+        impl futures_core::Stream<Item = FpPreviewFrame> + Unpin + 'static,
+    ) {
+        self.device.enroll_preview(template, max_width, max_height)
+    }
+    /// Verify a given print. See `FpDevice::verify_future`.
+    pub fn verify<T: 'static>(
+        &self,
+        enrolled_print: &FpPrint,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+    ) -> FpFuture<FpMatchOutcome> {
+        self.device
+            .verify_future(enrolled_print, match_cb, match_data)
+    }
+    /// Identify a print among a gallery. See `FpDevice::identify_future`.
+    pub fn identify<'a, T: 'static, I, G>(
+        &self,
+        prints: I,
+        match_cb: Option<FpMatchCb<T>>,
+        match_data: Option<T>,
+    ) -> FpFuture<FpIdentifyResult>
+    where
+        I: IntoIterator<Item = &'a G>,
+        G: AsRef<FpPrint> + 'a,
+    {
+        self.device.identify_future(prints, match_cb, match_data)
+    }
+    /// Capture an image. See `FpDevice::capture_future`.
+    pub fn capture(&self, wait_for_finger: bool) -> FpFuture<FpImage> {
+        self.device.capture_future(wait_for_finger)
+    }
+}
@@ -0,0 +1,426 @@
+use std::{
+    mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+};
+
+use gio::{prelude::CancellableExt, Cancellable};
+
+use crate::{
+    context::FpContext,
+    device::{wait, FpDevice, FpEnrollProgress, FpIdentifyResult, FpMatchOutcome},
+    error::FpError,
+    finger::FpFinger,
+    operation::{self, FpOperationHandle},
+    print::FpPrint,
+};
+
+/// A command run by the worker of an `FpDeviceHandle`, see `FpDeviceHandle::send`.
+#[derive(Debug)]
+pub enum FpDeviceCommand {
+    /// Enroll a new print of `finger` for `username`, answered by `FpHandleEvent::EnrollProgress` events and
+    /// an `FpHandleEvent::Enrolled` event.
+    Enroll {
+        /// The username of the new print.
+        username: String,
+        /// The finger of the new print.
+        finger: FpFinger,
+    },
+    /// Verify the scanned finger against a print, answered by an `FpHandleEvent::Verified` event.
+    Verify(FpPrint),
+    /// Identify the scanned finger among prints, answered by an `FpHandleEvent::Identified` event.
+    Identify(Vec<FpPrint>),
+    /// Cancel the running operation, if any. The commands queued behind it still run.
+    Cancel,
+}
+
+/// An event sent by the worker of an `FpDeviceHandle`.
+#[derive(Debug)]
+pub enum FpHandleEvent {
+    /// The worker found and opened the device, or failed to.
+    Opened(Result<(), FpError>),
+    /// A stage of an enrollment completed, or its scan failed with the given error and is retried.
+    EnrollProgress {
+        /// The number of completed stages.
+        completed_stages: i32,
+        /// The error of a retried scan, if any.
+        error: Option<crate::GError>,
+    },
+    /// An enrollment completed.
+    Enrolled(Result<FpPrint, FpError>),
+    /// A verification completed.
+    Verified(Result<FpMatchOutcome, FpError>),
+    /// An identification completed, with the matching print, if any.
+    Identified(Result<FpIdentifyResult, FpError>),
+}
+
+#[cfg(not(doctest))]
+/// Runs the operations of a device on a worker thread, which owns the device and its main context.
+///
+/// libfprint dispatches the events of a device on the main context of the thread that enumerated it, so
+/// running its operations from other threads, e.g. the threads of a service, stalls or fails. The worker
+/// creates its own `FpContext`, picks the device with the given selector and opens it, then runs the commands
+/// sent to the handle one after the other, sending back their events. The operations started with
+/// `FpDeviceHandle::enroll`, `FpDeviceHandle::verify` or `FpDeviceHandle::identify` report to the returned
+/// `FpOperationHandle` instead. When the handle is dropped, the
+/// running operation is cancelled, the queued commands are discarded and the device is closed.
+///
+/// The worker drives the device with the asynchronous API of `FpDevice` and iterates its own main context,
+/// the thread default main context of the worker thread. It does not run the `_sync` operations: libfprint
+/// waits for them by iterating the global default main context, which the application may be running on
+/// another thread.
+/// # Example:
+/// ```no_run
+/// use libfprint_rs::{FpDeviceCommand, FpDeviceHandle, FpFinger, FpHandleEvent};
+///
+/// let (handle, events) = FpDeviceHandle::spawn(|dev| dev.persistent_id() == "goodixmoc@1-3.2");
+/// handle
+///     .send(FpDeviceCommand::Enroll {
+///         username: "bruce".to_string(),
+///         finger: FpFinger::RightIndex,
+///     })
+///     .unwrap();
+///
+/// for event in events {
+///     match event {
+///         FpHandleEvent::EnrollProgress { completed_stages, .. } => println!("{} stages", completed_stages),
+///         FpHandleEvent::Enrolled(_) => break,
+///         _ => {}
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct FpDeviceHandle {
+    commands: Option<mpsc::Sender<Job>>,
+    running: Arc<Mutex<Option<Cancellable>>>,
+    stopped: Arc<AtomicBool>,
+    worker: Option<thread::JoinHandle<()>>,
+}
+
+impl FpDeviceHandle {
+    /// Starts a worker for the first device accepted by `select`, returning the handle with the receiver of
+    /// the events. The first event is `FpHandleEvent::Opened`; if the device could not be found or opened,
+    /// the worker stops after sending it.
+    pub fn spawn(
+        select: impl Fn(&FpDevice) -> bool + Send + 'static,
+    ) -> (Self, mpsc::Receiver<FpHandleEvent>) {
+        let (commands, command_rx) = mpsc::channel();
+        let (event_tx, events) = mpsc::channel();
+        let running = Arc::new(Mutex::new(None));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let worker = {
+            let running = running.clone();
+            let stopped = stopped.clone();
+            thread::spawn(move || {
+                let main_context = glib::MainContext::new();
+                // Only fails if another context is the thread default, the thread is new
+                let _ = main_context.with_thread_default(|| {
+                    run_worker(
+                        &main_context,
+                        select,
+                        &command_rx,
+                        &event_tx,
+                        &running,
+                        &stopped,
+                    )
+                });
+            })
+        };
+        let handle = FpDeviceHandle {
+            commands: Some(commands),
+            running,
+            stopped,
+            worker: Some(worker),
+        };
+        (handle, events)
+    }
+
+    /// Send `command` to the worker. `FpDeviceCommand::Cancel` takes effect right away, the other commands
+    /// are queued. Fails if the worker stopped, e.g. the device could not be opened.
+    pub fn send(&self, command: FpDeviceCommand) -> Result<(), mpsc::SendError<FpDeviceCommand>> {
+        match command {
+            FpDeviceCommand::Cancel => match self.commands.is_some() {
+                true => {
+                    self.cancel();
+                    Ok(())
+                }
+                false => Err(mpsc::SendError(command)),
+            },
+            command => self
+                .queue(Job::Command(command))
+                .map_err(|mut job| match &mut job {
+                    Job::Command(command) => {
+                        mpsc::SendError(mem::replace(command, FpDeviceCommand::Cancel))
+                    }
+                    _ => unreachable!("a command was queued"),
+                }),
+        }
+    }
+    /// Queue the enrollment of a new print of `finger` for `username`, like `FpDeviceCommand::Enroll`,
+    /// returning its handle. Fails if the worker stopped.
+    pub fn enroll(
+        &self,
+        username: &str,
+        finger: FpFinger,
+    ) -> Result<FpOperationHandle<FpPrint>, FpError> {
+        let handle = FpOperationHandle::new(None);
+        self.queue(Job::Enroll(username.to_string(), finger, handle.clone()))
+            .map_err(|_| stopped())?;
+        Ok(handle)
+    }
+    /// Queue the verification of the scanned finger against `print`, like `FpDeviceCommand::Verify`,
+    /// returning its handle. Fails if the worker stopped.
+    pub fn verify(&self, print: FpPrint) -> Result<FpOperationHandle<FpMatchOutcome>, FpError> {
+        let handle = FpOperationHandle::new(None);
+        self.queue(Job::Verify(print, handle.clone()))
+            .map_err(|_| stopped())?;
+        Ok(handle)
+    }
+    /// Queue the identification of the scanned finger among `prints`, like `FpDeviceCommand::Identify`,
+    /// returning its handle. Fails if the worker stopped.
+    pub fn identify(
+        &self,
+        prints: Vec<FpPrint>,
+    ) -> Result<FpOperationHandle<FpIdentifyResult>, FpError> {
+        let handle = FpOperationHandle::new(None);
+        self.queue(Job::Identify(prints, handle.clone()))
+            .map_err(|_| stopped())?;
+        Ok(handle)
+    }
+    /// Queue `job`, returning it back if the worker stopped.
+    fn queue(&self, job: Job) -> Result<(), Job> {
+        match &self.commands {
+            Some(commands) => commands.send(job).map_err(|err| err.0),
+            None => Err(job),
+        }
+    }
+    /// Cancel the running operation, if any, like sending `FpDeviceCommand::Cancel`.
+    pub fn cancel(&self) {
+        if let Some(cancellable) = self.running.lock().unwrap().as_ref() {
+            cancellable.cancel();
+        }
+    }
+}
+
+impl Drop for FpDeviceHandle {
+    fn drop(&mut self) {
+        // The worker stops once the running operation returned, the queued commands are skipped. The flag is
+        // set under the lock so the worker can not start an operation that is not cancelled
+        {
+            let running = self.running.lock().unwrap();
+            self.stopped.store(true, Ordering::SeqCst);
+            if let Some(cancellable) = running.as_ref() {
+                cancellable.cancel();
+            }
+        }
+        self.commands.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// The error of the operations queued after the worker stopped.
+fn stopped() -> FpError {
+    glib::Error::new(gio::IOErrorEnum::Closed, "The worker of the device stopped").into()
+}
+
+/// A command queued for the worker, with the handle of its operation if it was started with one.
+enum Job {
+    Command(FpDeviceCommand),
+    Enroll(String, FpFinger, FpOperationHandle<FpPrint>),
+    Verify(FpPrint, FpOperationHandle<FpMatchOutcome>),
+    Identify(Vec<FpPrint>, FpOperationHandle<FpIdentifyResult>),
+}
+
+impl Job {
+    /// The cancellable of the operation, cancelled by the handle of the operation or of the device.
+    fn cancellable(&self) -> Cancellable {
+        match self {
+            Job::Command(_) => Cancellable::new(),
+            Job::Enroll(_, _, handle) => handle.cancellable().clone(),
+            Job::Verify(_, handle) => handle.cancellable().clone(),
+            Job::Identify(_, handle) => handle.cancellable().clone(),
+        }
+    }
+}
+
+impl Drop for Job {
+    /// The jobs discarded by the worker, e.g. when the device handle is dropped, finish their handle.
+    fn drop(&mut self) {
+        match self {
+            Job::Command(_) => {}
+            Job::Enroll(_, _, handle) => handle.abandon(),
+            Job::Verify(_, handle) => handle.abandon(),
+            Job::Identify(_, handle) => handle.abandon(),
+        }
+    }
+}
+
+/// The loop of the worker of an `FpDeviceHandle`, on `main_context`, the thread default main context of
+/// the worker.
+fn run_worker(
+    main_context: &glib::MainContext,
+    select: impl Fn(&FpDevice) -> bool,
+    commands: &mpsc::Receiver<Job>,
+    events: &mpsc::Sender<FpHandleEvent>,
+    running: &Mutex<Option<Cancellable>>,
+    stopped: &AtomicBool,
+) {
+    let context = FpContext::new();
+    let device = match context.devices().into_iter().find(|device| select(device)) {
+        Some(device) => device,
+        None => {
+            let err = glib::Error::new(gio::IOErrorEnum::NotFound, "No device was selected");
+            let _ = events.send(FpHandleEvent::Opened(Err(err.into())));
+            return;
+        }
+    };
+    if let Err(err) = wait(main_context, |done| device.open(None, done)) {
+        let _ = events.send(FpHandleEvent::Opened(Err(err.into())));
+        return;
+    }
+    let _ = events.send(FpHandleEvent::Opened(Ok(())));
+
+    for job in commands {
+        let cancellable = job.cancellable();
+        {
+            let mut running = running.lock().unwrap();
+            if stopped.load(Ordering::SeqCst) {
+                break;
+            }
+            running.replace(cancellable.clone());
+        }
+        let event = run_job(main_context, &device, &job, &cancellable, events);
+        running.lock().unwrap().take();
+        if let Some(event) = event {
+            // The receiver may be gone, the commands still run until the handle is dropped
+            let _ = events.send(event);
+        }
+    }
+    let _ = wait(main_context, |done| device.close(None, done));
+}
+
+/// Run `job` on `device`, returning the event of its completion if it has no handle.
+fn run_job(
+    main_context: &glib::MainContext,
+    device: &FpDevice,
+    job: &Job,
+    cancellable: &Cancellable,
+    events: &mpsc::Sender<FpHandleEvent>,
+) -> Option<FpHandleEvent> {
+    match job {
+        Job::Command(FpDeviceCommand::Enroll { username, finger }) => {
+            let enrolled = enroll(
+                main_context,
+                device,
+                username,
+                *finger,
+                cancellable,
+                enroll_progress,
+                events.clone(),
+            );
+            Some(FpHandleEvent::Enrolled(enrolled))
+        }
+        Job::Command(FpDeviceCommand::Verify(print)) => {
+            let outcome = wait(main_context, |done| {
+                device.verify(print, Some(cancellable), None, None::<()>, done)
+            });
+            Some(FpHandleEvent::Verified(outcome.map_err(FpError::from)))
+        }
+        Job::Command(FpDeviceCommand::Identify(prints)) => {
+            let matched = wait(main_context, |done| {
+                device.identify(prints, Some(cancellable), None, None::<()>, done)
+            });
+            Some(FpHandleEvent::Identified(matched.map_err(FpError::from)))
+        }
+        // Cancelling is done by the handle, there is nothing to run
+        Job::Command(FpDeviceCommand::Cancel) => None,
+        Job::Enroll(username, finger, handle) => {
+            let progress = operation::enroll_progress::<FpPrint>;
+            let enrolled = enroll(
+                main_context,
+                device,
+                username,
+                *finger,
+                cancellable,
+                progress,
+                handle.clone(),
+            );
+            handle.finish(enrolled);
+            None
+        }
+        Job::Verify(print, handle) => {
+            let progress = operation::match_progress::<FpMatchOutcome>;
+            let outcome = wait(main_context, |done| {
+                device.verify(
+                    print,
+                    Some(cancellable),
+                    Some(progress),
+                    Some(handle.clone()),
+                    done,
+                )
+            });
+            handle.finish(outcome.map_err(FpError::from));
+            None
+        }
+        Job::Identify(prints, handle) => {
+            let progress = operation::match_progress::<FpIdentifyResult>;
+            let matched = wait(main_context, |done| {
+                device.identify(
+                    prints,
+                    Some(cancellable),
+                    Some(progress),
+                    Some(handle.clone()),
+                    done,
+                )
+            });
+            handle.finish(matched.map_err(FpError::from));
+            None
+        }
+    }
+}
+
+/// Enroll a new print of `finger` for `username` on `device`.
+fn enroll<T: 'static>(
+    main_context: &glib::MainContext,
+    device: &FpDevice,
+    username: &str,
+    finger: FpFinger,
+    cancellable: &Cancellable,
+    progress_cb: FpEnrollProgress<T>,
+    progress_data: T,
+) -> Result<FpPrint, FpError> {
+    let template = FpPrint::new(device);
+    template.set_finger(finger);
+    template.set_username(username)?;
+    let enrolled = wait(main_context, |done| {
+        device.enroll(
+            template,
+            Some(cancellable),
+            Some(progress_cb),
+            Some(progress_data),
+            done,
+        )
+    })?;
+    Ok(enrolled)
+}
+
+fn enroll_progress(
+    _device: &FpDevice,
+    completed_stages: i32,
+    _print: Option<FpPrint>,
+    error: Option<crate::GError>,
+    events: &Option<mpsc::Sender<FpHandleEvent>>,
+) {
+    if let Some(events) = events {
+        let _ = events.send(FpHandleEvent::EnrollProgress {
+            completed_stages,
+            error,
+        });
+    }
+}
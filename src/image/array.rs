@@ -0,0 +1,18 @@
+use ndarray::{Array2, ArrayView2};
+
+use super::FpImage;
+
+impl FpImage {
+    /// Returns the greyscale data of the image as an array of `height` rows and `width` columns.
+    pub fn to_array2(&self) -> Array2<u8> {
+        let shape = (self.height() as usize, self.width() as usize);
+        Array2::from_shape_vec(shape, self.data()).expect("The data matches the size of the image")
+    }
+    /// Creates an image from an array of greyscale pixels, of `height` rows and `width` columns. The array does
+    /// not need to be contiguous. See `FpImage::from_data`.
+    pub fn from_array2(array: ArrayView2<u8>) -> Self {
+        let (height, width) = array.dim();
+        let data: Vec<u8> = array.iter().copied().collect();
+        FpImage::from_data(width as u32, height as u32, &data)
+    }
+}
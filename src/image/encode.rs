@@ -0,0 +1,71 @@
+use super::FpImage;
+use crate::error::FpError;
+
+impl FpImage {
+    #[cfg(not(doctest))]
+    /// Encodes the image as a `data:image/png;base64,` URI, e.g. to embed a capture in the page of a local
+    /// admin panel without a temporary file (requires the `png` feature).
+    /// # Example:
+    /// ```no_run
+    /// let image = device.capture_sync(true, None).unwrap();
+    /// let html = format!(r#"<img src="{}" alt="Capture">"#, image.to_data_uri().unwrap());
+    /// ```
+    pub fn to_data_uri(&self) -> Result<String, FpError> {
+        let png = encode_png(
+            self.width(),
+            self.height(),
+            png::ColorType::Grayscale,
+            &self.data(),
+        )?;
+        Ok(format!("data:image/png;base64,{}", base64(&png)))
+    }
+}
+
+/// Encodes 8 bit `data` as a PNG image.
+pub(super) fn encode_png(
+    width: u32,
+    height: u32,
+    color: png::ColorType,
+    data: &[u8],
+) -> Result<Vec<u8>, FpError> {
+    let to_error = |err: png::EncodingError| std::io::Error::other(err);
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width, height);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(to_error)?;
+    writer.write_image_data(data).map_err(to_error)?;
+    writer.finish().map_err(to_error)?;
+    Ok(out)
+}
+
+/// Standard base64 with padding, for data URIs.
+pub(super) fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let triple = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(ALPHABET[(triple >> (18 - 6 * i) & 0x3f) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn base64() {
+        assert_eq!(super::base64(b"Man"), "TWFu");
+        assert_eq!(super::base64(b"Ma"), "TWE=");
+        assert_eq!(super::base64(b"M"), "TQ==");
+    }
+}
@@ -0,0 +1,119 @@
+use std::os::raw::c_uint;
+
+use glib::translate::ToGlibPtr;
+
+use super::FpImage;
+
+/// How the data of an image differs from the standard orientation, as reported by its driver, see
+/// `FpImage::flags`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FpImageFlags(u32);
+
+impl FpImageFlags {
+    /// The image is upside down.
+    pub const V_FLIPPED: Self = Self(1 << 0);
+    /// The image is mirrored left to right.
+    pub const H_FLIPPED: Self = Self(1 << 1);
+    /// The ridges are light on a dark background.
+    pub const COLORS_INVERTED: Self = Self(1 << 2);
+    /// The image only covers part of the finger, e.g. a swipe that was too short.
+    pub const PARTIAL: Self = Self(1 << 3);
+
+    /// Returns the raw flags, the values of libfprint's internal `FpiImageFlags`.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+    /// Whether every flag of `other` is set.
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+    /// Whether no flag is set: the image is in the standard orientation.
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// The beginning of libfprint's `struct _FpImage`, which `fpi-image.h` marks public for the drivers. The
+/// layout has not changed since libfprint 1.90.
+#[repr(C)]
+struct RawImage {
+    parent: glib::gobject_ffi::GObject,
+    width: c_uint,
+    height: c_uint,
+    ppmm: f64,
+    flags: c_uint,
+}
+
+impl FpImage {
+    /// Returns how the image differs from the standard orientation, e.g. to normalize it before host side
+    /// matching or an export, see `FpImage::standardized`.
+    ///
+    /// libfprint does not export a getter for the flags, they are read from the instance structure. `None` is
+    /// returned if that structure does not have the expected layout, i.e. with an unknown libfprint version.
+    pub fn flags(&self) -> Option<FpImageFlags> {
+        let ptr: *mut libfprint_sys::FpImage = self.to_glib_none().0;
+        let raw = unsafe { &*(ptr as *const RawImage) };
+        match (raw.width, raw.height) == (self.width(), self.height()) && raw.ppmm == self.ppmm() {
+            true => Some(FpImageFlags(raw.flags)),
+            false => None,
+        }
+    }
+
+    /// Returns a new image in the standard orientation: flipped back and with dark ridges on a light
+    /// background. Images whose flags can not be read are copied.
+    pub fn standardized(&self) -> FpImage {
+        let flags = self.flags().unwrap_or_default();
+        let (width, height) = (self.width() as usize, self.height() as usize);
+        FpImage::from_data(
+            width as u32,
+            height as u32,
+            &standardize(&self.data(), width, height, flags),
+        )
+    }
+}
+
+fn standardize(data: &[u8], width: usize, height: usize, flags: FpImageFlags) -> Vec<u8> {
+    let mut out = data.to_vec();
+    if flags.contains(FpImageFlags::H_FLIPPED) {
+        out.chunks_exact_mut(width.max(1)).for_each(<[u8]>::reverse);
+    }
+    if flags.contains(FpImageFlags::V_FLIPPED) {
+        out = out
+            .chunks_exact(width.max(1))
+            .take(height)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+    }
+    if flags.contains(FpImageFlags::COLORS_INVERTED) {
+        out.iter_mut().for_each(|pixel| *pixel = 255 - *pixel);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{standardize, FpImageFlags};
+
+    #[test]
+    fn standardization() {
+        // 1 2 3
+        // 4 5 6
+        let data = [1, 2, 3, 4, 5, 6];
+        assert_eq!(
+            standardize(&data, 3, 2, FpImageFlags::H_FLIPPED),
+            vec![3, 2, 1, 6, 5, 4]
+        );
+        assert_eq!(
+            standardize(&data, 3, 2, FpImageFlags::V_FLIPPED),
+            vec![4, 5, 6, 1, 2, 3]
+        );
+        assert_eq!(
+            standardize(&data, 3, 2, FpImageFlags::COLORS_INVERTED),
+            vec![254, 253, 252, 251, 250, 249]
+        );
+        assert_eq!(standardize(&data, 3, 2, FpImageFlags::PARTIAL), data);
+        assert!(FpImageFlags::default().is_empty());
+    }
+}
@@ -0,0 +1,89 @@
+use crate::error::FpError;
+
+/// A minutia detected on an image, its coordinates are in pixels from the top left corner.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FpMinutia {
+    /// The horizontal coordinate.
+    pub x: i32,
+    /// The vertical coordinate.
+    pub y: i32,
+}
+
+/// The minutiae detected on an image, see `FpImage::minutiae`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FpMinutiae {
+    /// The detected minutiae.
+    pub minutiae: Vec<FpMinutia>,
+    /// The resolution of the image, in pixels per millimeter.
+    pub ppmm: f64,
+}
+
+/// Magic bytes starting every serialized `FpMinutiae`.
+const MINUTIAE_MAGIC: &[u8; 4] = b"FPMN";
+/// Current version of the serialized `FpMinutiae` format.
+const MINUTIAE_VERSION: u8 = 1;
+
+impl FpMinutiae {
+    /// Serialize the minutiae, so they can be stored or transmitted without the image. The format is the `FPMN`
+    /// magic, a version byte, the resolution as a `f64`, the number of minutiae as a `u32` and the coordinates of
+    /// each minutia as two `i32`, all little endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(17 + 8 * self.minutiae.len());
+        out.extend_from_slice(MINUTIAE_MAGIC);
+        out.push(MINUTIAE_VERSION);
+        out.extend_from_slice(&self.ppmm.to_le_bytes());
+        out.extend_from_slice(&(self.minutiae.len() as u32).to_le_bytes());
+        for minutia in &self.minutiae {
+            out.extend_from_slice(&minutia.x.to_le_bytes());
+            out.extend_from_slice(&minutia.y.to_le_bytes());
+        }
+        out
+    }
+    /// Deserialize minutiae written by `FpMinutiae::to_bytes`.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, FpError> {
+        let data = data
+            .strip_prefix(MINUTIAE_MAGIC)
+            .ok_or(FpError::CorruptPrint("missing minutiae magic"))?;
+        let (&version, data) = data
+            .split_first()
+            .ok_or(FpError::CorruptPrint("truncated minutiae"))?;
+        if version != MINUTIAE_VERSION {
+            return Err(FpError::UnsupportedFormat(version));
+        }
+        if data.len() < 12 {
+            return Err(FpError::CorruptPrint("truncated minutiae"));
+        }
+        let (ppmm, data) = data.split_at(8);
+        let (count, data) = data.split_at(4);
+        let ppmm = f64::from_le_bytes(ppmm.try_into().unwrap());
+        let count = u32::from_le_bytes(count.try_into().unwrap()) as usize;
+        if data.len() != count.saturating_mul(8) {
+            return Err(FpError::CorruptPrint("truncated minutiae"));
+        }
+
+        let minutiae = data
+            .chunks_exact(8)
+            .map(|chunk| FpMinutia {
+                x: i32::from_le_bytes(chunk[..4].try_into().unwrap()),
+                y: i32::from_le_bytes(chunk[4..].try_into().unwrap()),
+            })
+            .collect();
+        Ok(FpMinutiae { minutiae, ppmm })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FpMinutia, FpMinutiae};
+
+    #[test]
+    fn minutiae_round_trip() {
+        let minutiae = FpMinutiae {
+            minutiae: vec![FpMinutia { x: 12, y: 40 }, FpMinutia { x: -3, y: 7 }],
+            ppmm: 19.685,
+        };
+        let bytes = minutiae.to_bytes();
+        assert_eq!(FpMinutiae::from_bytes(&bytes).unwrap(), minutiae);
+        assert!(FpMinutiae::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
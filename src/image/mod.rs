@@ -0,0 +1,218 @@
+// All methods are declared
+#[cfg(feature = "ndarray")]
+mod array;
+#[cfg(feature = "png")]
+mod encode;
+mod flags;
+mod minutiae;
+mod overlay;
+mod processing;
+
+use std::{cell::RefCell, os::raw::c_void, rc::Rc};
+
+use gio::Cancellable;
+use glib::{
+    translate::{FromGlibContainer, FromGlibPtrFull, ToGlibPtr},
+    wrapper,
+};
+
+pub use flags::FpImageFlags;
+pub use minutiae::{FpMinutia, FpMinutiae};
+pub use processing::FpRotation;
+
+wrapper! {
+#[cfg(not(doctest))]
+/// Struct representing an image of a fingerprint. Not all devices support this feature.
+/// # Examples:
+/// ```rust
+/// use libfprint_rs::FpContext;
+/// use std::fs::File;
+/// use std::io::Write;
+///
+/// let context = FpContext::new();
+/// let devices = context.devices();
+/// let device = devices.get(0).unwrap();
+///
+/// device.open_sync(None).unwrap();
+/// let image = device.capture_sync(true, None).unwrap();
+/// let data = image.data();
+///
+/// let mut file = File::create("image.pgm").unwrap();
+/// let header = format!("P5\n{} {}\n255\n", image.width(), image.height());
+/// file.write_all(header.as_bytes()).unwrap();
+/// file.write_all(data.as_slice()).unwrap();
+/// ```
+    pub struct FpImage(Object<libfprint_sys::FpImage, libfprint_sys::FpImageClass>);
+
+    match fn {
+        type_ => || libfprint_sys::fp_image_get_type() as usize,
+    }
+}
+
+unsafe extern "C" fn detect_minutiae_trampoline<P: FnOnce(Result<(), crate::GError>) + 'static>(
+    source: *mut libfprint_sys::GObject,
+    res: *mut libfprint_sys::GAsyncResult,
+    user_data: *mut c_void,
+) {
+    let callback: Box<P> = Box::from_raw(user_data.cast());
+    let mut error = std::ptr::null_mut();
+
+    let ok = libfprint_sys::fp_image_detect_minutiae_finish(
+        source.cast(),
+        res,
+        std::ptr::addr_of_mut!(error),
+    );
+    if ok == glib::ffi::GFALSE {
+        callback(Err(glib::Error::from_glib_full(error.cast())));
+    } else {
+        callback(Ok(()));
+    }
+}
+
+impl FpImage {
+    pub fn new(width: u32, height: u32) -> Self {
+        unsafe { FpImage::from_glib_full(libfprint_sys::fp_image_new(width as i32, height as i32)) }
+    }
+
+    /// Gets the pixel width of an image.
+    pub fn width(&self) -> u32 {
+        unsafe { libfprint_sys::fp_image_get_width(self.to_glib_none().0) as u32 }
+    }
+
+    /// Gets the pixel height of an image.
+    pub fn height(&self) -> u32 {
+        unsafe { libfprint_sys::fp_image_get_height(self.to_glib_none().0) as u32 }
+    }
+
+    /// Gets the resolution of the image. Note that this is assumed to be fixed to 500 points per inch (~19.685 p/mm) for most drivers.
+    pub fn ppmm(&self) -> f64 {
+        unsafe { libfprint_sys::fp_image_get_ppmm(self.to_glib_none().0) }
+    }
+
+    /// Gets the minutiae of the image, detected by `FpImage::detect_minutiae`. The minutiae are empty if they
+    /// were not detected yet.
+    pub fn minutiae(&self) -> FpMinutiae {
+        let minutiae = unsafe {
+            let array = libfprint_sys::fp_image_get_minutiae(self.to_glib_none().0);
+            crate::ptr_array::as_slice(array.cast())
+                .iter()
+                .map(|&minutia| {
+                    let (mut x, mut y) = (0, 0);
+                    libfprint_sys::fp_minutia_get_coords(minutia.cast(), &mut x, &mut y);
+                    FpMinutia { x, y }
+                })
+                .collect()
+        };
+        FpMinutiae {
+            minutiae,
+            ppmm: self.ppmm(),
+        }
+    }
+    /// Start an asynchronous operation to detect the minutiae of the image. `callback` is called on the thread
+    /// default main context once the operation completes, the minutiae are then returned by `FpImage::minutiae`.
+    pub fn detect_minutiae<P: FnOnce(Result<(), crate::GError>) + 'static>(
+        &self,
+        cancellable: Option<&Cancellable>,
+        callback: P,
+    ) {
+        let raw_cancel = match cancellable {
+            Some(p) => p.to_glib_none().0,
+            None => std::ptr::null_mut(),
+        };
+        let callback: Box<P> = Box::new(callback);
+
+        unsafe {
+            libfprint_sys::fp_image_detect_minutiae(
+                self.to_glib_none().0,
+                raw_cancel.cast(),
+                Some(detect_minutiae_trampoline::<P>),
+                Box::into_raw(callback).cast(),
+            )
+        };
+    }
+    /// Detect the minutiae of the image, blocking until done while iterating the thread default main context.
+    pub fn detect_minutiae_sync(
+        &self,
+        cancellable: Option<&Cancellable>,
+    ) -> Result<FpMinutiae, crate::GError> {
+        let context = glib::MainContext::ref_thread_default();
+        let result = Rc::new(RefCell::new(None));
+        {
+            let result = result.clone();
+            self.detect_minutiae(cancellable, move |res| *result.borrow_mut() = Some(res));
+        }
+        while result.borrow().is_none() {
+            context.iteration(true);
+        }
+        let res = result.take().unwrap();
+        res.map(|()| self.minutiae())
+    }
+    #[cfg(not(doctest))]
+    /// Detect the minutiae of the image, returning a future. See `FpImage::detect_minutiae`.
+    ///
+    /// libfprint runs the detection on a worker thread, so a UI thread awaiting the future keeps handling its
+    /// events meanwhile. Dropping the future cancels the detection.
+    /// # Example:
+    /// ```no_run
+    /// glib::MainContext::default().spawn_local(async move {
+    ///     let image = device.capture_future(true).await.unwrap();
+    ///     let minutiae = image.detect_minutiae_future().await.unwrap();
+    ///     label.set_text(&format!("{} minutiae", minutiae.minutiae.len()));
+    /// });
+    /// ```
+    #[cfg(feature = "futures")]
+    pub fn detect_minutiae_future(&self) -> crate::FpFuture<FpMinutiae> {
+        Box::pin(gio::GioFuture::new(self, |obj, cancellable, send| {
+            let image = obj.clone();
+            obj.detect_minutiae(Some(cancellable), move |res| {
+                send.resolve(res.map(|()| image.minutiae()))
+            });
+        }))
+    }
+    /// Gets the greyscale data for an image.
+    pub fn data(&self) -> Vec<u8> {
+        unsafe {
+            let mut len = 0;
+            let data = libfprint_sys::fp_image_get_data(self.to_glib_none().0, &mut len);
+
+            Vec::from_glib_none_num(data, len as usize)
+        }
+    }
+    #[cfg(not(doctest))]
+    /// Writes the greyscale data of the image to `writer` row by row, straight from the buffer of libfprint,
+    /// so large captures are not copied on targets with little memory. The data is the one of `FpImage::data`.
+    /// # Example:
+    /// ```no_run
+    /// use std::{fs::File, io::{BufWriter, Write}};
+    ///
+    /// let image = device.capture_sync(true, None).unwrap();
+    /// let mut file = BufWriter::new(File::create("image.pgm").unwrap());
+    /// write!(file, "P5\n{} {}\n255\n", image.width(), image.height()).unwrap();
+    /// image.write_raw_to(&mut file).unwrap();
+    /// ```
+    pub fn write_raw_to<W: std::io::Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let data = unsafe {
+            let mut len = 0;
+            let data = libfprint_sys::fp_image_get_data(self.to_glib_none().0, &mut len);
+            match data.is_null() {
+                true => &[][..],
+                // The buffer is owned by the image, which outlives the borrow
+                false => std::slice::from_raw_parts(data, len as usize),
+            }
+        };
+        let row = (self.width() as usize).max(1);
+        for chunk in data.chunks(row) {
+            writer.write_all(chunk)?;
+        }
+        Ok(())
+    }
+
+    pub fn binarized(&self) -> Vec<u8> {
+        unsafe {
+            let mut len = 0;
+            let data = libfprint_sys::fp_image_get_binarized(self.to_glib_none().0, &mut len);
+
+            Vec::from_glib_none_num(data, len as usize)
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::{f64::consts::FRAC_PI_2, fmt::Write};
+
+#[cfg(feature = "png")]
+use super::encode::{base64, encode_png};
+use super::{FpImage, FpMinutiae};
+
+/// Half side of the window the ridge orientation is estimated on, in pixels.
+const ORIENTATION_WINDOW: i32 = 6;
+/// Color of the markers.
+const MARKER_COLOR: [u8; 3] = [230, 30, 30];
+
+/// A minutia as drawn: its position and the orientation of the ridges around it, in radians.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Marker {
+    x: f64,
+    y: f64,
+    angle: f64,
+}
+
+impl FpImage {
+    /// Renders `minutiae` over the image as an SVG document, e.g. to debug poor match rates. Each minutia is
+    /// circled, with a line along the orientation of the ridges around it: libfprint does not expose the
+    /// direction found by its detector, the orientation is estimated from the image.
+    ///
+    /// The image itself is embedded as a PNG with the `png` feature, otherwise the document only holds the
+    /// markers, the size of the image.
+    pub fn minutiae_svg(&self, minutiae: &FpMinutiae) -> String {
+        let (width, height) = (self.width(), self.height());
+        let radius = marker_radius(minutiae.ppmm);
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+            width, height
+        );
+        #[cfg(feature = "png")]
+        if let Ok(png) = encode_png(width, height, png::ColorType::Grayscale, &self.data()) {
+            let _ = write!(
+                svg,
+                r#"<image width="{}" height="{}" href="data:image/png;base64,{}"/>"#,
+                width,
+                height,
+                base64(&png)
+            );
+        }
+        let [r, g, b] = MARKER_COLOR;
+        let _ = write!(
+            svg,
+            r#"<g fill="none" stroke="rgb({},{},{})" stroke-width="1">"#,
+            r, g, b
+        );
+        for marker in markers(self, minutiae) {
+            let (dx, dy) = (
+                marker.angle.cos() * radius * 2.0,
+                marker.angle.sin() * radius * 2.0,
+            );
+            let _ = write!(
+                svg,
+                r#"<circle cx="{:.1}" cy="{:.1}" r="{:.1}"/><line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}"/>"#,
+                marker.x,
+                marker.y,
+                radius,
+                marker.x - dx,
+                marker.y - dy,
+                marker.x + dx,
+                marker.y + dy
+            );
+        }
+        svg.push_str("</g></svg>");
+        svg
+    }
+
+    /// Renders `minutiae` over the image as an RGB PNG image, see `FpImage::minutiae_svg` (requires the `png`
+    /// feature).
+    #[cfg(feature = "png")]
+    pub fn minutiae_png(&self, minutiae: &FpMinutiae) -> Result<Vec<u8>, crate::error::FpError> {
+        let (width, height) = (self.width(), self.height());
+        let mut rgb: Vec<u8> = self.data().iter().flat_map(|&grey| [grey; 3]).collect();
+        let radius = marker_radius(minutiae.ppmm);
+        for marker in markers(self, minutiae) {
+            draw_marker(&mut rgb, width as usize, height as usize, marker, radius);
+        }
+        encode_png(width, height, png::ColorType::Rgb, &rgb)
+    }
+}
+
+/// The radius of the markers, about a third of a millimeter.
+fn marker_radius(ppmm: f64) -> f64 {
+    (ppmm / 3.0).max(3.0)
+}
+
+fn markers(image: &FpImage, minutiae: &FpMinutiae) -> Vec<Marker> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let data = image.data();
+    minutiae
+        .minutiae
+        .iter()
+        .map(|minutia| Marker {
+            x: minutia.x as f64,
+            y: minutia.y as f64,
+            angle: ridge_orientation(&data, width, height, minutia.x, minutia.y),
+        })
+        .collect()
+}
+
+/// The orientation of the ridges around (`x`, `y`), in radians, from the structure tensor of the gradient.
+fn ridge_orientation(data: &[u8], width: usize, height: usize, x: i32, y: i32) -> f64 {
+    let pixel = |x: i32, y: i32| {
+        let x = x.clamp(0, width as i32 - 1) as usize;
+        let y = y.clamp(0, height as i32 - 1) as usize;
+        data[y * width + x] as f64
+    };
+    let (mut gxx, mut gyy, mut gxy) = (0.0, 0.0, 0.0);
+    for wy in y - ORIENTATION_WINDOW..=y + ORIENTATION_WINDOW {
+        for wx in x - ORIENTATION_WINDOW..=x + ORIENTATION_WINDOW {
+            let gx = pixel(wx + 1, wy) - pixel(wx - 1, wy);
+            let gy = pixel(wx, wy + 1) - pixel(wx, wy - 1);
+            gxx += gx * gx;
+            gyy += gy * gy;
+            gxy += gx * gy;
+        }
+    }
+    // The ridges run across the dominant gradient
+    0.5 * (2.0 * gxy).atan2(gxx - gyy) + FRAC_PI_2
+}
+
+#[cfg(feature = "png")]
+fn draw_marker(rgb: &mut [u8], width: usize, height: usize, marker: Marker, radius: f64) {
+    let mut plot = |x: f64, y: f64| {
+        let (x, y) = (x.round(), y.round());
+        if x >= 0.0 && y >= 0.0 && (x as usize) < width && (y as usize) < height {
+            let offset = (y as usize * width + x as usize) * 3;
+            rgb[offset..offset + 3].copy_from_slice(&MARKER_COLOR);
+        }
+    };
+    let steps = (radius * 8.0).ceil() as usize;
+    for step in 0..steps {
+        let theta = step as f64 / steps as f64 * std::f64::consts::TAU;
+        plot(
+            marker.x + theta.cos() * radius,
+            marker.y + theta.sin() * radius,
+        );
+    }
+    let length = radius * 2.0;
+    for step in -(length as i32)..=length as i32 {
+        let t = step as f64;
+        plot(
+            marker.x + marker.angle.cos() * t,
+            marker.y + marker.angle.sin() * t,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ridge_orientation;
+
+    #[test]
+    fn orientation() {
+        // Vertical ridges: the intensity varies along x only
+        let (width, height) = (32, 32);
+        let data: Vec<u8> = (0..width * height)
+            .map(|i| if (i % width) % 4 < 2 { 50 } else { 200 })
+            .collect();
+        let angle = ridge_orientation(&data, width, height, 16, 16);
+        assert!((angle.sin().abs() - 1.0).abs() < 1e-6, "{}", angle);
+
+        // Horizontal ridges
+        let data: Vec<u8> = (0..width * height)
+            .map(|i| if (i / width) % 4 < 2 { 50 } else { 200 })
+            .collect();
+        let angle = ridge_orientation(&data, width, height, 16, 16);
+        assert!(angle.sin().abs() < 1e-6, "{}", angle);
+    }
+}
@@ -0,0 +1,282 @@
+use super::FpImage;
+
+/// Side of the square blocks used to find the content of an image, in pixels.
+const CONTENT_BLOCK: usize = 8;
+/// Blocks with a lower standard deviation are background.
+const CONTENT_CONTRAST: f64 = 10.0;
+
+/// A rotation by a multiple of a quarter turn, see `FpImage::rotate`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FpRotation {
+    /// Rotate by 90 degrees clockwise.
+    Clockwise,
+    /// Rotate by 180 degrees.
+    HalfTurn,
+    /// Rotate by 90 degrees counterclockwise.
+    Counterclockwise,
+}
+
+impl FpImage {
+    /// Creates an image from its greyscale data, one byte per pixel, row by row.
+    ///
+    /// The resolution of the new image is not set.
+    /// # Panics
+    /// Panics if `data` is not `width * height` bytes long.
+    pub fn from_data(width: u32, height: u32, data: &[u8]) -> Self {
+        assert_eq!(
+            data.len(),
+            width as usize * height as usize,
+            "The data does not match the size of the image"
+        );
+        let image = FpImage::new(width, height);
+        unsafe {
+            use glib::translate::ToGlibPtr;
+
+            let mut len = 0;
+            // The buffer is allocated by fp_image_new, libfprint only exposes it as const
+            let ptr = libfprint_sys::fp_image_get_data(image.to_glib_none().0, &mut len);
+            assert_eq!(len as usize, data.len());
+            std::ptr::copy_nonoverlapping(data.as_ptr(), ptr as *mut u8, data.len());
+        }
+        image
+    }
+
+    /// Returns a new image cropped to the part covered by the finger, or a copy of the image if no finger is found.
+    pub fn crop_to_content(&self) -> FpImage {
+        let (width, height) = (self.width() as usize, self.height() as usize);
+        let data = self.data();
+        match content_bounds(&data, width, height) {
+            Some((x, y, w, h)) => {
+                FpImage::from_data(w as u32, h as u32, &crop(&data, width, x, y, w, h))
+            }
+            None => FpImage::from_data(width as u32, height as u32, &data),
+        }
+    }
+    /// Returns a new image with its contrast stretched over the full range of grey levels. The darkest and lightest
+    /// percent of the pixels are clipped, so a few outliers do not prevent the stretch.
+    pub fn normalize_contrast(&self) -> FpImage {
+        FpImage::from_data(
+            self.width(),
+            self.height(),
+            &normalize_contrast(&self.data()),
+        )
+    }
+    /// Returns a new image rotated by `rotation`.
+    pub fn rotate(&self, rotation: FpRotation) -> FpImage {
+        let (width, height) = (self.width() as usize, self.height() as usize);
+        let (w, h, data) = rotate(&self.data(), width, height, rotation);
+        FpImage::from_data(w as u32, h as u32, &data)
+    }
+    /// Returns a new image downscaled to fit in `max_width` by `max_height`, averaging blocks of pixels, e.g.
+    /// for a preview. Images that already fit are copied.
+    pub fn thumbnail(&self, max_width: u32, max_height: u32) -> FpImage {
+        let (width, height) = (self.width() as usize, self.height() as usize);
+        let (w, h, data) = downscale(
+            &self.data(),
+            width,
+            height,
+            max_width.max(1) as usize,
+            max_height.max(1) as usize,
+        );
+        FpImage::from_data(w as u32, h as u32, &data)
+    }
+}
+
+/// Returns the bounding box (x, y, width, height) of the blocks with enough contrast.
+fn content_bounds(
+    data: &[u8],
+    width: usize,
+    height: usize,
+) -> Option<(usize, usize, usize, usize)> {
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    for by in (0..height).step_by(CONTENT_BLOCK) {
+        for bx in (0..width).step_by(CONTENT_BLOCK) {
+            let (bw, bh) = (
+                CONTENT_BLOCK.min(width - bx),
+                CONTENT_BLOCK.min(height - by),
+            );
+            let (mut sum, mut sum_sq) = (0.0, 0.0);
+            for y in by..by + bh {
+                for x in bx..bx + bw {
+                    let value = data[y * width + x] as f64;
+                    sum += value;
+                    sum_sq += value * value;
+                }
+            }
+            let n = (bw * bh) as f64;
+            let mean = sum / n;
+            if (sum_sq / n - mean * mean).max(0.0).sqrt() < CONTENT_CONTRAST {
+                continue;
+            }
+            let (x0, y0, x1, y1) = bounds.unwrap_or((bx, by, bx + bw, by + bh));
+            bounds = Some((x0.min(bx), y0.min(by), x1.max(bx + bw), y1.max(by + bh)));
+        }
+    }
+    bounds.map(|(x0, y0, x1, y1)| (x0, y0, x1 - x0, y1 - y0))
+}
+
+fn crop(data: &[u8], width: usize, x: usize, y: usize, w: usize, h: usize) -> Vec<u8> {
+    (y..y + h)
+        .flat_map(|row| data[row * width + x..row * width + x + w].iter().copied())
+        .collect()
+}
+
+fn normalize_contrast(data: &[u8]) -> Vec<u8> {
+    if data.is_empty() {
+        return Vec::new();
+    }
+    let mut histogram = [0usize; 256];
+    data.iter()
+        .for_each(|&value| histogram[value as usize] += 1);
+
+    let clip = data.len() / 100;
+    let low = clipped_level(&histogram, 0..256, clip);
+    let high = clipped_level(&histogram, (0..256).rev(), clip);
+    if high <= low {
+        return data.to_vec();
+    }
+
+    let scale = 255.0 / (high - low) as f64;
+    data.iter()
+        .map(|&value| {
+            ((value as f64 - low as f64) * scale)
+                .clamp(0.0, 255.0)
+                .round() as u8
+        })
+        .collect()
+}
+
+/// Returns the first grey level of `levels` after skipping `clip` pixels.
+fn clipped_level(
+    histogram: &[usize; 256],
+    levels: impl Iterator<Item = usize>,
+    clip: usize,
+) -> usize {
+    let mut count = 0;
+    for level in levels {
+        count += histogram[level];
+        if count > clip {
+            return level;
+        }
+    }
+    0
+}
+
+fn rotate(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    rotation: FpRotation,
+) -> (usize, usize, Vec<u8>) {
+    let source = |x: usize, y: usize| data[y * width + x];
+    match rotation {
+        FpRotation::HalfTurn => {
+            let mut out = data.to_vec();
+            out.reverse();
+            (width, height, out)
+        }
+        // Pixel (x, y) of the rotated image, which is `height` wide
+        FpRotation::Clockwise => {
+            let out = (0..width)
+                .flat_map(|y| (0..height).map(move |x| (x, y)))
+                .map(|(x, y)| source(y, height - 1 - x))
+                .collect();
+            (height, width, out)
+        }
+        FpRotation::Counterclockwise => {
+            let out = (0..width)
+                .flat_map(|y| (0..height).map(move |x| (x, y)))
+                .map(|(x, y)| source(width - 1 - y, x))
+                .collect();
+            (height, width, out)
+        }
+    }
+}
+
+/// Averages square blocks of pixels, the side of the blocks is the smallest one fitting the bounds.
+fn downscale(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    max_width: usize,
+    max_height: usize,
+) -> (usize, usize, Vec<u8>) {
+    let factor = width
+        .div_ceil(max_width)
+        .max(height.div_ceil(max_height))
+        .max(1);
+    let (w, h) = ((width / factor).max(1), (height / factor).max(1));
+    let out = (0..h)
+        .flat_map(|y| (0..w).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            // An image thinner than a block in a direction is averaged over its whole thickness
+            let (right, bottom) = (
+                (x * factor + factor).min(width),
+                (y * factor + factor).min(height),
+            );
+            let (sum, count) = (y * factor..bottom)
+                .flat_map(|y| (x * factor..right).map(move |x| data[y * width + x] as usize))
+                .fold((0, 0), |(sum, count), pixel| (sum + pixel, count + 1));
+            (sum / count.max(1)) as u8
+        })
+        .collect();
+    (w, h, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_bounds, downscale, normalize_contrast, rotate, FpRotation};
+
+    #[test]
+    fn rotations() {
+        // 1 2 3
+        // 4 5 6
+        let data = [1, 2, 3, 4, 5, 6];
+        assert_eq!(
+            rotate(&data, 3, 2, FpRotation::Clockwise),
+            (2, 3, vec![4, 1, 5, 2, 6, 3])
+        );
+        assert_eq!(
+            rotate(&data, 3, 2, FpRotation::Counterclockwise),
+            (2, 3, vec![3, 6, 2, 5, 1, 4])
+        );
+        assert_eq!(
+            rotate(&data, 3, 2, FpRotation::HalfTurn),
+            (3, 2, vec![6, 5, 4, 3, 2, 1])
+        );
+    }
+
+    #[test]
+    fn thumbnails() {
+        // 0 2 4 6
+        // 2 4 6 8
+        let data = [0, 2, 4, 6, 2, 4, 6, 8];
+        assert_eq!(downscale(&data, 4, 2, 2, 2), (2, 1, vec![2, 6]));
+        assert_eq!(downscale(&data, 4, 2, 4, 2), (4, 2, data.to_vec()));
+        assert_eq!(downscale(&data, 4, 2, 1, 1), (1, 1, vec![4]));
+    }
+
+    #[test]
+    fn content_and_contrast() {
+        let (width, height) = (64, 64);
+        let data: Vec<u8> = (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                if (16..40).contains(&x) && (8..32).contains(&y) {
+                    if x % 2 == 0 {
+                        100
+                    } else {
+                        150
+                    }
+                } else {
+                    200
+                }
+            })
+            .collect();
+        assert_eq!(content_bounds(&data, width, height), Some((16, 8, 24, 24)));
+
+        let stretched = normalize_contrast(&data);
+        assert_eq!(stretched.iter().min(), Some(&0));
+        assert_eq!(stretched.iter().max(), Some(&255));
+    }
+}
@@ -0,0 +1,83 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::device::{FpDevice, FpDeviceFeature, FpDeviceId};
+
+#[cfg(not(doctest))]
+/// Queries devices periodically so they do not power down, for sensors whose first operation after a sleep
+/// stalls while they initialize again.
+///
+/// The keep-alive is enabled per device, with its own interval. By default an open device with a storage
+/// lists its prints, the lightest command libfprint sends to the sensor; other devices need a probe given to
+/// `FpKeepAlive::enable_with`. Closed devices are not probed, and the errors of the probes are ignored, e.g. a
+/// probe running while the device is busy. The probes run on the global default main context, which must be
+/// iterated for them to run, e.g. by the `_sync` operations, and stop when the keep-alive is dropped. The
+/// keep-alive must be enabled on a thread that can acquire that context, e.g. the main thread.
+/// # Example:
+/// ```no_run
+/// use std::time::Duration;
+/// use libfprint_rs::{FpContext, FpKeepAlive};
+///
+/// let ctx = FpContext::new();
+/// let devices = ctx.devices();
+/// let dev = devices.get(0).unwrap();
+/// dev.open_sync(None).unwrap();
+///
+/// let mut keep_alive = FpKeepAlive::new();
+/// keep_alive.enable(&dev, Duration::from_secs(20));
+/// ```
+#[derive(Debug, Default)]
+pub struct FpKeepAlive {
+    sources: HashMap<FpDeviceId, glib::SourceId>,
+}
+
+impl FpKeepAlive {
+    /// Creates a keep-alive probing no device.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Probe `device` every `interval` with the default probe, replacing its previous probe.
+    pub fn enable(&mut self, device: &FpDevice, interval: Duration) {
+        self.enable_with(device, interval, |device| {
+            if device.supports(FpDeviceFeature::StorageList) {
+                let _ = device.list_prints_sync(None);
+            }
+        });
+    }
+    /// Probe `device` every `interval` with `probe`, replacing its previous probe. The probe is only called
+    /// while the device is open.
+    pub fn enable_with(
+        &mut self,
+        device: &FpDevice,
+        interval: Duration,
+        probe: impl Fn(&FpDevice) + 'static,
+    ) {
+        self.disable(device);
+        let weak = device.downgrade();
+        // The source is only removed by the keep-alive, so it is never removed twice
+        let source = glib::timeout_add_local(interval, move || {
+            if let Some(device) = weak.upgrade().filter(FpDevice::is_open) {
+                probe(&device);
+            }
+            glib::ControlFlow::Continue
+        });
+        self.sources.insert(device.id(), source);
+    }
+    /// Stop probing `device`.
+    pub fn disable(&mut self, device: &FpDevice) {
+        if let Some(source) = self.sources.remove(&device.id()) {
+            source.remove();
+        }
+    }
+    /// Whether `device` is probed.
+    pub fn is_enabled(&self, device: &FpDevice) -> bool {
+        self.sources.contains_key(&device.id())
+    }
+}
+
+impl Drop for FpKeepAlive {
+    fn drop(&mut self) {
+        for (_, source) in self.sources.drain() {
+            source.remove();
+        }
+    }
+}
@@ -16,7 +16,7 @@
 //! dev.open_sync(None)?;
 //!
 //! let template = FpPrint::new(&dev);
-//! template.set_username("Bruce Banner");
+//! template.set_username("Bruce Banner")?;
 //!
 //! let print = dev.enroll_sync(template, None, None, None::<()>)?;
 //! ```
@@ -30,30 +30,120 @@
 //!
 //! let enrolled_print = load_print_from_file();
 //!
-//! let match_res = dev.verify_sync(enrolled_print, None, None, None::<()>, None)?;
+//! let match_res = dev.verify_sync(&enrolled_print, None, None, None::<()>)?;
 //! ```
+//! # Blocking and asynchronous operations
+//! The `_sync` methods of `FpDevice` block the calling thread until the operation completes, which may take
+//! as long as the user takes to place their finger on the sensor. They must not be called from an async
+//! executor thread. A device runs one operation at a time: calling a `_sync` method on a device busy with
+//! another one, e.g. from another thread, fails with the `FP_DEVICE_ERROR_BUSY` error, unless
+//! `FpDevice::set_queue_operations` makes it wait.
+//!
+//! Every blocking method has an asynchronous counterpart taking a completion callback (e.g. `FpDevice::open`)
+//! and, with the `futures` feature, another one returning a future (e.g. `FpDevice::open_future`). The
+//! operations are driven by the GLib main context of the calling thread, so the futures must be polled from a
+//! thread owning its thread default `glib::MainContext`, e.g. with `glib::MainContext::block_on`.
+//!
+//! The `blocking` and `futures` modules wrap a device to only expose one of both sets, under the same names:
+//! `blocking::FpDevice::open` blocks, `futures::FpDevice::open` returns a future. Services can hand the
+//! wrapper of their flavour to their code so async code can not call a blocking method by mistake.
+//!
 //! For more examples on how to use this crate, please refer to the [github oficial repository](https://github.com/AlvaroParker/libfprint-rs)
 //! and the [documentation](https://docs.rs/libfprint-rs/latest/libfprint_rs/).
+mod attendance;
+pub mod audit;
+#[cfg(feature = "uniffi")]
+pub mod bindings;
+pub mod blocking;
+#[cfg(feature = "capi")]
+pub mod capi;
 mod context;
+mod date;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+mod debug;
 mod device;
+#[cfg(feature = "async-io")]
+mod driven;
+mod error;
 mod finger;
+#[cfg(feature = "futures")]
+pub mod futures;
+mod handle;
 mod image;
+mod keepalive;
+mod limiter;
+#[cfg(feature = "futures")]
+mod manager;
+#[cfg(feature = "host-matching")]
+pub mod matching;
+mod operation;
+mod poll;
+mod pool;
 mod print;
+#[cfg(feature = "indicatif")]
+mod progress;
+mod ptr_array;
+mod serialized;
+#[cfg(feature = "signal-hook")]
+mod signal;
+pub mod store;
+#[cfg(feature = "umockdev")]
+pub mod umockdev;
+pub mod virtual_device;
 
 pub use gio::traits::CancellableExt;
 /// Re-export `gio::Cancellable`, it provides a way to cancel sync operations, i.e
 /// `FpDevice::enroll_sync`
 pub use gio::Cancellable;
-/// Re-export `glib::Error`, it provides a way to pass enroll dates to `FpPrint` metadata
+/// Re-export `glib::Date`, it can be converted from and to `FpDate`
 pub use glib::Date as GDate;
 /// Re-export `glib::Error`, it provides error handling for sync operations.
 pub use glib::Error as GError;
 
-pub use context::FpContext;
-pub use device::{FpDevice, FpEnrollProgress, FpMatchCb};
+pub use attendance::{FpAttendance, FpAttendanceEvent};
+pub use context::{FpContext, SHUTDOWN_GRACE};
+#[cfg(feature = "futures")]
+pub use context::{FpDeviceEvent, FpDeviceEvents};
+pub use date::FpDate;
+pub use debug::FpDebug;
+#[cfg(libfprint_1_94)]
+pub use device::FpTemperature;
+pub use device::{
+    FpDevice, FpDeviceCapabilities, FpDeviceFeature, FpDeviceId, FpDeviceInfo, FpDeviceLock,
+    FpDiagnostics, FpDriverQuirks, FpEnrollEvent, FpEnrollGuidance, FpEnrollOutcome,
+    FpEnrollProgress, FpEnrollSet, FpEnrollTracker, FpExclusiveOutcome, FpFingerPrompt,
+    FpFingerStatus, FpFingerStatusFlags, FpGalleryMatch, FpGuidanceCb, FpIdentifyResult,
+    FpMainContextBridge, FpMatchCb, FpMatchEvent, FpMatchOutcome, FpMatchTiming, FpQuirkTable,
+    FpScanType, FpWatchdog, FpWeakDevice, FpWedgeRecovery,
+};
+#[cfg(feature = "futures")]
+pub use device::{FpEnrollStage, FpFuture, FpPreviewFrame};
+#[cfg(feature = "async-io")]
+pub use driven::FpDriven;
+pub use error::{FpContextual, FpError, FpResultExt, FpRetryError};
 pub use finger::FpFinger;
-pub use image::FpImage;
-pub use print::FpPrint;
+pub use handle::{FpDeviceCommand, FpDeviceHandle, FpHandleEvent};
+pub use image::{FpImage, FpImageFlags, FpMinutia, FpMinutiae, FpRotation};
+pub use keepalive::FpKeepAlive;
+pub use limiter::{FpAttemptLimiter, FpAttemptRefusal};
+#[cfg(feature = "futures")]
+pub use manager::{
+    FpDeviceManager, FpForgetReport, FpManagerEvent, FpOperation, FpOperationResult,
+    FpSelectionPreference,
+};
+pub use operation::{FpOperationHandle, FpOperationProgress};
+pub use poll::FpMainContextFd;
+pub use pool::FpDevicePool;
+pub use print::{FpMetadataField, FpPrint, FpPrintBuilder, MAX_DESCRIPTION_LEN, MAX_USERNAME_LEN};
+#[cfg(feature = "indicatif")]
+pub use progress::FpProgressBar;
+pub use serialized::{FpPrintEnvelope, FpSerializedPrint};
+#[cfg(feature = "signal-hook")]
+pub use signal::FpInterruptGuard;
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
 
 #[cfg(test)]
 mod tests {
@@ -80,17 +170,80 @@ mod tests {
             prints.push(print);
         }
 
-        let mut new_print = FpPrint::new(&dev);
-        let matched = dev
-            .identify_sync(&prints, None, Some(match_cb), None, Some(&mut new_print))
+        let result = dev
+            .identify_sync(&prints, None, Some(match_cb), None)
             .unwrap();
 
-        if matched.is_some() {
+        if result.is_match() {
             println!("Matched");
         } else {
             println!("Not matched");
         }
     }
+    #[test]
+    fn teardown() {
+        use glib::prelude::ObjectExt;
+
+        let ctx = FpContext::new();
+        let devices = ctx.devices();
+        let dev = devices.first().unwrap();
+        dev.open_sync(None).unwrap();
+
+        let weak_ctx = ctx.downgrade();
+        let weak_dev = dev.downgrade();
+        ctx.shutdown().unwrap();
+        assert!(!dev.is_open());
+        assert!(dev.running_operation().is_none());
+        // The devices were detached, the context is freed once the user drops it
+        assert!(dev.context().is_none());
+        drop(devices);
+        drop(ctx);
+        assert!(weak_ctx.upgrade().is_none());
+        assert!(weak_dev.upgrade().is_none());
+    }
+    #[test]
+    fn drop_cancels_pending() {
+        use std::{cell::RefCell, rc::Rc};
+
+        let ctx = FpContext::new();
+        let main_context = glib::MainContext::default();
+        let result = Rc::new(RefCell::new(None));
+        {
+            let devices = ctx.devices();
+            let dev = devices.first().unwrap();
+            dev.open_sync(None).unwrap();
+            let result = result.clone();
+            dev.capture(true, None, move |res| *result.borrow_mut() = Some(res));
+        }
+        // The last handle was dropped, the capture is cancelled and the device closed once it completed
+        while result.borrow().is_none() {
+            main_context.iteration(true);
+        }
+        let err = result.borrow_mut().take().unwrap().unwrap_err();
+        assert!(err.matches(gio::IOErrorEnum::Cancelled));
+        let dev = ctx.devices().remove(0);
+        while dev.is_open() {
+            main_context.iteration(true);
+        }
+    }
+    #[test]
+    fn context_not_kept_alive() {
+        use glib::prelude::ObjectExt;
+
+        let ctx = FpContext::new();
+        let devices = ctx.devices();
+        assert!(!devices.is_empty());
+        assert_eq!(devices[0].context().as_ref(), Some(&ctx));
+        drop(devices);
+        // Neither the devices nor the device list hold a strong reference to the context
+        assert_eq!(ctx.ref_count(), 1);
+
+        let weak_ctx = ctx.downgrade();
+        let dev = ctx.devices().remove(0);
+        drop(ctx);
+        assert!(weak_ctx.upgrade().is_none());
+        assert!(dev.context().is_none());
+    }
     pub fn _enroll_print(dev: &FpDevice) -> FpPrint {
         let template = FpPrint::new(&dev);
         let print = dev.enroll_sync(template, None, Some(enroll_cb), None);
@@ -126,7 +279,7 @@ mod tests {
     pub fn match_cb(
         _device: &FpDevice,
         matched_print: Option<FpPrint>,
-        _print: FpPrint,
+        _print: Option<FpPrint>,
         _error: Option<glib::Error>,
         _data: &Option<i32>,
     ) -> () {
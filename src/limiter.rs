@@ -0,0 +1,214 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+type LockoutCallback = Box<dyn Fn(&str, Duration)>;
+
+#[cfg(not(doctest))]
+/// Limits the failed verifications of each user, against brute force attempts.
+///
+/// A user who fails `max_failures` verifications within `window` is locked out for `lockout`: their
+/// verifications are refused until it is over. A successful verification clears the failures of the user.
+///
+/// Verifications running concurrently, e.g. on several devices, are checked with `FpAttemptLimiter::begin` and
+/// recorded with `FpAttemptLimiter::finish`: a user verifies on one device at a time, so every attempt is
+/// checked against the outcome of the previous one and none slips through the lockout.
+/// # Example:
+/// ```no_run
+/// use std::time::Duration;
+/// use libfprint_rs::{FpAttemptLimiter, FpContext, FpDeviceManager};
+///
+/// let limiter = FpAttemptLimiter::new(5, Duration::from_secs(60), Duration::from_secs(300))
+///     .on_lockout(|username, duration| println!("{} locked out for {:?}", username, duration));
+///
+/// let ctx = FpContext::new();
+/// let mut manager = FpDeviceManager::new(&ctx);
+/// manager.set_attempt_limiter(limiter);
+/// ```
+pub struct FpAttemptLimiter {
+    max_failures: usize,
+    window: Duration,
+    lockout: Duration,
+    failures: HashMap<String, VecDeque<Instant>>,
+    locked: HashMap<String, Instant>,
+    pending: HashSet<String>,
+    on_lockout: Option<LockoutCallback>,
+}
+
+/// Why `FpAttemptLimiter::begin` refused a verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FpAttemptRefusal {
+    /// The user is locked out for the remaining duration.
+    LockedOut(Duration),
+    /// Another verification of the user is running.
+    Pending,
+}
+
+impl FpAttemptLimiter {
+    /// Creates a limiter locking a user out for `lockout` after `max_failures` failures within `window`.
+    pub fn new(max_failures: usize, window: Duration, lockout: Duration) -> Self {
+        FpAttemptLimiter {
+            max_failures: max_failures.max(1),
+            window,
+            lockout,
+            failures: HashMap::new(),
+            locked: HashMap::new(),
+            pending: HashSet::new(),
+            on_lockout: None,
+        }
+    }
+    /// Set the callback called with the username and the duration when a user is locked out.
+    pub fn on_lockout(mut self, callback: impl Fn(&str, Duration) + 'static) -> Self {
+        self.on_lockout = Some(Box::new(callback));
+        self
+    }
+
+    /// Returns the remaining time `username` is locked out for, `None` if they may verify.
+    pub fn locked_out(&mut self, username: &str) -> Option<Duration> {
+        self.locked_out_at(username, Instant::now())
+    }
+    fn locked_out_at(&mut self, username: &str, now: Instant) -> Option<Duration> {
+        let until = *self.locked.get(username)?;
+        if now >= until {
+            self.locked.remove(username);
+            return None;
+        }
+        Some(until - now)
+    }
+
+    /// Start a verification of `username`, unless they are locked out or already verifying. Every accepted
+    /// verification must be followed by `FpAttemptLimiter::finish`.
+    pub fn begin(&mut self, username: &str) -> Result<(), FpAttemptRefusal> {
+        self.begin_at(username, Instant::now())
+    }
+    fn begin_at(&mut self, username: &str, now: Instant) -> Result<(), FpAttemptRefusal> {
+        if let Some(remaining) = self.locked_out_at(username, now) {
+            return Err(FpAttemptRefusal::LockedOut(remaining));
+        }
+        match self.pending.insert(username.to_string()) {
+            true => Ok(()),
+            false => Err(FpAttemptRefusal::Pending),
+        }
+    }
+    /// Finish a verification of `username` started with `FpAttemptLimiter::begin`: `Some(matched)` records its
+    /// outcome, `None` a verification that was no attempt, e.g. a scan to retry or an error. Returns the lockout
+    /// duration if it locked them out.
+    pub fn finish(&mut self, username: &str, matched: Option<bool>) -> Option<Duration> {
+        self.finish_at(username, matched, Instant::now())
+    }
+    fn finish_at(
+        &mut self,
+        username: &str,
+        matched: Option<bool>,
+        now: Instant,
+    ) -> Option<Duration> {
+        self.pending.remove(username);
+        match matched? {
+            true => {
+                self.record_success(username);
+                None
+            }
+            false => self.record_failure_at(username, now),
+        }
+    }
+
+    /// Record a failed verification of `username`, returning the lockout duration if it locked them out.
+    pub fn record_failure(&mut self, username: &str) -> Option<Duration> {
+        self.record_failure_at(username, Instant::now())
+    }
+    fn record_failure_at(&mut self, username: &str, now: Instant) -> Option<Duration> {
+        let failures = self.failures.entry(username.to_string()).or_default();
+        while failures
+            .front()
+            .is_some_and(|failure| now.saturating_duration_since(*failure) >= self.window)
+        {
+            failures.pop_front();
+        }
+        failures.push_back(now);
+        if failures.len() < self.max_failures {
+            return None;
+        }
+
+        self.failures.remove(username);
+        self.locked.insert(username.to_string(), now + self.lockout);
+        if let Some(on_lockout) = &self.on_lockout {
+            on_lockout(username, self.lockout);
+        }
+        Some(self.lockout)
+    }
+    /// Record a successful verification of `username`, clearing their failures.
+    pub fn record_success(&mut self, username: &str) {
+        self.failures.remove(username);
+    }
+    /// Lift the lockout of `username` and clear their failures, e.g. after an administrator unlocked them.
+    pub fn reset(&mut self, username: &str) {
+        self.failures.remove(username);
+        self.locked.remove(username);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{FpAttemptLimiter, FpAttemptRefusal};
+
+    #[test]
+    fn lockout() {
+        let start = Instant::now();
+        let at = |s: u64| start + Duration::from_secs(s);
+        let mut limiter =
+            FpAttemptLimiter::new(3, Duration::from_secs(60), Duration::from_secs(300));
+
+        assert_eq!(limiter.record_failure_at("alice", at(0)), None);
+        assert_eq!(limiter.record_failure_at("alice", at(10)), None);
+        // The first failure left the window
+        assert_eq!(limiter.record_failure_at("alice", at(61)), None);
+        assert_eq!(limiter.locked_out_at("alice", at(61)), None);
+        assert_eq!(
+            limiter.record_failure_at("alice", at(62)),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(
+            limiter.locked_out_at("alice", at(162)),
+            Some(Duration::from_secs(200))
+        );
+        assert_eq!(limiter.locked_out_at("bob", at(162)), None);
+        assert_eq!(limiter.locked_out_at("alice", at(362)), None);
+
+        // A success clears the failures
+        limiter.record_failure_at("bob", at(0));
+        limiter.record_failure_at("bob", at(1));
+        limiter.record_success("bob");
+        assert_eq!(limiter.record_failure_at("bob", at(2)), None);
+    }
+
+    #[test]
+    fn concurrent_attempts() {
+        let start = Instant::now();
+        let at = |s: u64| start + Duration::from_secs(s);
+        let mut limiter =
+            FpAttemptLimiter::new(2, Duration::from_secs(60), Duration::from_secs(300));
+
+        assert_eq!(limiter.begin_at("alice", at(0)), Ok(()));
+        // A second device can not start a verification before the first one is recorded
+        assert_eq!(
+            limiter.begin_at("alice", at(0)),
+            Err(FpAttemptRefusal::Pending)
+        );
+        assert_eq!(limiter.begin_at("bob", at(0)), Ok(()));
+        assert_eq!(limiter.finish_at("alice", Some(false), at(1)), None);
+        assert_eq!(limiter.finish_at("bob", None, at(1)), None);
+
+        assert_eq!(limiter.begin_at("alice", at(2)), Ok(()));
+        assert_eq!(
+            limiter.finish_at("alice", Some(false), at(3)),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(
+            limiter.begin_at("alice", at(4)),
+            Err(FpAttemptRefusal::LockedOut(Duration::from_secs(299)))
+        );
+    }
+}
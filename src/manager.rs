@@ -0,0 +1,564 @@
+use std::{cell::RefCell, future::poll_fn, pin::Pin, rc::Rc};
+
+use futures_channel::{mpsc, oneshot};
+use futures_core::Stream;
+use gio::{prelude::CancellableExt, Cancellable};
+
+use crate::{
+    audit::{AuditRecord, AuditSink},
+    context::{FpContext, FpDeviceEvent},
+    device::{
+        FpDevice, FpDeviceFeature, FpEnrollProgress, FpIdentifyResult, FpMatchCb, FpMatchOutcome,
+        FpQuirkTable, FpScanType,
+    },
+    error::FpError,
+    image::FpImage,
+    limiter::{FpAttemptLimiter, FpAttemptRefusal},
+    print::FpPrint,
+    store::PrintStore,
+};
+
+/// The audit sink and the attempt limiter of a `FpDeviceManager`, shared with its workers.
+#[derive(Default)]
+struct Hooks {
+    audit: Option<Rc<dyn AuditSink>>,
+    limiter: Option<FpAttemptLimiter>,
+}
+type SharedHooks = Rc<RefCell<Hooks>>;
+
+/// An operation to run on a device of a `FpDeviceManager`.
+#[derive(Debug, Clone)]
+pub enum FpOperation {
+    /// Open the device.
+    Open,
+    /// Close the device.
+    Close,
+    /// Enroll a print using the given template.
+    Enroll(FpPrint),
+    /// Verify the given print.
+    Verify(FpPrint),
+    /// Identify a print among the given prints.
+    Identify(Vec<FpPrint>),
+    /// Capture an image, waiting for a finger if `true`.
+    Capture(bool),
+}
+
+/// The result of a successful `FpOperation`.
+#[derive(Debug)]
+pub enum FpOperationResult {
+    /// The device was opened.
+    Opened,
+    /// The device was closed.
+    Closed,
+    /// The enrolled print.
+    Enrolled(FpPrint),
+    /// The outcome of the verification.
+    Verified(FpMatchOutcome),
+    /// The result of the identification, holding the matching print, if any.
+    Identified(FpIdentifyResult),
+    /// The captured image.
+    Captured(FpImage),
+}
+
+/// An event of a `FpDeviceManager`, see `FpDeviceManager::events`.
+#[derive(Debug)]
+pub enum FpManagerEvent {
+    /// A device was plugged and is now managed.
+    Added(FpDevice),
+    /// A device was unplugged, its pending operations are dropped.
+    Removed(FpDevice),
+    /// An operation completed on `device`.
+    Completed {
+        /// The device the operation ran on.
+        device: FpDevice,
+        /// The result of the operation.
+        result: Result<FpOperationResult, crate::GError>,
+    },
+}
+
+/// A preference of `FpDeviceManager::select`. A policy is a list of preferences, ordered from the most to the
+/// least important one.
+#[derive(Debug, Clone)]
+pub enum FpSelectionPreference {
+    /// Prefer devices matching on the chip, according to their features and the given quirks.
+    MatchOnChip(FpQuirkTable),
+    /// Prefer press devices over swipe devices.
+    Press,
+    /// Prefer devices of the driver the given prints were enrolled with, so no re-enrollment is needed.
+    DriverOf(Vec<FpPrint>),
+    /// Prefer the device with the given driver.
+    Driver(String),
+}
+
+impl FpSelectionPreference {
+    fn prefers(&self, device: &FpDevice) -> bool {
+        match self {
+            FpSelectionPreference::MatchOnChip(quirks) => {
+                device.capabilities(quirks).quirks.match_on_chip
+            }
+            FpSelectionPreference::Press => device.scan_type() == FpScanType::Press,
+            FpSelectionPreference::DriverOf(prints) => {
+                let driver = device.driver();
+                prints.iter().any(|print| print.driver() == driver)
+            }
+            FpSelectionPreference::Driver(driver) => device.driver() == *driver,
+        }
+    }
+}
+
+/// What `FpDeviceManager::forget_user` deleted, for each location holding prints.
+#[derive(Debug)]
+pub struct FpForgetReport {
+    /// The number of prints deleted from the host store, or the error that stopped the deletion.
+    pub store: Result<usize, FpError>,
+    /// The number of prints deleted from the storage of each device having one, or the error that stopped the
+    /// deletion, whose message names the operation that failed.
+    pub devices: Vec<(FpDevice, Result<usize, crate::GError>)>,
+}
+
+impl FpForgetReport {
+    /// Whether the prints were deleted from every location.
+    pub fn is_complete(&self) -> bool {
+        self.store.is_ok() && self.devices.iter().all(|(_, result)| result.is_ok())
+    }
+    /// Returns the total number of deleted prints.
+    pub fn deleted(&self) -> usize {
+        let store = self.store.as_ref().map_or(0, |deleted| *deleted);
+        let devices: usize = self
+            .devices
+            .iter()
+            .filter_map(|(_, result)| result.as_ref().ok())
+            .sum();
+        store + devices
+    }
+}
+
+/// The worker of a managed device: operations are queued and run one after the other.
+struct Worker {
+    device: FpDevice,
+    queue: mpsc::UnboundedSender<FpOperation>,
+    cancellable: Rc<RefCell<Cancellable>>,
+}
+
+#[cfg(not(doctest))]
+/// Owns every device of a context and runs operations on several of them at once.
+///
+/// Each device has its own worker, running the operations started on the device one after the other, while the
+/// workers of different devices run concurrently. The workers are tasks of the thread default main context of
+/// the thread creating the manager, which must be iterated for the operations to progress. Completions and
+/// hotplug events of every device are delivered on a single stream, see `FpDeviceManager::events`.
+/// # Example:
+/// ```no_run
+/// use futures_util::StreamExt;
+/// use libfprint_rs::{FpContext, FpDeviceManager, FpManagerEvent, FpOperation, FpOperationResult};
+///
+/// let ctx = FpContext::new();
+/// let mut manager = FpDeviceManager::new(&ctx);
+/// let mut events = manager.events().unwrap();
+/// let gallery = load_gallery();
+///
+/// manager.start_all(FpOperation::Open);
+/// manager.start_all(FpOperation::Identify(gallery));
+/// glib::MainContext::default().block_on(async {
+///     while let Some(event) = events.next().await {
+///         if let FpManagerEvent::Completed { device, result: Ok(FpOperationResult::Identified(result)) } = event {
+///             let Some(print) = result.print() else { continue };
+///             println!("{:?} identified on {}", print.username(), device);
+///             manager.cancel_all();
+///             break;
+///         }
+///     }
+/// });
+/// ```
+pub struct FpDeviceManager {
+    workers: Rc<RefCell<Vec<Worker>>>,
+    events: mpsc::UnboundedSender<FpManagerEvent>,
+    receiver: Option<mpsc::UnboundedReceiver<FpManagerEvent>>,
+    hotplug: glib::JoinHandle<()>,
+    policy: Vec<FpSelectionPreference>,
+    hooks: SharedHooks,
+}
+
+impl FpDeviceManager {
+    /// Creates a manager for the devices of `context`, including the devices plugged later.
+    pub fn new(context: &FpContext) -> Self {
+        let (events, receiver) = mpsc::unbounded();
+        let hooks = SharedHooks::default();
+        let workers = Rc::new(RefCell::new(Vec::new()));
+        for device in context.devices() {
+            workers
+                .borrow_mut()
+                .push(spawn_worker(device, events.clone(), hooks.clone()));
+        }
+
+        let hotplug = {
+            let workers = workers.clone();
+            let events = events.clone();
+            let hooks = hooks.clone();
+            let mut device_events = context.device_events();
+            glib::MainContext::ref_thread_default().spawn_local(async move {
+                while let Some(event) =
+                    poll_fn(|cx| Pin::new(&mut device_events).poll_next(cx)).await
+                {
+                    match event {
+                        FpDeviceEvent::Added(device) => {
+                            workers.borrow_mut().push(spawn_worker(
+                                device.clone(),
+                                events.clone(),
+                                hooks.clone(),
+                            ));
+                            let _ = events.unbounded_send(FpManagerEvent::Added(device));
+                        }
+                        FpDeviceEvent::Removed(device) => {
+                            workers
+                                .borrow_mut()
+                                .retain(|worker| worker.device != device);
+                            let _ = events.unbounded_send(FpManagerEvent::Removed(device));
+                        }
+                    }
+                }
+            })
+        };
+
+        FpDeviceManager {
+            workers,
+            events,
+            receiver: Some(receiver),
+            hotplug,
+            policy: Vec::new(),
+            hooks,
+        }
+    }
+
+    /// Returns the managed devices.
+    pub fn devices(&self) -> Vec<FpDevice> {
+        self.workers
+            .borrow()
+            .iter()
+            .map(|worker| worker.device.clone())
+            .collect()
+    }
+    /// Set the policy used by `FpDeviceManager::select`, preferences are ordered from the most to the least
+    /// important one. The default policy is empty, selecting the first enumerated device.
+    pub fn set_selection_policy(&mut self, policy: Vec<FpSelectionPreference>) {
+        self.policy = policy;
+    }
+    /// Returns the device preferred by the selection policy. Devices that are equally preferred are selected in
+    /// enumeration order.
+    pub fn select(&self) -> Option<FpDevice> {
+        let mut selected: Option<(Vec<bool>, FpDevice)> = None;
+        for device in self.devices() {
+            let score: Vec<bool> = self
+                .policy
+                .iter()
+                .map(|preference| preference.prefers(&device))
+                .collect();
+            if selected.as_ref().is_none_or(|(best, _)| score > *best) {
+                selected = Some((score, device));
+            }
+        }
+        selected.map(|(_, device)| device)
+    }
+    /// Queue `operation` on the device returned by `FpDeviceManager::select`, returning it.
+    pub fn start_selected(&self, operation: FpOperation) -> Option<FpDevice> {
+        let device = self.select()?;
+        self.start(&device, operation);
+        Some(device)
+    }
+
+    /// Report the enrollments, matches and failures of the operations of every device to `sink`, replacing the
+    /// previous sink. The operations already running report to the previous one.
+    pub fn set_audit_sink(&mut self, sink: impl AuditSink + 'static) {
+        self.hooks.borrow_mut().audit = Some(Rc::new(sink));
+    }
+    /// Limit the failed verifications of each user with `limiter`, replacing the previous limiter. The
+    /// verifications of a user who is locked out fail with `gio::IOErrorEnum::PermissionDenied`, without
+    /// scanning. Only verifications count, identifications do not know the user.
+    pub fn set_attempt_limiter(&mut self, limiter: FpAttemptLimiter) {
+        self.hooks.borrow_mut().limiter = Some(limiter);
+    }
+    /// Lift the lockout of `username`, see `FpAttemptLimiter::reset`.
+    pub fn unlock(&self, username: &str) {
+        if let Some(limiter) = self.hooks.borrow_mut().limiter.as_mut() {
+            limiter.reset(username);
+        }
+    }
+
+    #[cfg(not(doctest))]
+    /// Delete every print of `username`, from `store` and from the storage of every managed device having one,
+    /// e.g. to honour a deletion request of the user.
+    ///
+    /// The devices are used synchronously, the operations queued on them should be cancelled first or their
+    /// deletion may fail because they are busy. Closed devices are opened for the deletion and closed after.
+    /// Every location is tried even if another one failed, see `FpForgetReport::is_complete`.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{store::MemoryStore, FpContext, FpDeviceManager};
+    ///
+    /// let ctx = FpContext::new();
+    /// let manager = FpDeviceManager::new(&ctx);
+    /// let mut store: MemoryStore = load_store();
+    ///
+    /// manager.cancel_all();
+    /// let report = manager.forget_user("bruce", &mut store);
+    /// assert!(report.is_complete(), "{:?}", report);
+    /// ```
+    pub fn forget_user<S: PrintStore + ?Sized>(
+        &self,
+        username: &str,
+        store: &mut S,
+    ) -> FpForgetReport {
+        let devices = self
+            .devices()
+            .into_iter()
+            .filter(|device| device.supports(FpDeviceFeature::StorageList))
+            .map(|device| {
+                let result = forget_on_device(&device, username);
+                (device, result)
+            })
+            .collect();
+        FpForgetReport {
+            store: store.delete_user(username),
+            devices,
+        }
+    }
+
+    /// Returns the stream of events of every device. The stream can only be taken once, `None` is returned after.
+    pub fn events(&mut self) -> Option<mpsc::UnboundedReceiver<FpManagerEvent>> {
+        self.receiver.take()
+    }
+
+    /// Queue `operation` on `device`, returning `false` if the device is not managed.
+    pub fn start(&self, device: &FpDevice, operation: FpOperation) -> bool {
+        self.workers
+            .borrow()
+            .iter()
+            .find(|worker| worker.device == *device)
+            .map(|worker| worker.queue.unbounded_send(operation).is_ok())
+            .unwrap_or(false)
+    }
+    /// Queue `operation` on every managed device.
+    pub fn start_all(&self, operation: FpOperation) {
+        for worker in self.workers.borrow().iter() {
+            let _ = worker.queue.unbounded_send(operation.clone());
+        }
+    }
+    /// Cancel the operation running on `device`, the following queued operations still run.
+    pub fn cancel(&self, device: &FpDevice) {
+        if let Some(worker) = self
+            .workers
+            .borrow()
+            .iter()
+            .find(|worker| worker.device == *device)
+        {
+            worker.cancellable.borrow().cancel();
+        }
+    }
+    /// Cancel the operations running on every managed device.
+    pub fn cancel_all(&self) {
+        for worker in self.workers.borrow().iter() {
+            worker.cancellable.borrow().cancel();
+        }
+    }
+}
+
+impl Drop for FpDeviceManager {
+    fn drop(&mut self) {
+        // Dropping the queues stops the workers once their running operation completes
+        self.hotplug.abort();
+        self.workers.borrow_mut().clear();
+        self.events.close_channel();
+    }
+}
+
+/// Delete the prints of `username` stored on `device`, returning their number.
+fn forget_on_device(device: &FpDevice, username: &str) -> Result<usize, crate::GError> {
+    let opened = !device.is_open();
+    if opened {
+        device.open_sync(None)?;
+    }
+    let deleted = device.list_prints_sync(None).and_then(|prints| {
+        let mut deleted = 0;
+        for print in prints {
+            if print.username().as_deref() == Some(username) {
+                device.delete_print_sync(&print, None)?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    });
+    if opened {
+        let closed = device.close_sync(None);
+        // The deletion error matters more than the closing one
+        return deleted.and_then(|deleted| closed.map(|()| deleted));
+    }
+    deleted
+}
+
+fn spawn_worker(
+    device: FpDevice,
+    events: mpsc::UnboundedSender<FpManagerEvent>,
+    hooks: SharedHooks,
+) -> Worker {
+    let (queue, mut receiver) = mpsc::unbounded();
+    let cancellable = Rc::new(RefCell::new(Cancellable::new()));
+
+    let worker = Worker {
+        device: device.clone(),
+        queue,
+        cancellable: cancellable.clone(),
+    };
+    glib::MainContext::ref_thread_default().spawn_local(async move {
+        while let Some(operation) = poll_fn(|cx| Pin::new(&mut receiver).poll_next(cx)).await {
+            let current = Cancellable::new();
+            *cancellable.borrow_mut() = current.clone();
+
+            let sink = hooks.borrow().audit.clone();
+            // The print the operation is about, for the audit records
+            let subject = match &operation {
+                FpOperation::Enroll(print) | FpOperation::Verify(print) => Some(print.clone()),
+                _ => None,
+            };
+            if let (Some(sink), FpOperation::Enroll(template)) = (&sink, &operation) {
+                sink.on_enroll_started(&AuditRecord::new(&device, Some(template)));
+            }
+
+            let verified = match &operation {
+                FpOperation::Verify(print) => print.username(),
+                _ => None,
+            };
+            // The limiter checks the lockout and reserves the attempt in one step, the verifications of the
+            // user on other devices are refused until its outcome is recorded
+            let limited = verified
+                .as_deref()
+                .filter(|_| hooks.borrow().limiter.is_some());
+            let refused = limited.and_then(|username| {
+                let mut hooks = hooks.borrow_mut();
+                let refusal = hooks.limiter.as_mut()?.begin(username).err()?;
+                Some(match refusal {
+                    FpAttemptRefusal::LockedOut(remaining) => crate::GError::new(
+                        gio::IOErrorEnum::PermissionDenied,
+                        &format!(
+                            "Too many failed attempts for {}, retry in {} s",
+                            username,
+                            remaining.as_secs().max(1)
+                        ),
+                    ),
+                    FpAttemptRefusal::Pending => crate::GError::new(
+                        gio::IOErrorEnum::Busy,
+                        &format!("{} is already verifying on another device", username),
+                    ),
+                })
+            });
+
+            let begun = limited.filter(|_| refused.is_none());
+            let result = match refused {
+                Some(err) => Err(err),
+                None => run(&device, operation, &current).await,
+            };
+            if let Some(username) = begun {
+                let matched = match &result {
+                    Ok(FpOperationResult::Verified(FpMatchOutcome::Matched(_))) => Some(true),
+                    Ok(FpOperationResult::Verified(FpMatchOutcome::NotMatched(_))) => Some(false),
+                    _ => None,
+                };
+                if let Some(limiter) = hooks.borrow_mut().limiter.as_mut() {
+                    limiter.finish(username, matched);
+                }
+            }
+            if let Some(sink) = sink {
+                report(&*sink, &device, subject.as_ref(), &result);
+            }
+            let _ = events.unbounded_send(FpManagerEvent::Completed {
+                device: device.clone(),
+                result,
+            });
+        }
+    });
+    worker
+}
+
+/// Report the result of an operation about `subject` to `sink`.
+fn report(
+    sink: &dyn AuditSink,
+    device: &FpDevice,
+    subject: Option<&FpPrint>,
+    result: &Result<FpOperationResult, crate::GError>,
+) {
+    match result {
+        Ok(FpOperationResult::Enrolled(print)) => {
+            sink.on_enroll_completed(&AuditRecord::new(device, Some(print)))
+        }
+        Ok(FpOperationResult::Verified(FpMatchOutcome::Matched(_))) => {
+            sink.on_match(&AuditRecord::new(device, subject))
+        }
+        Ok(FpOperationResult::Verified(FpMatchOutcome::NotMatched(_))) => {
+            sink.on_no_match(&AuditRecord::new(device, subject))
+        }
+        Ok(FpOperationResult::Identified(result)) => match result.print() {
+            Some(print) => sink.on_match(&AuditRecord::new(device, Some(print))),
+            None => sink.on_no_match(&AuditRecord::new(device, None)),
+        },
+        // A scan to retry is not an attempt
+        Ok(_) => {}
+        Err(err) => sink.on_error(
+            &AuditRecord::new(device, subject),
+            &FpError::GLib(err.clone()),
+        ),
+    }
+}
+
+async fn run(
+    device: &FpDevice,
+    operation: FpOperation,
+    cancellable: &Cancellable,
+) -> Result<FpOperationResult, crate::GError> {
+    let (sender, receiver) = oneshot::channel();
+    let cancellable = Some(cancellable);
+    match operation {
+        FpOperation::Open => device.open(cancellable, move |res| {
+            let _ = sender.send(res.map(|()| FpOperationResult::Opened));
+        }),
+        FpOperation::Close => device.close(cancellable, move |res| {
+            let _ = sender.send(res.map(|()| FpOperationResult::Closed));
+        }),
+        FpOperation::Enroll(template) => device.enroll(
+            template,
+            cancellable,
+            None::<FpEnrollProgress<()>>,
+            None,
+            move |res| {
+                let _ = sender.send(res.map(FpOperationResult::Enrolled));
+            },
+        ),
+        FpOperation::Verify(print) => device.verify(
+            &print,
+            cancellable,
+            None::<FpMatchCb<()>>,
+            None,
+            move |res| {
+                let _ = sender.send(res.map(FpOperationResult::Verified));
+            },
+        ),
+        FpOperation::Identify(prints) => device.identify(
+            &prints,
+            cancellable,
+            None::<FpMatchCb<()>>,
+            None,
+            move |res| {
+                let _ = sender.send(res.map(FpOperationResult::Identified));
+            },
+        ),
+        FpOperation::Capture(wait_for_finger) => {
+            device.capture(wait_for_finger, cancellable, move |res| {
+                let _ = sender.send(res.map(FpOperationResult::Captured));
+            })
+        }
+    }
+    receiver.await.unwrap_or_else(|_| {
+        Err(crate::GError::new(
+            gio::IOErrorEnum::Cancelled,
+            "The operation was dropped",
+        ))
+    })
+}
@@ -0,0 +1,359 @@
+//! Host side matching with NBIS, the minutiae detector and matcher libfprint uses for the prints of image
+//! devices (requires the `host-matching` feature).
+//!
+//! The mindtct detector and the bozorth3 matcher of the NIST Biometric Image Software are bundled in `nbis/`
+//! and built with the crate, which requires a C compiler. `FpMinutiaeTemplate::from_image` detects the minutiae
+//! of an image with the direction of their ridges, as libfprint does when enrolling or verifying on an image
+//! device, and `FpMinutiaeTemplate::from_print` reads the templates of a print enrolled on an image device, so
+//! externally captured images can be compared with each other or with the prints of image devices. The scores
+//! are the ones libfprint computes, from which image drivers report a match at `BOZORTH3_THRESHOLD` unless they
+//! set their own threshold.
+//!
+//! libfprint removes the minutiae on the perimeter of partial images, `FpImageFlags::PARTIAL`, this NBIS
+//! release can not, so the scores of partial swipes may differ slightly. NBIS is not reentrant, the detections
+//! and matches of all threads run one at a time.
+//! # Example:
+//! ```no_run
+//! use libfprint_rs::{
+//!     matching::{FpMinutiaeTemplate, BOZORTH3_THRESHOLD},
+//!     FpContext, FpPrint,
+//! };
+//!
+//! let ctx = FpContext::new();
+//! let device = ctx.devices().remove(0);
+//! let enrolled_print = FpPrint::deserialize(&std::fs::read("print.bin").unwrap()).unwrap();
+//!
+//! device.open_sync(None).unwrap();
+//! let image = device.capture_sync(true, None).unwrap();
+//! let score = enrolled_print.match_image(&image).unwrap();
+//! println!("Score {}, matched: {}", score, score >= BOZORTH3_THRESHOLD);
+//!
+//! let template = FpMinutiaeTemplate::from_image(&image).unwrap();
+//! println!("{} minutiae", template.minutiae().len());
+//! ```
+mod nbis;
+
+use glib::Variant;
+
+use crate::{error::FpError, image::FpImage, print::FpPrint, serialized::PRINT_TYPE_NBIS};
+use nbis::{XytStruct, MAX_BOZORTH_MINUTIAE};
+
+/// Score from which libfprint reports that a template matches, for the image drivers that do not set their own
+/// threshold.
+pub const BOZORTH3_THRESHOLD: i32 = 40;
+
+/// A minutia of an `FpMinutiaeTemplate`, in the NIST convention of bozorth3: the origin is the bottom left corner
+/// of the image and `theta` is the direction of the ridge in degrees, counterclockwise and between -180 and 180.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FpTemplateMinutia {
+    pub x: i32,
+    pub y: i32,
+    pub theta: i32,
+}
+
+/// The minutiae bozorth3 compares, as libfprint keeps them in the prints of image devices. Only the first 200
+/// minutiae are compared.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FpMinutiaeTemplate {
+    minutiae: Vec<FpTemplateMinutia>,
+}
+
+impl FpMinutiaeTemplate {
+    /// Creates a template from its minutiae, e.g. from a `.xyt` file of the NBIS tools.
+    pub fn new(minutiae: Vec<FpTemplateMinutia>) -> Self {
+        FpMinutiaeTemplate { minutiae }
+    }
+    /// Detects the minutiae of `image` with mindtct, as libfprint does for the images of image devices. The image
+    /// is first put in the standard orientation, see `FpImage::standardized`.
+    ///
+    /// The detection errors and images without minutiae are reported as `G_IO_ERROR_FAILED`, as libfprint
+    /// reports them.
+    pub fn from_image(image: &FpImage) -> Result<Self, FpError> {
+        let (width, height) = (image.width() as i32, image.height() as i32);
+        let mut data = image.standardized().data();
+
+        let mut minutiae = {
+            let _nbis = nbis::lock();
+            unsafe { detect(&mut data, width, height, image.ppmm()) }
+        }
+        .map_err(|code| {
+            FpError::GLib(glib::Error::new(
+                gio::IOErrorEnum::Failed,
+                &format!("Minutiae scan failed with code {}", code),
+            ))
+        })?;
+        if minutiae.is_empty() {
+            return Err(FpError::GLib(glib::Error::new(
+                gio::IOErrorEnum::Failed,
+                "No minutiae found",
+            )));
+        }
+
+        minutiae.truncate(MAX_BOZORTH_MINUTIAE);
+        minutiae.sort_by_key(|minutia| (minutia.x, minutia.y));
+        Ok(FpMinutiaeTemplate { minutiae })
+    }
+    /// Returns the templates of `print`, one per enroll stage, if it was enrolled on an image device. The prints
+    /// of the devices matching on the chip hold no minutiae, `G_IO_ERROR_NOT_SUPPORTED` is returned for them.
+    pub fn from_print(print: &FpPrint) -> Result<Vec<Self>, FpError> {
+        let variant = print.as_variant()?;
+        if variant.child_value(0).get::<i32>() != Some(PRINT_TYPE_NBIS) {
+            return Err(FpError::GLib(glib::Error::new(
+                gio::IOErrorEnum::NotSupported,
+                "The print was not enrolled on an image device",
+            )));
+        }
+        let data = variant
+            .child_value(9)
+            .as_variant()
+            .ok_or(FpError::CorruptPrint("missing print data"))?;
+        templates_from_data(&data)
+    }
+
+    /// Returns the minutiae of the template.
+    pub fn minutiae(&self) -> &[FpTemplateMinutia] {
+        &self.minutiae
+    }
+    /// Compares the template, as the probe, with `gallery`, returning the bozorth3 score.
+    pub fn score(&self, gallery: &FpMinutiaeTemplate) -> i32 {
+        self.best_score(std::slice::from_ref(gallery))
+    }
+    /// Whether the score of `FpMinutiaeTemplate::score` reaches `BOZORTH3_THRESHOLD`.
+    pub fn matches(&self, gallery: &FpMinutiaeTemplate) -> bool {
+        self.score(gallery) >= BOZORTH3_THRESHOLD
+    }
+
+    /// Returns the best score of the template against the templates of `gallery`, 0 if it is empty.
+    fn best_score(&self, gallery: &[FpMinutiaeTemplate]) -> i32 {
+        let mut probe = self.xyt();
+        let _nbis = nbis::lock();
+        let probe_len = unsafe { nbis::bozorth_probe_init(&mut *probe) };
+        gallery
+            .iter()
+            .map(|template| {
+                let mut gallery = template.xyt();
+                unsafe { nbis::bozorth_to_gallery(probe_len, &mut *probe, &mut *gallery) }
+            })
+            .max()
+            .unwrap_or(0)
+    }
+    fn xyt(&self) -> Box<XytStruct> {
+        let mut xyt = Box::new(XytStruct {
+            nrows: 0,
+            xcol: [0; MAX_BOZORTH_MINUTIAE],
+            ycol: [0; MAX_BOZORTH_MINUTIAE],
+            thetacol: [0; MAX_BOZORTH_MINUTIAE],
+        });
+        for (i, minutia) in self.minutiae.iter().take(MAX_BOZORTH_MINUTIAE).enumerate() {
+            xyt.xcol[i] = minutia.x;
+            xyt.ycol[i] = minutia.y;
+            xyt.thetacol[i] = minutia.theta;
+            xyt.nrows += 1;
+        }
+        xyt
+    }
+}
+
+/// Runs mindtct on the 8 bit greyscale `data`, returning the minutiae in the order it detected them, or its error
+/// code. NBIS must be locked.
+unsafe fn detect(
+    data: &mut [u8],
+    width: i32,
+    height: i32,
+    ppmm: f64,
+) -> Result<Vec<FpTemplateMinutia>, i32> {
+    let mut minutiae = std::ptr::null_mut();
+    let mut maps = [std::ptr::null_mut(); 5];
+    let (mut map_width, mut map_height) = (0, 0);
+    let mut binarized = std::ptr::null_mut();
+    let (mut binarized_width, mut binarized_height, mut binarized_depth) = (0, 0, 0);
+
+    let [quality, direction, low_contrast, low_flow, high_curve] = &mut maps;
+    let code = nbis::get_minutiae(
+        &mut minutiae,
+        quality,
+        direction,
+        low_contrast,
+        low_flow,
+        high_curve,
+        &mut map_width,
+        &mut map_height,
+        &mut binarized,
+        &mut binarized_width,
+        &mut binarized_height,
+        &mut binarized_depth,
+        data.as_mut_ptr(),
+        width,
+        height,
+        8,
+        ppmm,
+        &nbis::lfsparms_V2,
+    );
+    for map in maps {
+        libc::free(map.cast());
+    }
+    libc::free(binarized.cast());
+    if code != 0 {
+        return Err(code);
+    }
+    if minutiae.is_null() {
+        return Ok(Vec::new());
+    }
+
+    let list = match (*minutiae).list.is_null() {
+        true => &[][..],
+        false => std::slice::from_raw_parts((*minutiae).list, (*minutiae).num.max(0) as usize),
+    };
+    let converted = list
+        .iter()
+        .map(|&minutia| {
+            let (mut x, mut y, mut theta) = (0, 0, 0);
+            nbis::lfs2nist_minutia_XYT(&mut x, &mut y, &mut theta, minutia, width, height);
+            // libfprint stores the directions between -180 and 180
+            if theta > 180 {
+                theta -= 360;
+            }
+            FpTemplateMinutia { x, y, theta }
+        })
+        .collect();
+    nbis::free_minutiae(minutiae);
+    Ok(converted)
+}
+
+/// Reads the templates of the print data of an NBIS print, of type `(a(aiaiai))`: the x, y and theta columns of
+/// each template.
+fn templates_from_data(data: &Variant) -> Result<Vec<FpMinutiaeTemplate>, FpError> {
+    let corrupt = || FpError::CorruptPrint("malformed minutiae template");
+    if data.type_().as_str() != "(a(aiaiai))" {
+        return Err(corrupt());
+    }
+    let mut templates = Vec::new();
+    for template in data.child_value(0).iter() {
+        let column = |index| template.child_value(index).get::<Vec<i32>>();
+        let (Some(x), Some(y), Some(theta)) = (column(0), column(1), column(2)) else {
+            return Err(corrupt());
+        };
+        if x.len() != y.len() || x.len() != theta.len() || x.len() > MAX_BOZORTH_MINUTIAE {
+            return Err(corrupt());
+        }
+        let minutiae = (0..x.len())
+            .map(|i| FpTemplateMinutia {
+                x: x[i],
+                y: y[i],
+                theta: theta[i],
+            })
+            .collect();
+        templates.push(FpMinutiaeTemplate { minutiae });
+    }
+    Ok(templates)
+}
+
+impl FpImage {
+    /// Compares the minutiae of two images with bozorth3, `self` being the probe, see
+    /// `FpMinutiaeTemplate::from_image`.
+    pub fn match_score(&self, other: &FpImage) -> Result<i32, FpError> {
+        let probe = FpMinutiaeTemplate::from_image(self)?;
+        Ok(probe.score(&FpMinutiaeTemplate::from_image(other)?))
+    }
+}
+
+impl FpPrint {
+    /// Compares `image` with the templates of the print, enrolled on an image device, returning the best
+    /// bozorth3 score, as libfprint does when verifying the print. See `FpMinutiaeTemplate::from_print`.
+    pub fn match_image(&self, image: &FpImage) -> Result<i32, FpError> {
+        let templates = FpMinutiaeTemplate::from_print(self)?;
+        Ok(FpMinutiaeTemplate::from_image(image)?.best_score(&templates))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glib::{ToVariant, Variant, VariantTy};
+
+    use super::{templates_from_data, FpMinutiaeTemplate, FpTemplateMinutia, BOZORTH3_THRESHOLD};
+    use crate::error::FpError;
+
+    fn random_template(seed: u64, count: usize) -> FpMinutiaeTemplate {
+        let mut state = seed;
+        let mut next = move |range: u64| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((state >> 33) % range) as i32
+        };
+        let mut minutiae: Vec<FpTemplateMinutia> = (0..count)
+            .map(|_| FpTemplateMinutia {
+                x: next(300),
+                y: next(300),
+                theta: next(360) - 179,
+            })
+            .collect();
+        minutiae.sort_by_key(|minutia| (minutia.x, minutia.y));
+        FpMinutiaeTemplate::new(minutiae)
+    }
+
+    #[test]
+    fn bozorth3_scores() {
+        let probe = random_template(1, 40);
+        let moved = FpMinutiaeTemplate::new(
+            probe
+                .minutiae()
+                .iter()
+                .map(|minutia| FpTemplateMinutia {
+                    x: minutia.x + 12,
+                    y: minutia.y - 7,
+                    theta: minutia.theta,
+                })
+                .collect(),
+        );
+        let other = random_template(2, 40);
+
+        assert!(probe.score(&moved) >= BOZORTH3_THRESHOLD);
+        assert!(probe.matches(&probe));
+        assert!(!probe.matches(&other));
+        assert_eq!(probe.score(&FpMinutiaeTemplate::default()), 0);
+    }
+
+    #[test]
+    fn nbis_print_data() {
+        let template = |columns: [&[i32]; 3]| {
+            Variant::tuple_from_iter(columns.iter().map(|column| column.to_variant()))
+        };
+        let ty = VariantTy::new("(aiaiai)").unwrap();
+        let data = Variant::tuple_from_iter([Variant::array_from_iter_with_type(
+            ty,
+            [
+                template([&[1, 2], &[3, 4], &[-90, 180]]),
+                template([&[], &[], &[]]),
+            ],
+        )]);
+
+        let templates = templates_from_data(&data).unwrap();
+        assert_eq!(templates.len(), 2);
+        assert_eq!(
+            templates[0].minutiae(),
+            &[
+                FpTemplateMinutia {
+                    x: 1,
+                    y: 3,
+                    theta: -90
+                },
+                FpTemplateMinutia {
+                    x: 2,
+                    y: 4,
+                    theta: 180
+                },
+            ]
+        );
+        assert!(templates[1].minutiae().is_empty());
+
+        let uneven = Variant::tuple_from_iter([Variant::array_from_iter_with_type(
+            ty,
+            [template([&[1, 2], &[3], &[0, 0]])],
+        )]);
+        assert!(matches!(
+            templates_from_data(&uneven),
+            Err(FpError::CorruptPrint(_))
+        ));
+    }
+}
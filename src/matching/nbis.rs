@@ -0,0 +1,93 @@
+//! Bindings of the NBIS libraries bundled in `nbis/`, built by the build script.
+use std::{
+    os::raw::{c_double, c_int, c_uchar},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// Maximum number of minutiae of an `XytStruct`, `MAX_BOZORTH_MINUTIAE`.
+pub(super) const MAX_BOZORTH_MINUTIAE: usize = 200;
+
+/// The minutiae bozorth3 compares, `struct xyt_struct`.
+#[repr(C)]
+pub(super) struct XytStruct {
+    pub nrows: c_int,
+    pub xcol: [c_int; MAX_BOZORTH_MINUTIAE],
+    pub ycol: [c_int; MAX_BOZORTH_MINUTIAE],
+    pub thetacol: [c_int; MAX_BOZORTH_MINUTIAE],
+}
+
+/// A minutia detected by mindtct, `MINUTIA`, only read by `lfs2nist_minutia_XYT`.
+#[repr(C)]
+pub(super) struct Minutia {
+    _private: [u8; 0],
+}
+
+/// The minutiae detected by mindtct, `MINUTIAE`.
+#[repr(C)]
+pub(super) struct Minutiae {
+    pub alloc: c_int,
+    pub num: c_int,
+    pub list: *mut *mut Minutia,
+}
+
+/// The parameters of mindtct, `LFSPARMS`.
+#[repr(C)]
+pub(super) struct LfsParms {
+    _private: [u8; 0],
+}
+
+extern "C" {
+    /// The parameters libfprint detects the minutiae with.
+    pub(super) static lfsparms_V2: LfsParms;
+
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn get_minutiae(
+        ominutiae: *mut *mut Minutiae,
+        oquality_map: *mut *mut c_int,
+        odirection_map: *mut *mut c_int,
+        olow_contrast_map: *mut *mut c_int,
+        olow_flow_map: *mut *mut c_int,
+        ohigh_curve_map: *mut *mut c_int,
+        omap_w: *mut c_int,
+        omap_h: *mut c_int,
+        obdata: *mut *mut c_uchar,
+        obw: *mut c_int,
+        obh: *mut c_int,
+        obd: *mut c_int,
+        idata: *mut c_uchar,
+        iw: c_int,
+        ih: c_int,
+        id: c_int,
+        ippmm: c_double,
+        lfsparms: *const LfsParms,
+    ) -> c_int;
+    pub(super) fn free_minutiae(minutiae: *mut Minutiae);
+    pub(super) fn lfs2nist_minutia_XYT(
+        ox: *mut c_int,
+        oy: *mut c_int,
+        ot: *mut c_int,
+        minutia: *const Minutia,
+        iw: c_int,
+        ih: c_int,
+    );
+
+    pub(super) fn bozorth_probe_init(pstruct: *mut XytStruct) -> c_int;
+    pub(super) fn bozorth_to_gallery(
+        probe_len: c_int,
+        pstruct: *mut XytStruct,
+        gstruct: *mut XytStruct,
+    ) -> c_int;
+
+    /// Defined in `nbis/globals.c`.
+    fn fprs_nbis_init();
+}
+
+/// NBIS keeps its state in globals, the calls of all threads are serialized.
+static NBIS: Mutex<()> = Mutex::new(());
+
+/// Locks NBIS for the calling thread, until the guard is dropped.
+pub(super) fn lock() -> MutexGuard<'static, ()> {
+    let guard = NBIS.lock().unwrap_or_else(PoisonError::into_inner);
+    unsafe { fprs_nbis_init() };
+    guard
+}
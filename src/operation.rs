@@ -0,0 +1,287 @@
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use gio::{prelude::CancellableExt, Cancellable};
+
+#[cfg(feature = "futures")]
+use crate::device::{FpIdentifyResult, FpMatchOutcome};
+use crate::{
+    device::FpDevice,
+    error::{FpError, FpRetryError},
+    print::FpPrint,
+};
+
+/// The progress of an operation, see `FpOperationHandle::progress`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FpOperationProgress {
+    /// The number of completed enroll stages, 0 for other operations.
+    pub completed_stages: u32,
+    /// The number of enroll stages, 0 for other operations or until the first scan.
+    pub total_stages: u32,
+    /// Why the last scan failed, if it is retried.
+    pub retry: Option<FpRetryError>,
+}
+
+impl FpOperationProgress {
+    /// The completed part of an enrollment, between 0 and 1, e.g. for a progress bar.
+    pub fn fraction(&self) -> f64 {
+        match self.total_stages {
+            0 => 0.0,
+            total => f64::from(self.completed_stages.min(total)) / f64::from(total),
+        }
+    }
+}
+
+struct State<T> {
+    progress: FpOperationProgress,
+    result: Option<Result<T, FpError>>,
+    finished: bool,
+}
+
+struct Shared<T> {
+    cancellable: Cancellable,
+    /// The main context running the operation, for operations started on the calling thread.
+    context: Option<glib::MainContext>,
+    state: Mutex<State<T>>,
+    completed: Condvar,
+}
+
+#[cfg(not(doctest))]
+/// A running operation, to wire a Cancel button and a progress readout to a single object.
+///
+/// The handle is returned by the operations started on a worker thread, e.g. `FpDeviceHandle::enroll`, and
+/// by the asynchronous operations, e.g. `FpDevice::enroll_handle` (requires the `futures` feature). It can be
+/// cloned, e.g. to keep one in the UI and wait for the result elsewhere.
+/// # Example:
+/// ```no_run
+/// use std::time::Duration;
+/// use libfprint_rs::{FpDeviceHandle, FpFinger};
+///
+/// let (device, _events) = FpDeviceHandle::spawn(|dev| dev.driver() == "goodixmoc");
+/// let enrollment = device.enroll("bruce", FpFinger::RightIndex).unwrap();
+///
+/// let cancel = enrollment.clone();
+/// cancel_button.connect_clicked(move |_| cancel.cancel());
+/// glib::timeout_add_local(Duration::from_millis(100), move || {
+///     progress_bar.set_fraction(enrollment.progress().fraction());
+///     match enrollment.is_finished() {
+///         true => glib::ControlFlow::Break,
+///         false => glib::ControlFlow::Continue,
+///     }
+/// });
+/// ```
+pub struct FpOperationHandle<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Clone for FpOperationHandle<T> {
+    fn clone(&self) -> Self {
+        FpOperationHandle {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for FpOperationHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FpOperationHandle")
+            .field("progress", &self.progress())
+            .field("finished", &self.is_finished())
+            .finish()
+    }
+}
+
+impl<T> FpOperationHandle<T> {
+    /// Creates the handle of an operation, running on `context` if it is driven by the calling thread.
+    pub(crate) fn new(context: Option<glib::MainContext>) -> Self {
+        FpOperationHandle {
+            shared: Arc::new(Shared {
+                cancellable: Cancellable::new(),
+                context,
+                state: Mutex::new(State {
+                    progress: FpOperationProgress::default(),
+                    result: None,
+                    finished: false,
+                }),
+                completed: Condvar::new(),
+            }),
+        }
+    }
+    /// The cancellable to pass to the operation.
+    pub(crate) fn cancellable(&self) -> &Cancellable {
+        &self.shared.cancellable
+    }
+    /// Update the progress of the operation.
+    pub(crate) fn update(&self, update: impl FnOnce(&mut FpOperationProgress)) {
+        update(&mut self.lock().progress);
+    }
+    /// Record the result of the operation, waking up `FpOperationHandle::wait`.
+    pub(crate) fn finish(&self, result: Result<T, FpError>) {
+        let mut state = self.lock();
+        state.result = Some(result);
+        state.finished = true;
+        self.shared.completed.notify_all();
+    }
+
+    /// Finish an operation that will never run with a `gio::IOErrorEnum::Cancelled` error, unless it finished.
+    pub(crate) fn abandon(&self) {
+        if !self.is_finished() {
+            let err = glib::Error::new(
+                gio::IOErrorEnum::Cancelled,
+                "The operation was dropped before it started",
+            );
+            self.finish(Err(err.into()));
+        }
+    }
+
+    /// Cancel the operation. It still has to complete, with a `gio::IOErrorEnum::Cancelled` error unless it
+    /// completed meanwhile.
+    pub fn cancel(&self) {
+        self.shared.cancellable.cancel();
+    }
+    /// Whether the operation completed.
+    pub fn is_finished(&self) -> bool {
+        self.lock().finished
+    }
+    /// The progress of the operation so far.
+    pub fn progress(&self) -> FpOperationProgress {
+        self.lock().progress
+    }
+    /// Block until the operation completes, returning its result. Operations started on the calling thread
+    /// iterate its main context meanwhile.
+    ///
+    /// # Panics
+    /// The result is taken by the first call, waiting again from a clone of the handle panics.
+    pub fn wait(self) -> Result<T, FpError> {
+        if let Some(context) = &self.shared.context {
+            while !self.is_finished() {
+                context.iteration(true);
+            }
+        }
+        let mut state = self.lock();
+        while !state.finished {
+            state = self
+                .shared
+                .completed
+                .wait(state)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+        state
+            .result
+            .take()
+            .expect("the result of the operation was already taken")
+    }
+
+    /// The state is only updated by short critical sections, a poisoned lock is still consistent.
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.shared
+            .state
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// The enroll progress callback of the operations with a handle.
+pub(crate) fn enroll_progress<T>(
+    device: &FpDevice,
+    completed_stages: i32,
+    _print: Option<FpPrint>,
+    error: Option<crate::GError>,
+    handle: &Option<FpOperationHandle<T>>,
+) {
+    if let Some(handle) = handle {
+        let total_stages = device.nr_enroll_stage().max(0) as u32;
+        handle.update(|progress| {
+            progress.completed_stages = completed_stages.max(0) as u32;
+            progress.total_stages = total_stages;
+            progress.retry = error.map(|err| err.kind().unwrap_or(FpRetryError::General));
+        });
+    }
+}
+
+/// The match callback of the operations with a handle.
+pub(crate) fn match_progress<T>(
+    _device: &FpDevice,
+    _matched: Option<FpPrint>,
+    _print: Option<FpPrint>,
+    error: Option<crate::GError>,
+    handle: &Option<FpOperationHandle<T>>,
+) {
+    if let Some(handle) = handle {
+        handle.update(|progress| {
+            progress.retry = error.map(|err| err.kind().unwrap_or(FpRetryError::General));
+        });
+    }
+}
+
+#[cfg(feature = "futures")]
+impl FpDevice {
+    /// Start an asynchronous operation to enroll a new print, returning its handle. The operation runs on the
+    /// thread default main context, see `FpDevice::enroll`.
+    pub fn enroll_handle(&self, template: FpPrint) -> FpOperationHandle<FpPrint> {
+        let handle = FpOperationHandle::new(Some(glib::MainContext::ref_thread_default()));
+        let done = handle.clone();
+        self.enroll(
+            template,
+            Some(handle.cancellable()),
+            Some(enroll_progress::<FpPrint>),
+            Some(handle.clone()),
+            move |res| done.finish(res.map_err(FpError::from)),
+        );
+        handle
+    }
+    /// Start an asynchronous operation to verify a given print, returning its handle. See `FpDevice::verify`.
+    pub fn verify_handle(&self, enrolled_print: &FpPrint) -> FpOperationHandle<FpMatchOutcome> {
+        let handle = FpOperationHandle::new(Some(glib::MainContext::ref_thread_default()));
+        let done = handle.clone();
+        self.verify(
+            enrolled_print,
+            Some(handle.cancellable()),
+            Some(match_progress::<FpMatchOutcome>),
+            Some(handle.clone()),
+            move |res| done.finish(res.map_err(FpError::from)),
+        );
+        handle
+    }
+    /// Start an asynchronous operation to identify a print, returning its handle. See `FpDevice::identify`.
+    pub fn identify_handle<'a, I, G>(&self, prints: I) -> FpOperationHandle<FpIdentifyResult>
+    where
+        I: IntoIterator<Item = &'a G>,
+        G: AsRef<FpPrint> + 'a,
+    {
+        let handle = FpOperationHandle::new(Some(glib::MainContext::ref_thread_default()));
+        let done = handle.clone();
+        self.identify(
+            prints,
+            Some(handle.cancellable()),
+            Some(match_progress::<FpIdentifyResult>),
+            Some(handle.clone()),
+            move |res| done.finish(res.map_err(FpError::from)),
+        );
+        handle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FpOperationHandle, FpOperationProgress};
+    use crate::error::FpRetryError;
+
+    #[test]
+    fn handle() {
+        let handle: FpOperationHandle<u32> = FpOperationHandle::new(None);
+        handle.update(|progress| {
+            progress.completed_stages = 2;
+            progress.total_stages = 8;
+            progress.retry = Some(FpRetryError::CenterFinger);
+        });
+        assert_eq!(handle.progress().fraction(), 0.25);
+        assert_eq!(FpOperationProgress::default().fraction(), 0.0);
+        assert!(!handle.is_finished());
+
+        let waiting = handle.clone();
+        let waiter = std::thread::spawn(move || waiting.wait().unwrap());
+        handle.finish(Ok(7));
+        assert!(handle.is_finished());
+        assert_eq!(waiter.join().unwrap(), 7);
+    }
+}
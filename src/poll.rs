@@ -0,0 +1,263 @@
+use std::{
+    collections::HashMap,
+    io,
+    marker::PhantomData,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    time::Duration,
+};
+
+use glib::translate::ToGlibPtr;
+
+#[cfg(not(doctest))]
+/// A pollable file descriptor driving a `glib::MainContext`, so event loops other than GLib's, e.g. the
+/// `calloop` loop of a Wayland compositor or a `mio` server, can run libfprint without a dedicated thread.
+///
+/// The descriptor is an epoll instance watching the descriptors of the main context: register it for
+/// readability with the event loop, and call `FpMainContextFd::dispatch` when it is readable or when
+/// `FpMainContextFd::timeout` elapsed, e.g. with a `calloop::generic::Generic` source or a
+/// `mio::unix::SourceFd`. The main context is acquired by the calling thread until the value is dropped, so
+/// the `_sync` operations must not be used meanwhile, use the asynchronous ones (requires the `futures`
+/// feature).
+///
+/// GLib only wakes the descriptor up for the sources attached by other threads: after starting an operation
+/// from the thread, call `FpMainContextFd::dispatch` so its sources are watched.
+/// # Example:
+/// ```no_run
+/// use std::os::fd::AsRawFd;
+/// use libfprint_rs::FpMainContextFd;
+///
+/// let mut source = FpMainContextFd::new(&glib::MainContext::default()).unwrap();
+/// loop {
+///     let mut fds = [libc::pollfd { fd: source.as_raw_fd(), events: libc::POLLIN, revents: 0 }];
+///     let timeout = source.timeout().map_or(-1, |timeout| timeout.as_millis() as i32);
+///     unsafe { libc::poll(fds.as_mut_ptr(), 1, timeout) };
+///     source.dispatch().unwrap();
+/// }
+/// ```
+pub struct FpMainContextFd {
+    context: glib::MainContext,
+    epoll: OwnedFd,
+    /// The descriptors of the main context, as returned by its last query.
+    fds: Vec<glib::ffi::GPollFD>,
+    /// The descriptors registered with the epoll instance.
+    registered: Vec<RawFd>,
+    max_priority: i32,
+    timeout: Option<Duration>,
+    /// The main context is acquired by the thread that created the value.
+    _thread: PhantomData<*const ()>,
+}
+
+impl FpMainContextFd {
+    /// Acquire `context` and start watching its descriptors. Fails if another thread owns the context, e.g.
+    /// it runs a `glib::MainLoop`.
+    pub fn new(context: &glib::MainContext) -> io::Result<Self> {
+        let raw = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if raw < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let epoll = unsafe { OwnedFd::from_raw_fd(raw) };
+        let acquired = unsafe { glib::ffi::g_main_context_acquire(context.to_glib_none().0) };
+        if acquired == glib::ffi::GFALSE {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "The main context is owned by another thread",
+            ));
+        }
+        let mut source = FpMainContextFd {
+            context: context.clone(),
+            epoll,
+            fds: Vec::new(),
+            registered: Vec::new(),
+            max_priority: 0,
+            timeout: None,
+            _thread: PhantomData,
+        };
+        source.prepare()?;
+        Ok(source)
+    }
+
+    /// How long the event loop may wait for the descriptor before calling `FpMainContextFd::dispatch`,
+    /// `None` to wait for the descriptor only.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Dispatch the sources of the main context that are ready, then watch its descriptors again. Returns
+    /// whether a source was dispatched.
+    pub fn dispatch(&mut self) -> io::Result<bool> {
+        let raw_context = self.context.to_glib_none().0;
+        let dispatched = unsafe {
+            glib::ffi::g_poll(self.fds.as_mut_ptr(), self.fds.len() as u32, 0);
+            let ready = glib::ffi::g_main_context_check(
+                raw_context,
+                self.max_priority,
+                self.fds.as_mut_ptr(),
+                self.fds.len() as i32,
+            );
+            if ready != glib::ffi::GFALSE {
+                glib::ffi::g_main_context_dispatch(raw_context);
+            }
+            ready != glib::ffi::GFALSE
+        };
+        self.prepare()?;
+        Ok(dispatched)
+    }
+
+    /// Prepare the next iteration of the main context, and register its descriptors with the epoll instance.
+    fn prepare(&mut self) -> io::Result<()> {
+        let raw_context = self.context.to_glib_none().0;
+        let mut timeout = -1;
+        let ready = unsafe {
+            let ready = glib::ffi::g_main_context_prepare(raw_context, &mut self.max_priority);
+            // The query returns the number of descriptors, which may not fit
+            loop {
+                let count = glib::ffi::g_main_context_query(
+                    raw_context,
+                    self.max_priority,
+                    &mut timeout,
+                    self.fds.as_mut_ptr(),
+                    self.fds.len() as i32,
+                )
+                .max(0) as usize;
+                if count <= self.fds.len() {
+                    self.fds.truncate(count);
+                    break;
+                }
+                self.fds.resize(
+                    count,
+                    glib::ffi::GPollFD {
+                        fd: -1,
+                        events: 0,
+                        revents: 0,
+                    },
+                );
+            }
+            ready != glib::ffi::GFALSE
+        };
+        self.timeout = match (ready, u64::try_from(timeout)) {
+            (true, _) => Some(Duration::ZERO),
+            (false, Ok(timeout)) => Some(Duration::from_millis(timeout)),
+            (false, Err(_)) => None,
+        };
+        self.register()
+    }
+
+    /// Replace the descriptors watched by the epoll instance with the ones of the main context.
+    fn register(&mut self) -> io::Result<()> {
+        for fd in self.registered.drain(..) {
+            unsafe {
+                libc::epoll_ctl(
+                    self.epoll.as_raw_fd(),
+                    libc::EPOLL_CTL_DEL,
+                    fd,
+                    std::ptr::null_mut(),
+                )
+            };
+        }
+        // A descriptor can be listed twice by different sources, epoll watches it once
+        let mut events: HashMap<RawFd, u32> = HashMap::new();
+        for fd in &self.fds {
+            *events.entry(fd.fd).or_default() |= epoll_events(fd.events);
+        }
+        for (fd, events) in events {
+            let mut event = libc::epoll_event {
+                events,
+                u64: fd as u64,
+            };
+            let res = unsafe {
+                libc::epoll_ctl(self.epoll.as_raw_fd(), libc::EPOLL_CTL_ADD, fd, &mut event)
+            };
+            if res < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            self.registered.push(fd);
+        }
+        Ok(())
+    }
+}
+
+/// The epoll events of the GLib `GIOCondition` bits.
+fn epoll_events(condition: u16) -> u32 {
+    let condition = u32::from(condition);
+    let mut events = 0;
+    if condition & glib::ffi::G_IO_IN != 0 {
+        events |= libc::EPOLLIN as u32;
+    }
+    if condition & glib::ffi::G_IO_OUT != 0 {
+        events |= libc::EPOLLOUT as u32;
+    }
+    if condition & glib::ffi::G_IO_PRI != 0 {
+        events |= libc::EPOLLPRI as u32;
+    }
+    events
+}
+
+impl AsFd for FpMainContextFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.epoll.as_fd()
+    }
+}
+
+impl AsRawFd for FpMainContextFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.epoll.as_raw_fd()
+    }
+}
+
+impl Drop for FpMainContextFd {
+    fn drop(&mut self) {
+        unsafe { glib::ffi::g_main_context_release(self.context.to_glib_none().0) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        os::fd::AsRawFd,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::FpMainContextFd;
+
+    #[test]
+    fn dispatch() {
+        let context = glib::MainContext::new();
+        let mut source = FpMainContextFd::new(&context).unwrap();
+        assert_eq!(source.timeout(), None);
+
+        let fired = Arc::new(AtomicBool::new(false));
+        let timeout =
+            glib::timeout_source_new(Duration::from_millis(10), None, glib::Priority::DEFAULT, {
+                let fired = fired.clone();
+                move || {
+                    fired.store(true, Ordering::SeqCst);
+                    glib::ControlFlow::Break
+                }
+            });
+        timeout.attach(Some(&context));
+        // The sources attached by the owner thread are watched from the next dispatch
+        assert!(!source.dispatch().unwrap());
+        assert!(source.timeout().unwrap() <= Duration::from_millis(10));
+
+        while !fired.load(Ordering::SeqCst) {
+            let timeout = source.timeout().unwrap();
+            std::thread::sleep(timeout);
+            source.dispatch().unwrap();
+        }
+
+        // Other threads wake the context up through the descriptor
+        let remote = context.clone();
+        std::thread::spawn(move || remote.wakeup()).join().unwrap();
+        let mut fds = [libc::pollfd {
+            fd: source.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+        assert_eq!(unsafe { libc::poll(fds.as_mut_ptr(), 1, 1000) }, 1);
+        source.dispatch().unwrap();
+    }
+}
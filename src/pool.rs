@@ -0,0 +1,164 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use glib::ObjectExt;
+
+use crate::{
+    context::FpContext,
+    device::{is_busy, FpDevice, FpDeviceFeature},
+};
+
+#[cfg(not(doctest))]
+/// Keeps selected devices of a context open, so the first verification after a long idle period does not pay
+/// the cost of opening and initializing the device, often 1 to 2 seconds.
+///
+/// The pool opens the selected devices, including the ones plugged later, and polls them periodically. The poll
+/// probes the open devices that list their storage, with `FpDevice::list_prints_sync`, the only command of
+/// libfprint without side effects: a device that fails the probe is closed. Then the poll opens again the closed
+/// devices, e.g. after an error. The other devices are only checked to be open. Devices running another
+/// operation are left alone, and unplugged devices leave the pool. The devices are closed when the pool is
+/// dropped.
+///
+/// The poll is a source of the global default main context, it must be iterated for the poll to run, which
+/// the `_sync` operations do. The pool must be created on a thread that can acquire that context, e.g. the
+/// main thread.
+/// # Example:
+/// ```no_run
+/// use std::time::Duration;
+/// use libfprint_rs::{FpContext, FpDevicePool};
+///
+/// let ctx = FpContext::new();
+/// let pool = FpDevicePool::new(&ctx, |dev| dev.driver() == "goodixmoc", Duration::from_secs(30));
+///
+/// let print = load_print();
+/// let dev = pool.get().unwrap();
+/// let outcome = dev.verify_sync(&print, None, None, None::<()>);
+/// ```
+pub struct FpDevicePool {
+    context: FpContext,
+    devices: Rc<RefCell<Vec<FpDevice>>>,
+    handlers: Option<(glib::SignalHandlerId, glib::SignalHandlerId)>,
+    poll: Option<glib::SourceId>,
+}
+
+impl FpDevicePool {
+    /// Creates a pool of the devices of `context` accepted by `select`, polled every `interval`. The devices
+    /// are opened right away, see `FpDevicePool::warm`.
+    pub fn new(
+        context: &FpContext,
+        select: impl Fn(&FpDevice) -> bool + 'static,
+        interval: Duration,
+    ) -> Self {
+        let devices: Vec<FpDevice> = context.devices().into_iter().filter(&select).collect();
+        let devices = Rc::new(RefCell::new(devices));
+
+        let added = {
+            let devices = devices.clone();
+            context.connect_local("device-added", false, move |values| {
+                if let (Ok(context), Ok(device)) =
+                    (values[0].get::<FpContext>(), values[1].get::<FpDevice>())
+                {
+                    if select(&device) {
+                        device.set_context(&context);
+                        devices.borrow_mut().push(device);
+                    }
+                }
+                None
+            })
+        };
+        let removed = {
+            let devices = devices.clone();
+            context.connect_local("device-removed", false, move |values| {
+                if let Ok(device) = values[1].get::<FpDevice>() {
+                    devices.borrow_mut().retain(|other| *other != device);
+                }
+                None
+            })
+        };
+        let poll = {
+            let devices = devices.clone();
+            glib::timeout_add_local(interval, move || {
+                let list = devices.borrow().clone();
+                list.iter()
+                    .filter(|device| device.is_open())
+                    .for_each(probe);
+                let _ = open_closed(&list);
+                glib::ControlFlow::Continue
+            })
+        };
+
+        let pool = FpDevicePool {
+            context: context.clone(),
+            devices,
+            handlers: Some((added, removed)),
+            poll: Some(poll),
+        };
+        let _ = pool.warm();
+        pool
+    }
+
+    /// Returns the devices of the pool.
+    pub fn devices(&self) -> Vec<FpDevice> {
+        self.devices.borrow().clone()
+    }
+    /// Returns the first open device of the pool.
+    pub fn get(&self) -> Option<FpDevice> {
+        self.devices
+            .borrow()
+            .iter()
+            .find(|device| device.is_open())
+            .cloned()
+    }
+
+    /// Open the devices of the pool that are closed now, without waiting for the next poll. Returns the
+    /// devices that could not be opened, with their error; they are tried again on the next poll.
+    pub fn warm(&self) -> Result<(), Vec<(FpDevice, crate::GError)>> {
+        // The list is copied, opening a device iterates the main context which may update it
+        let devices = self.devices();
+        open_closed(&devices)
+    }
+}
+
+/// Probe `device` with a command, closing it when it fails so it is opened again.
+fn probe(device: &FpDevice) {
+    if !device.supports(FpDeviceFeature::StorageList) {
+        return;
+    }
+    if let Err(err) = device.list_prints_sync(None) {
+        if !is_busy(&err) {
+            let _ = device.close_sync(None);
+        }
+    }
+}
+
+/// Open the closed devices among `devices`.
+fn open_closed(devices: &[FpDevice]) -> Result<(), Vec<(FpDevice, crate::GError)>> {
+    let failed: Vec<_> = devices
+        .iter()
+        .filter(|device| !device.is_open())
+        .filter_map(|device| {
+            device
+                .open_sync(None)
+                .err()
+                .map(|err| (device.clone(), err))
+        })
+        .collect();
+    match failed.is_empty() {
+        true => Ok(()),
+        false => Err(failed),
+    }
+}
+
+impl Drop for FpDevicePool {
+    fn drop(&mut self) {
+        if let Some(poll) = self.poll.take() {
+            poll.remove();
+        }
+        if let Some((added, removed)) = self.handlers.take() {
+            self.context.disconnect(added);
+            self.context.disconnect(removed);
+        }
+        for device in self.devices().iter().filter(|device| device.is_open()) {
+            let _ = device.close_sync(None);
+        }
+    }
+}
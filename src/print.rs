@@ -5,7 +5,59 @@ use glib::{
     wrapper, ObjectExt,
 };
 
-use crate::{device::FpDevice, finger::FpFinger, image::FpImage};
+use crate::{date::FpDate, device::FpDevice, error::FpError, finger::FpFinger, image::FpImage};
+
+/// Maximum length in bytes of the username of a print, see `FpPrint::set_username`.
+pub const MAX_USERNAME_LEN: usize = 256;
+/// Maximum length in bytes of the description of a print, see `FpPrint::set_description`.
+pub const MAX_DESCRIPTION_LEN: usize = 1024;
+
+/// A metadata field of a print, see `FpError::MissingMetadata` and `FpError::InvalidMetadata`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FpMetadataField {
+    /// The username, see `FpPrint::set_username`.
+    Username,
+    /// The finger, see `FpPrint::set_finger`.
+    Finger,
+    /// The description, see `FpPrint::set_description`.
+    Description,
+}
+
+impl std::fmt::Display for FpMetadataField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FpMetadataField::Username => write!(f, "username"),
+            FpMetadataField::Finger => write!(f, "finger"),
+            FpMetadataField::Description => write!(f, "description"),
+        }
+    }
+}
+
+/// Trims `value`, normalizes it to NFC with the `nfc` feature, and checks its length and characters.
+fn normalize_metadata(
+    field: FpMetadataField,
+    value: &str,
+    max_len: usize,
+) -> Result<String, FpError> {
+    let value = value.trim();
+    #[cfg(feature = "nfc")]
+    let value: String = unicode_normalization::UnicodeNormalization::nfc(value).collect();
+    #[cfg(not(feature = "nfc"))]
+    let value = value.to_string();
+
+    if value.len() > max_len {
+        return Err(FpError::InvalidMetadata(field, "is too long"));
+    }
+    // Newlines and tabs are fine in descriptions, usernames are on a single line
+    let allowed = |c: char| field == FpMetadataField::Description && (c == '\n' || c == '\t');
+    if value.chars().any(|c| c.is_control() && !allowed(c)) {
+        return Err(FpError::InvalidMetadata(
+            field,
+            "contains control characters",
+        ));
+    }
+    Ok(value)
+}
 
 wrapper! {
     /// Struct representing a fingerprint.
@@ -22,9 +74,48 @@ impl FpPrint {
     /// Create a new `FpPrint`. This is only useful to prepare an enrollment of a new print using `FpDevice::enroll_sync`.
     /// For this you should first create a new print, fill in the relevant metadata, and then start the enrollment
     pub fn new(dev: &FpDevice) -> Self {
-        unsafe {
+        let print: FpPrint = unsafe {
             let ptr = libfprint_sys::fp_print_new(dev.to_glib_none().0);
             Self::from_glib_full(ptr)
+        };
+        unsafe { print.set_data("device", dev.clone()) };
+        print
+    }
+
+    #[cfg(not(doctest))]
+    /// Returns a builder for a new enroll template for `dev`, setting all the metadata of the print at once.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{FpContext, FpFinger, FpPrint};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    ///
+    /// let template = FpPrint::builder(&dev)
+    ///     .username("x")
+    ///     .finger(FpFinger::RightIndex)
+    ///     .description("work badge")
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn builder(dev: &FpDevice) -> FpPrintBuilder<'_> {
+        FpPrintBuilder {
+            device: dev,
+            username: None,
+            finger: FpFinger::Unknown,
+            description: None,
+            enroll_date: None,
+        }
+    }
+
+    /// The device the print was created with `FpPrint::new` for, or enrolled on. The print holds a strong
+    /// reference to it, so the device stays alive for as long as the print does. Prints loaded with
+    /// `FpPrint::deserialize` have no device.
+    pub fn device(&self) -> Option<FpDevice> {
+        unsafe {
+            self.data::<FpDevice>("device")
+                .map(|dev| dev.as_ref().clone())
         }
     }
 
@@ -59,10 +150,18 @@ impl FpPrint {
             }
         }
     }
-    /// Returns the finger that the print was created for.
+    /// Returns the image of the print downscaled to fit in a `max_px` square, e.g. for a list of the enrolled
+    /// fingers, or None if the print has no image.
+    ///
+    /// Only the prints enrolled by image devices in this process have an image: libfprint does not serialize
+    /// it, so store the thumbnail along with the print to show it later.
+    pub fn thumbnail(&self, max_px: u32) -> Option<FpImage> {
+        self.image().map(|image| image.thumbnail(max_px, max_px))
+    }
+    /// Returns the finger that the print was created for, `FpFinger::Unknown` for values this crate does not know.
     pub fn finger(&self) -> FpFinger {
         let raw_finger = unsafe { libfprint_sys::fp_print_get_finger(self.to_glib_none().0) };
-        FpFinger::from(raw_finger)
+        FpFinger::try_from(raw_finger as i32).unwrap_or(FpFinger::Unknown)
     }
     /// Returns the user defined username for the print.
     pub fn username(&self) -> Option<String> {
@@ -86,14 +185,15 @@ impl FpPrint {
             }
         }
     }
-    /// Returns the user defined enroll date for the print.
-    pub fn enroll_date(&self) -> Option<crate::GDate> {
+    /// Returns the user defined enroll date for the print, or None if it is not set.
+    pub fn enroll_date(&self) -> Option<FpDate> {
         unsafe {
             let ptr = libfprint_sys::fp_print_get_enroll_date(self.to_glib_none().0);
             if ptr.is_null() {
                 None
             } else {
-                Some(glib::Date::from_glib_none(ptr.cast()))
+                let date = crate::GDate::from_glib_none(ptr.cast());
+                FpDate::try_from(&date).ok()
             }
         }
     }
@@ -102,24 +202,41 @@ impl FpPrint {
     pub fn set_finger(&self, finger: FpFinger) {
         unsafe { libfprint_sys::fp_print_set_finger(self.to_glib_none().0, finger as u32) };
     }
-    /// Set the username for the print.
-    pub fn set_username(&self, username: &str) {
+    /// Set the username for the print. The username is trimmed, it must not be empty, contain control
+    /// characters or be longer than `MAX_USERNAME_LEN` bytes.
+    pub fn set_username(&self, username: &str) -> Result<(), FpError> {
+        let username = normalize_metadata(FpMetadataField::Username, username, MAX_USERNAME_LEN)?;
+        if username.is_empty() {
+            return Err(FpError::InvalidMetadata(
+                FpMetadataField::Username,
+                "is empty",
+            ));
+        }
         unsafe {
             libfprint_sys::fp_print_set_username(self.to_glib_none().0, username.to_glib_none().0);
         }
+        Ok(())
     }
-    /// Set the description for the print.
-    pub fn set_description(&self, description: &str) {
+    /// Set the description for the print. The description is trimmed, it must not contain control
+    /// characters other than newlines and tabs or be longer than `MAX_DESCRIPTION_LEN` bytes.
+    pub fn set_description(&self, description: &str) -> Result<(), FpError> {
+        let description = normalize_metadata(
+            FpMetadataField::Description,
+            description,
+            MAX_DESCRIPTION_LEN,
+        )?;
         unsafe {
             libfprint_sys::fp_print_set_description(
                 self.to_glib_none().0,
                 description.to_glib_none().0,
             );
         }
+        Ok(())
     }
 
     /// Set the enroll date for the print.
-    pub fn set_enroll_date(&self, enroll_date: crate::GDate) {
+    pub fn set_enroll_date(&self, enroll_date: FpDate) {
+        let enroll_date = crate::GDate::from(enroll_date);
         unsafe {
             libfprint_sys::fp_print_set_enroll_date(
                 self.to_glib_none().0,
@@ -127,7 +244,38 @@ impl FpPrint {
             );
         }
     }
-    /// Tests whether the prints is compatible with the given device.
+
+    /// Returns the enroll date of the print as a `chrono::NaiveDate`, or None if it is not set.
+    #[cfg(feature = "chrono")]
+    pub fn enroll_naive_date(&self) -> Option<chrono::NaiveDate> {
+        self.enroll_date().map(chrono::NaiveDate::from)
+    }
+    /// Set the enroll date for the print from a `chrono::NaiveDate`. Fails if the year is outside of the
+    /// range supported by `FpDate` (1 to 65535).
+    #[cfg(feature = "chrono")]
+    pub fn set_enroll_naive_date(&self, date: chrono::NaiveDate) -> Result<(), glib::BoolError> {
+        self.set_enroll_date(FpDate::try_from(date)?);
+        Ok(())
+    }
+
+    /// Returns the enroll date of the print as a `time::Date`, or None if it is not set or out of the
+    /// range supported by `time`.
+    #[cfg(feature = "time")]
+    pub fn enroll_time_date(&self) -> Option<time::Date> {
+        self.enroll_date()
+            .and_then(|date| time::Date::try_from(date).ok())
+    }
+    /// Set the enroll date for the print from a `time::Date`. Fails if the year is outside of the range
+    /// supported by `FpDate` (1 to 65535).
+    #[cfg(feature = "time")]
+    pub fn set_enroll_time_date(&self, date: time::Date) -> Result<(), glib::BoolError> {
+        self.set_enroll_date(FpDate::try_from(date)?);
+        Ok(())
+    }
+
+    /// Tests whether the print is compatible with the given device, i.e. the device can match it: the print was
+    /// enrolled with the driver of the device and, for match-on-chip devices, on that very device. See
+    /// `FpDevice::compatible_prints` to filter the prints of a user.
     pub fn compatible(&self, device: &FpDevice) -> bool {
         unsafe {
             libfprint_sys::fp_print_compatible(self.to_glib_none().0, device.to_glib_none().0)
@@ -163,12 +311,13 @@ impl FpPrint {
         }
     }
 
-    /// Deserialize a print definition from permanent storage.
+    /// Deserialize a print definition from permanent storage. `FpSerializedPrint::deserialize` validates the
+    /// data before handing it to libfprint.
     pub fn deserialize(data: &[u8]) -> Result<FpPrint, glib::Error> {
         let len = data.len();
+        let mut error = std::ptr::null_mut();
         let ptr = unsafe {
             let ptr = glib::translate::ToGlibPtr::to_glib_none(data);
-            let mut error = std::ptr::null_mut();
 
             libfprint_sys::fp_print_deserialize(
                 ptr.0,
@@ -178,11 +327,77 @@ impl FpPrint {
         };
 
         if ptr.is_null() {
-            Err(unsafe { glib::Error::from_glib_full(ptr.cast()) })
+            Err(unsafe { glib::Error::from_glib_full(error.cast()) })
         } else {
             let print = unsafe { FpPrint::from_glib_full(ptr) };
             unsafe { print.set_data("set", true) };
             Ok(print)
         }
     }
+    /// Returns a copy of the print without its username, description and enroll date, e.g. to export the template
+    /// to a third party matcher or to a test fixture. The finger and the template data are kept.
+    ///
+    /// The copy goes through `FpPrint::serialize`, so it has no image either.
+    pub fn strip_metadata(&self) -> Result<FpPrint, glib::Error> {
+        let print = FpPrint::deserialize(&self.serialize()?)?;
+        unsafe {
+            let ptr = print.to_glib_none().0;
+            libfprint_sys::fp_print_set_username(ptr, std::ptr::null());
+            libfprint_sys::fp_print_set_description(ptr, std::ptr::null());
+            libfprint_sys::fp_print_set_enroll_date(ptr, std::ptr::null());
+        }
+        Ok(print)
+    }
+}
+
+/// Builder for enroll templates, see `FpPrint::builder`.
+#[derive(Debug, Clone)]
+pub struct FpPrintBuilder<'a> {
+    device: &'a FpDevice,
+    username: Option<String>,
+    finger: FpFinger,
+    description: Option<String>,
+    enroll_date: Option<FpDate>,
+}
+
+impl FpPrintBuilder<'_> {
+    /// Set the username of the print, required.
+    pub fn username(mut self, username: &str) -> Self {
+        self.username = Some(username.to_string());
+        self
+    }
+    /// Set the finger of the print, required.
+    pub fn finger(mut self, finger: FpFinger) -> Self {
+        self.finger = finger;
+        self
+    }
+    /// Set the description of the print.
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+    /// Set the enroll date of the print, defaults to today.
+    pub fn enroll_date(mut self, enroll_date: FpDate) -> Self {
+        self.enroll_date = Some(enroll_date);
+        self
+    }
+    /// Creates the template, failing with `FpError::MissingMetadata` if the username is empty or the finger is unknown.
+    pub fn build(self) -> Result<FpPrint, FpError> {
+        let username = self
+            .username
+            .filter(|username| !username.is_empty())
+            .ok_or(FpError::MissingMetadata(FpMetadataField::Username))?;
+        if self.finger == FpFinger::Unknown {
+            return Err(FpError::MissingMetadata(FpMetadataField::Finger));
+        }
+
+        let print = FpPrint::new(self.device);
+        print.set_username(&username)?;
+        print.set_finger(self.finger);
+        if let Some(description) = self.description {
+            print.set_description(&description)?;
+        }
+        print.set_enroll_date(self.enroll_date.unwrap_or_else(FpDate::today));
+        Ok(print)
+    }
 }
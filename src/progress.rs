@@ -0,0 +1,93 @@
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::{device::FpDevice, error::FpRetryError, print::FpPrint};
+
+/// The style of the bars created by `FpProgressBar::new`.
+const TEMPLATE: &str = "{prefix} [{bar:30}] {pos}/{len} {msg}";
+
+#[cfg(not(doctest))]
+/// A terminal progress bar following an enrollment (requires the `indicatif` feature).
+///
+/// `FpProgressBar::enroll_progress` is an enroll progress callback advancing the bar by the completed
+/// stages. Failed scans are printed above the bar with the reason to retry, e.g. "The finger was not
+/// centered on the sensor, please try again", and shown as the message of the bar until the next stage. The
+/// bar is shared by its clones, keep one to finish it.
+/// # Example:
+/// ```no_run
+/// use libfprint_rs::{FpContext, FpPrint, FpProgressBar};
+///
+/// let ctx = FpContext::new();
+/// let dev = ctx.devices().remove(0);
+/// dev.open_sync(None).unwrap();
+///
+/// let bar = FpProgressBar::new(&dev);
+/// let template = FpPrint::new(&dev);
+/// let result = dev.enroll_sync(template, None, Some(FpProgressBar::enroll_progress), Some(bar.clone()));
+/// bar.finish(result.is_ok());
+/// ```
+#[derive(Debug, Clone)]
+pub struct FpProgressBar {
+    bar: ProgressBar,
+}
+
+impl FpProgressBar {
+    /// Creates a bar with a stage per enroll stage of `device`, prefixed by the name of the device.
+    pub fn new(device: &FpDevice) -> Self {
+        let bar = ProgressBar::new(device.nr_enroll_stage().max(0) as u64)
+            .with_prefix(device.name())
+            .with_message("Place your finger on the sensor");
+        if let Ok(style) = ProgressStyle::with_template(TEMPLATE) {
+            bar.set_style(style.progress_chars("=> "));
+        }
+        FpProgressBar { bar }
+    }
+    /// Follows an enrollment with `bar`, keeping its style. Its length is set to the number of enroll stages
+    /// on the first progress.
+    pub fn with_bar(bar: ProgressBar) -> Self {
+        FpProgressBar { bar }
+    }
+    /// The underlying bar.
+    pub fn bar(&self) -> &ProgressBar {
+        &self.bar
+    }
+
+    /// Move the bar to `completed_stages`, annotating it with the failed scan, if any. This is called by
+    /// `FpProgressBar::enroll_progress`, e.g. call it for each `FpEnrollStage` of `FpDevice::enroll_async`.
+    pub fn update(&self, device: &FpDevice, completed_stages: i32, error: Option<&crate::GError>) {
+        let stages = device.nr_enroll_stage().max(0) as u64;
+        if self.bar.length() != Some(stages) {
+            self.bar.set_length(stages);
+        }
+        self.bar.set_position(completed_stages.max(0) as u64);
+        match error {
+            Some(err) => {
+                let retry = err.kind().unwrap_or(FpRetryError::General);
+                self.bar
+                    .println(format!("Stage {} failed: {}", completed_stages + 1, retry));
+                self.bar.set_message(retry.to_string());
+            }
+            None => self.bar.set_message("Lift and place your finger again"),
+        }
+    }
+
+    /// An enroll progress callback updating the bar given as data, see `FpDevice::enroll_sync`.
+    pub fn enroll_progress(
+        device: &FpDevice,
+        completed_stages: i32,
+        _print: Option<FpPrint>,
+        error: Option<crate::GError>,
+        bar: &Option<FpProgressBar>,
+    ) {
+        if let Some(bar) = bar {
+            bar.update(device, completed_stages, error.as_ref());
+        }
+    }
+
+    /// Finish the bar with the outcome of the enrollment, leaving it on the terminal.
+    pub fn finish(&self, enrolled: bool) {
+        match enrolled {
+            true => self.bar.finish_with_message("Enrolled"),
+            false => self.bar.abandon_with_message("Enrollment failed"),
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! Conversions between Rust collections and `GPtrArray`, in a single pass and without intermediate vectors.
+use glib::translate::{FromGlibPtrNone, ToGlibPtr};
+
+use crate::print::FpPrint;
+
+/// Returns the elements of `array`, empty for a null array.
+///
+/// # Safety
+/// `array` must be null or a valid `GPtrArray`, left unchanged while the slice is used.
+pub(crate) unsafe fn as_slice<'a>(array: *const glib::ffi::GPtrArray) -> &'a [glib::ffi::gpointer] {
+    if array.is_null() || (*array).len == 0 {
+        return &[];
+    }
+    std::slice::from_raw_parts((*array).pdata, (*array).len as usize)
+}
+
+/// Returns a new reference to each object of `array`, which is left unchanged.
+///
+/// # Safety
+/// `array` must be null or a valid `GPtrArray` of objects of type `T`.
+pub(crate) unsafe fn to_vec_none<T, P: 'static>(array: *const glib::ffi::GPtrArray) -> Vec<T>
+where
+    T: FromGlibPtrNone<*mut P>,
+{
+    let elements = as_slice(array);
+    let mut vec = Vec::with_capacity(elements.len());
+    vec.extend(
+        elements
+            .iter()
+            .map(|&element| T::from_glib_none(element.cast::<P>())),
+    );
+    vec
+}
+
+/// Builds the `GPtrArray` of a gallery, without taking references to the prints, which outlive the array.
+/// libfprint copies the gallery when the identification starts, the array can be freed right after.
+pub(crate) fn gallery<'a, I, P>(prints: I) -> *mut glib::ffi::GPtrArray
+where
+    I: IntoIterator<Item = &'a P>,
+    P: AsRef<FpPrint> + 'a,
+{
+    let prints = prints.into_iter();
+    let array = unsafe { glib::ffi::g_ptr_array_sized_new(prints.size_hint().0 as u32) };
+    for print in prints {
+        let raw: *mut libfprint_sys::FpPrint = print.as_ref().to_glib_none().0;
+        unsafe { glib::ffi::g_ptr_array_add(array, raw.cast()) };
+    }
+    array
+}
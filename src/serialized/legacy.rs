@@ -0,0 +1,86 @@
+//! Decoder of the print formats of libfprint 0.x.
+//!
+//! Legacy prints start with a header: the `FP1` or `FP2` magic, the little endian `u16` id of the driver, the
+//! `u32` type of the device and a data type byte. `FP1` prints hold a single item after the header, `FP2`
+//! prints a list of items each prefixed by its little endian `u32` length. Items of image devices are NBIS
+//! `xyt_struct`: the number of minutiae followed by their fixed size x, y and theta columns.
+use glib::{ToVariant, Variant, VariantTy};
+
+/// Length of the header of legacy prints.
+const HEADER_LEN: usize = 10;
+/// Data type of the NBIS minutiae of image devices.
+const DATA_TYPE_NBIS: u8 = 1;
+/// Data type of the data of match on chip devices.
+const DATA_TYPE_RAW: u8 = 0;
+/// Number of minutiae of each column of a `xyt_struct`.
+const MAX_MINUTIAE: usize = 200;
+/// Length of a `xyt_struct`.
+const XYT_LEN: usize = 4 * (1 + 3 * MAX_MINUTIAE);
+
+/// A print in a legacy format.
+pub(super) struct LegacyPrint<'a> {
+    data_type: u8,
+    items: Vec<&'a [u8]>,
+}
+
+impl<'a> LegacyPrint<'a> {
+    /// Parse a legacy print, returning `None` for anything else. Single item `FP1` prints are only recognized
+    /// with NBIS data, the raw ones can not be told apart from a corrupt print of the current format.
+    pub(super) fn parse(data: &'a [u8]) -> Option<Self> {
+        if data.len() < HEADER_LEN {
+            return None;
+        }
+        let data_type = data[HEADER_LEN - 1];
+        let body = &data[HEADER_LEN..];
+        let items = match &data[..3] {
+            b"FP1" if data_type == DATA_TYPE_NBIS => vec![body],
+            b"FP2" => {
+                let mut items = Vec::new();
+                let mut rest = body;
+                while !rest.is_empty() {
+                    let len = rest.get(..4)?;
+                    let len = u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize;
+                    let end = 4usize.checked_add(len)?;
+                    let item = rest.get(4..end)?;
+                    items.push(item);
+                    rest = &rest[end..];
+                }
+                items
+            }
+            _ => return None,
+        };
+        let valid = match data_type {
+            DATA_TYPE_NBIS => !items.is_empty() && items.iter().all(|item| item.len() == XYT_LEN),
+            DATA_TYPE_RAW => true,
+            _ => false,
+        };
+        valid.then_some(LegacyPrint { data_type, items })
+    }
+
+    /// Returns the minutiae as the data variant of NBIS prints of the current format, `(a(aiaiai))`, or `None`
+    /// for the data of match on chip devices that can not be converted.
+    pub(super) fn nbis_variant(&self) -> Option<Variant> {
+        if self.data_type != DATA_TYPE_NBIS {
+            return None;
+        }
+        let mut prints = Vec::new();
+        for item in &self.items {
+            let ints: Vec<i32> = item
+                .chunks_exact(4)
+                .map(|int| i32::from_le_bytes([int[0], int[1], int[2], int[3]]))
+                .collect();
+            let count = usize::try_from(ints[0])
+                .ok()
+                .filter(|&n| n <= MAX_MINUTIAE)?;
+            let column = |index: usize| {
+                let start = 1 + index * MAX_MINUTIAE;
+                ints[start..start + count].to_variant()
+            };
+            prints.push(Variant::tuple_from_iter([column(0), column(1), column(2)]));
+        }
+        let ty = VariantTy::new("(aiaiai)").unwrap();
+        Some(Variant::tuple_from_iter([
+            Variant::array_from_iter_with_type(ty, prints),
+        ]))
+    }
+}
@@ -0,0 +1,656 @@
+mod legacy;
+
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fs::File,
+    io::{Read, Write},
+    ops::Deref,
+    path::Path,
+};
+
+use glib::{ToVariant, Variant, VariantDict, VariantTy};
+
+use crate::{device::FpDevice, error::FpError, print::FpPrint};
+use legacy::LegacyPrint;
+
+/// Magic bytes starting the data written by `fp_print_serialize`, the last one is the format version.
+const PRINT_MAGIC: &[u8; 2] = b"FP";
+/// Format version of the data written by `fp_print_serialize`.
+const PRINT_VERSION: u8 = b'1';
+/// Type of the variant following the magic: type, driver, device id, device stored, finger, username,
+/// description, julian enroll date, extra data and the print data itself.
+const PRINT_VARIANT_TYPE: &str = "(issbymsmsia{sv}v)";
+/// Values of `FpPrintType` libfprint can deserialize.
+const PRINT_TYPE_RAW: i32 = 1;
+pub(crate) const PRINT_TYPE_NBIS: i32 = 2;
+/// Magic bytes starting every zstd frame, see `FpSerializedPrint::compress`.
+const ZSTD_MAGIC: &[u8; 4] = &[0x28, 0xb5, 0x2f, 0xfd];
+/// Maximum length of a decompressed print, the decompression of malicious data stops there.
+#[cfg(feature = "compression")]
+const MAX_DECOMPRESSED_LEN: u64 = 64 * 1024 * 1024;
+
+/// A print serialized by libfprint, as returned by `FpPrint::serialize`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FpSerializedPrint {
+    data: Vec<u8>,
+}
+
+impl FpSerializedPrint {
+    /// Serialize `print`.
+    pub fn new(print: &FpPrint) -> Result<Self, crate::GError> {
+        Ok(FpSerializedPrint {
+            data: print.serialize()?,
+        })
+    }
+    /// Wraps data previously returned by `FpPrint::serialize` or `FpSerializedPrint::into_bytes`.
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        FpSerializedPrint { data }
+    }
+    /// Returns the serialized data.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+    /// Returns the serialized data, consuming the struct.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+    /// Write the data to the file at `path`. The data is written to a temporary file of the same directory
+    /// first, which then replaces `path`, so the file holds either the previous or the new print, even if the
+    /// process is interrupted.
+    pub fn write_to(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        write_atomically(path.as_ref(), &self.data)
+    }
+    /// Read the data written to the file at `path` by `FpSerializedPrint::write_to`, or produced by
+    /// `FpPrint::serialize`. The data is not validated until it is deserialized.
+    pub fn read_from(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(FpSerializedPrint {
+            data: std::fs::read(path)?,
+        })
+    }
+
+    /// Whether the print was compressed with `FpSerializedPrint::compress`.
+    pub fn is_compressed(&self) -> bool {
+        self.data.starts_with(ZSTD_MAGIC)
+    }
+    /// Compress the print with zstd, which mostly benefits the prints of image devices embedding full images
+    /// (requires the `compression` feature). Compressed prints are decompressed transparently when they are
+    /// deserialized.
+    #[cfg(feature = "compression")]
+    pub fn compress(&self) -> Result<FpSerializedPrint, FpError> {
+        if self.is_compressed() {
+            return Ok(self.clone());
+        }
+        Ok(FpSerializedPrint {
+            data: zstd::bulk::compress(&self.data, zstd::DEFAULT_COMPRESSION_LEVEL)?,
+        })
+    }
+    /// Decompress a print compressed with `FpSerializedPrint::compress` (requires the `compression` feature).
+    /// Prints that are not compressed are returned as is.
+    #[cfg(feature = "compression")]
+    pub fn decompress(&self) -> Result<FpSerializedPrint, FpError> {
+        if !self.is_compressed() {
+            return Ok(self.clone());
+        }
+        let mut data = Vec::new();
+        zstd::stream::Decoder::new(self.data.as_slice())?
+            .take(MAX_DECOMPRESSED_LEN + 1)
+            .read_to_end(&mut data)
+            .map_err(|_| FpError::CorruptPrint("invalid compressed data"))?;
+        if data.len() as u64 > MAX_DECOMPRESSED_LEN {
+            return Err(FpError::CorruptPrint("decompressed print too large"));
+        }
+        Ok(FpSerializedPrint { data })
+    }
+    /// Returns the print, decompressed if needed.
+    fn plain(&self) -> Result<Cow<'_, FpSerializedPrint>, FpError> {
+        if !self.is_compressed() {
+            return Ok(Cow::Borrowed(self));
+        }
+        #[cfg(feature = "compression")]
+        return Ok(Cow::Owned(self.decompress()?));
+        #[cfg(not(feature = "compression"))]
+        Err(FpError::CorruptPrint(
+            "the print is compressed, which requires the compression feature",
+        ))
+    }
+
+    /// Check the structure of the data, returning the name of the driver the print was enrolled with.
+    pub fn driver(&self) -> Result<String, FpError> {
+        let variant = self.validate()?;
+        Ok(variant.child_value(1).str().unwrap_or_default().to_string())
+    }
+    /// Deserialize the print. The data is validated first, so malformed data is reported as
+    /// `FpError::CorruptPrint` or `FpError::UnsupportedFormat` rather than by libfprint.
+    pub fn deserialize(&self) -> Result<FpPrint, FpError> {
+        let plain = self.plain()?;
+        plain.validate()?;
+        Ok(FpPrint::deserialize(&plain.data)?)
+    }
+    /// Deserialize the print, checking it was enrolled with the driver of `device`, otherwise
+    /// `FpError::DriverMismatch` is returned. Prints in a legacy format are upgraded first, see
+    /// `FpSerializedPrint::upgrade`.
+    pub fn deserialize_for(&self, device: &FpDevice) -> Result<FpPrint, FpError> {
+        if self.is_legacy() {
+            return self.upgrade(device)?.deserialize();
+        }
+        let driver = self.driver()?;
+        let expected = device.driver();
+        if driver != expected {
+            return Err(FpError::DriverMismatch(driver, expected));
+        }
+        self.deserialize()
+    }
+
+    /// Whether the print was serialized by libfprint 0.x, and must be upgraded to be deserialized.
+    pub fn is_legacy(&self) -> bool {
+        matches!(self.validate(), Err(FpError::LegacyPrint))
+    }
+    /// Re-serialize the print in the current format of libfprint. Prints in the current format are returned as
+    /// is.
+    ///
+    /// Legacy prints only identify their driver by a numeric id, the print is upgraded as enrolled on `device`,
+    /// which must use the driver the print was enrolled with. Their finger, username and enroll date are not
+    /// set, libfprint 0.x did not store them in the print. Only prints of image devices can be upgraded, the
+    /// match on chip drivers of libfprint 0.x no longer exist and `FpError::LegacyPrint` is returned for theirs.
+    pub fn upgrade(&self, device: &FpDevice) -> Result<FpSerializedPrint, FpError> {
+        self.upgrade_as(&device.driver(), &device.device_id())
+    }
+
+    fn upgrade_as(&self, driver: &str, device_id: &str) -> Result<FpSerializedPrint, FpError> {
+        match self.validate() {
+            Ok(_) => return Ok(self.clone()),
+            Err(FpError::LegacyPrint) => {}
+            Err(err) => return Err(err),
+        }
+        let plain = self.plain()?;
+        let prints = LegacyPrint::parse(&plain.data)
+            .and_then(|legacy| legacy.nbis_variant())
+            .ok_or(FpError::LegacyPrint)?;
+
+        let variant = Variant::tuple_from_iter([
+            PRINT_TYPE_NBIS.to_variant(),
+            driver.to_variant(),
+            device_id.to_variant(),
+            false.to_variant(),
+            (libfprint_sys::FpFinger_FP_FINGER_UNKNOWN as u8).to_variant(),
+            None::<&str>.to_variant(),
+            None::<&str>.to_variant(),
+            i32::MIN.to_variant(),
+            VariantDict::new(None).end(),
+            Variant::from_variant(&prints),
+        ]);
+        Ok(FpSerializedPrint::from_variant(&variant))
+    }
+
+    /// Serialize a print variant, in normal form, as libfprint does.
+    fn from_variant(variant: &Variant) -> FpSerializedPrint {
+        // libfprint stores the variant little endian
+        let variant = if cfg!(target_endian = "big") {
+            variant.byteswap()
+        } else {
+            variant.clone()
+        };
+        let mut data = PRINT_MAGIC.to_vec();
+        data.push(PRINT_VERSION);
+        data.extend_from_slice(variant.data());
+        FpSerializedPrint { data }
+    }
+
+    /// Check the header and the variant of the data, returning the variant.
+    fn validate(&self) -> Result<Variant, FpError> {
+        let plain = self.plain()?;
+        let data = &plain.data;
+        if data.len() < PRINT_MAGIC.len() + 1 {
+            return Err(FpError::CorruptPrint("truncated print"));
+        }
+        if !data.starts_with(PRINT_MAGIC) {
+            return Err(FpError::CorruptPrint("missing print magic"));
+        }
+        let version = data[PRINT_MAGIC.len()];
+        if LegacyPrint::parse(data).is_some() && version != PRINT_VERSION {
+            return Err(FpError::LegacyPrint);
+        }
+        if version != PRINT_VERSION {
+            return match version {
+                b'0'..=b'9' => Err(FpError::UnsupportedFormat(version - b'0')),
+                _ => Err(FpError::CorruptPrint("missing print magic")),
+            };
+        }
+
+        let ty = VariantTy::new(PRINT_VARIANT_TYPE).unwrap();
+        let mut variant = Variant::from_data_with_type(&data[PRINT_MAGIC.len() + 1..], ty);
+        // Non normal data can not be trusted to hold the expected children
+        if !variant.is_normal_form() {
+            // Single item prints of libfprint 0.x used the same magic
+            if LegacyPrint::parse(data).is_some() {
+                return Err(FpError::LegacyPrint);
+            }
+            return Err(FpError::CorruptPrint("malformed print variant"));
+        }
+        // libfprint stores the variant little endian
+        if cfg!(target_endian = "big") {
+            variant = variant.byteswap();
+        }
+
+        let print_type = variant.child_value(0).get::<i32>().unwrap_or_default();
+        if print_type != PRINT_TYPE_RAW && print_type != PRINT_TYPE_NBIS {
+            return Err(FpError::CorruptPrint("unknown print type"));
+        }
+        if variant.child_value(1).str().unwrap_or_default().is_empty() {
+            return Err(FpError::CorruptPrint("missing driver"));
+        }
+        let finger = variant.child_value(4).get::<u8>().unwrap_or_default();
+        if finger as u32 > libfprint_sys::FpFinger_FP_FINGER_LAST {
+            return Err(FpError::CorruptPrint("invalid finger"));
+        }
+        Ok(variant)
+    }
+}
+
+impl AsRef<[u8]> for FpSerializedPrint {
+    fn as_ref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl Deref for FpSerializedPrint {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl FpPrint {
+    /// Returns the print as the GVariant libfprint serializes it to, of type `(issbymsmsia{sv}v)`, as passed by
+    /// other GLib based software.
+    pub fn as_variant(&self) -> Result<Variant, FpError> {
+        FpSerializedPrint::new(self)?.validate()
+    }
+    /// Creates a print from a GVariant returned by `FpPrint::as_variant` or by other GLib based software.
+    pub fn from_variant(variant: &Variant) -> Result<FpPrint, FpError> {
+        if variant.type_().as_str() != PRINT_VARIANT_TYPE {
+            return Err(FpError::CorruptPrint("unexpected variant type"));
+        }
+        FpSerializedPrint::from_variant(&variant.normal_form()).deserialize()
+    }
+    /// Serialize the print into `writer`, prefixed by its length as a little endian `u32`, so several prints
+    /// can be streamed one after the other. Read it back with `FpPrint::deserialize_from`.
+    pub fn serialize_to(&self, mut writer: impl Write) -> Result<(), FpError> {
+        let data = self.serialize()?;
+        writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        writer.write_all(&data)?;
+        Ok(())
+    }
+    /// Deserialize a print written by `FpPrint::serialize_to` from `reader`, reading no further than its end.
+    pub fn deserialize_from(mut reader: impl Read) -> Result<FpPrint, FpError> {
+        let mut len = [0; 4];
+        reader.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len) as u64;
+        // The length is not trusted to preallocate the buffer
+        let mut data = Vec::new();
+        reader.take(len).read_to_end(&mut data)?;
+        if data.len() as u64 != len {
+            return Err(FpError::CorruptPrint("truncated print"));
+        }
+        FpSerializedPrint { data }.deserialize()
+    }
+}
+
+/// Magic bytes starting every `FpPrintEnvelope`.
+const ENVELOPE_MAGIC: &[u8; 4] = b"FPRS";
+/// Current version of the `FpPrintEnvelope` format. Version 1 envelopes have no flags byte.
+const ENVELOPE_VERSION: u8 = 2;
+/// Flag of envelopes holding a print compressed with `FpSerializedPrint::compress`.
+const ENVELOPE_COMPRESSED: u8 = 1;
+
+/// Write `data` to a temporary file of the directory of `path`, which then replaces `path`.
+fn write_atomically(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let name = path.file_name().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "The path has no file name",
+        )
+    })?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp = path.with_file_name(temp_name);
+
+    let written = File::create(&temp).and_then(|mut file| {
+        file.write_all(data)?;
+        file.sync_all()
+    });
+    match written.and_then(|()| std::fs::rename(&temp, path)) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            let _ = std::fs::remove_file(&temp);
+            Err(err)
+        }
+    }
+}
+
+/// A serialized print together with custom string metadata (employee id, badge number, enrollment operator...)
+/// that travels with the template.
+///
+/// The envelope is written as the `FPRS` magic, a version byte, a flags byte, the number of metadata entries,
+/// each key and value prefixed by its length, and finally the length prefixed libfprint data. Lengths are little
+/// endian `u32`. The only flag marks compressed prints, see `FpSerializedPrint::compress`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FpPrintEnvelope {
+    print: FpSerializedPrint,
+    metadata: BTreeMap<String, String>,
+}
+
+impl FpPrintEnvelope {
+    /// Creates an envelope without metadata around `print`.
+    pub fn new(print: FpSerializedPrint) -> Self {
+        FpPrintEnvelope {
+            print,
+            metadata: BTreeMap::new(),
+        }
+    }
+    /// Serialize `print` into a new envelope without metadata.
+    pub fn from_print(print: &FpPrint) -> Result<Self, crate::GError> {
+        Ok(FpPrintEnvelope::new(FpSerializedPrint::new(print)?))
+    }
+
+    /// Returns the serialized print.
+    pub fn print(&self) -> &FpSerializedPrint {
+        &self.print
+    }
+    /// Returns all the metadata, ordered by key.
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+    /// Returns the value of the metadata `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(String::as_str)
+    }
+    /// Set the metadata `key` to `value`, returning the previous value.
+    pub fn insert(&mut self, key: &str, value: &str) -> Option<String> {
+        self.metadata.insert(key.to_string(), value.to_string())
+    }
+    /// Remove the metadata `key`, returning its value.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.metadata.remove(key)
+    }
+
+    /// Whether `data` starts like an envelope, as opposed to bare libfprint data.
+    pub fn is_envelope(data: &[u8]) -> bool {
+        data.starts_with(ENVELOPE_MAGIC)
+    }
+    /// Serialize the envelope.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        fn put(out: &mut Vec<u8>, bytes: &[u8]) {
+            out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            out.extend_from_slice(bytes);
+        }
+
+        let mut out = Vec::new();
+        out.extend_from_slice(ENVELOPE_MAGIC);
+        out.push(ENVELOPE_VERSION);
+        out.push(if self.print.is_compressed() {
+            ENVELOPE_COMPRESSED
+        } else {
+            0
+        });
+        out.extend_from_slice(&(self.metadata.len() as u32).to_le_bytes());
+        for (key, value) in &self.metadata {
+            put(&mut out, key.as_bytes());
+            put(&mut out, value.as_bytes());
+        }
+        put(&mut out, self.print.as_bytes());
+        out
+    }
+    /// Deserialize an envelope written by `FpPrintEnvelope::to_bytes`.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, FpError> {
+        if !Self::is_envelope(data) {
+            return Err(FpError::CorruptPrint("missing envelope magic"));
+        }
+        let mut reader = Reader {
+            data: &data[ENVELOPE_MAGIC.len()..],
+        };
+        let version = reader.take(1)?[0];
+        let flags = match version {
+            1 => 0,
+            ENVELOPE_VERSION => reader.take(1)?[0],
+            _ => return Err(FpError::UnsupportedFormat(version)),
+        };
+        if flags & !ENVELOPE_COMPRESSED != 0 {
+            return Err(FpError::CorruptPrint("unknown envelope flags"));
+        }
+
+        let count = reader.u32()?;
+        let mut metadata = BTreeMap::new();
+        for _ in 0..count {
+            let key = reader.string()?;
+            let value = reader.string()?;
+            metadata.insert(key, value);
+        }
+        let print = FpSerializedPrint::from_bytes(reader.bytes()?.to_vec());
+        if !reader.data.is_empty() {
+            return Err(FpError::CorruptPrint("trailing data after the print"));
+        }
+        if print.is_compressed() != (flags & ENVELOPE_COMPRESSED != 0) {
+            return Err(FpError::CorruptPrint(
+                "compression flag does not match the print",
+            ));
+        }
+
+        Ok(FpPrintEnvelope { print, metadata })
+    }
+    /// Write the envelope to the file at `path`, replacing it atomically like `FpSerializedPrint::write_to`.
+    pub fn write_to(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        write_atomically(path.as_ref(), &self.to_bytes())
+    }
+    /// Read an envelope written to the file at `path` by `FpPrintEnvelope::write_to`.
+    pub fn read_from(path: impl AsRef<Path>) -> Result<Self, FpError> {
+        FpPrintEnvelope::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+/// Cursor over the bytes of an envelope.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], FpError> {
+        if self.data.len() < len {
+            return Err(FpError::CorruptPrint("truncated envelope"));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+    fn u32(&mut self) -> Result<u32, FpError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+    fn bytes(&mut self) -> Result<&'a [u8], FpError> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+    fn string(&mut self) -> Result<String, FpError> {
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| FpError::CorruptPrint("metadata is not valid UTF-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glib::{ToVariant, Variant, VariantDict};
+
+    use super::{FpPrintEnvelope, FpSerializedPrint};
+    use crate::error::FpError;
+
+    fn print_data(print_type: i32, driver: &str) -> Vec<u8> {
+        let variant = Variant::tuple_from_iter([
+            print_type.to_variant(),
+            driver.to_variant(),
+            "0".to_variant(),
+            false.to_variant(),
+            7u8.to_variant(),
+            Some("Bruce Banner").to_variant(),
+            None::<&str>.to_variant(),
+            i32::MIN.to_variant(),
+            VariantDict::new(None).end(),
+            Variant::from_variant(&[0u8; 4].to_variant()),
+        ]);
+        let mut data = b"FP1".to_vec();
+        data.extend_from_slice(variant.data());
+        data
+    }
+
+    fn legacy_data(minutiae: &[(i32, i32, i32)]) -> Vec<u8> {
+        // Header, then a single length prefixed xyt_struct of 200 entries columns
+        let mut item = vec![0; 4 * 601];
+        item[..4].copy_from_slice(&(minutiae.len() as i32).to_le_bytes());
+        for (i, (x, y, theta)) in minutiae.iter().enumerate() {
+            for (column, value) in [x, y, theta].iter().enumerate() {
+                let offset = 4 * (1 + column * 200 + i);
+                item[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            }
+        }
+        let mut data = b"FP2".to_vec();
+        data.extend_from_slice(&4u16.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&(item.len() as u32).to_le_bytes());
+        data.extend_from_slice(&item);
+        data
+    }
+
+    #[test]
+    fn legacy_upgrade() {
+        let legacy = FpSerializedPrint::from_bytes(legacy_data(&[(10, 20, 90), (30, 40, 180)]));
+        assert!(legacy.is_legacy());
+        assert!(matches!(legacy.driver(), Err(FpError::LegacyPrint)));
+
+        let upgraded = legacy.upgrade_as("aes2501", "0").unwrap();
+        assert!(!upgraded.is_legacy());
+        assert_eq!(upgraded.driver().unwrap(), "aes2501");
+        let prints = upgraded
+            .validate()
+            .unwrap()
+            .child_value(9)
+            .as_variant()
+            .unwrap();
+        let columns = prints.child_value(0).child_value(0);
+        assert_eq!(
+            columns.child_value(0).get::<Vec<i32>>().unwrap(),
+            vec![10, 30]
+        );
+        assert_eq!(
+            columns.child_value(2).get::<Vec<i32>>().unwrap(),
+            vec![90, 180]
+        );
+        assert_eq!(upgraded.upgrade_as("aes2501", "0").unwrap(), upgraded);
+
+        let mut raw = legacy_data(&[]);
+        raw[9] = 0;
+        let raw = FpSerializedPrint::from_bytes(raw);
+        assert!(raw.is_legacy());
+        assert!(matches!(
+            raw.upgrade_as("upekts", "0"),
+            Err(FpError::LegacyPrint)
+        ));
+    }
+
+    #[test]
+    fn legacy_item_length() {
+        let mut data = legacy_data(&[(10, 20, 90)]);
+        data[10..14].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(!FpSerializedPrint::from_bytes(data).is_legacy());
+    }
+
+    #[test]
+    fn print_validation() {
+        let data = print_data(2, "synaptics");
+        let print = FpSerializedPrint::from_bytes(data.clone());
+        assert_eq!(print.driver().unwrap(), "synaptics");
+
+        for len in 0..16 {
+            let truncated = FpSerializedPrint::from_bytes(data[..len].to_vec());
+            assert!(matches!(truncated.driver(), Err(FpError::CorruptPrint(_))));
+        }
+        let mut future = data.clone();
+        future[2] = b'3';
+        assert!(matches!(
+            FpSerializedPrint::from_bytes(future).driver(),
+            Err(FpError::UnsupportedFormat(3))
+        ));
+        let unknown = FpSerializedPrint::from_bytes(print_data(7, "synaptics"));
+        assert!(matches!(unknown.driver(), Err(FpError::CorruptPrint(_))));
+        let no_driver = FpSerializedPrint::from_bytes(print_data(2, ""));
+        assert!(matches!(no_driver.driver(), Err(FpError::CorruptPrint(_))));
+    }
+
+    #[test]
+    fn legacy_envelope() {
+        let mut bytes = b"FPRS\x01".to_vec();
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        for value in ["operator", "Bruce Banner", "\x01\x02"] {
+            bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(value.as_bytes());
+        }
+        let envelope = FpPrintEnvelope::from_bytes(&bytes).unwrap();
+        assert_eq!(envelope.get("operator"), Some("Bruce Banner"));
+        assert_eq!(envelope.print().as_bytes(), &[1, 2]);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compressed_envelope() {
+        let print = FpSerializedPrint::from_bytes(print_data(1, "synaptics"));
+        let compressed = print.compress().unwrap();
+        assert!(compressed.is_compressed());
+        assert_eq!(compressed.driver().unwrap(), "synaptics");
+        assert_eq!(compressed.decompress().unwrap(), print);
+
+        let bytes = FpPrintEnvelope::new(compressed.clone()).to_bytes();
+        assert_eq!(bytes[5], 1);
+        assert_eq!(
+            FpPrintEnvelope::from_bytes(&bytes).unwrap().print(),
+            &compressed
+        );
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let dir = std::env::temp_dir().join(format!("libfprint-rs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("print.bin");
+
+        let print = FpSerializedPrint::from_bytes(vec![1, 2, 3]);
+        print.write_to(&path).unwrap();
+        FpSerializedPrint::from_bytes(vec![4, 5])
+            .write_to(&path)
+            .unwrap();
+        assert_eq!(&*FpSerializedPrint::read_from(&path).unwrap(), &[4, 5]);
+        // Only the print remains in the directory
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn envelope_round_trip() {
+        let mut envelope = FpPrintEnvelope::new(FpSerializedPrint::from_bytes(vec![1, 2, 3]));
+        envelope.insert("employee_id", "4242");
+        envelope.insert("operator", "Bruce Banner");
+
+        let bytes = envelope.to_bytes();
+        assert!(FpPrintEnvelope::is_envelope(&bytes));
+        assert_eq!(FpPrintEnvelope::from_bytes(&bytes).unwrap(), envelope);
+
+        for len in 0..bytes.len() {
+            assert!(matches!(
+                FpPrintEnvelope::from_bytes(&bytes[..len]),
+                Err(FpError::CorruptPrint(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+};
+
+use gio::{prelude::CancellableExt, Cancellable};
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::{Handle, Signals},
+};
+
+use crate::device::FpDevice;
+
+#[cfg(not(doctest))]
+/// Cancels the operations in flight when the process receives SIGINT or SIGTERM, so interrupted tools can close
+/// their devices cleanly instead of leaving the sensor claimed in the middle of an enrollment (requires the
+/// `signal-hook` feature).
+///
+/// Pass `FpInterruptGuard::cancellable` to every operation: the first signal cancels it, the operations then
+/// fail with `gio::IOErrorEnum::Cancelled` and the devices can be closed with `FpInterruptGuard::close_devices`.
+/// A second signal exits the process immediately, in case the cleanup hangs. Keep the guard alive for as long
+/// as the operations run.
+/// # Example:
+/// ```no_run
+/// use libfprint_rs::{FpContext, FpInterruptGuard};
+///
+/// let ctx = FpContext::new();
+/// let devices = ctx.devices();
+/// let dev = devices.get(0).unwrap();
+/// let guard = FpInterruptGuard::install().unwrap();
+///
+/// dev.open_sync(Some(guard.cancellable())).unwrap();
+/// let result = dev.enroll_sync(template, Some(guard.cancellable()), None, None::<()>);
+/// if guard.is_interrupted() {
+///     guard.close_devices(&devices);
+///     std::process::exit(130);
+/// }
+/// ```
+pub struct FpInterruptGuard {
+    cancellable: Cancellable,
+    signal: Arc<AtomicI32>,
+    handle: Handle,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FpInterruptGuard {
+    /// Cancel the operations on SIGINT and SIGTERM.
+    pub fn install() -> std::io::Result<Self> {
+        Self::with_signals(&[SIGINT, SIGTERM])
+    }
+    /// Cancel the operations on any of `signals`.
+    pub fn with_signals(signals: &[i32]) -> std::io::Result<Self> {
+        let mut signals = Signals::new(signals)?;
+        let handle = signals.handle();
+        let cancellable = Cancellable::new();
+        let signal = Arc::new(AtomicI32::new(0));
+
+        let thread = {
+            let cancellable = cancellable.clone();
+            let signal = signal.clone();
+            std::thread::spawn(move || {
+                for received in signals.forever() {
+                    let first =
+                        signal.compare_exchange(0, received, Ordering::SeqCst, Ordering::SeqCst);
+                    if first.is_err() {
+                        // The cleanup after the first signal did not complete
+                        std::process::exit(128 + received);
+                    }
+                    cancellable.cancel();
+                }
+            })
+        };
+
+        Ok(FpInterruptGuard {
+            cancellable,
+            signal,
+            handle,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the cancellable cancelled by the first signal, to pass to every operation.
+    pub fn cancellable(&self) -> &Cancellable {
+        &self.cancellable
+    }
+    /// Whether a signal was received.
+    pub fn is_interrupted(&self) -> bool {
+        self.signal().is_some()
+    }
+    /// Returns the first signal received, if any.
+    pub fn signal(&self) -> Option<i32> {
+        match self.signal.load(Ordering::SeqCst) {
+            0 => None,
+            signal => Some(signal),
+        }
+    }
+
+    /// Close the open devices among `devices`, without cancellation. Must be called from the thread owning
+    /// their context, once their operations completed. Every device is closed even if some fail, the first
+    /// error is returned.
+    pub fn close_devices(&self, devices: &[FpDevice]) -> Result<(), crate::GError> {
+        let mut result = Ok(());
+        for device in devices.iter().filter(|device| device.is_open()) {
+            if let Err(err) = device.close_sync(None) {
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Drop for FpInterruptGuard {
+    fn drop(&mut self) {
+        // Closing the handle ends the iteration of the signals
+        self.handle.close();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
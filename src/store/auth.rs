@@ -0,0 +1,135 @@
+use gio::Cancellable;
+
+use crate::{
+    audit::{AuditRecord, AuditSink},
+    device::{FpDevice, FpMatchCb, FpMatchOutcome},
+    error::{FpError, FpRetryError},
+    finger::FpFinger,
+    print::FpPrint,
+};
+
+use super::{MatchAttempt, PrintStore};
+
+#[cfg(not(doctest))]
+/// Verify that the finger on `device` belongs to `username`, against every template stored for them.
+///
+/// The templates of `finger`, or of every finger of the user if `None`, are matched in a single scan, which stops
+/// at the first matching template. Keeping several templates per finger, see `PrintStore::add`, improves the
+/// acceptance of image sensors. The outcome is recorded in the statistics of the store: matches for the matched
+/// finger, retries and rejects only when `finger` is given, as they can not be attributed otherwise.
+///
+/// The outcome is reported to `audit` if given, as `FpDeviceManager` reports its verifications: the record holds
+/// the user and the matched or given finger. Scans to retry are not reported.
+///
+/// Fails with `gio::IOErrorEnum::NotFound` if no template is stored, without scanning.
+/// # Example:
+/// ```no_run
+/// use libfprint_rs::{store::{self, MemoryStore, PrintStore}, FpContext};
+///
+/// let ctx = FpContext::new();
+/// let devices = ctx.devices();
+/// let dev = devices.get(0).unwrap();
+/// dev.open_sync(None).unwrap();
+///
+/// let mut store = MemoryStore::with_templates_per_finger(3);
+/// for print in enroll_three_times(&dev, "bruce") {
+///     store.add(&print).unwrap();
+/// }
+/// let outcome = store::authenticate_user(&mut store, &dev, "bruce", None, None, None, None, None::<()>).unwrap();
+/// println!("Authenticated: {}", outcome.is_match());
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub fn authenticate_user<S: PrintStore + ?Sized, T>(
+    store: &mut S,
+    device: &FpDevice,
+    username: &str,
+    finger: Option<FpFinger>,
+    audit: Option<&dyn AuditSink>,
+    cancellable: Option<&Cancellable>,
+    match_cb: Option<FpMatchCb<T>>,
+    match_data: Option<T>,
+) -> Result<FpMatchOutcome, FpError> {
+    let result = scan(
+        store,
+        device,
+        username,
+        finger,
+        cancellable,
+        match_cb,
+        match_data,
+    );
+    if let Some(sink) = audit {
+        report(sink, device, username, finger, &result);
+    }
+
+    let (outcome, matched) = result?;
+    let attempt = match &outcome {
+        FpMatchOutcome::Matched(_) => matched.map(|print| (print.finger(), MatchAttempt::Matched)),
+        FpMatchOutcome::NotMatched(_) => finger.map(|finger| (finger, MatchAttempt::Rejected)),
+        FpMatchOutcome::Retry(_) => finger.map(|finger| (finger, MatchAttempt::Retried)),
+    };
+    if let Some((finger, attempt)) = attempt {
+        store.record_attempt(username, finger, attempt)?;
+    }
+    Ok(outcome)
+}
+
+/// Matches the finger on `device` against the templates of `username`, returning the outcome and the matching
+/// template.
+fn scan<S: PrintStore + ?Sized, T>(
+    store: &S,
+    device: &FpDevice,
+    username: &str,
+    finger: Option<FpFinger>,
+    cancellable: Option<&Cancellable>,
+    match_cb: Option<FpMatchCb<T>>,
+    match_data: Option<T>,
+) -> Result<(FpMatchOutcome, Option<FpPrint>), FpError> {
+    let fingers = match finger {
+        Some(finger) => vec![finger],
+        None => store.list(username)?,
+    };
+    let mut templates: Vec<FpPrint> = Vec::new();
+    for finger in fingers {
+        templates.extend(store.get_all(username, finger)?);
+    }
+    if templates.is_empty() {
+        let message = format!("No print stored for {}", username);
+        return Err(crate::GError::new(gio::IOErrorEnum::NotFound, &message).into());
+    }
+
+    let result = match device.identify_sync(&templates, cancellable, match_cb, match_data) {
+        Ok(result) => result,
+        Err(err) => match err.kind::<FpRetryError>() {
+            Some(retry) => return Ok((FpMatchOutcome::Retry(retry), None)),
+            None => return Err(err.into()),
+        },
+    };
+    Ok(match result.matched {
+        Some((_, matched)) => (FpMatchOutcome::Matched(result.new_scan), Some(matched)),
+        None => (FpMatchOutcome::NotMatched(result.new_scan), None),
+    })
+}
+
+/// Report the outcome of `scan` to `sink`, as `FpDeviceManager` reports its verifications.
+fn report(
+    sink: &dyn AuditSink,
+    device: &FpDevice,
+    username: &str,
+    finger: Option<FpFinger>,
+    result: &Result<(FpMatchOutcome, Option<FpPrint>), FpError>,
+) {
+    let mut record = AuditRecord::new(device, None);
+    record.username = Some(username.to_string());
+    record.finger = finger;
+    match result {
+        Ok((FpMatchOutcome::Matched(_), matched)) => {
+            record.finger = matched.as_ref().map(FpPrint::finger).or(finger);
+            sink.on_match(&record)
+        }
+        Ok((FpMatchOutcome::NotMatched(_), _)) => sink.on_no_match(&record),
+        // A scan to retry is not an attempt
+        Ok((FpMatchOutcome::Retry(_), _)) => {}
+        Err(err) => sink.on_error(&record, err),
+    }
+}
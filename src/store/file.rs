@@ -0,0 +1,274 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::FpError,
+    finger::FpFinger,
+    print::FpPrint,
+    serialized::{FpPrintEnvelope, FpSerializedPrint},
+};
+
+use super::{print_key, PrintStore};
+
+/// Extension of the files holding the prints.
+const PRINT_EXTENSION: &str = "print";
+
+/// A `PrintStore` keeping each print in its own file, under a directory.
+///
+/// The prints of a user are kept in a directory named after the username, where each print is written as an
+/// `FpPrintEnvelope` holding its key in the `username` and `finger` metadata, in a file named after the number
+/// of the finger, e.g. `bruce/7.print` for the right index of `bruce`. The characters of the usernames that are
+/// not letters, digits, `-` and `_` are percent-encoded in the directory names. The files are replaced
+/// atomically, see `FpPrintEnvelope::write_to`, so a print being stored while the process is interrupted is
+/// either the previous or the new one.
+///
+/// The store keeps one template per finger and no match statistics.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    root: PathBuf,
+}
+
+impl FileStore {
+    /// Opens the store kept in the directory `root`, creating it if needed.
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, FpError> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)?;
+        Ok(FileStore { root })
+    }
+    /// Returns the directory of the store.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn user_dir(&self, username: &str) -> PathBuf {
+        self.root.join(escape(username))
+    }
+    fn path(&self, username: &str, finger: FpFinger) -> PathBuf {
+        self.user_dir(username)
+            .join(format!("{}.{}", finger.as_u32(), PRINT_EXTENSION))
+    }
+    /// Returns the print stored for the key, without deserializing it.
+    fn read(&self, username: &str, finger: FpFinger) -> Result<Option<FpSerializedPrint>, FpError> {
+        let envelope = match FpPrintEnvelope::read_from(self.path(username, finger)) {
+            Ok(envelope) => envelope,
+            Err(FpError::Io(err)) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        if envelope.get("username") != Some(username)
+            || envelope.get("finger") != Some(finger.as_u32().to_string().as_str())
+        {
+            return Err(FpError::CorruptPrint(
+                "the print file is not stored under its username and finger",
+            ));
+        }
+        Ok(Some(envelope.print().clone()))
+    }
+    /// Stores `print` under the key, replacing the previous print.
+    fn write(
+        &self,
+        username: &str,
+        finger: FpFinger,
+        print: FpSerializedPrint,
+    ) -> Result<(), FpError> {
+        let mut envelope = FpPrintEnvelope::new(print);
+        envelope.insert("username", username);
+        envelope.insert("finger", &finger.as_u32().to_string());
+
+        std::fs::create_dir_all(self.user_dir(username))?;
+        envelope.write_to(self.path(username, finger))?;
+        Ok(())
+    }
+}
+
+impl PrintStore for FileStore {
+    fn get(&self, username: &str, finger: FpFinger) -> Result<Option<FpPrint>, FpError> {
+        match self.read(username, finger)? {
+            Some(print) => Ok(Some(print.deserialize()?)),
+            None => Ok(None),
+        }
+    }
+    fn put(&mut self, print: &FpPrint) -> Result<(), FpError> {
+        let (username, finger) = print_key(print)?;
+        self.write(&username, finger, FpSerializedPrint::new(print)?)
+    }
+    fn delete(&mut self, username: &str, finger: FpFinger) -> Result<bool, FpError> {
+        match std::fs::remove_file(self.path(username, finger)) {
+            Ok(()) => {
+                // Only removes the directory once the last print of the user is gone
+                let _ = std::fs::remove_dir(self.user_dir(username));
+                Ok(true)
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+    fn list(&self, username: &str) -> Result<Vec<FpFinger>, FpError> {
+        let entries = match std::fs::read_dir(self.user_dir(username)) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut fingers = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(PRINT_EXTENSION) {
+                continue;
+            }
+            // Skips the temporary files of write_to and the files of other programs
+            let finger = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<i32>().ok())
+                .and_then(|finger| FpFinger::try_from(finger).ok());
+            fingers.extend(finger);
+        }
+        fingers.sort_by_key(|finger| finger.as_u32());
+        Ok(fingers)
+    }
+    fn users(&self) -> Result<Vec<String>, FpError> {
+        let mut users = Vec::new();
+        for entry in std::fs::read_dir(&self.root)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let Some(username) = entry.file_name().to_str().and_then(unescape) else {
+                continue;
+            };
+            if !self.list(&username)?.is_empty() {
+                users.push(username);
+            }
+        }
+        users.sort();
+        Ok(users)
+    }
+}
+
+/// Percent-encodes the bytes of `username` that are not ASCII letters, digits, `-` or `_`.
+fn escape(username: &str) -> String {
+    let mut escaped = String::with_capacity(username.len());
+    for byte in username.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    escaped
+}
+
+/// Decodes a directory name written by `escape`, `None` if it was not.
+fn unescape(name: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut rest = name.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    let username = String::from_utf8(bytes).ok()?;
+    (escape(&username) == name).then_some(username)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, unescape, FileStore, PrintStore};
+    use crate::{error::FpError, finger::FpFinger, serialized::FpSerializedPrint};
+
+    fn temp_store(name: &str) -> FileStore {
+        let root = std::env::temp_dir().join(format!(
+            "libfprint-rs-store-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        FileStore::open(root).unwrap()
+    }
+
+    #[test]
+    fn file_store_round_trip() {
+        let mut store = temp_store("round-trip");
+        let print = FpSerializedPrint::from_bytes(vec![1, 2, 3]);
+        store
+            .write("Bruce Banner", FpFinger::RightIndex, print.clone())
+            .unwrap();
+        store
+            .write(
+                "Bruce Banner",
+                FpFinger::LeftThumb,
+                FpSerializedPrint::from_bytes(vec![4]),
+            )
+            .unwrap();
+        store
+            .write(
+                "Bruce Banner",
+                FpFinger::LeftThumb,
+                FpSerializedPrint::from_bytes(vec![5, 6]),
+            )
+            .unwrap();
+
+        assert_eq!(
+            store.read("Bruce Banner", FpFinger::RightIndex).unwrap(),
+            Some(print)
+        );
+        assert_eq!(
+            store
+                .read("Bruce Banner", FpFinger::LeftThumb)
+                .unwrap()
+                .unwrap()
+                .as_bytes(),
+            &[5, 6]
+        );
+        assert_eq!(store.read("bruce", FpFinger::LeftThumb).unwrap(), None);
+        assert_eq!(store.users().unwrap(), vec!["Bruce Banner".to_string()]);
+        assert_eq!(
+            store.list("Bruce Banner").unwrap(),
+            vec![FpFinger::LeftThumb, FpFinger::RightIndex]
+        );
+
+        assert!(store.delete("Bruce Banner", FpFinger::LeftThumb).unwrap());
+        assert!(!store.delete("Bruce Banner", FpFinger::LeftThumb).unwrap());
+        assert!(store.delete("Bruce Banner", FpFinger::RightIndex).unwrap());
+        assert!(store.users().unwrap().is_empty());
+        std::fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn file_store_misplaced_print() {
+        let store = temp_store("misplaced");
+        store
+            .write(
+                "bruce",
+                FpFinger::RightIndex,
+                FpSerializedPrint::from_bytes(vec![1]),
+            )
+            .unwrap();
+        std::fs::rename(
+            store.path("bruce", FpFinger::RightIndex),
+            store.path("bruce", FpFinger::LeftIndex),
+        )
+        .unwrap();
+        assert!(matches!(
+            store.read("bruce", FpFinger::LeftIndex),
+            Err(FpError::CorruptPrint(_))
+        ));
+        std::fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn username_escaping() {
+        for username in ["bruce", "Bruce Banner", "../etc", "élodie", "a%2F"] {
+            let escaped = escape(username);
+            assert!(!escaped.contains(['/', '.', ' ']));
+            assert_eq!(unescape(&escaped).as_deref(), Some(username));
+        }
+        assert_eq!(unescape("%2"), None);
+        assert_eq!(unescape("a%2f"), None);
+    }
+}
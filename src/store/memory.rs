@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    error::FpError,
+    finger::FpFinger,
+    print::FpPrint,
+    serialized::{FpPrintEnvelope, FpSerializedPrint},
+};
+
+use super::{print_key, MatchAttempt, PrintStats, PrintStore};
+
+/// A `PrintStore` keeping the prints in memory, useful for tests and for kiosks that load their
+/// prints from a server at boot with `MemoryStore::restore`.
+///
+/// Prints are kept serialized, so the prints returned by `get` are independent copies. The match statistics are
+/// kept too, but are not part of the snapshots.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    prints: BTreeMap<(String, FpFinger), Vec<FpSerializedPrint>>,
+    stats: BTreeMap<(String, FpFinger), PrintStats>,
+    templates_per_finger: usize,
+}
+
+/// The content of a `MemoryStore` at some point, see `MemoryStore::snapshot`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemorySnapshot {
+    prints: BTreeMap<(String, FpFinger), Vec<FpSerializedPrint>>,
+}
+
+impl MemoryStore {
+    /// Creates an empty store, keeping one template per finger.
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+    /// Creates an empty store keeping up to `templates` templates per finger, `PrintStore::add` drops the oldest
+    /// template of a finger once there are more.
+    pub fn with_templates_per_finger(templates: usize) -> Self {
+        MemoryStore {
+            templates_per_finger: templates,
+            ..Default::default()
+        }
+    }
+    /// Returns the number of stored prints, counting every template.
+    pub fn len(&self) -> usize {
+        self.prints.values().map(Vec::len).sum()
+    }
+    /// Whether the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.prints.is_empty()
+    }
+    /// Returns a copy of the current content of the store.
+    pub fn snapshot(&self) -> MemorySnapshot {
+        MemorySnapshot {
+            prints: self.prints.clone(),
+        }
+    }
+    /// Replaces the content of the store with `snapshot`, the statistics start over.
+    pub fn restore(&mut self, snapshot: MemorySnapshot) {
+        self.stats = snapshot
+            .prints
+            .keys()
+            .map(|key| (key.clone(), PrintStats::default()))
+            .collect();
+        self.prints = snapshot.prints;
+    }
+}
+
+impl PrintStore for MemoryStore {
+    fn get(&self, username: &str, finger: FpFinger) -> Result<Option<FpPrint>, FpError> {
+        match self
+            .prints
+            .get(&(username.to_string(), finger))
+            .and_then(|prints| prints.last())
+        {
+            Some(print) => Ok(Some(print.deserialize()?)),
+            None => Ok(None),
+        }
+    }
+    fn put(&mut self, print: &FpPrint) -> Result<(), FpError> {
+        let key = print_key(print)?;
+        self.prints
+            .insert(key.clone(), vec![FpSerializedPrint::new(print)?]);
+        self.stats.insert(key, PrintStats::new(print.enroll_date()));
+        Ok(())
+    }
+    fn get_all(&self, username: &str, finger: FpFinger) -> Result<Vec<FpPrint>, FpError> {
+        self.prints
+            .get(&(username.to_string(), finger))
+            .map_or(Ok(Vec::new()), |prints| {
+                prints.iter().map(FpSerializedPrint::deserialize).collect()
+            })
+    }
+    fn add(&mut self, print: &FpPrint) -> Result<(), FpError> {
+        let key = print_key(print)?;
+        let serialized = FpSerializedPrint::new(print)?;
+        let prints = self.prints.entry(key.clone()).or_default();
+        prints.push(serialized);
+        let excess = prints
+            .len()
+            .saturating_sub(self.templates_per_finger.max(1));
+        prints.drain(..excess);
+        // The statistics cover every template of the finger, they only start with the first one
+        self.stats
+            .entry(key)
+            .or_insert_with(|| PrintStats::new(print.enroll_date()));
+        Ok(())
+    }
+    fn delete(&mut self, username: &str, finger: FpFinger) -> Result<bool, FpError> {
+        let key = (username.to_string(), finger);
+        self.stats.remove(&key);
+        Ok(self.prints.remove(&key).is_some())
+    }
+    fn list(&self, username: &str) -> Result<Vec<FpFinger>, FpError> {
+        Ok(self
+            .prints
+            .keys()
+            .filter(|(user, _)| user == username)
+            .map(|(_, finger)| *finger)
+            .collect())
+    }
+    fn users(&self) -> Result<Vec<String>, FpError> {
+        let mut users: Vec<String> = self.prints.keys().map(|(user, _)| user.clone()).collect();
+        users.dedup();
+        Ok(users)
+    }
+    fn stats(&self, username: &str, finger: FpFinger) -> Result<Option<PrintStats>, FpError> {
+        Ok(self.stats.get(&(username.to_string(), finger)).cloned())
+    }
+    fn record_attempt(
+        &mut self,
+        username: &str,
+        finger: FpFinger,
+        attempt: MatchAttempt,
+    ) -> Result<(), FpError> {
+        if let Some(stats) = self.stats.get_mut(&(username.to_string(), finger)) {
+            stats.record(attempt);
+        }
+        Ok(())
+    }
+}
+
+impl MemorySnapshot {
+    /// Returns the number of prints in the snapshot, counting every template.
+    pub fn len(&self) -> usize {
+        self.prints.values().map(Vec::len).sum()
+    }
+    /// Whether the snapshot is empty.
+    pub fn is_empty(&self) -> bool {
+        self.prints.is_empty()
+    }
+    /// Serialize the snapshot, e.g. to send it to other machines. Each print is written as an `FpPrintEnvelope`
+    /// holding its key in the `username` and `finger` metadata, the templates of a finger are written oldest first.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&(self.len() as u32).to_le_bytes());
+        for ((username, finger), print) in self
+            .prints
+            .iter()
+            .flat_map(|(key, prints)| prints.iter().map(move |print| (key, print)))
+        {
+            let mut envelope = FpPrintEnvelope::new(print.clone());
+            envelope.insert("username", username);
+            envelope.insert("finger", &finger.as_u32().to_string());
+
+            let bytes = envelope.to_bytes();
+            out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            out.extend_from_slice(&bytes);
+        }
+        out
+    }
+    /// Deserialize a snapshot written by `MemorySnapshot::to_bytes`.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, FpError> {
+        fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], FpError> {
+            if data.len() < len {
+                return Err(FpError::CorruptPrint("truncated snapshot"));
+            }
+            let (head, tail) = data.split_at(len);
+            *data = tail;
+            Ok(head)
+        }
+        fn take_u32(data: &mut &[u8]) -> Result<u32, FpError> {
+            let bytes = take(data, 4)?;
+            Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        }
+
+        let mut data = data;
+        let mut prints = BTreeMap::new();
+        for _ in 0..take_u32(&mut data)? {
+            let len = take_u32(&mut data)? as usize;
+            let envelope = FpPrintEnvelope::from_bytes(take(&mut data, len)?)?;
+
+            let username = envelope
+                .get("username")
+                .ok_or(FpError::CorruptPrint("snapshot entry without username"))?;
+            let finger = envelope
+                .get("finger")
+                .and_then(|finger| finger.parse::<i32>().ok())
+                .ok_or(FpError::CorruptPrint("snapshot entry without finger"))?;
+            let finger = FpFinger::try_from(finger)
+                .map_err(|_| FpError::CorruptPrint("snapshot entry with an unknown finger"))?;
+            prints
+                .entry((username.to_string(), finger))
+                .or_insert_with(Vec::new)
+                .push(envelope.print().clone());
+        }
+        if !data.is_empty() {
+            return Err(FpError::CorruptPrint("trailing data after the snapshot"));
+        }
+        Ok(MemorySnapshot { prints })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemorySnapshot, MemoryStore, PrintStore};
+    use crate::{
+        error::FpError,
+        finger::FpFinger,
+        serialized::{FpPrintEnvelope, FpSerializedPrint},
+    };
+
+    #[test]
+    fn snapshot_round_trip() {
+        let mut snapshot = MemorySnapshot::default();
+        snapshot.prints.insert(
+            ("bruce".to_string(), FpFinger::RightIndex),
+            vec![
+                FpSerializedPrint::from_bytes(vec![1, 2, 3]),
+                FpSerializedPrint::from_bytes(vec![5, 6]),
+            ],
+        );
+        snapshot.prints.insert(
+            ("bruce".to_string(), FpFinger::LeftThumb),
+            vec![FpSerializedPrint::from_bytes(vec![4])],
+        );
+
+        let restored = MemorySnapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+        assert_eq!(restored, snapshot);
+
+        let mut store = MemoryStore::new();
+        store.restore(restored);
+        assert_eq!(store.users().unwrap(), vec!["bruce".to_string()]);
+        assert_eq!(store.list("bruce").unwrap().len(), 2);
+        assert_eq!(store.len(), 3);
+        assert!(store.delete("bruce", FpFinger::LeftThumb).unwrap());
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn snapshot_unknown_finger() {
+        let mut envelope = FpPrintEnvelope::new(FpSerializedPrint::from_bytes(vec![1, 2, 3]));
+        envelope.insert("username", "bruce");
+        envelope.insert("finger", "99");
+        let entry = envelope.to_bytes();
+
+        let mut data = 1u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+        data.extend_from_slice(&entry);
+        assert!(matches!(
+            MemorySnapshot::from_bytes(&data),
+            Err(FpError::CorruptPrint(_))
+        ));
+    }
+}
@@ -0,0 +1,135 @@
+//! Storage of enrolled prints.
+//!
+//! The `PrintStore` trait abstracts where the prints are kept, so the enrollment and verification code of
+//! an application does not depend on the storage backend. Prints are keyed by their username and finger, a
+//! store holds one print for each pair unless it keeps several templates per finger, see `PrintStore::add` and
+//! `authenticate_user`.
+//!
+//! The crate ships three backends: `MemoryStore` keeps the prints in memory, `FileStore` keeps each print in
+//! its own file and `SqliteStore` keeps them in an SQLite database (requires the `sqlite` feature).
+//!
+//! Stores may also keep match statistics for their prints, see `PrintStore::record_attempt`, to recommend
+//! enrolling again the prints that degrade with `PrintStore::needs_reenrollment`.
+mod auth;
+mod file;
+mod memory;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stats;
+mod sync;
+
+use std::collections::HashMap;
+
+use crate::{
+    error::FpError,
+    finger::FpFinger,
+    print::{FpMetadataField, FpPrint},
+};
+
+pub use auth::authenticate_user;
+pub use file::FileStore;
+pub use memory::{MemorySnapshot, MemoryStore};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+pub use stats::{MatchAttempt, PrintStats, ReenrollPolicy, RECENT_ATTEMPTS};
+pub use sync::{sync_device, SyncOptions, SyncReport};
+
+/// A storage backend for enrolled prints, keyed by username and finger.
+pub trait PrintStore {
+    /// Returns the print of `username` for `finger`, if stored. With several templates, the last added one.
+    fn get(&self, username: &str, finger: FpFinger) -> Result<Option<FpPrint>, FpError>;
+    /// Stores `print` under its username and finger, replacing the previous prints for the pair.
+    /// Fails with `FpError::MissingMetadata` if the print has no username.
+    fn put(&mut self, print: &FpPrint) -> Result<(), FpError>;
+    /// Removes the prints of `username` for `finger`, returning whether any was stored.
+    fn delete(&mut self, username: &str, finger: FpFinger) -> Result<bool, FpError>;
+    /// Returns the fingers stored for `username`.
+    fn list(&self, username: &str) -> Result<Vec<FpFinger>, FpError>;
+    /// Returns the users that have at least one stored print.
+    fn users(&self) -> Result<Vec<String>, FpError>;
+
+    /// Returns every template of `username` for `finger`, oldest first. Stores keeping one template per finger
+    /// return the print given by `get`.
+    fn get_all(&self, username: &str, finger: FpFinger) -> Result<Vec<FpPrint>, FpError> {
+        Ok(self.get(username, finger)?.into_iter().collect())
+    }
+    /// Adds `print` to the templates of its username and finger. Stores keeping one template per finger replace
+    /// the previous print, like `put`.
+    /// Fails with `FpError::MissingMetadata` if the print has no username.
+    fn add(&mut self, print: &FpPrint) -> Result<(), FpError> {
+        self.put(print)
+    }
+
+    /// Returns every stored template grouped by user, as expected by `FpDevice::identify_users`.
+    fn gallery(&self) -> Result<HashMap<String, Vec<FpPrint>>, FpError> {
+        let mut gallery = HashMap::new();
+        for username in self.users()? {
+            let mut prints = Vec::new();
+            for finger in self.list(&username)? {
+                prints.extend(self.get_all(&username, finger)?);
+            }
+            gallery.insert(username, prints);
+        }
+        Ok(gallery)
+    }
+    /// Returns the match statistics of the print of `username` for `finger`, `None` if it is not stored or the
+    /// store keeps no statistics.
+    fn stats(&self, _username: &str, _finger: FpFinger) -> Result<Option<PrintStats>, FpError> {
+        Ok(None)
+    }
+    /// Record an attempt against the print of `username` for `finger`. Does nothing if the print is not stored or
+    /// the store keeps no statistics. Storing a print again resets its statistics.
+    fn record_attempt(
+        &mut self,
+        _username: &str,
+        _finger: FpFinger,
+        _attempt: MatchAttempt,
+    ) -> Result<(), FpError> {
+        Ok(())
+    }
+    /// Whether the print of `username` for `finger` should be enrolled again, see `PrintStats::needs_reenrollment`.
+    fn needs_reenrollment(
+        &self,
+        username: &str,
+        finger: FpFinger,
+        policy: &ReenrollPolicy,
+    ) -> Result<bool, FpError> {
+        Ok(self
+            .stats(username, finger)?
+            .is_some_and(|stats| stats.needs_reenrollment(policy)))
+    }
+    /// Returns the key of every stored print that should be enrolled again according to `policy`.
+    fn reenrollment_candidates(
+        &self,
+        policy: &ReenrollPolicy,
+    ) -> Result<Vec<(String, FpFinger)>, FpError> {
+        let mut candidates = Vec::new();
+        for username in self.users()? {
+            for finger in self.list(&username)? {
+                if self.needs_reenrollment(&username, finger, policy)? {
+                    candidates.push((username.clone(), finger));
+                }
+            }
+        }
+        Ok(candidates)
+    }
+    /// Removes every print of `username`, returning the number of removed prints.
+    fn delete_user(&mut self, username: &str) -> Result<usize, FpError> {
+        let mut removed = 0;
+        for finger in self.list(username)? {
+            if self.delete(username, finger)? {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Returns the key of `print` in a `PrintStore`, failing with `FpError::MissingMetadata` if it has no username.
+pub fn print_key(print: &FpPrint) -> Result<(String, FpFinger), FpError> {
+    let username = print
+        .username()
+        .filter(|username| !username.is_empty())
+        .ok_or(FpError::MissingMetadata(FpMetadataField::Username))?;
+    Ok((username, print.finger()))
+}
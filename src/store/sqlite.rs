@@ -0,0 +1,189 @@
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{error::FpError, finger::FpFinger, print::FpPrint, serialized::FpSerializedPrint};
+
+use super::{print_key, PrintStore};
+
+/// A `PrintStore` keeping the prints in an SQLite database (requires the `sqlite` feature).
+///
+/// The prints are kept serialized in the `fprs_prints` table, keyed by their username and finger number, which
+/// is created if needed. The store keeps one template per finger and no match statistics.
+#[derive(Debug)]
+pub struct SqliteStore {
+    connection: Connection,
+}
+
+impl SqliteStore {
+    /// Opens the store kept in the database at `path`, creating the database if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, FpError> {
+        SqliteStore::from_connection(Connection::open(path)?)
+    }
+    /// Opens the store kept in the database of `connection`, e.g. the database of the application.
+    pub fn from_connection(connection: Connection) -> Result<Self, FpError> {
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS fprs_prints (
+                username TEXT NOT NULL,
+                finger INTEGER NOT NULL,
+                data BLOB NOT NULL,
+                PRIMARY KEY (username, finger)
+            )",
+            (),
+        )?;
+        Ok(SqliteStore { connection })
+    }
+    /// Returns the connection to the database.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Returns the print stored for the key, without deserializing it.
+    fn read(&self, username: &str, finger: FpFinger) -> Result<Option<FpSerializedPrint>, FpError> {
+        let data: Option<Vec<u8>> = self
+            .connection
+            .query_row(
+                "SELECT data FROM fprs_prints WHERE username = ?1 AND finger = ?2",
+                params![username, finger.as_u32()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(data.map(FpSerializedPrint::from_bytes))
+    }
+    /// Stores `print` under the key, replacing the previous print.
+    fn write(
+        &self,
+        username: &str,
+        finger: FpFinger,
+        print: FpSerializedPrint,
+    ) -> Result<(), FpError> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO fprs_prints (username, finger, data) VALUES (?1, ?2, ?3)",
+            params![username, finger.as_u32(), print.as_bytes()],
+        )?;
+        Ok(())
+    }
+}
+
+impl PrintStore for SqliteStore {
+    fn get(&self, username: &str, finger: FpFinger) -> Result<Option<FpPrint>, FpError> {
+        match self.read(username, finger)? {
+            Some(print) => Ok(Some(print.deserialize()?)),
+            None => Ok(None),
+        }
+    }
+    fn put(&mut self, print: &FpPrint) -> Result<(), FpError> {
+        let (username, finger) = print_key(print)?;
+        self.write(&username, finger, FpSerializedPrint::new(print)?)
+    }
+    fn delete(&mut self, username: &str, finger: FpFinger) -> Result<bool, FpError> {
+        let removed = self.connection.execute(
+            "DELETE FROM fprs_prints WHERE username = ?1 AND finger = ?2",
+            params![username, finger.as_u32()],
+        )?;
+        Ok(removed > 0)
+    }
+    fn list(&self, username: &str) -> Result<Vec<FpFinger>, FpError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT finger FROM fprs_prints WHERE username = ?1 ORDER BY finger")?;
+        let fingers = statement
+            .query_map([username], |row| row.get::<_, i32>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        fingers
+            .into_iter()
+            .map(|finger| {
+                FpFinger::try_from(finger)
+                    .map_err(|_| FpError::CorruptPrint("stored print with an unknown finger"))
+            })
+            .collect()
+    }
+    fn users(&self) -> Result<Vec<String>, FpError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT DISTINCT username FROM fprs_prints ORDER BY username")?;
+        let users = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(users)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{PrintStore, SqliteStore};
+    use crate::{error::FpError, finger::FpFinger, serialized::FpSerializedPrint};
+
+    #[test]
+    fn sqlite_store_round_trip() {
+        let mut store =
+            SqliteStore::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let print = FpSerializedPrint::from_bytes(vec![1, 2, 3]);
+        store
+            .write("bruce", FpFinger::RightIndex, print.clone())
+            .unwrap();
+        store
+            .write(
+                "bruce",
+                FpFinger::LeftThumb,
+                FpSerializedPrint::from_bytes(vec![4]),
+            )
+            .unwrap();
+        store
+            .write(
+                "bruce",
+                FpFinger::LeftThumb,
+                FpSerializedPrint::from_bytes(vec![5, 6]),
+            )
+            .unwrap();
+        store
+            .write(
+                "natasha",
+                FpFinger::LeftIndex,
+                FpSerializedPrint::from_bytes(vec![7]),
+            )
+            .unwrap();
+
+        assert_eq!(
+            store.read("bruce", FpFinger::RightIndex).unwrap(),
+            Some(print)
+        );
+        assert_eq!(
+            store
+                .read("bruce", FpFinger::LeftThumb)
+                .unwrap()
+                .unwrap()
+                .as_bytes(),
+            &[5, 6]
+        );
+        assert_eq!(store.read("bruce", FpFinger::LeftIndex).unwrap(), None);
+        assert_eq!(
+            store.users().unwrap(),
+            vec!["bruce".to_string(), "natasha".to_string()]
+        );
+        assert_eq!(
+            store.list("bruce").unwrap(),
+            vec![FpFinger::LeftThumb, FpFinger::RightIndex]
+        );
+
+        assert!(store.delete("bruce", FpFinger::LeftThumb).unwrap());
+        assert!(!store.delete("bruce", FpFinger::LeftThumb).unwrap());
+        assert_eq!(store.delete_user("natasha").unwrap(), 1);
+        assert_eq!(store.users().unwrap(), vec!["bruce".to_string()]);
+    }
+
+    #[test]
+    fn sqlite_store_unknown_finger() {
+        let store = SqliteStore::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        store
+            .connection()
+            .execute(
+                "INSERT INTO fprs_prints (username, finger, data) VALUES ('bruce', 99, x'01')",
+                (),
+            )
+            .unwrap();
+        assert!(matches!(store.list("bruce"), Err(FpError::CorruptPrint(_))));
+    }
+}
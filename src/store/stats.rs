@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+
+use crate::date::FpDate;
+
+/// The number of recent attempts kept by `PrintStats`, the rates of these are compared to the lifetime rates.
+pub const RECENT_ATTEMPTS: usize = 20;
+
+/// The outcome of a scan matched against a stored print, see `PrintStore::record_attempt`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchAttempt {
+    /// The finger matched the print.
+    Matched,
+    /// The scan had to be retried, e.g. the finger was not centered.
+    Retried,
+    /// The finger of the user did not match their print.
+    Rejected,
+}
+
+/// When a stored print should be enrolled again, see `PrintStats::needs_reenrollment`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReenrollPolicy {
+    /// Prints enrolled more than this number of days ago must be enrolled again, `None` to ignore the age.
+    pub max_age_days: Option<u32>,
+    /// The retry rate of the recent attempts above which the print must be enrolled again, from 0 to 1.
+    pub max_retry_rate: f32,
+    /// The reject rate of the recent attempts above which the print must be enrolled again, from 0 to 1.
+    pub max_reject_rate: f32,
+    /// The number of recent attempts needed before the rates are taken into account.
+    pub min_attempts: usize,
+}
+
+impl Default for ReenrollPolicy {
+    fn default() -> Self {
+        ReenrollPolicy {
+            max_age_days: Some(730),
+            max_retry_rate: 0.5,
+            max_reject_rate: 0.3,
+            min_attempts: 10,
+        }
+    }
+}
+
+/// The match statistics of a stored print, see `PrintStore::stats`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrintStats {
+    /// The enroll date of the print, if it has one.
+    pub enroll_date: Option<FpDate>,
+    /// The number of matches since the print was stored.
+    pub matches: u64,
+    /// The number of retried scans since the print was stored.
+    pub retries: u64,
+    /// The number of rejected scans since the print was stored.
+    pub rejects: u64,
+    recent: VecDeque<MatchAttempt>,
+}
+
+impl PrintStats {
+    /// Creates the statistics of a print enrolled on `enroll_date`, without any attempt.
+    pub fn new(enroll_date: Option<FpDate>) -> Self {
+        PrintStats {
+            enroll_date,
+            ..Default::default()
+        }
+    }
+    /// Record an attempt against the print.
+    pub fn record(&mut self, attempt: MatchAttempt) {
+        match attempt {
+            MatchAttempt::Matched => self.matches += 1,
+            MatchAttempt::Retried => self.retries += 1,
+            MatchAttempt::Rejected => self.rejects += 1,
+        }
+        if self.recent.len() == RECENT_ATTEMPTS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(attempt);
+    }
+    /// Returns the total number of attempts.
+    pub fn attempts(&self) -> u64 {
+        self.matches + self.retries + self.rejects
+    }
+    /// Returns the retry and reject rates of the last `RECENT_ATTEMPTS` attempts, `None` without attempts.
+    pub fn recent_rates(&self) -> Option<(f32, f32)> {
+        if self.recent.is_empty() {
+            return None;
+        }
+        let count = |kind| self.recent.iter().filter(|&&a| a == kind).count() as f32;
+        let len = self.recent.len() as f32;
+        Some((
+            count(MatchAttempt::Retried) / len,
+            count(MatchAttempt::Rejected) / len,
+        ))
+    }
+    /// Returns the retry and reject rates of every attempt, `None` without attempts.
+    pub fn lifetime_rates(&self) -> Option<(f32, f32)> {
+        let attempts = self.attempts();
+        if attempts == 0 {
+            return None;
+        }
+        Some((
+            self.retries as f32 / attempts as f32,
+            self.rejects as f32 / attempts as f32,
+        ))
+    }
+
+    /// Whether the print should be enrolled again according to `policy`: it is older than the maximum age, or the
+    /// retry or reject rate of its recent attempts is above the maximum and rising, i.e. above the lifetime rate.
+    pub fn needs_reenrollment(&self, policy: &ReenrollPolicy) -> bool {
+        self.needs_reenrollment_on(policy, FpDate::today())
+    }
+    fn needs_reenrollment_on(&self, policy: &ReenrollPolicy, today: FpDate) -> bool {
+        let too_old = match (policy.max_age_days, self.enroll_date) {
+            (Some(max_age), Some(enrolled)) => enrolled.days_until(&today) > max_age as i64,
+            _ => false,
+        };
+        if too_old {
+            return true;
+        }
+        if self.recent.len() < policy.min_attempts.max(1) {
+            return false;
+        }
+        let (Some((recent_retry, recent_reject)), Some((retry, reject))) =
+            (self.recent_rates(), self.lifetime_rates())
+        else {
+            return false;
+        };
+        (recent_retry > policy.max_retry_rate && recent_retry > retry)
+            || (recent_reject > policy.max_reject_rate && recent_reject > reject)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MatchAttempt, PrintStats, ReenrollPolicy};
+    use crate::date::FpDate;
+
+    #[test]
+    fn reenrollment() {
+        let policy = ReenrollPolicy {
+            max_age_days: Some(365),
+            min_attempts: 5,
+            ..Default::default()
+        };
+        let today = FpDate::new(2024, 6, 1).unwrap();
+
+        let old = PrintStats::new(FpDate::new(2023, 5, 1));
+        assert!(old.needs_reenrollment_on(&policy, today));
+        let recent = PrintStats::new(FpDate::new(2024, 1, 1));
+        assert!(!recent.needs_reenrollment_on(&policy, today));
+
+        // A print that always worked, then started rejecting
+        let mut stats = PrintStats::new(None);
+        for _ in 0..50 {
+            stats.record(MatchAttempt::Matched);
+        }
+        assert!(!stats.needs_reenrollment_on(&policy, today));
+        for _ in 0..8 {
+            stats.record(MatchAttempt::Rejected);
+        }
+        assert_eq!(stats.attempts(), 58);
+        assert!(stats.needs_reenrollment_on(&policy, today));
+
+        // A print that always had a high retry rate is not degrading
+        let mut stats = PrintStats::new(None);
+        for _ in 0..30 {
+            stats.record(MatchAttempt::Retried);
+            stats.record(MatchAttempt::Matched);
+        }
+        assert!(!stats.needs_reenrollment_on(&policy, today));
+    }
+}
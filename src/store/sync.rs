@@ -0,0 +1,146 @@
+use gio::Cancellable;
+
+use crate::{
+    device::{FpDevice, FpEnrollProgress, FpFingerPrompt},
+    error::FpError,
+    finger::FpFinger,
+    print::FpPrint,
+};
+
+use super::{print_key, PrintStore};
+
+/// What `sync_device` is allowed to change on the device.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncOptions {
+    /// Enroll the prints of the store that are missing on the device, this requires the users to scan their fingers.
+    pub enroll_missing: bool,
+    /// Delete the prints of the device that are not in the store.
+    pub delete_orphaned: bool,
+}
+
+/// The differences found, and fixed, between a `PrintStore` and the storage of a device by `sync_device`.
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    /// Prints of the store that are also on the device.
+    pub in_sync: Vec<(String, FpFinger)>,
+    /// Prints of the store missing on the device that were enrolled again, the store was updated with the new prints.
+    pub enrolled: Vec<(String, FpFinger)>,
+    /// Prints of the store missing on the device that were not enrolled.
+    pub missing: Vec<(String, FpFinger)>,
+    /// Prints of the device that are not in the store and were deleted from the device.
+    pub deleted: Vec<FpPrint>,
+    /// Prints of the device that are not in the store and were kept.
+    pub orphaned: Vec<FpPrint>,
+    /// Operations that failed, with the error that made them fail.
+    pub failed: Vec<(String, FpFinger, FpError)>,
+}
+
+impl SyncReport {
+    /// Whether the store and the device hold the same prints after the synchronization.
+    pub fn is_synced(&self) -> bool {
+        self.missing.is_empty() && self.orphaned.is_empty() && self.failed.is_empty()
+    }
+}
+
+#[cfg(not(doctest))]
+/// Reconcile `store` with the storage of the match-on-chip `device`, which must be open.
+///
+/// A print of the store is on the device if the device lists an equal print, see `FpPrint::equal`. Missing prints
+/// are enrolled again with `FpDevice::enroll_fingers_sync` and orphaned prints are deleted, according to `options`.
+/// With the default options nothing is changed and the report only lists the differences.
+/// # Example:
+/// ```no_run
+/// use libfprint_rs::{store::{self, MemoryStore, SyncOptions}, FpContext, FpDevice, FpFinger};
+///
+/// let ctx = FpContext::new();
+/// let devices = ctx.devices();
+/// let dev = devices.get(0).unwrap();
+/// dev.open_sync(None).unwrap();
+///
+/// let mut store = MemoryStore::new();
+/// let options = SyncOptions { delete_orphaned: true, ..Default::default() };
+/// let report = store::sync_device(&mut store, &dev, options, None, None, None, None::<()>).unwrap();
+/// println!("{} prints must be enrolled again", report.missing.len());
+/// ```
+pub fn sync_device<S: PrintStore + ?Sized, T: Clone>(
+    store: &mut S,
+    device: &FpDevice,
+    options: SyncOptions,
+    cancellable: Option<&Cancellable>,
+    prompt_cb: Option<FpFingerPrompt<T>>,
+    progress_cb: Option<FpEnrollProgress<T>>,
+    data: Option<T>,
+) -> Result<SyncReport, FpError> {
+    let mut report = SyncReport::default();
+    let mut device_prints = device.list_prints_sync(cancellable)?;
+
+    let mut missing: Vec<(String, FpFinger)> = Vec::new();
+    for username in store.users()? {
+        for finger in store.list(&username)? {
+            let Some(print) = store.get(&username, finger)? else {
+                continue;
+            };
+            match device_prints.iter().position(|p| p.equal(&print)) {
+                Some(i) => {
+                    device_prints.swap_remove(i);
+                    report.in_sync.push((username.clone(), finger));
+                }
+                None => missing.push((username.clone(), finger)),
+            }
+        }
+    }
+
+    for print in device_prints {
+        if !options.delete_orphaned {
+            report.orphaned.push(print);
+            continue;
+        }
+        match device.delete_print_sync(&print, cancellable) {
+            Ok(()) => report.deleted.push(print),
+            Err(error) => {
+                let (username, finger) =
+                    print_key(&print).unwrap_or_else(|_| (String::new(), print.finger()));
+                report.failed.push((username, finger, error.into()));
+                report.orphaned.push(print);
+            }
+        }
+    }
+
+    if !options.enroll_missing {
+        report.missing = missing;
+        return Ok(report);
+    }
+
+    let mut users: Vec<&String> = missing.iter().map(|(username, _)| username).collect();
+    users.dedup();
+    for username in users {
+        let fingers: Vec<FpFinger> = missing
+            .iter()
+            .filter(|(user, _)| user == username)
+            .map(|(_, finger)| *finger)
+            .collect();
+        let set = device.enroll_fingers_sync(
+            username,
+            &fingers,
+            cancellable,
+            prompt_cb,
+            progress_cb,
+            data.clone(),
+        );
+
+        for (finger, print) in set.prints {
+            match store.put(&print) {
+                Ok(()) => report.enrolled.push((username.clone(), finger)),
+                Err(error) => report.failed.push((username.clone(), finger, error)),
+            }
+        }
+        for (finger, error) in set.failed {
+            report.failed.push((username.clone(), finger, error.into()));
+            report.missing.push((username.clone(), finger));
+        }
+        for finger in set.skipped {
+            report.missing.push((username.clone(), finger));
+        }
+    }
+    Ok(report)
+}
@@ -0,0 +1,167 @@
+//! Recording and replay of USB readers with umockdev (requires the `umockdev` feature).
+//!
+//! The traffic of a real reader is recorded once with `Recording::record`, and replayed in CI by running the
+//! test program under `umockdev-run`, see `Recording::replay_command`, so the real driver code paths are tested
+//! without hardware attached. umockdev works by preloading a library into the process, hence the replayed
+//! program is started as a child process. The `umockdev-record` and `umockdev-run` tools must be installed.
+//!
+//! A recording is a directory holding a `device` file, describing the device as seen by udev, and a
+//! `device.ioctl` file with the USB traffic. This is the layout used by the tests of libfprint.
+//! # Example:
+//! ```no_run
+//! use libfprint_rs::umockdev::{self, Recording};
+//! # fn enroll_and_verify() {}
+//!
+//! #[test]
+//! fn enroll_replay() {
+//!     if umockdev::is_replaying() {
+//!         // Running under umockdev-run, the recorded reader is the only device
+//!         return enroll_and_verify();
+//!     }
+//!     let recording = Recording::open("tests/recordings/synaptics").unwrap();
+//!     let status = recording
+//!         .replay_command(std::env::current_exe().unwrap())
+//!         .args(["enroll_replay", "--exact"])
+//!         .status()
+//!         .unwrap();
+//!     assert!(status.success());
+//! }
+//! ```
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::error::FpError;
+
+/// Name of the file describing the device in a recording.
+const DEVICE_FILE: &str = "device";
+/// Name of the file holding the USB traffic in a recording.
+const IOCTL_FILE: &str = "device.ioctl";
+
+/// Whether the current process runs under `umockdev-run`.
+pub fn is_replaying() -> bool {
+    std::env::var_os("UMOCKDEV_DIR").is_some()
+}
+
+/// A recording of a reader, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recording {
+    dir: PathBuf,
+    node: PathBuf,
+}
+
+impl Recording {
+    /// Open the recording stored in `dir`.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, FpError> {
+        let dir = dir.as_ref().to_path_buf();
+        let description = std::fs::read_to_string(dir.join(DEVICE_FILE))?;
+        let node = device_node(&description).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "The recorded device has no USB device node",
+            )
+        })?;
+        Ok(Recording { dir, node })
+    }
+    /// Record the reader at the device `node` (e.g. `/dev/bus/usb/001/005`) into `dir`, while running `command`.
+    /// The command must use the reader, e.g. enroll and verify a finger, the traffic it causes is recorded.
+    pub fn record(
+        node: impl AsRef<Path>,
+        dir: impl AsRef<Path>,
+        command: &Command,
+    ) -> Result<Self, FpError> {
+        let node = node.as_ref();
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+
+        let description = Command::new("umockdev-record").arg(node).output()?;
+        check("umockdev-record", description.status)?;
+        std::fs::write(dir.join(DEVICE_FILE), description.stdout)?;
+
+        let mut ioctl = node.as_os_str().to_os_string();
+        ioctl.push("=");
+        ioctl.push(dir.join(IOCTL_FILE));
+        let mut record = Command::new("umockdev-record");
+        record
+            .arg("--ioctl")
+            .arg(ioctl)
+            .arg("--")
+            .arg(command.get_program())
+            .args(command.get_args());
+        for (key, value) in command.get_envs() {
+            match value {
+                Some(value) => record.env(key, value),
+                None => record.env_remove(key),
+            };
+        }
+        check("The recorded command", record.status()?)?;
+
+        Recording::open(dir)
+    }
+
+    /// Returns the directory of the recording.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+    /// Returns the device node the reader was recorded at.
+    pub fn node(&self) -> &Path {
+        &self.node
+    }
+
+    /// Returns a command running `program` under `umockdev-run`, with the recorded reader as the only device.
+    /// `FP_DEVICE_EMULATION` is set, so drivers behave deterministically as in the tests of libfprint.
+    pub fn replay_command(&self, program: impl AsRef<OsStr>) -> Command {
+        let mut ioctl = self.node.as_os_str().to_os_string();
+        ioctl.push("=");
+        ioctl.push(self.dir.join(IOCTL_FILE));
+
+        let mut command = Command::new("umockdev-run");
+        command
+            .arg("--device")
+            .arg(self.dir.join(DEVICE_FILE))
+            .arg("--ioctl")
+            .arg(ioctl)
+            .arg("--")
+            .arg(program)
+            .env("FP_DEVICE_EMULATION", "1");
+        command
+    }
+}
+
+fn check(what: &str, status: std::process::ExitStatus) -> Result<(), FpError> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!("{} failed with {}", what, status)).into())
+    }
+}
+
+/// Returns the USB device node of a recorded device description, its `N: bus/usb/...` line. The name can be
+/// followed by `=` and the recorded content of the node.
+fn device_node(description: &str) -> Option<PathBuf> {
+    description
+        .lines()
+        .filter_map(|line| line.strip_prefix("N: "))
+        .map(|name| name.split('=').next().unwrap_or(name).trim())
+        .find(|name| name.starts_with("bus/usb/"))
+        .map(|name| Path::new("/dev").join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::device_node;
+
+    #[test]
+    fn recorded_device_node() {
+        let description = "P: /devices/pci0000:00/0000:00:14.0/usb1/1-9\nN: bus/usb/001/005=1201\nE: DEVNAME=/dev/bus/usb/001/005\n";
+        assert_eq!(
+            device_node(description).unwrap(),
+            Path::new("/dev/bus/usb/001/005")
+        );
+        assert_eq!(device_node("P: /devices/virtual\n"), None);
+    }
+}
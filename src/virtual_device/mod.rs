@@ -0,0 +1,229 @@
+//! Control of libfprint's virtual devices, for automated tests of enroll and verify logic without hardware.
+//!
+//! libfprint creates a `virtual_image` device when the `FP_VIRTUAL_IMAGE` environment variable is set to the path
+//! of a UNIX socket, before the `FpContext` is created. `VirtualImageDevice` connects to that socket to submit the
+//! images the device then "scans". A `Scenario` plays a scripted sequence of scans and errors on the device.
+//! # Example:
+//! ```no_run
+//! use libfprint_rs::{virtual_device::VirtualImageDevice, FpContext};
+//!
+//! std::env::set_var("FP_VIRTUAL_IMAGE", "/tmp/virtual-image.socket");
+//! let ctx = FpContext::new();
+//! let devices = ctx.devices();
+//! let dev = devices.iter().find(|dev| dev.driver() == "virtual_image").unwrap();
+//! dev.open_sync(None).unwrap();
+//!
+//! let mut control = VirtualImageDevice::from_env().unwrap();
+//! control.submit_file("finger.pgm").unwrap();
+//! let image = dev.capture_sync(true, None).unwrap();
+//! ```
+mod scenario;
+
+use std::{
+    io::Write,
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{FpError, FpRetryError},
+    image::FpImage,
+};
+
+pub use scenario::{Scenario, ScenarioStep};
+
+/// Environment variable holding the path of the socket of the `virtual_image` driver.
+pub const VIRTUAL_IMAGE_ENV: &str = "FP_VIRTUAL_IMAGE";
+
+/// A connection to the control socket of a `virtual_image` device.
+#[derive(Debug)]
+pub struct VirtualImageDevice {
+    stream: UnixStream,
+    path: PathBuf,
+}
+
+impl VirtualImageDevice {
+    /// Connect to the socket at `path`. The device must have been opened, it creates the socket.
+    pub fn connect(path: impl AsRef<Path>) -> Result<Self, FpError> {
+        let stream = UnixStream::connect(path.as_ref())?;
+        Ok(VirtualImageDevice {
+            stream,
+            path: path.as_ref().to_path_buf(),
+        })
+    }
+    /// Connect to the socket given by the `FP_VIRTUAL_IMAGE` environment variable.
+    pub fn from_env() -> Result<Self, FpError> {
+        let path = std::env::var_os(VIRTUAL_IMAGE_ENV).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} is not set", VIRTUAL_IMAGE_ENV),
+            )
+        })?;
+        Self::connect(path)
+    }
+    /// The path of the socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Submit an image, that the device reports as scanned.
+    pub fn submit_image(&mut self, image: &FpImage) -> Result<(), FpError> {
+        self.submit_data(image.width(), image.height(), &image.data())
+    }
+    /// Submit greyscale data, one byte per pixel, row by row.
+    pub fn submit_data(&mut self, width: u32, height: u32, data: &[u8]) -> Result<(), FpError> {
+        if data.len() != width as usize * height as usize {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "The data does not match the size of the image",
+            )
+            .into());
+        }
+        let width = i32::try_from(width).map_err(|_| invalid_size())?;
+        let height = i32::try_from(height).map_err(|_| invalid_size())?;
+        self.send(width, height)?;
+        self.stream.write_all(data)?;
+        Ok(())
+    }
+    /// Submit the image stored in the file at `path`, either a binary PGM (`P5`) file, or a PNG file with the `png`
+    /// feature.
+    pub fn submit_file(&mut self, path: impl AsRef<Path>) -> Result<(), FpError> {
+        let content = std::fs::read(path)?;
+        let (width, height, data) = decode_image(&content)?;
+        self.submit_data(width, height, &data)
+    }
+
+    /// Make the next scan fail with the retry error `retry`.
+    pub fn retry(&mut self, retry: FpRetryError) -> Result<(), FpError> {
+        self.send(-1, retry as i32)
+    }
+    /// Make the next scan fail with the device error of code `code`, see `FpDeviceError` in libfprint.
+    pub fn error(&mut self, code: i32) -> Result<(), FpError> {
+        self.send(-2, code)
+    }
+    /// Set whether a finger is reported on the sensor automatically when an image is submitted, the default.
+    pub fn set_automatic_finger(&mut self, automatic: bool) -> Result<(), FpError> {
+        self.send(-3, automatic as i32)
+    }
+    /// Report whether a finger is on the sensor, when the automatic finger detection is disabled.
+    pub fn report_finger(&mut self, present: bool) -> Result<(), FpError> {
+        self.send(-4, present as i32)
+    }
+
+    fn send(&mut self, first: i32, second: i32) -> Result<(), FpError> {
+        // The driver reads two native endian integers
+        let mut header = [0; 8];
+        header[..4].copy_from_slice(&first.to_ne_bytes());
+        header[4..].copy_from_slice(&second.to_ne_bytes());
+        self.stream.write_all(&header)?;
+        Ok(())
+    }
+}
+
+fn invalid_size() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, "The image is too large")
+}
+
+fn invalid_file(message: &str) -> FpError {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string()).into()
+}
+
+/// Decode a PGM or PNG file into its width, height, and greyscale data.
+fn decode_image(content: &[u8]) -> Result<(u32, u32, Vec<u8>), FpError> {
+    if content.starts_with(b"P5") {
+        return decode_pgm(content);
+    }
+    #[cfg(feature = "png")]
+    if content.starts_with(b"\x89PNG") {
+        return decode_png(content);
+    }
+    Err(invalid_file("Unsupported image format"))
+}
+
+fn decode_pgm(content: &[u8]) -> Result<(u32, u32, Vec<u8>), FpError> {
+    // Header: magic, width, height and maximum value separated by whitespace, comments start with '#'
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while fields.len() < 4 {
+        while pos < content.len() && (content[pos].is_ascii_whitespace() || content[pos] == b'#') {
+            if content[pos] == b'#' {
+                while pos < content.len() && content[pos] != b'\n' {
+                    pos += 1;
+                }
+            } else {
+                pos += 1;
+            }
+        }
+        let start = pos;
+        while pos < content.len() && !content[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return Err(invalid_file("Truncated PGM header"));
+        }
+        fields.push(std::str::from_utf8(&content[start..pos]).unwrap_or(""));
+    }
+    // A single whitespace separates the header from the data
+    pos += 1;
+
+    let parse = |field: &str| {
+        field
+            .parse::<u32>()
+            .map_err(|_| invalid_file("Invalid PGM header"))
+    };
+    let (width, height, max) = (parse(fields[1])?, parse(fields[2])?, parse(fields[3])?);
+    if max == 0 || max > 255 {
+        return Err(invalid_file("Only 8 bits PGM files are supported"));
+    }
+    let len = width as usize * height as usize;
+    let data = content
+        .get(pos..pos + len)
+        .ok_or_else(|| invalid_file("Truncated PGM data"))?;
+    let data = data
+        .iter()
+        .map(|&value| (value as u32 * 255 / max) as u8)
+        .collect();
+    Ok((width, height, data))
+}
+
+#[cfg(feature = "png")]
+fn decode_png(content: &[u8]) -> Result<(u32, u32, Vec<u8>), FpError> {
+    let mut decoder = png::Decoder::new(content);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder
+        .read_info()
+        .map_err(|err| invalid_file(&err.to_string()))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buffer)
+        .map_err(|err| invalid_file(&err.to_string()))?;
+    let buffer = &buffer[..info.buffer_size()];
+
+    let channels = info.color_type.samples();
+    let data = buffer
+        .chunks_exact(channels)
+        .map(|pixel| match info.color_type {
+            png::ColorType::Rgb | png::ColorType::Rgba => {
+                let (r, g, b) = (pixel[0] as u32, pixel[1] as u32, pixel[2] as u32);
+                ((299 * r + 587 * g + 114 * b) / 1000) as u8
+            }
+            _ => pixel[0],
+        })
+        .collect();
+    Ok((info.width, info.height, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_pgm;
+
+    #[test]
+    fn pgm_decoding() {
+        let mut content = b"P5\n# scanned\n3 2\n255\n".to_vec();
+        content.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+        let (width, height, data) = decode_pgm(&content).unwrap();
+        assert_eq!((width, height), (3, 2));
+        assert_eq!(data, vec![1, 2, 3, 4, 5, 6]);
+        assert!(decode_pgm(&content[..content.len() - 1]).is_err());
+    }
+}
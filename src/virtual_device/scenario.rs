@@ -0,0 +1,245 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Duration,
+};
+
+use glib::{error::ErrorDomain, ObjectExt};
+
+use super::VirtualImageDevice;
+use crate::{
+    device::FpDevice,
+    error::{FpError, FpRetryError},
+};
+
+/// How long a step waits for the device to ask for a finger, or to take the finger it was given.
+const STEP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A step of a `Scenario`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScenarioStep {
+    /// Submit the image file at the given path, see `VirtualImageDevice::submit_file`.
+    Scan(PathBuf),
+    /// Make the next scan fail with the given retry error.
+    Retry(FpRetryError),
+    /// Make the next scan fail with the given device error code.
+    Error(i32),
+    /// Wait before running the next step, e.g. to leave a finger on the sensor for a while.
+    Sleep(Duration),
+}
+
+/// A sequence of steps played on a `VirtualImageDevice`, to reproduce the behavior of a flaky sensor
+/// deterministically.
+///
+/// Scenarios are written one step per line, empty lines and lines starting with `#` are ignored:
+/// ```text
+/// # A swipe too short, then two good scans
+/// RETRY too-short
+/// SCAN finger.pgm
+/// SCAN finger.pgm
+/// ```
+/// - `SCAN <path>` submits an image file, relative paths are resolved against the directory of the scenario
+///   file when loaded with `Scenario::load`.
+/// - `RETRY <code>` fails the next scan with a retry error, given by its code or by one of `general`,
+///   `too-short`, `center-finger`, `remove-finger` and `too-fast`.
+/// - `ERROR <code>` fails the next scan with the `FpDeviceError` of the given code.
+/// - `SLEEP <milliseconds>` waits before the next step.
+///
+/// The argument can also be written in parentheses, e.g. `RETRY(1)`.
+///
+/// The steps are sequenced on the finger status of the device rather than on time: `SCAN`, `RETRY` and `ERROR`
+/// wait for the device to ask for a finger, i.e. for a capture to be running, and then for the device to take
+/// the finger before the next step runs. A step fails with `std::io::ErrorKind::TimedOut` if the device does
+/// not ask for a finger or take it within 30 seconds.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Scenario {
+    steps: Vec<ScenarioStep>,
+}
+
+impl Scenario {
+    /// Creates a scenario playing `steps`.
+    pub fn new(steps: Vec<ScenarioStep>) -> Self {
+        Scenario { steps }
+    }
+    /// Load the scenario stored in the file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FpError> {
+        let path = path.as_ref();
+        let mut scenario: Scenario = std::fs::read_to_string(path)?.parse()?;
+        if let Some(dir) = path.parent() {
+            for step in &mut scenario.steps {
+                if let ScenarioStep::Scan(image) = step {
+                    if image.is_relative() {
+                        *image = dir.join(&image);
+                    }
+                }
+            }
+        }
+        Ok(scenario)
+    }
+    /// Returns the steps of the scenario.
+    pub fn steps(&self) -> &[ScenarioStep] {
+        &self.steps
+    }
+    /// Append `step` to the scenario.
+    pub fn push(&mut self, step: ScenarioStep) {
+        self.steps.push(step);
+    }
+
+    /// Play the scenario through `control`, the control socket of `device`, blocking until the device took the
+    /// last step. The operations of the device must run on another thread meanwhile, see `Scenario::spawn`.
+    pub fn run(&self, control: &mut VirtualImageDevice, device: &FpDevice) -> Result<(), FpError> {
+        let mut status = FingerNeeded::watch(device);
+        let result = self.steps.iter().try_for_each(|step| match step {
+            ScenarioStep::Scan(path) => status.feed(|| control.submit_file(path)),
+            ScenarioStep::Retry(retry) => status.feed(|| control.retry(*retry)),
+            ScenarioStep::Error(code) => status.feed(|| control.error(*code)),
+            ScenarioStep::Sleep(duration) => {
+                thread::sleep(*duration);
+                Ok(())
+            }
+        });
+        status.unwatch(device);
+        result
+    }
+    /// Play the scenario on `device` from a new thread, so the calling thread can run the device operations.
+    pub fn spawn(
+        self,
+        mut control: VirtualImageDevice,
+        device: &FpDevice,
+    ) -> thread::JoinHandle<Result<(), FpError>> {
+        let device = device.clone();
+        thread::spawn(move || self.run(&mut control, &device))
+    }
+}
+
+/// Whether a device asks for a finger, followed through the notifications of its finger status.
+struct FingerNeeded {
+    needed: bool,
+    changes: Receiver<bool>,
+    handler: Option<glib::SignalHandlerId>,
+}
+
+impl FingerNeeded {
+    fn watch(device: &FpDevice) -> Self {
+        let (sender, changes) = mpsc::channel();
+        // Emitted on the thread running the operations
+        let handler = device.connect_notify(Some("finger-status"), move |device, _| {
+            let _ = sender.send(device.finger_status_flags().is_needed());
+        });
+        FingerNeeded {
+            needed: device.finger_status_flags().is_needed(),
+            changes,
+            handler: Some(handler),
+        }
+    }
+    fn unwatch(&mut self, device: &FpDevice) {
+        if let Some(handler) = self.handler.take() {
+            device.disconnect(handler);
+        }
+    }
+    /// Wait for the device to ask for a finger, `send` it, and wait for the device to take it.
+    fn feed(&mut self, send: impl FnOnce() -> Result<(), FpError>) -> Result<(), FpError> {
+        self.wait_for(true, "The device did not ask for a finger")?;
+        send()?;
+        self.wait_for(false, "The device did not take the finger")
+    }
+    fn wait_for(&mut self, needed: bool, message: &str) -> Result<(), FpError> {
+        while self.needed != needed {
+            self.needed = self.changes.recv_timeout(STEP_TIMEOUT).map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::TimedOut, message.to_string())
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = FpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut steps = Vec::new();
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let step = parse_step(line).map_err(|message| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Line {} of the scenario: {}", number + 1, message),
+                )
+            })?;
+            steps.push(step);
+        }
+        Ok(Scenario { steps })
+    }
+}
+
+fn parse_step(line: &str) -> Result<ScenarioStep, String> {
+    let (keyword, argument) = match line.split_once('(') {
+        Some((keyword, rest)) if rest.ends_with(')') => (keyword, &rest[..rest.len() - 1]),
+        _ => line.split_once(char::is_whitespace).unwrap_or((line, "")),
+    };
+    let argument = argument.trim();
+    if argument.is_empty() {
+        return Err(format!("{} expects an argument", keyword));
+    }
+    let code = || {
+        argument
+            .parse::<i32>()
+            .map_err(|_| format!("Invalid code {}", argument))
+    };
+
+    match keyword.trim().to_ascii_uppercase().as_str() {
+        "SCAN" => Ok(ScenarioStep::Scan(PathBuf::from(argument))),
+        "RETRY" => {
+            let retry = match argument {
+                "general" => Some(FpRetryError::General),
+                "too-short" => Some(FpRetryError::TooShort),
+                "center-finger" => Some(FpRetryError::CenterFinger),
+                "remove-finger" => Some(FpRetryError::RemoveFinger),
+                "too-fast" => Some(FpRetryError::TooFast),
+                _ => <FpRetryError as ErrorDomain>::from(code()?),
+            };
+            retry
+                .map(ScenarioStep::Retry)
+                .ok_or_else(|| format!("Unknown retry error {}", argument))
+        }
+        "ERROR" => Ok(ScenarioStep::Error(code()?)),
+        "SLEEP" => argument
+            .parse()
+            .map(|ms| ScenarioStep::Sleep(Duration::from_millis(ms)))
+            .map_err(|_| format!("Invalid duration {}", argument)),
+        keyword => Err(format!("Unknown step {}", keyword)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use super::{Scenario, ScenarioStep};
+    use crate::error::FpRetryError;
+
+    #[test]
+    fn scenario_parsing() {
+        let scenario: Scenario =
+            "# flaky sensor\nRETRY too-short\n\nSLEEP 250\nERROR(2)\nscan finger.pgm\n"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            scenario.steps(),
+            &[
+                ScenarioStep::Retry(FpRetryError::TooShort),
+                ScenarioStep::Sleep(Duration::from_millis(250)),
+                ScenarioStep::Error(2),
+                ScenarioStep::Scan(PathBuf::from("finger.pgm")),
+            ]
+        );
+        assert!("SCAN".parse::<Scenario>().is_err());
+        assert!("RETRY 42".parse::<Scenario>().is_err());
+        assert!("SWIPE finger.pgm".parse::<Scenario>().is_err());
+    }
+}